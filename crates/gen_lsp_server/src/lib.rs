@@ -58,18 +58,21 @@
 use std::error::Error;
 
 mod msg;
+mod record;
 mod stdio;
 
 use crossbeam_channel::{Receiver, Sender};
 use lsp_types::{
     notification::{Exit, Initialized},
-    request::{Initialize, Shutdown},
-    InitializeParams, InitializeResult, ServerCapabilities,
+    request::{Initialize, Request, Shutdown},
+    InitializeParams, ServerCapabilities,
 };
+use serde_json::Value;
 
 pub type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
 pub use crate::{
     msg::{ErrorCode, RawMessage, RawNotification, RawRequest, RawResponse, RawResponseError},
+    record::{read_recording, record_transport, Peer, RecordedMessage, Recording},
     stdio::{stdio_transport, Threads},
 };
 
@@ -84,6 +87,22 @@ pub fn run_server(
     receiver: Receiver<RawMessage>,
     sender: Sender<RawMessage>,
     server: impl FnOnce(InitializeParams, &Receiver<RawMessage>, &Sender<RawMessage>) -> Result<()>,
+) -> Result<()> {
+    let caps = serde_json::to_value(caps)?;
+    run_server_raw(caps, receiver, sender, |params, receiver, sender| {
+        let params = serde_json::from_value(params)?;
+        server(params, receiver, sender)
+    })
+}
+
+/// Like `run_server`, but the server capabilities and the parameters of the
+/// `initialize` request are JSON, for the capabilities which `lsp_types`
+/// doesn't know yet.
+pub fn run_server_raw(
+    caps: Value,
+    receiver: Receiver<RawMessage>,
+    sender: Sender<RawMessage>,
+    server: impl FnOnce(Value, &Receiver<RawMessage>, &Sender<RawMessage>) -> Result<()>,
 ) -> Result<()> {
     log::info!("lsp server initializes");
     let params = initialize(&receiver, &sender, caps)?;
@@ -115,16 +134,14 @@ pub fn handle_shutdown(req: RawRequest, sender: &Sender<RawMessage>) -> Option<R
 fn initialize(
     receiver: &Receiver<RawMessage>,
     sender: &Sender<RawMessage>,
-    caps: ServerCapabilities,
-) -> Result<InitializeParams> {
+    caps: Value,
+) -> Result<Value> {
     let (id, params) = match receiver.recv() {
-        Ok(RawMessage::Request(req)) => match req.cast::<Initialize>() {
-            Err(req) => Err(format!("expected initialize request, got {:?}", req))?,
-            Ok(req) => req,
-        },
+        Ok(RawMessage::Request(req)) if req.method == Initialize::METHOD => (req.id, req.params),
         msg => Err(format!("expected initialize request, got {:?}", msg))?,
     };
-    let resp = RawResponse::ok::<Initialize>(id, &InitializeResult { capabilities: caps });
+    let result = serde_json::json!({ "capabilities": caps });
+    let resp = RawResponse { id, result: Some(result), error: None };
     sender.send(RawMessage::Response(resp)).unwrap();
     match receiver.recv() {
         Ok(RawMessage::Notification(n)) => {
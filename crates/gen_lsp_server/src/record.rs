@@ -0,0 +1,256 @@
+//! Recording of LSP sessions, to reproduce the bugs which only show up after
+//! a long sequence of edits.
+//!
+//! A recording is a file with one JSON `RecordedMessage` per line. The paths
+//! and URIs of the workspace, the root of the `initialize` request, are
+//! replaced by `${workspace}` and `${workspaceUri}`, so that the recording can
+//! be replayed on another machine.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use lsp_types::{request::Initialize, request::Request, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{RawMessage, Result};
+
+const WORKSPACE: &str = "${workspace}";
+const WORKSPACE_URI: &str = "${workspaceUri}";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordedMessage {
+    /// Milliseconds since the start of the recording.
+    pub time_ms: u64,
+    pub from: Peer,
+    pub msg: RawMessage,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Peer {
+    Client,
+    Server,
+}
+
+/// Records the messages which go through `receiver` and `sender`, the
+/// channels of a transport, to `path`. The server should use the returned
+/// channels instead.
+pub fn record_transport(
+    receiver: Receiver<RawMessage>,
+    sender: Sender<RawMessage>,
+    path: &Path,
+) -> Result<(Receiver<RawMessage>, Sender<RawMessage>, Recording)> {
+    let recorder = Arc::new(Mutex::new(Recorder {
+        out: BufWriter::new(File::create(path)?),
+        start: Instant::now(),
+        workspace: None,
+    }));
+
+    let (client_sender, client_receiver) = bounded::<RawMessage>(16);
+    let client = {
+        let recorder = Arc::clone(&recorder);
+        thread::spawn(move || {
+            for msg in receiver {
+                recorder.lock().unwrap().record(Peer::Client, &msg)?;
+                if client_sender.send(msg).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    };
+    let (server_sender, server_receiver) = bounded::<RawMessage>(16);
+    let server = thread::spawn(move || {
+        for msg in server_receiver {
+            recorder.lock().unwrap().record(Peer::Server, &msg)?;
+            if sender.send(msg).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    Ok((client_receiver, server_sender, Recording { client, server }))
+}
+
+pub struct Recording {
+    client: thread::JoinHandle<Result<()>>,
+    server: thread::JoinHandle<Result<()>>,
+}
+
+impl Recording {
+    pub fn join(self) -> Result<()> {
+        match self.client.join() {
+            Ok(r) => r?,
+            Err(_) => Err("recorder panicked")?,
+        }
+        match self.server.join() {
+            Ok(r) => r,
+            Err(_) => Err("recorder panicked")?,
+        }
+    }
+}
+
+/// Reads a recording, with the placeholders replaced by the paths of
+/// `workspace`.
+pub fn read_recording(path: &Path, workspace: &Path) -> Result<Vec<RecordedMessage>> {
+    let workspace = Workspace::new(workspace)?;
+    let mut res = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: Value = serde_json::from_str(&line)?;
+        workspace.resolve(&mut value);
+        res.push(serde_json::from_value(value)?);
+    }
+    Ok(res)
+}
+
+struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+    /// Known once the `initialize` request is recorded.
+    workspace: Option<Workspace>,
+}
+
+impl Recorder {
+    fn record(&mut self, from: Peer, msg: &RawMessage) -> Result<()> {
+        if let RawMessage::Request(req) = msg {
+            if req.method == Initialize::METHOD {
+                self.workspace = Workspace::from_initialize_params(&req.params);
+            }
+        }
+        let recorded = RecordedMessage {
+            time_ms: self.start.elapsed().as_millis() as u64,
+            from,
+            msg: msg.clone(),
+        };
+        let mut value = serde_json::to_value(recorded)?;
+        if let Some(workspace) = &self.workspace {
+            workspace.relativize(&mut value);
+        }
+        serde_json::to_writer(&mut self.out, &value)?;
+        self.out.write_all(b"\n")?;
+        // Flushed right away, the server may be killed rather than shut down.
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// The root of the workspace, as a path and as a URI.
+#[derive(Debug)]
+struct Workspace {
+    path: String,
+    uri: String,
+}
+
+impl Workspace {
+    fn new(path: &Path) -> Result<Workspace> {
+        let uri = Url::from_directory_path(path).map_err(|()| "workspace should be absolute")?;
+        Workspace::from_uri(&uri).ok_or_else(|| "workspace should be absolute".into())
+    }
+
+    fn from_initialize_params(params: &Value) -> Option<Workspace> {
+        if let Some(uri) = params.get("rootUri").and_then(|it| it.as_str()) {
+            return Workspace::from_uri(&Url::parse(uri).ok()?);
+        }
+        let path = params.get("rootPath").and_then(|it| it.as_str())?;
+        Workspace::from_uri(&Url::from_directory_path(path).ok()?)
+    }
+
+    fn from_uri(uri: &Url) -> Option<Workspace> {
+        let path = uri.to_file_path().ok()?;
+        let path = path.to_str()?.trim_end_matches(std::path::MAIN_SEPARATOR).to_string();
+        let uri = uri.as_str().trim_end_matches('/').to_string();
+        Some(Workspace { path, uri })
+    }
+
+    fn relativize(&self, value: &mut Value) {
+        // The URIs first, they contain the path on Unix.
+        replace_strings(
+            value,
+            &[(self.uri.as_str(), WORKSPACE_URI), (self.path.as_str(), WORKSPACE)],
+        );
+    }
+
+    fn resolve(&self, value: &mut Value) {
+        replace_strings(
+            value,
+            &[(WORKSPACE_URI, self.uri.as_str()), (WORKSPACE, self.path.as_str())],
+        );
+    }
+}
+
+fn replace_strings(value: &mut Value, replacements: &[(&str, &str)]) {
+    match value {
+        Value::String(s) => {
+            for (from, to) in replacements {
+                if s.contains(from) {
+                    *s = replace_path(s, from, to);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|it| replace_strings(it, replacements)),
+        Value::Object(map) => map.values_mut().for_each(|it| replace_strings(it, replacements)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => (),
+    }
+}
+
+/// Replaces `path` in `s`, but not the paths which only start like it.
+fn replace_path(s: &str, path: &str, to: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut last = 0;
+    for (idx, _) in s.match_indices(path) {
+        let end = idx + path.len();
+        match s[end..].chars().next() {
+            None | Some('/') | Some('\\') => {
+                res.push_str(&s[last..idx]);
+                res.push_str(to);
+                last = end;
+            }
+            Some(_) => (),
+        }
+    }
+    res.push_str(&s[last..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn workspace_paths_are_relativized() {
+        let workspace = Workspace::from_initialize_params(&json!({
+            "rootUri": "file:///home/ferris/hello",
+        }))
+        .unwrap();
+        let mut params = json!({
+            "textDocument": { "uri": "file:///home/ferris/hello/src/main.rs" },
+            "arguments": ["/home/ferris/hello/Cargo.toml", "/home/ferris/hello-world"],
+        });
+        workspace.relativize(&mut params);
+        assert_eq!(
+            params,
+            json!({
+                "textDocument": { "uri": "${workspaceUri}/src/main.rs" },
+                "arguments": ["${workspace}/Cargo.toml", "/home/ferris/hello-world"],
+            })
+        );
+
+        let other = Workspace::new(Path::new("/tmp/replay")).unwrap();
+        other.resolve(&mut params);
+        assert_eq!(params["textDocument"]["uri"], "file:///tmp/replay/src/main.rs");
+        assert_eq!(params["arguments"][0], "/tmp/replay/Cargo.toml");
+    }
+}
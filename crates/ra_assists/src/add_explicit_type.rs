@@ -7,7 +7,7 @@ use ra_syntax::{
     ast::{LetStmt, PatKind, NameOwner, AstNode}
 };
 
-use crate::{AssistCtx, Assist, AssistId};
+use crate::{AssistCtx, Assist, AssistId, AssistKind};
 
 /// Add explicit type assist.
 pub(crate) fn add_explicit_type(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
@@ -36,10 +36,14 @@ pub(crate) fn add_explicit_type(mut ctx: AssistCtx<impl HirDatabase>) -> Option<
         return None;
     }
 
-    ctx.add_action(AssistId("add_explicit_type"), "add explicit type", |edit| {
-        edit.target(pat_range);
-        edit.insert(name_range.end(), format!(": {}", ty.display(db)));
-    });
+    ctx.add_action(
+        AssistId("add_explicit_type", AssistKind::RefactorRewrite),
+        "add explicit type",
+        |edit| {
+            edit.target(pat_range);
+            edit.insert(name_range.end(), format!(": {}", ty.display(db)));
+        },
+    );
     ctx.build()
 }
 
@@ -1,4 +1,4 @@
-use crate::{Assist, AssistId, AssistCtx, ast_editor::{AstEditor, AstBuilder}};
+use crate::{Assist, AssistId, AssistKind, AssistCtx, ast_editor::{AstEditor, AstBuilder}};
 
 use hir::{HasSource, db::HirDatabase};
 use ra_syntax::{SmolStr, TreeArc};
@@ -14,7 +14,7 @@ pub(crate) fn add_missing_impl_members(ctx: AssistCtx<impl HirDatabase>) -> Opti
     add_missing_impl_members_inner(
         ctx,
         AddMissingImplMembersMode::NoDefaultMethods,
-        "add_impl_missing_members",
+        AssistId("add_impl_missing_members", AssistKind::QuickFix),
         "add missing impl members",
     )
 }
@@ -23,7 +23,7 @@ pub(crate) fn add_missing_default_members(ctx: AssistCtx<impl HirDatabase>) -> O
     add_missing_impl_members_inner(
         ctx,
         AddMissingImplMembersMode::DefaultMethodsOnly,
-        "add_impl_default_members",
+        AssistId("add_impl_default_members", AssistKind::Refactor),
         "add impl default members",
     )
 }
@@ -31,7 +31,7 @@ pub(crate) fn add_missing_default_members(ctx: AssistCtx<impl HirDatabase>) -> O
 fn add_missing_impl_members_inner(
     mut ctx: AssistCtx<impl HirDatabase>,
     mode: AddMissingImplMembersMode,
-    assist_id: &'static str,
+    assist_id: AssistId,
     label: &'static str,
 ) -> Option<Assist> {
     let impl_node = ctx.node_at_offset::<ast::ImplBlock>()?;
@@ -69,7 +69,7 @@ fn add_missing_impl_members_inner(
         return None;
     }
 
-    ctx.add_action(AssistId(assist_id), label, |edit| {
+    ctx.add_action(assist_id, label, |edit| {
         let n_existing_items = impl_item_list.impl_items().count();
         let fns = missing_fns.into_iter().map(add_body_and_strip_docstring).collect::<Vec<_>>();
 
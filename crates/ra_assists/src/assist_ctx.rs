@@ -42,15 +42,16 @@ pub(crate) enum Assist {
 /// with `should_compute_edit = false`, and then applying the selected edit
 /// again, with `should_compute_edit = true` this time.
 ///
-/// Note, however, that we don't actually use such two-phase logic at the
-/// moment, because the LSP API is pretty awkward in this place, and it's much
-/// easier to just compute the edit eagerly :-)#[derive(Debug, Clone)]
+/// When applying, only the edit of the selected assist is computed: the other
+/// assists still check whether they are applicable, but skip their edits.
 #[derive(Debug)]
 pub(crate) struct AssistCtx<'a, DB> {
     pub(crate) db: &'a DB,
     pub(crate) frange: FileRange,
     source_file: &'a SourceFile,
     should_compute_edit: bool,
+    /// The id and the label of the only assist whose edit is computed.
+    resolve_only: Option<(&'a str, &'a str)>,
     assist: Assist,
 }
 
@@ -61,6 +62,7 @@ impl<'a, DB> Clone for AssistCtx<'a, DB> {
             frange: self.frange,
             source_file: self.source_file,
             should_compute_edit: self.should_compute_edit,
+            resolve_only: self.resolve_only,
             assist: self.assist.clone(),
         }
     }
@@ -75,10 +77,25 @@ impl<'a, DB: HirDatabase> AssistCtx<'a, DB> {
         let assist =
             if should_compute_edit { Assist::Resolved(vec![]) } else { Assist::Unresolved(vec![]) };
 
-        let ctx = AssistCtx { db, frange, source_file, should_compute_edit, assist };
+        let ctx =
+            AssistCtx { db, frange, source_file, should_compute_edit, resolve_only: None, assist };
         f(ctx)
     }
 
+    /// Like `with_ctx` with `should_compute_edit`, but if `resolve_only` is
+    /// set, only the assist with this id and label is computed.
+    pub(crate) fn with_ctx_resolving<F, T>(
+        db: &DB,
+        frange: FileRange,
+        resolve_only: Option<(&str, &str)>,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce(AssistCtx<DB>) -> T,
+    {
+        AssistCtx::with_ctx(db, frange, true, |ctx| f(AssistCtx { resolve_only, ..ctx }))
+    }
+
     pub(crate) fn add_action(
         &mut self,
         id: AssistId,
@@ -89,6 +106,11 @@ impl<'a, DB: HirDatabase> AssistCtx<'a, DB> {
         match &mut self.assist {
             Assist::Unresolved(labels) => labels.push(label),
             Assist::Resolved(labels_actions) => {
+                if let Some((id, label_text)) = self.resolve_only {
+                    if label.id.0 != id || label.label != label_text {
+                        return self;
+                    }
+                }
                 let action = {
                     let mut edit = AssistBuilder::default();
                     f(&mut edit);
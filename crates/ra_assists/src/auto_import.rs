@@ -7,7 +7,7 @@ use ra_syntax::{
     SyntaxKind::{ PATH, PATH_SEGMENT }
 };
 use crate::{
-    AssistId,
+    AssistId, AssistKind,
     assist_ctx::{AssistCtx, Assist},
 };
 
@@ -549,7 +549,7 @@ pub(crate) fn auto_import(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist
     if let Some(module) = path.syntax().ancestors().find_map(ast::Module::cast) {
         if let (Some(item_list), Some(name)) = (module.item_list(), module.name()) {
             ctx.add_action(
-                AssistId("auto_import"),
+                AssistId("auto_import", AssistKind::QuickFix),
                 format!("import {} in mod {}", fmt_segments(&segments), name.text()),
                 |edit| {
                     let mut text_edit = TextEditBuilder::default();
@@ -561,7 +561,7 @@ pub(crate) fn auto_import(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist
     } else {
         let current_file = path.syntax().ancestors().find_map(ast::SourceFile::cast)?;
         ctx.add_action(
-            AssistId("auto_import"),
+            AssistId("auto_import", AssistKind::QuickFix),
             format!("import {} in the current file", fmt_segments(&segments)),
             |edit| {
                 let mut text_edit = TextEditBuilder::default();
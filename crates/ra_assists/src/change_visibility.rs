@@ -6,7 +6,7 @@ use ra_syntax::{
     SyntaxKind::{VISIBILITY, FN_DEF, MODULE, STRUCT_DEF, ENUM_DEF, TRAIT_DEF, IDENT, WHITESPACE, COMMENT, ATTR},
 };
 
-use crate::{AssistCtx, Assist, AssistId};
+use crate::{AssistCtx, Assist, AssistId, AssistKind};
 
 pub(crate) fn change_visibility(ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
     if let Some(vis) = ctx.node_at_offset::<ast::Visibility>() {
@@ -42,11 +42,15 @@ fn add_vis(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
         (vis_offset(field.syntax()), ident.range())
     };
 
-    ctx.add_action(AssistId("change_visibility"), "make pub(crate)", |edit| {
-        edit.target(target);
-        edit.insert(offset, "pub(crate) ");
-        edit.set_cursor(offset);
-    });
+    ctx.add_action(
+        AssistId("change_visibility", AssistKind::RefactorRewrite),
+        "make pub(crate)",
+        |edit| {
+            edit.target(target);
+            edit.insert(offset, "pub(crate) ");
+            edit.set_cursor(offset);
+        },
+    );
 
     ctx.build()
 }
@@ -64,20 +68,28 @@ fn vis_offset(node: &SyntaxNode) -> TextUnit {
 
 fn change_vis(mut ctx: AssistCtx<impl HirDatabase>, vis: &ast::Visibility) -> Option<Assist> {
     if vis.syntax().text() == "pub" {
-        ctx.add_action(AssistId("change_visibility"), "change to pub(crate)", |edit| {
-            edit.target(vis.syntax().range());
-            edit.replace(vis.syntax().range(), "pub(crate)");
-            edit.set_cursor(vis.syntax().range().start())
-        });
+        ctx.add_action(
+            AssistId("change_visibility", AssistKind::RefactorRewrite),
+            "change to pub(crate)",
+            |edit| {
+                edit.target(vis.syntax().range());
+                edit.replace(vis.syntax().range(), "pub(crate)");
+                edit.set_cursor(vis.syntax().range().start())
+            },
+        );
 
         return ctx.build();
     }
     if vis.syntax().text() == "pub(crate)" {
-        ctx.add_action(AssistId("change_visibility"), "change to pub", |edit| {
-            edit.target(vis.syntax().range());
-            edit.replace(vis.syntax().range(), "pub");
-            edit.set_cursor(vis.syntax().range().start());
-        });
+        ctx.add_action(
+            AssistId("change_visibility", AssistKind::RefactorRewrite),
+            "change to pub",
+            |edit| {
+                edit.target(vis.syntax().range());
+                edit.replace(vis.syntax().range(), "pub");
+                edit.set_cursor(vis.syntax().range().start());
+            },
+        );
 
         return ctx.build();
     }
@@ -1,7 +1,7 @@
 use hir::db::HirDatabase;
 use ra_syntax::ast::{AstNode, BinExpr, BinOp};
 
-use crate::{AssistCtx, Assist, AssistId};
+use crate::{AssistCtx, Assist, AssistId, AssistKind};
 
 /// Flip binary expression assist.
 pub(crate) fn flip_binexpr(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
@@ -20,14 +20,18 @@ pub(crate) fn flip_binexpr(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assis
         return None;
     }
 
-    ctx.add_action(AssistId("flip_binexpr"), "flip binary expression", |edit| {
-        edit.target(op_range);
-        if let FlipAction::FlipAndReplaceOp(new_op) = action {
-            edit.replace(op_range, new_op);
-        }
-        edit.replace(lhs.range(), rhs.text());
-        edit.replace(rhs.range(), lhs.text());
-    });
+    ctx.add_action(
+        AssistId("flip_binexpr", AssistKind::RefactorRewrite),
+        "flip binary expression",
+        |edit| {
+            edit.target(op_range);
+            if let FlipAction::FlipAndReplaceOp(new_op) = action {
+                edit.replace(op_range, new_op);
+            }
+            edit.replace(lhs.range(), rhs.text());
+            edit.replace(rhs.range(), lhs.text());
+        },
+    );
 
     ctx.build()
 }
@@ -5,13 +5,13 @@ use ra_syntax::{
     algo::non_trivia_sibling,
 };
 
-use crate::{AssistCtx, Assist, AssistId};
+use crate::{AssistCtx, Assist, AssistId, AssistKind};
 
 pub(crate) fn flip_comma(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
     let comma = ctx.token_at_offset().find(|leaf| leaf.kind() == T![,])?;
     let prev = non_trivia_sibling(comma.into(), Direction::Prev)?;
     let next = non_trivia_sibling(comma.into(), Direction::Next)?;
-    ctx.add_action(AssistId("flip_comma"), "flip comma", |edit| {
+    ctx.add_action(AssistId("flip_comma", AssistKind::RefactorRewrite), "flip comma", |edit| {
         edit.target(comma.range());
         edit.replace(prev.range(), next.to_string());
         edit.replace(next.range(), prev.to_string());
@@ -4,7 +4,7 @@ use ra_syntax::{
     TextRange,
 };
 
-use crate::{Assist, AssistCtx, AssistId};
+use crate::{Assist, AssistCtx, AssistId, AssistKind};
 use crate::assist_ctx::AssistBuilder;
 
 pub(crate) fn inline_local_varialbe(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
@@ -75,7 +75,7 @@ pub(crate) fn inline_local_varialbe(mut ctx: AssistCtx<impl HirDatabase>) -> Opt
     let init_in_paren = format!("({})", &init_str);
 
     ctx.add_action(
-        AssistId("inline_local_variable"),
+        AssistId("inline_local_variable", AssistKind::RefactorInline),
         "inline local variable",
         move |edit: &mut AssistBuilder| {
             edit.delete(delete_range);
@@ -19,9 +19,25 @@ use hir::db::HirDatabase;
 pub(crate) use crate::assist_ctx::{AssistCtx, Assist};
 
 /// Unique identifier of the assist, should not be shown to the user
-/// directly.
+/// directly, with the kind of the assist.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct AssistId(pub &'static str);
+pub struct AssistId(pub &'static str, pub AssistKind);
+
+/// What an assist does, which editors use to group the assists in their
+/// menus. The kinds correspond to the `CodeActionKind`s of LSP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AssistKind {
+    /// Fixes code which is wrong or incomplete.
+    QuickFix,
+    /// Generates new code, like impls or derives.
+    Refactor,
+    RefactorExtract,
+    RefactorInline,
+    RefactorRewrite,
+    /// Sorts and merges all the imports of a file, not just the one at the
+    /// cursor.
+    OrganizeImports,
+}
 
 #[derive(Debug, Clone)]
 pub struct AssistLabel {
@@ -57,6 +73,32 @@ where
     })
 }
 
+/// Return the assist with the given id and label, applicable at the given
+/// position.
+///
+/// The assist is returned in the "resolved" state, the edits of the other
+/// assists are not computed.
+pub fn resolved_assist<H>(
+    db: &H,
+    range: FileRange,
+    id: &str,
+    label: &str,
+) -> Option<(AssistLabel, AssistAction)>
+where
+    H: HirDatabase + 'static,
+{
+    AssistCtx::with_ctx_resolving(db, range, Some((id, label)), |ctx| {
+        all_assists()
+            .iter()
+            .filter_map(|f| f(ctx.clone()))
+            .flat_map(|a| match a {
+                Assist::Resolved(labels_actions) => labels_actions,
+                Assist::Unresolved(..) => unreachable!(),
+            })
+            .next()
+    })
+}
+
 /// Return all the assists applicable at the given position.
 ///
 /// Assists are returned in the "resolved" state, that is with edit fully
@@ -293,6 +335,8 @@ mod tests {
     use ra_db::FileRange;
     use test_utils::{extract_offset, extract_range};
 
+    use crate::{AssistId, AssistKind};
+
     #[test]
     fn assist_order_field_struct() {
         let before = "struct Foo { <|>bar: u32 }";
@@ -327,4 +371,23 @@ mod tests {
         assert_eq!(assists.next().expect("expected assist").0.label, "replace with match");
     }
 
+    #[test]
+    fn resolves_assist_by_id_and_label() {
+        let before = "struct Foo { <|>bar: u32 }";
+        let (before_cursor_pos, before) = extract_offset(before);
+        let (db, _source_root, file_id) = MockDatabase::with_single_file(&before);
+        let frange =
+            FileRange { file_id, range: TextRange::offset_len(before_cursor_pos, 0.into()) };
+        let labels = super::applicable_assists(&db, frange);
+        assert!(labels.iter().any(|it| it.id == AssistId("add_derive", AssistKind::Refactor)));
+
+        let (label, action) =
+            super::resolved_assist(&db, frange, "change_visibility", "make pub(crate)")
+                .expect("expected assist");
+        assert_eq!(label.id, AssistId("change_visibility", AssistKind::RefactorRewrite));
+        assert_eq!(action.edit.apply(&before), "struct Foo { pub(crate) bar: u32 }");
+
+        let resolved = super::resolved_assist(&db, frange, "change_visibility", "change to pub");
+        assert!(resolved.is_none());
+    }
 }
@@ -6,7 +6,7 @@ use ra_syntax::{
     ast,
 };
 
-use crate::{AssistCtx, Assist, AssistId};
+use crate::{AssistCtx, Assist, AssistId, AssistKind};
 
 pub(crate) fn move_guard_to_arm_body(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
     let match_arm = ctx.node_at_offset::<MatchArm>()?;
@@ -17,25 +17,31 @@ pub(crate) fn move_guard_to_arm_body(mut ctx: AssistCtx<impl HirDatabase>) -> Op
     let arm_expr = match_arm.expr()?;
     let buf = format!("if {} {{ {} }}", guard_conditions.syntax().text(), arm_expr.syntax().text());
 
-    ctx.add_action(AssistId("move_guard_to_arm_body"), "move guard to arm body", |edit| {
-        edit.target(guard.syntax().range());
-        let offseting_amount = match space_before_guard {
-            Some(SyntaxElement::Token(tok)) => {
-                if let Some(_) = ast::Whitespace::cast(tok) {
-                    let ele = space_before_guard.unwrap().range();
-                    edit.delete(ele);
-                    ele.len()
-                } else {
-                    TextUnit::from(0)
+    ctx.add_action(
+        AssistId("move_guard_to_arm_body", AssistKind::RefactorRewrite),
+        "move guard to arm body",
+        |edit| {
+            edit.target(guard.syntax().range());
+            let offseting_amount = match space_before_guard {
+                Some(SyntaxElement::Token(tok)) => {
+                    if let Some(_) = ast::Whitespace::cast(tok) {
+                        let ele = space_before_guard.unwrap().range();
+                        edit.delete(ele);
+                        ele.len()
+                    } else {
+                        TextUnit::from(0)
+                    }
                 }
-            }
-            _ => TextUnit::from(0),
-        };
+                _ => TextUnit::from(0),
+            };
 
-        edit.delete(guard.syntax().range());
-        edit.replace_node_and_indent(arm_expr.syntax(), buf);
-        edit.set_cursor(arm_expr.syntax().range().start() + TextUnit::from(3) - offseting_amount);
-    });
+            edit.delete(guard.syntax().range());
+            edit.replace_node_and_indent(arm_expr.syntax(), buf);
+            edit.set_cursor(
+                arm_expr.syntax().range().start() + TextUnit::from(3) - offseting_amount,
+            );
+        },
+    );
     ctx.build()
 }
 
@@ -60,7 +66,7 @@ pub(crate) fn move_arm_cond_to_match_guard(mut ctx: AssistCtx<impl HirDatabase>)
     let buf = format!(" if {}", cond.syntax().text());
 
     ctx.add_action(
-        AssistId("move_arm_cond_to_match_guard"),
+        AssistId("move_arm_cond_to_match_guard", AssistKind::RefactorRewrite),
         "move condition to match guard",
         |edit| {
             edit.target(if_expr.syntax().range());
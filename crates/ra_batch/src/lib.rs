@@ -8,8 +8,10 @@ use ra_db::{
     CrateGraph, FileId, SourceRootId,
 };
 use ra_ide_api::{AnalysisHost, AnalysisChange};
-use ra_project_model::{ProjectWorkspace, ProjectRoot};
-use ra_vfs::{Vfs, VfsChange};
+use ra_project_model::{
+    ProjectWorkspace, ProjectRoot, ProcMacroClient, CargoFeatures, CargoCheckOutput, TargetConfig,
+};
+use ra_vfs::{Vfs, VfsChange, Watch};
 use vfs_filter::IncludeRustFiles;
 
 type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
@@ -23,10 +25,24 @@ fn vfs_root_to_id(r: ra_vfs::VfsRoot) -> SourceRootId {
 
 pub fn load_cargo(root: &Path) -> Result<(AnalysisHost, FxHashMap<SourceRootId, ProjectRoot>)> {
     let root = std::env::current_dir()?.join(root);
-    let ws = ProjectWorkspace::discover(root.as_ref())?;
+    let mut ws = ProjectWorkspace::discover(
+        root.as_ref(),
+        &CargoFeatures::default(),
+        &TargetConfig::default(),
+    )?;
+    let proc_macro_client = match std::env::var_os("RA_PROC_MACRO_SRV") {
+        Some(server_path) => {
+            ws.load_cargo_check_output(&CargoCheckOutput::Run)?;
+            ProcMacroClient::extern_process(server_path.as_ref())?
+        }
+        None => ProcMacroClient::dummy(),
+    };
     let project_roots = ws.to_roots();
-    let (mut vfs, roots) = Vfs::new(IncludeRustFiles::from_roots(project_roots.clone()).collect());
-    let crate_graph = ws.to_crate_graph(&mut |path: &Path| {
+    // The files are loaded once, there's no need to watch them
+    let (mut vfs, roots) =
+        Vfs::new(IncludeRustFiles::from_roots(project_roots.clone()).collect(), Watch(false));
+    // Cyclic dependencies are logged by the project model
+    let (crate_graph, _) = ws.to_crate_graph(&proc_macro_client, &mut |path: &Path| {
         let vfs_file = vfs.load(path);
         log::debug!("vfs file {:?} -> {:?}", path, vfs_file);
         vfs_file.map(vfs_file_to_id)
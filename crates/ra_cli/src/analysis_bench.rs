@@ -13,7 +13,9 @@ pub(crate) enum Op {
     Complete { path: PathBuf, line: u32, column: u32 },
 }
 
-pub(crate) fn run(verbose: bool, path: &Path, op: Op) -> Result<()> {
+/// Loads the project at `path` and measures `op`. When `trace` is set, the
+/// scopes of `op` are written there as a Chrome trace.
+pub(crate) fn run(verbose: bool, path: &Path, op: Op, trace: Option<&Path>) -> Result<()> {
     let start = Instant::now();
     eprint!("loading: ");
     let (host, roots) = ra_batch::load_cargo(path)?;
@@ -42,6 +44,9 @@ pub(crate) fn run(verbose: bool, path: &Path, op: Op) -> Result<()> {
             .ok_or_else(|| format!("Can't find {:?}", path))?
     };
 
+    if let Some(trace) = trace {
+        ra_prof::start_trace(trace)?;
+    }
     match op {
         Op::Highlight { .. } => {
             let res = do_work(&host, |analysis| {
@@ -59,12 +64,16 @@ pub(crate) fn run(verbose: bool, path: &Path, op: Op) -> Result<()> {
                 .offset(LineCol { line, col_utf16: column });
             let file_postion = FilePosition { file_id, offset };
 
-            let res = do_work(&host, |analysis| analysis.completions(file_postion));
+            let res =
+                do_work(&host, |analysis| analysis.completions(file_postion, &Default::default()));
             if verbose {
                 println!("\n{:#?}", res);
             }
         }
     }
+    if trace.is_some() {
+        ra_prof::stop_trace();
+    }
     Ok(())
 }
 
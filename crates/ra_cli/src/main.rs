@@ -1,7 +1,8 @@
 mod analysis_stats;
 mod analysis_bench;
+mod replay;
 
-use std::{io::Read, error::Error};
+use std::{io::Read, error::Error, path::Path};
 
 use clap::{App, Arg, SubCommand};
 use ra_ide_api::{file_structure, Analysis};
@@ -13,6 +14,9 @@ type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
 
 fn main() -> Result<()> {
     Logger::with_env().start()?;
+    if let Ok(path) = std::env::var("RA_PROFILE_TRACE") {
+        ra_prof::start_trace(path.as_ref())?;
+    }
     let matches = App::new("ra-cli")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(SubCommand::with_name("parse").arg(Arg::with_name("no-dump").long("--no-dump")))
@@ -46,8 +50,32 @@ fn main() -> Result<()> {
                         .value_name("PATH:LINE:COLUMN")
                         .help("compute completions at this location"),
                 )
+                .arg(
+                    Arg::with_name("trace")
+                        .long("trace")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("write a Chrome trace of the measured operation to this file"),
+                )
                 .arg(Arg::with_name("path").value_name("PATH").help("project to analyze")),
         )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .arg(Arg::with_name("verbose").short("v").long("verbose"))
+                .arg(
+                    Arg::with_name("workspace")
+                        .long("workspace")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("workspace to replay in, the current directory by default"),
+                )
+                .arg(
+                    Arg::with_name("recording")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("a session recorded with RA_LSP_RECORD"),
+                ),
+        )
         .get_matches();
     match matches.subcommand() {
         ("parse", Some(matches)) => {
@@ -91,10 +119,18 @@ fn main() -> Result<()> {
             } else {
                 panic!("either --highlight or --complete must be set")
             };
-            analysis_bench::run(verbose, path.as_ref(), op)?;
+            let trace = matches.value_of("trace").map(Path::new);
+            analysis_bench::run(verbose, path.as_ref(), op, trace)?;
+        }
+        ("replay", Some(matches)) => {
+            let verbose = matches.is_present("verbose");
+            let recording = matches.value_of("recording").unwrap();
+            let workspace = matches.value_of("workspace").unwrap_or("");
+            replay::run(verbose, recording.as_ref(), workspace.as_ref())?;
         }
         _ => unreachable!(),
     }
+    ra_prof::stop_trace();
     Ok(())
 }
 
@@ -0,0 +1,206 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use gen_lsp_server::{read_recording, ErrorCode, Peer, RawMessage, RawRequest, RawResponse};
+use serde_json::Value;
+
+use crate::Result;
+
+/// How long to wait for the server, when the recording has a response to a
+/// request of the server, or at the end for the responses to the requests.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+static NULL: Value = Value::Null;
+
+/// Sends the messages of the client in the recording at `path` to a fresh
+/// server, with the same timing, and compares the responses of the server
+/// with the recorded ones.
+pub(crate) fn run(verbose: bool, path: &Path, workspace: &Path) -> Result<()> {
+    let workspace = std::env::current_dir()?.join(workspace);
+    let messages = read_recording(path, &workspace)?;
+    let recorded: HashMap<u64, &RawResponse> = messages
+        .iter()
+        .filter(|it| it.from == Peer::Server)
+        .filter_map(|it| match &it.msg {
+            RawMessage::Response(resp) => Some((resp.id, resp)),
+            _ => None,
+        })
+        .collect();
+
+    let (client_sender, server_receiver) = unbounded::<RawMessage>();
+    let (server_sender, client_receiver) = unbounded::<RawMessage>();
+    let server =
+        thread::spawn(move || ra_lsp_server::run_server(server_receiver, server_sender, workspace));
+
+    let mut replay = Replay {
+        receiver: client_receiver,
+        start: Instant::now(),
+        responses: HashMap::new(),
+        server_requests: HashSet::new(),
+        disconnected: false,
+    };
+    let mut requests: Vec<&RawRequest> = Vec::new();
+    for message in messages.iter().filter(|it| it.from == Peer::Client) {
+        replay.wait_until(replay.start + Duration::from_millis(message.time_ms));
+        match &message.msg {
+            RawMessage::Request(req) => requests.push(req),
+            // The server ignores the responses to the requests it didn't send.
+            RawMessage::Response(resp) => replay.wait_for_server_request(resp.id),
+            RawMessage::Notification(_) => (),
+        }
+        if client_sender.send(message.msg.clone()).is_err() {
+            break;
+        }
+    }
+    replay.wait_for_responses(requests.iter().map(|it| it.id));
+    drop(client_sender);
+    match server.join() {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => eprintln!("server exited with an error: {}", e),
+        Err(_) => eprintln!("server panicked"),
+    }
+
+    let mut same = 0;
+    let mut different = 0;
+    let mut canceled = 0;
+    let mut unanswered = 0;
+    for req in requests {
+        let (recorded, replayed) = match (recorded.get(&req.id), replay.responses.get(&req.id)) {
+            (Some(recorded), Some(replayed)) => {
+                (response_value(recorded), response_value(replayed))
+            }
+            (Some(_), None) => {
+                println!("{} #{}: no response", req.method, req.id);
+                unanswered += 1;
+                continue;
+            }
+            // The recording stopped before the response.
+            (None, _) => continue,
+        };
+        if is_canceled(&recorded) || is_canceled(&replayed) {
+            canceled += 1;
+            continue;
+        }
+        match find_difference(&recorded, &replayed, String::new()) {
+            None => same += 1,
+            Some((pointer, recorded_part, replayed_part)) => {
+                different += 1;
+                println!("{} #{}: responses differ at `{}`", req.method, req.id, pointer);
+                println!("    recorded: {}", recorded_part);
+                println!("    replayed: {}", replayed_part);
+                if verbose {
+                    println!("params:\n{}", serde_json::to_string_pretty(&req.params)?);
+                    println!("recorded:\n{}", serde_json::to_string_pretty(&recorded)?);
+                    println!("replayed:\n{}\n", serde_json::to_string_pretty(&replayed)?);
+                }
+            }
+        }
+    }
+    println!(
+        "{} same, {} different, {} canceled, {} unanswered",
+        same, different, canceled, unanswered
+    );
+    if different + unanswered > 0 {
+        Err(format!("{} responses don't match the recording", different + unanswered))?;
+    }
+    Ok(())
+}
+
+struct Replay {
+    receiver: Receiver<RawMessage>,
+    start: Instant,
+    responses: HashMap<u64, RawResponse>,
+    /// The ids of the requests which the server sent to the client.
+    server_requests: HashSet<u64>,
+    disconnected: bool,
+}
+
+impl Replay {
+    /// Handles the messages of the server until `deadline`, or until `done`.
+    fn receive_until(&mut self, deadline: Instant, done: impl Fn(&Replay) -> bool) {
+        while !self.disconnected && !done(self) {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            match self.receiver.recv_timeout(deadline - now) {
+                Ok(RawMessage::Response(resp)) => {
+                    self.responses.insert(resp.id, resp);
+                }
+                Ok(RawMessage::Request(req)) => {
+                    self.server_requests.insert(req.id);
+                }
+                Ok(RawMessage::Notification(_)) => (),
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => self.disconnected = true,
+            }
+        }
+    }
+
+    fn wait_until(&mut self, deadline: Instant) {
+        self.receive_until(deadline, |_| false)
+    }
+
+    fn wait_for_server_request(&mut self, id: u64) {
+        self.receive_until(Instant::now() + TIMEOUT, |it| it.server_requests.contains(&id));
+        if !self.server_requests.contains(&id) {
+            eprintln!("the server didn't send the request #{}", id);
+        }
+    }
+
+    fn wait_for_responses(&mut self, ids: impl Iterator<Item = u64>) {
+        let ids: Vec<u64> = ids.collect();
+        self.receive_until(Instant::now() + TIMEOUT, |it| {
+            ids.iter().all(|id| it.responses.contains_key(id))
+        });
+    }
+}
+
+fn response_value(resp: &RawResponse) -> Value {
+    match &resp.error {
+        Some(error) => {
+            serde_json::json!({ "error": { "code": error.code, "message": error.message } })
+        }
+        None => serde_json::json!({ "result": resp.result }),
+    }
+}
+
+/// Whether the request was canceled, by the client or because of an edit,
+/// which depends on timing rather than on the server.
+fn is_canceled(value: &Value) -> bool {
+    let code = value.pointer("/error/code").and_then(|it| it.as_i64());
+    code == Some(ErrorCode::RequestCanceled as i64)
+        || code == Some(ErrorCode::ContentModified as i64)
+}
+
+/// Returns the JSON pointer of the first difference between the values, and
+/// the values there.
+fn find_difference<'a>(
+    recorded: &'a Value,
+    replayed: &'a Value,
+    pointer: String,
+) -> Option<(String, &'a Value, &'a Value)> {
+    match (recorded, replayed) {
+        (Value::Object(recorded), Value::Object(replayed)) => {
+            let keys: Vec<&String> = recorded.keys().chain(replayed.keys()).collect();
+            keys.into_iter().find_map(|key| match (recorded.get(key), replayed.get(key)) {
+                (Some(l), Some(r)) => find_difference(l, r, format!("{}/{}", pointer, key)),
+                (l, r) => {
+                    Some((format!("{}/{}", pointer, key), l.unwrap_or(&NULL), r.unwrap_or(&NULL)))
+                }
+            })
+        }
+        (Value::Array(l), Value::Array(r)) if l.len() == r.len() => l
+            .iter()
+            .zip(r.iter())
+            .enumerate()
+            .find_map(|(i, (l, r))| find_difference(l, r, format!("{}/{}", pointer, i))),
+        (l, r) if l == r => None,
+        (l, r) => Some((pointer, l, r)),
+    }
+}
@@ -5,6 +5,8 @@
 /// Note that neither this module, nor any other part of the analyzer's core do
 /// actual IO. See `vfs` and `project_model` in the `ra_lsp_server` crate for how
 /// actual IO is done and lowered to input.
+use std::{fmt, panic::RefUnwindSafe, sync::Arc};
+
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashMap;
 
@@ -36,7 +38,7 @@ pub struct SourceRoot {
 
 /// `CrateGraph` is a bit of information which turns a set of text files into a
 /// number of Rust crates. Each crate is defined by the `FileId` of its root module,
-/// the set of cfg flags (not yet implemented) and the set of dependencies. Note
+/// the set of cfg flags and the set of dependencies. Note
 /// that, due to cfg's, there might be several crates for a single `FileId`! As
 /// in the rust-lang proper, a crate does not have a name. Instead, names are
 /// specified on dependency edges. That is, a crate might be known under
@@ -71,20 +73,101 @@ impl Edition {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcMacroKind {
+    CustomDerive,
+    FuncLike,
+    Attr,
+}
+
+/// Expands a single procedural macro. The actual expansion happens outside of
+/// the analyzer (for example, in a separate process which loads the compiled
+/// proc-macro crate), so this is an opaque callback from the point of view of
+/// the database.
+pub trait ProcMacroExpander: fmt::Debug + Send + Sync + RefUnwindSafe {
+    /// Expands `subtree`. For attribute macros, `attrs` holds the arguments of
+    /// the attribute, and `subtree` is the item the attribute is applied to.
+    fn expand(
+        &self,
+        subtree: &tt::Subtree,
+        attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, String>;
+}
+
+/// A procedural macro exported by a proc-macro crate.
+#[derive(Debug, Clone)]
+pub struct ProcMacro {
+    pub name: SmolStr,
+    pub kind: ProcMacroKind,
+    pub expander: Arc<dyn ProcMacroExpander>,
+}
+
+// `CrateGraph` is a salsa input, so it has to be comparable. Expanders are
+// compared by identity: reloading a proc-macro library creates new expanders.
+impl PartialEq for ProcMacro {
+    fn eq(&self, other: &ProcMacro) -> bool {
+        self.name == other.name
+            && self.kind == other.kind
+            && Arc::ptr_eq(&self.expander, &other.expander)
+    }
+}
+
+impl Eq for ProcMacro {}
+
+/// The `cfg` flags a crate is compiled with: atoms like `test` and key-value
+/// pairs like `feature = "serde"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CfgOptions {
+    atoms: FxHashSet<SmolStr>,
+    key_values: FxHashSet<(SmolStr, SmolStr)>,
+}
+
+impl CfgOptions {
+    pub fn insert_atom(&mut self, key: SmolStr) {
+        self.atoms.insert(key);
+    }
+
+    pub fn insert_key_value(&mut self, key: SmolStr, value: SmolStr) {
+        self.key_values.insert((key, value));
+    }
+
+    pub fn check_atom(&self, name: &str) -> bool {
+        self.atoms.contains(name)
+    }
+
+    pub fn check_key_value(&self, key: &str, value: &str) -> bool {
+        self.key_values.contains(&(key.into(), value.into()))
+    }
+
+    /// Values of all `key = "..."` pairs with the given key, like the enabled
+    /// features for `feature`.
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a SmolStr> + 'a {
+        self.key_values.iter().filter(move |(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
     edition: Edition,
     dependencies: Vec<Dependency>,
+    proc_macros: Vec<ProcMacro>,
+    cfg_options: CfgOptions,
 }
 
 impl CrateData {
     fn new(file_id: FileId, edition: Edition) -> CrateData {
-        CrateData { file_id, edition, dependencies: Vec::new() }
+        CrateData {
+            file_id,
+            edition,
+            dependencies: Vec::new(),
+            proc_macros: Vec::new(),
+            cfg_options: CfgOptions::default(),
+        }
     }
 
-    fn add_dep(&mut self, name: SmolStr, crate_id: CrateId) {
-        self.dependencies.push(Dependency { name, crate_id })
+    fn add_dep(&mut self, name: SmolStr, crate_id: CrateId, prelude: bool) {
+        self.dependencies.push(Dependency { name, crate_id, prelude })
     }
 }
 
@@ -92,6 +175,9 @@ impl CrateData {
 pub struct Dependency {
     pub crate_id: CrateId,
     pub name: SmolStr,
+    /// Whether the dependency is in the extern prelude. Crates of the
+    /// sysroot, like `alloc`, are only visible after an `extern crate`.
+    pub prelude: bool,
 }
 
 impl Dependency {
@@ -113,11 +199,30 @@ impl CrateGraph {
         from: CrateId,
         name: SmolStr,
         to: CrateId,
+    ) -> Result<(), CyclicDependencies> {
+        self.add_dep_with_prelude(from, name, to, true)
+    }
+
+    pub fn add_dep_with_prelude(
+        &mut self,
+        from: CrateId,
+        name: SmolStr,
+        to: CrateId,
+        prelude: bool,
     ) -> Result<(), CyclicDependencies> {
         if self.dfs_find(from, to, &mut FxHashSet::default()) {
             return Err(CyclicDependencies);
         }
-        Ok(self.arena.get_mut(&from).unwrap().add_dep(name, to))
+        Ok(self.arena.get_mut(&from).unwrap().add_dep(name, to, prelude))
+    }
+
+    /// Sets the procedural macros exported by a proc-macro crate.
+    pub fn set_proc_macros(&mut self, crate_id: CrateId, proc_macros: Vec<ProcMacro>) {
+        self.arena.get_mut(&crate_id).unwrap().proc_macros = proc_macros;
+    }
+
+    pub fn set_cfg_options(&mut self, crate_id: CrateId, cfg_options: CfgOptions) {
+        self.arena.get_mut(&crate_id).unwrap().cfg_options = cfg_options;
     }
 
     pub fn is_empty(&self) -> bool {
@@ -136,12 +241,28 @@ impl CrateGraph {
         self.arena[&crate_id].edition
     }
 
+    pub fn proc_macros(&self, crate_id: CrateId) -> &[ProcMacro] {
+        &self.arena[&crate_id].proc_macros
+    }
+
+    pub fn cfg_options(&self, crate_id: CrateId) -> &CfgOptions {
+        &self.arena[&crate_id].cfg_options
+    }
+
     // FIXME: this only finds one crate with the given root; we could have multiple
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self.arena.iter().find(|(_crate_id, data)| data.file_id == file_id)?;
         Some(crate_id)
     }
 
+    /// All crates with the given root, like a library and its unit tests.
+    pub fn crates_for_crate_root<'a>(
+        &'a self,
+        file_id: FileId,
+    ) -> impl Iterator<Item = CrateId> + 'a {
+        self.arena.iter().filter(move |(_, data)| data.file_id == file_id).map(|(&id, _)| id)
+    }
+
     pub fn dependencies<'a>(
         &'a self,
         crate_id: CrateId,
@@ -183,7 +304,7 @@ impl CrateGraph {
 
 #[cfg(test)]
 mod tests {
-    use super::{CrateGraph, FileId, SmolStr, Edition::Edition2018};
+    use super::{CrateGraph, CfgOptions, FileId, SmolStr, Edition::Edition2018};
 
     #[test]
     fn it_should_panic_because_of_cycle_dependencies() {
@@ -205,4 +326,31 @@ mod tests {
         assert!(graph.add_dep(crate1, SmolStr::new("crate2"), crate2).is_ok());
         assert!(graph.add_dep(crate2, SmolStr::new("crate3"), crate3).is_ok());
     }
+
+    #[test]
+    fn cfg_options_are_per_crate() {
+        let mut graph = CrateGraph::default();
+        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
+        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
+        let mut cfg = CfgOptions::default();
+        cfg.insert_atom("test".into());
+        cfg.insert_key_value("feature".into(), "serde".into());
+        graph.set_cfg_options(crate1, cfg);
+
+        assert!(graph.cfg_options(crate1).check_atom("test"));
+        assert!(graph.cfg_options(crate1).check_key_value("feature", "serde"));
+        assert!(!graph.cfg_options(crate1).check_key_value("feature", "std"));
+        assert!(!graph.cfg_options(crate2).check_atom("test"));
+    }
+
+    #[test]
+    fn several_crates_for_one_root() {
+        let mut graph = CrateGraph::default();
+        let lib = graph.add_crate_root(FileId(1u32), Edition2018);
+        let lib_tests = graph.add_crate_root(FileId(1u32), Edition2018);
+        graph.add_crate_root(FileId(2u32), Edition2018);
+        let mut crates = graph.crates_for_crate_root(FileId(1u32)).collect::<Vec<_>>();
+        crates.sort();
+        assert_eq!(crates, vec![lib, lib_tests]);
+    }
 }
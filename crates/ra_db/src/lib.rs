@@ -1,6 +1,7 @@
 //! ra_db defines basic database traits. The concrete DB is defined by ra_ide_api.
 mod cancellation;
 mod input;
+mod reparse;
 
 use std::{panic, sync::Arc};
 
@@ -12,8 +13,10 @@ pub use ::salsa as salsa;
 pub use crate::{
     cancellation::Canceled,
     input::{
-        FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency, Edition,
+        FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency, Edition, ProcMacro,
+        ProcMacroKind, ProcMacroExpander, CfgOptions,
     },
+    reparse::FileEdits,
 };
 
 pub trait CheckCanceled {
@@ -74,6 +77,11 @@ pub trait SourceDatabase: CheckCanceled + std::fmt::Debug {
     /// Text of the file.
     #[salsa::input]
     fn file_text(&self, file_id: FileId) -> Arc<String>;
+    /// The edits which turn the text the file had when it was last parsed
+    /// into the current one. Set to `None` together with the text of new
+    /// files.
+    #[salsa::input]
+    fn file_edits(&self, file_id: FileId) -> Option<Arc<FileEdits>>;
     // Parses the file into the syntax tree.
     #[salsa::invoke(parse_query)]
     fn parse(&self, file_id: FileId) -> Parse;
@@ -95,13 +103,20 @@ pub trait SourceDatabase: CheckCanceled + std::fmt::Debug {
 fn source_root_crates(db: &impl SourceDatabase, id: SourceRootId) -> Arc<Vec<CrateId>> {
     let root = db.source_root(id);
     let graph = db.crate_graph();
-    let res =
-        root.files.values().filter_map(|&it| graph.crate_id_for_crate_root(it)).collect::<Vec<_>>();
+    let mut res =
+        root.files.values().flat_map(|&it| graph.crates_for_crate_root(it)).collect::<Vec<_>>();
+    // Several crates might share a root, like a library and its unit tests in
+    // `rust-project.json`. The crate compiled without `cfg(test)` goes first,
+    // as that's the one other crates depend on.
+    res.sort_by_key(|&it| (graph.cfg_options(it).check_atom("test"), it));
     Arc::new(res)
 }
 
 fn parse_query(db: &impl SourceDatabase, file_id: FileId) -> Parse {
     let _p = profile("parse_query");
     let text = db.file_text(file_id);
+    if let Some(parse) = db.file_edits(file_id).and_then(|it| it.reparse(&text)) {
+        return parse;
+    }
     SourceFile::parse(&*text)
 }
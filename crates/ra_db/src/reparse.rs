@@ -0,0 +1,31 @@
+//! Incremental reparsing of edited files.
+//!
+//! `parse` is a function of the text of the file, so the old syntax tree
+//! would be thrown away when the text changes. For the files which are being
+//! edited, the `file_edits` input keeps the old tree around together with the
+//! edits, and `parse` reparses only the blocks which were touched by them.
+use ra_syntax::Parse;
+use ra_text_edit::AtomTextEdit;
+
+/// The syntax tree of a file before it was edited, and the edits which turn
+/// its text into the current one, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdits {
+    pub parse: Parse,
+    pub edits: Vec<AtomTextEdit>,
+}
+
+impl FileEdits {
+    /// Applies the edits to the old tree. Returns `None` if they don't result
+    /// in `text`.
+    pub(crate) fn reparse(&self, text: &str) -> Option<Parse> {
+        let mut parse = self.parse.clone();
+        for edit in self.edits.iter() {
+            parse = parse.reparse(edit);
+        }
+        if parse.tree.syntax().text() != text {
+            return None;
+        }
+        Some(parse)
+    }
+}
@@ -35,6 +35,8 @@ pub struct Crate {
 pub struct CrateDependency {
     pub krate: Crate,
     pub name: Name,
+    /// Whether the crate is in the extern prelude, or needs an `extern crate`.
+    pub prelude: bool,
 }
 
 impl Crate {
@@ -48,7 +50,7 @@ impl Crate {
             .map(|dep| {
                 let krate = Crate { crate_id: dep.crate_id() };
                 let name = dep.as_name();
-                CrateDependency { krate, name }
+                CrateDependency { krate, name, prelude: dep.prelude }
             })
             .collect()
     }
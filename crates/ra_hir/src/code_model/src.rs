@@ -3,7 +3,8 @@ use ra_syntax::{TreeArc, ast};
 use crate::{
     HirFileId, DefDatabase, AstDatabase, Module, ModuleSource,
     StructField, Struct, Enum, Union, EnumVariant, Function, Static, Trait, Const, TypeAlias,
-    FieldSource, MacroDef, ids::AstItemDef,
+    FieldSource, MacroDef,
+    ids::{AstItemDef, MacroDefKind},
 };
 
 pub struct Source<T> {
@@ -102,9 +103,18 @@ impl HasSource for TypeAlias {
         self.id.source(db)
     }
 }
-impl HasSource for MacroDef {
-    type Ast = TreeArc<ast::MacroCall>;
-    fn source(self, db: &(impl DefDatabase + AstDatabase)) -> Source<TreeArc<ast::MacroCall>> {
-        Source { file_id: self.id.0.file_id(), ast: self.id.0.to_node(db) }
+impl MacroDef {
+    /// Returns the `macro_rules!` definition of the macro. Procedural macros
+    /// are compiled separately and don't have a source.
+    pub fn source(
+        self,
+        db: &(impl DefDatabase + AstDatabase),
+    ) -> Option<Source<TreeArc<ast::MacroCall>>> {
+        match self.id.0 {
+            MacroDefKind::MacroRules(ast_id) => {
+                Some(Source { file_id: ast_id.file_id(), ast: ast_id.to_node(db) })
+            }
+            MacroDefKind::ProcMacro { .. } => None,
+        }
     }
 }
@@ -0,0 +1,130 @@
+//! Built-in derives, like `#[derive(Clone, Debug)]`.
+//!
+//! Custom derives are procedural macros and are expanded during name
+//! resolution, like any other macro. Built-in derives are not expanded.
+//! Instead, when collecting the impl blocks of a module, we synthesize an impl
+//! without items for every derive of a known core trait (see `impl_block.rs`).
+//! This is enough for trait solving and method resolution, which look up the
+//! methods on the trait itself.
+
+use std::sync::Arc;
+
+use ra_syntax::{
+    AstNode, SmolStr, T,
+    SyntaxKind::{IDENT, R_PAREN},
+    ast::{self, NameOwner, TypeParamsOwner},
+};
+
+use crate::{
+    Name, AsName,
+    path::{Path, PathKind, PathSegment, GenericArgs, GenericArg},
+    type_ref::TypeRef,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum BuiltinDerive {
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+}
+
+impl BuiltinDerive {
+    pub(crate) fn from_name(name: &str) -> Option<BuiltinDerive> {
+        let res = match name {
+            "Clone" => BuiltinDerive::Clone,
+            "Copy" => BuiltinDerive::Copy,
+            "Debug" => BuiltinDerive::Debug,
+            "Default" => BuiltinDerive::Default,
+            "Hash" => BuiltinDerive::Hash,
+            "PartialEq" => BuiltinDerive::PartialEq,
+            "Eq" => BuiltinDerive::Eq,
+            "PartialOrd" => BuiltinDerive::PartialOrd,
+            "Ord" => BuiltinDerive::Ord,
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    /// The module and the name of the derived trait in `core`.
+    fn core_module_and_name(self) -> (&'static str, &'static str) {
+        match self {
+            BuiltinDerive::Clone => ("clone", "Clone"),
+            BuiltinDerive::Copy => ("marker", "Copy"),
+            BuiltinDerive::Debug => ("fmt", "Debug"),
+            BuiltinDerive::Default => ("default", "Default"),
+            BuiltinDerive::Hash => ("hash", "Hash"),
+            BuiltinDerive::PartialEq => ("cmp", "PartialEq"),
+            BuiltinDerive::Eq => ("cmp", "Eq"),
+            BuiltinDerive::PartialOrd => ("cmp", "PartialOrd"),
+            BuiltinDerive::Ord => ("cmp", "Ord"),
+        }
+    }
+
+    /// The absolute path of the derived trait, e.g. `::core::clone::Clone`.
+    /// Like the expansions of rustc, it goes through `core`, which is in the
+    /// extern prelude of both the `std` and the `#![no_std]` crates.
+    pub(crate) fn trait_path(self) -> Path {
+        let (module, name) = self.core_module_and_name();
+        let segments = ["core", module, name]
+            .iter()
+            .map(|&it| PathSegment { name: Name::std_item(it), args_and_bindings: None })
+            .collect();
+        Path { kind: PathKind::Abs, segments }
+    }
+}
+
+/// Returns the built-in derives requested by `attr`, if it is a `#[derive]`.
+pub(crate) fn builtin_derives(attr: &ast::Attr) -> Vec<BuiltinDerive> {
+    derive_names(attr).iter().filter_map(|name| BuiltinDerive::from_name(name)).collect()
+}
+
+/// Returns the names of the derives requested by `attr`, if it is a
+/// `#[derive]`.
+pub(crate) fn derive_names(attr: &ast::Attr) -> Vec<SmolStr> {
+    let (name, tt) = match attr.as_call() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    if name != "derive" {
+        return Vec::new();
+    }
+    // In `derive(Foo, some::path::Bar)`, the last identifier of each
+    // comma-separated path names the derive.
+    let mut res = Vec::new();
+    let mut last_ident = None;
+    for element in tt.syntax().children_with_tokens() {
+        match element.kind() {
+            IDENT => last_ident = element.as_token().map(|it| it.text().clone()),
+            T![,] | R_PAREN => res.extend(last_ident.take()),
+            _ => (),
+        }
+    }
+    res
+}
+
+/// The self type of a derived impl: the ADT applied to its own type
+/// parameters, i.e. `Foo<T, U>` for `struct Foo<T, U>`.
+pub(crate) fn derived_self_type(adt: &(impl NameOwner + TypeParamsOwner)) -> TypeRef {
+    let name = adt.name().map(AsName::as_name).unwrap_or_else(Name::missing);
+    let args: Vec<_> = adt
+        .type_param_list()
+        .into_iter()
+        .flat_map(|it| it.type_params())
+        .map(|param| {
+            let name = param.name().map(AsName::as_name).unwrap_or_else(Name::missing);
+            GenericArg::Type(TypeRef::Path(name.into()))
+        })
+        .collect();
+    let args_and_bindings =
+        if args.is_empty() { None } else { Some(Arc::new(GenericArgs { args })) };
+    TypeRef::Path(Path {
+        kind: PathKind::Plain,
+        segments: vec![PathSegment { name, args_and_bindings }],
+    })
+}
@@ -114,6 +114,71 @@ impl Diagnostic for UnresolvedModule {
     }
 }
 
+#[derive(Debug)]
+pub struct UnresolvedImport {
+    pub file: HirFileId,
+    pub use_tree: AstPtr<ast::UseTree>,
+}
+
+impl Diagnostic for UnresolvedImport {
+    fn message(&self) -> String {
+        "unresolved import".to_string()
+    }
+    fn file(&self) -> HirFileId {
+        self.file
+    }
+    fn syntax_node_ptr(&self) -> SyntaxNodePtr {
+        self.use_tree.into()
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct AmbiguousImport {
+    pub file: HirFileId,
+    pub use_tree: AstPtr<ast::UseTree>,
+    pub name: Name,
+}
+
+impl Diagnostic for AmbiguousImport {
+    fn message(&self) -> String {
+        format!("`{}` is ambiguous", self.name)
+    }
+    fn file(&self) -> HirFileId {
+        self.file
+    }
+    fn syntax_node_ptr(&self) -> SyntaxNodePtr {
+        self.use_tree.into()
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct PrivateItem {
+    pub file: HirFileId,
+    pub use_tree: AstPtr<ast::UseTree>,
+    pub name: Name,
+}
+
+impl Diagnostic for PrivateItem {
+    fn message(&self) -> String {
+        format!("`{}` is private", self.name)
+    }
+    fn file(&self) -> HirFileId {
+        self.file
+    }
+    fn syntax_node_ptr(&self) -> SyntaxNodePtr {
+        self.use_tree.into()
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct MissingFields {
     pub file: HirFileId,
@@ -5,14 +5,14 @@ use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id, map::ArenaMap};
 use ra_syntax::{
-    SyntaxNodePtr, AstPtr, AstNode,
+    SyntaxNodePtr, AstPtr, AstNode, SyntaxNode, TreeArc,
     ast::{self, TryBlockBodyOwner, LoopBodyOwner, ArgListOwner, NameOwner, LiteralKind,ArrayExprKind, TypeAscriptionOwner},
 };
 
 use crate::{
-    Path, Name, HirDatabase, Resolver,DefWithBody, Either, HirFileId, MacroCallLoc, MacroFileKind,
-    HasSource,
+    Path, Name, HirDatabase, Resolver, DefWithBody, Either, HirFileId, MacroFileKind, HasSource,
     name::AsName,
+    ids::{expand_macro_call, MACRO_EXPANSION_DEPTH_LIMIT},
     type_ref::{Mutability, TypeRef},
 };
 use crate::{path::GenericArgs, ty::primitive::{IntTy, UncertainIntTy, FloatTy, UncertainFloatTy}};
@@ -499,6 +499,8 @@ pub(crate) struct ExprCollector<DB> {
     // current == original (see #1196)
     original_file_id: HirFileId,
     current_file_id: HirFileId,
+    /// How many macro expansions the current node is nested in.
+    macro_depth: usize,
 }
 
 impl<'a, DB> ExprCollector<&'a DB>
@@ -517,6 +519,7 @@ where
             body_expr: None,
             original_file_id: file_id,
             current_file_id: file_id,
+            macro_depth: 0,
         }
     }
     fn alloc_expr(&mut self, expr: Expr, syntax_ptr: SyntaxNodePtr) -> ExprId {
@@ -732,7 +735,7 @@ where
             }
             ast::ExprKind::CastExpr(e) => {
                 let expr = self.collect_expr_opt(e.expr());
-                let type_ref = TypeRef::from_ast_opt(e.type_ref());
+                let type_ref = self.collect_type_ref_opt(e.type_ref());
                 self.alloc_expr(Expr::Cast { expr, type_ref }, syntax_ptr)
             }
             ast::ExprKind::RefExpr(e) => {
@@ -754,7 +757,7 @@ where
                 if let Some(pl) = e.param_list() {
                     for param in pl.params() {
                         let pat = self.collect_pat_opt(param.pat());
-                        let type_ref = param.ascribed_type().map(TypeRef::from_ast);
+                        let type_ref = param.ascribed_type().map(|it| self.collect_type_ref(it));
                         args.push(pat);
                         arg_types.push(type_ref);
                     }
@@ -828,26 +831,10 @@ where
             ast::ExprKind::IndexExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
             ast::ExprKind::RangeExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
             ast::ExprKind::MacroCall(e) => {
-                let ast_id = self
-                    .db
-                    .ast_id_map(self.current_file_id)
-                    .ast_id(e)
-                    .with_file_id(self.current_file_id);
-
-                if let Some(path) = e.path().and_then(Path::from_ast) {
-                    if let Some(def) = self.resolver.resolve_path_as_macro(self.db, &path) {
-                        let call_id = MacroCallLoc { def: def.id, ast_id }.id(self.db);
-                        let file_id = call_id.as_file(MacroFileKind::Expr);
-                        if let Some(node) = self.db.parse_or_expand(file_id) {
-                            if let Some(expr) = ast::Expr::cast(&*node) {
-                                log::debug!("macro expansion {}", expr.syntax().debug_dump());
-                                let old_file_id =
-                                    std::mem::replace(&mut self.current_file_id, file_id);
-                                let id = self.collect_expr(&expr);
-                                self.current_file_id = old_file_id;
-                                return id;
-                            }
-                        }
+                if let Some((file_id, node)) = self.expand_macro(e, MacroFileKind::Expr) {
+                    if let Some(expr) = ast::Expr::cast(&*node) {
+                        log::debug!("macro expansion {}", expr.syntax().debug_dump());
+                        return self.in_expansion(file_id, |this| this.collect_expr(&expr));
                     }
                 }
                 // FIXME: Instead of just dropping the error from expansion
@@ -866,24 +853,48 @@ where
     }
 
     fn collect_block(&mut self, block: &ast::Block) -> ExprId {
-        let statements = block
-            .statements()
-            .map(|s| match s.kind() {
-                ast::StmtKind::LetStmt(stmt) => {
-                    let pat = self.collect_pat_opt(stmt.pat());
-                    let type_ref = stmt.ascribed_type().map(TypeRef::from_ast);
-                    let initializer = stmt.initializer().map(|e| self.collect_expr(e));
-                    Statement::Let { pat, type_ref, initializer }
-                }
-                ast::StmtKind::ExprStmt(stmt) => {
-                    Statement::Expr(self.collect_expr_opt(stmt.expr()))
-                }
-            })
-            .collect();
+        let mut statements = Vec::new();
+        for stmt in block.statements() {
+            self.collect_stmt(stmt, &mut statements);
+        }
         let tail = block.expr().map(|e| self.collect_expr(e));
         self.alloc_expr(Expr::Block { statements, tail }, SyntaxNodePtr::new(block.syntax()))
     }
 
+    fn collect_stmt(&mut self, stmt: &ast::Stmt, statements: &mut Vec<Statement>) {
+        match stmt.kind() {
+            ast::StmtKind::LetStmt(stmt) => {
+                let pat = self.collect_pat_opt(stmt.pat());
+                let type_ref = stmt.ascribed_type().map(|it| self.collect_type_ref(it));
+                let initializer = stmt.initializer().map(|e| self.collect_expr(e));
+                statements.push(Statement::Let { pat, type_ref, initializer });
+            }
+            ast::StmtKind::ExprStmt(stmt) => {
+                // A macro call in statement position may expand to several
+                // statements, which are spliced into the enclosing block.
+                if let Some(ast::ExprKind::MacroCall(call)) = stmt.expr().map(|e| e.kind()) {
+                    if let Some((file_id, node)) =
+                        self.expand_macro(call, MacroFileKind::Statements)
+                    {
+                        if let Some(macro_stmts) = ast::MacroStmts::cast(&*node) {
+                            self.in_expansion(file_id, |this| {
+                                for stmt in macro_stmts.statements() {
+                                    this.collect_stmt(stmt, statements);
+                                }
+                                if let Some(expr) = macro_stmts.expr() {
+                                    let expr = this.collect_expr(expr);
+                                    statements.push(Statement::Expr(expr));
+                                }
+                            });
+                            return;
+                        }
+                    }
+                }
+                statements.push(Statement::Expr(self.collect_expr_opt(stmt.expr())));
+            }
+        }
+    }
+
     fn collect_block_opt(&mut self, block: Option<&ast::Block>) -> ExprId {
         if let Some(block) = block {
             self.collect_block(block)
@@ -943,6 +954,17 @@ where
                 Pat::Struct { path, args: fields }
             }
 
+            ast::PatKind::MacroPat(p) => {
+                let expansion =
+                    p.macro_call().and_then(|call| self.expand_macro(call, MacroFileKind::Pat));
+                if let Some((file_id, node)) = expansion {
+                    if let Some(pat) = ast::Pat::cast(&*node) {
+                        return self.in_expansion(file_id, |this| this.collect_pat(pat));
+                    }
+                }
+                Pat::Missing
+            }
+
             // FIXME: implement
             ast::PatKind::LiteralPat(_) => Pat::Missing,
             ast::PatKind::SlicePat(_) | ast::PatKind::RangePat(_) => Pat::Missing,
@@ -959,6 +981,48 @@ where
         }
     }
 
+    fn collect_type_ref(&mut self, type_ref: &ast::TypeRef) -> TypeRef {
+        TypeRef::from_ast_with_macros(type_ref, &mut |call| self.expand_type_macro(call))
+    }
+
+    fn collect_type_ref_opt(&mut self, type_ref: Option<&ast::TypeRef>) -> TypeRef {
+        if let Some(type_ref) = type_ref {
+            self.collect_type_ref(type_ref)
+        } else {
+            TypeRef::Error
+        }
+    }
+
+    fn expand_type_macro(&mut self, call: &ast::MacroCall) -> Option<TypeRef> {
+        let (file_id, node) = self.expand_macro(call, MacroFileKind::Type)?;
+        let type_ref = ast::TypeRef::cast(&*node)?;
+        Some(self.in_expansion(file_id, |this| this.collect_type_ref(type_ref)))
+    }
+
+    /// Expands `call`, unless it is nested in too many expansions already,
+    /// like the calls of a recursive macro.
+    fn expand_macro(
+        &self,
+        call: &ast::MacroCall,
+        kind: MacroFileKind,
+    ) -> Option<(HirFileId, TreeArc<SyntaxNode>)> {
+        if self.macro_depth >= MACRO_EXPANSION_DEPTH_LIMIT {
+            log::debug!("macro expansion depth limit reached");
+            return None;
+        }
+        expand_macro_call(self.db, &self.resolver, self.current_file_id, call, kind)
+    }
+
+    /// Collects the nodes of the expansion `file_id` with `f`.
+    fn in_expansion<T>(&mut self, file_id: HirFileId, f: impl FnOnce(&mut Self) -> T) -> T {
+        let old_file_id = std::mem::replace(&mut self.current_file_id, file_id);
+        self.macro_depth += 1;
+        let res = f(self);
+        self.macro_depth -= 1;
+        self.current_file_id = old_file_id;
+        res
+    }
+
     fn collect_const_body(&mut self, node: &ast::ConstDef) {
         let body = self.collect_expr_opt(node.body());
         self.body_expr = Some(body);
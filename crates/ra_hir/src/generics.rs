@@ -8,7 +8,7 @@ use std::sync::Arc;
 use ra_syntax::ast::{self, NameOwner, TypeParamsOwner, TypeBoundsOwner, DefaultTypeParamOwner};
 
 use crate::{
-    HasSource,
+    HasSource, Either,
     Name, AsName, Function, Struct, Union, Enum, Trait, TypeAlias, ImplBlock, Container, AdtDef,
     db::{HirDatabase, DefDatabase, AstDatabase},
     path::Path, type_ref::TypeRef,
@@ -84,7 +84,14 @@ impl GenericParams {
                 generics.fill(&*it.source(db).ast, start + 1);
             }
             GenericDef::TypeAlias(it) => generics.fill(&*it.source(db).ast, start),
-            GenericDef::ImplBlock(it) => generics.fill(&*it.source(db).ast, start),
+            GenericDef::ImplBlock(it) => match it.derived_for(db) {
+                None => {
+                    if let Either::A(impl_block) = &it.source(db).ast {
+                        generics.fill(&**impl_block, start)
+                    }
+                }
+                Some(adt) => generics.fill_derived(db, it, adt, start),
+            },
         }
 
         Arc::new(generics)
@@ -99,6 +106,30 @@ impl GenericParams {
         }
     }
 
+    /// Derived impls have the generic parameters of the ADT they are derived
+    /// for, each additionally bounded by the derived trait.
+    fn fill_derived(
+        &mut self,
+        db: &(impl DefDatabase + AstDatabase),
+        impl_block: ImplBlock,
+        adt: AdtDef,
+        start: u32,
+    ) {
+        match adt {
+            AdtDef::Struct(it) => self.fill(&*it.source(db).ast, start),
+            AdtDef::Union(it) => self.fill(&*it.source(db).ast, start),
+            AdtDef::Enum(it) => self.fill(&*it.source(db).ast, start),
+        }
+        if let Some(TypeRef::Path(trait_ref)) = impl_block.target_trait(db) {
+            let names: Vec<Name> = self.params.iter().map(|p| p.name.clone()).collect();
+            for name in names {
+                let type_ref = TypeRef::Path(name.into());
+                self.where_predicates
+                    .push(WherePredicate { type_ref, trait_ref: trait_ref.clone() });
+            }
+        }
+    }
+
     fn fill_params(&mut self, params: &ast::TypeParamList, start: u32) {
         for (idx, type_param) in params.type_params().enumerate() {
             let name = type_param.name().map(AsName::as_name).unwrap_or_else(Name::missing);
@@ -3,14 +3,12 @@ use std::{
     sync::Arc,
 };
 
-use ra_db::{FileId, salsa};
+use ra_db::{FileId, CrateId, ProcMacroKind, salsa};
 use ra_syntax::{TreeArc, AstNode, ast, SyntaxNode};
 use ra_prof::profile;
 use mbe::MacroRules;
 
-use crate::{
-    Module, DefDatabase, AstId, FileAstId, AstDatabase, Source,
-};
+use crate::{Module, DefDatabase, AstId, FileAstId, AstDatabase, Source, Path, Resolver, Name};
 
 /// hir makes heavy use of ids: integer (u32) handlers to various things. You
 /// can think of id as a pointer (but without a lifetime) or a file descriptor
@@ -42,11 +40,20 @@ impl HirFileId {
             HirFileIdRepr::File(file_id) => file_id,
             HirFileIdRepr::Macro(macro_file) => {
                 let loc = macro_file.macro_call_id.loc(db);
-                loc.ast_id.file_id().original_file(db)
+                loc.kind.file_id().original_file(db)
             }
         }
     }
 
+    /// Returns the `FileId` if this file is written by the user, and `None`
+    /// for macro expansions.
+    pub(crate) fn as_file_id(self) -> Option<FileId> {
+        match self.0 {
+            HirFileIdRepr::File(file_id) => Some(file_id),
+            HirFileIdRepr::Macro(_) => None,
+        }
+    }
+
     /// XXX: this is a temporary function, which should go away when we implement the
     /// nameresolution+macro expansion combo. Prefer using `original_file` if
     /// possible.
@@ -91,6 +98,11 @@ impl HirFileId {
             MacroFileKind::Expr => {
                 mbe::token_tree_to_expr(&tt).ok().map(|it| it.syntax().to_owned())
             }
+            MacroFileKind::Pat => mbe::token_tree_to_pat(&tt).ok().map(|it| it.syntax().to_owned()),
+            MacroFileKind::Type => mbe::token_tree_to_ty(&tt).ok().map(|it| it.syntax().to_owned()),
+            MacroFileKind::Statements => {
+                mbe::token_tree_to_macro_stmts(&tt).ok().map(|it| it.syntax().to_owned())
+            }
         }
     }
 }
@@ -111,6 +123,9 @@ pub struct MacroFile {
 pub(crate) enum MacroFileKind {
     Items,
     Expr,
+    Pat,
+    Type,
+    Statements,
 }
 
 impl From<FileId> for HirFileId {
@@ -120,10 +135,23 @@ impl From<FileId> for HirFileId {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct MacroDefId(pub(crate) AstId<ast::MacroCall>);
+pub struct MacroDefId(pub(crate) MacroDefKind);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MacroDefKind {
+    /// A `macro_rules!` definition.
+    MacroRules(AstId<ast::MacroCall>),
+    /// The `idx`-th procedural macro exported by the proc-macro crate `krate`,
+    /// see `CrateGraph::proc_macros`.
+    ProcMacro { krate: CrateId, idx: u32 },
+}
 
 pub(crate) fn macro_def_query(db: &impl AstDatabase, id: MacroDefId) -> Option<Arc<MacroRules>> {
-    let macro_call = id.0.to_node(db);
+    let ast_id = match id.0 {
+        MacroDefKind::MacroRules(it) => it,
+        MacroDefKind::ProcMacro { .. } => return None,
+    };
+    let macro_call = ast_id.to_node(db);
     let arg = macro_call.token_tree()?;
     let (tt, _) = mbe::ast_to_token_tree(arg).or_else(|| {
         log::warn!("fail on macro_def to token tree: {:#?}", arg);
@@ -138,12 +166,59 @@ pub(crate) fn macro_def_query(db: &impl AstDatabase, id: MacroDefId) -> Option<A
 
 pub(crate) fn macro_arg_query(db: &impl AstDatabase, id: MacroCallId) -> Option<Arc<tt::Subtree>> {
     let loc = id.loc(db);
-    let macro_call = loc.ast_id.to_node(db);
-    let arg = macro_call.token_tree()?;
-    let (tt, _) = mbe::ast_to_token_tree(arg)?;
+    let tt = match &loc.kind {
+        MacroCallKind::FnLike(ast_id) => {
+            let macro_call = ast_id.to_node(db);
+            let arg = macro_call.token_tree()?;
+            mbe::ast_to_token_tree(arg)?.0
+        }
+        // Derives and attribute macros get the whole item as their input, but
+        // without the attribute which invoked them. Otherwise, we would expand
+        // the same attribute again in the expansion.
+        MacroCallKind::Derive(ast_id) => {
+            let (mut tt, _) = mbe::syntax_node_to_token_tree(ast_id.to_node(db).syntax())?;
+            strip_attrs(&mut tt, "derive");
+            tt
+        }
+        MacroCallKind::Attr(ast_id, name) => {
+            let (mut tt, _) = mbe::syntax_node_to_token_tree(ast_id.to_node(db).syntax())?;
+            strip_attrs(&mut tt, &name.to_string());
+            tt
+        }
+    };
     Some(Arc::new(tt))
 }
 
+/// Removes all `#[name...]` attributes from the top level of `tt`.
+fn strip_attrs(tt: &mut tt::Subtree, name: &str) {
+    let is_attr = |pound: &tt::TokenTree, body: Option<&tt::TokenTree>| match (pound, body) {
+        (
+            tt::TokenTree::Leaf(tt::Leaf::Punct(tt::Punct { char: '#', .. })),
+            Some(tt::TokenTree::Subtree(body)),
+        ) => {
+            body.delimiter == tt::Delimiter::Bracket
+                && match body.token_trees.first() {
+                    Some(tt::TokenTree::Leaf(tt::Leaf::Ident(ident))) => {
+                        ident.text.as_str() == name
+                    }
+                    _ => false,
+                }
+        }
+        _ => false,
+    };
+    let token_trees = std::mem::replace(&mut tt.token_trees, Vec::new());
+    let mut res = Vec::with_capacity(token_trees.len());
+    let mut iter = token_trees.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        if is_attr(&token, iter.peek()) {
+            iter.next();
+            continue;
+        }
+        res.push(token);
+    }
+    tt.token_trees = res;
+}
+
 pub(crate) fn macro_expand_query(
     db: &impl AstDatabase,
     id: MacroCallId,
@@ -151,8 +226,26 @@ pub(crate) fn macro_expand_query(
     let loc = id.loc(db);
     let macro_arg = db.macro_arg(id).ok_or("Fail to args in to tt::TokenTree")?;
 
-    let macro_rules = db.macro_def(loc.def).ok_or("Fail to find macro definition")?;
-    let tt = macro_rules.expand(&macro_arg).map_err(|err| format!("{:?}", err))?;
+    let tt = match loc.def.0 {
+        MacroDefKind::MacroRules(_) => {
+            let macro_rules = db.macro_def(loc.def).ok_or("Fail to find macro definition")?;
+            macro_rules.expand(&macro_arg).map_err(|err| format!("{:?}", err))?
+        }
+        MacroDefKind::ProcMacro { krate, idx } => {
+            let crate_graph = db.crate_graph();
+            let proc_macro = crate_graph
+                .proc_macros(krate)
+                .get(idx as usize)
+                .ok_or("Fail to find proc macro")?;
+            let attr_args = match (&loc.kind, proc_macro.kind) {
+                (MacroCallKind::Attr(ast_id, name), ProcMacroKind::Attr) => {
+                    Some(attr_macro_args(db, *ast_id, name).ok_or("Fail to find attribute")?)
+                }
+                _ => None,
+            };
+            proc_macro.expander.expand(&macro_arg, attr_args.as_ref())?
+        }
+    };
     // Set a hard limit for the expanded tt
     let count = tt.count();
     if count > 65536 {
@@ -161,6 +254,25 @@ pub(crate) fn macro_expand_query(
     Ok(Arc::new(tt))
 }
 
+/// Returns the arguments of the attribute `name` on the item, i.e. `a, b` for
+/// `#[name(a, b)]`.
+fn attr_macro_args(
+    db: &impl AstDatabase,
+    ast_id: AstId<ast::ModuleItem>,
+    name: &Name,
+) -> Option<tt::Subtree> {
+    let item = ast_id.to_node(db);
+    let attr = item
+        .syntax()
+        .children()
+        .filter_map(ast::Attr::cast)
+        .find(|attr| attr.as_named().map_or(false, |it| it.as_str() == name.to_string()))?;
+    match attr.as_call() {
+        Some((_, args)) => Some(mbe::ast_to_token_tree(args)?.0),
+        None => Some(tt::Subtree { delimiter: tt::Delimiter::None, token_trees: Vec::new() }),
+    }
+}
+
 macro_rules! impl_intern_key {
     ($name:ident) => {
         impl salsa::InternKey for $name {
@@ -183,7 +295,35 @@ impl_intern_key!(MacroCallId);
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MacroCallLoc {
     pub(crate) def: MacroDefId,
-    pub(crate) ast_id: AstId<ast::MacroCall>,
+    pub(crate) kind: MacroCallKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum MacroCallKind {
+    /// `foo!(...)`
+    FnLike(AstId<ast::MacroCall>),
+    /// A custom derive, listed in a `#[derive]` attribute of the item.
+    Derive(AstId<ast::ModuleItem>),
+    /// An attribute macro `#[name]` applied to the item.
+    Attr(AstId<ast::ModuleItem>, Name),
+}
+
+impl MacroCallKind {
+    fn file_id(&self) -> HirFileId {
+        match self {
+            MacroCallKind::FnLike(ast_id) => ast_id.file_id(),
+            MacroCallKind::Derive(ast_id) | MacroCallKind::Attr(ast_id, _) => ast_id.file_id(),
+        }
+    }
+
+    fn node(&self, db: &impl AstDatabase) -> TreeArc<SyntaxNode> {
+        match self {
+            MacroCallKind::FnLike(ast_id) => ast_id.to_node(db).syntax().to_owned(),
+            MacroCallKind::Derive(ast_id) | MacroCallKind::Attr(ast_id, _) => {
+                ast_id.to_node(db).syntax().to_owned()
+            }
+        }
+    }
 }
 
 impl MacroCallId {
@@ -203,6 +343,30 @@ impl MacroCallLoc {
     }
 }
 
+/// How deeply the macro calls in expressions, patterns and types may be
+/// nested in expansions, like rustc's default `recursion_limit`.
+pub(crate) const MACRO_EXPANSION_DEPTH_LIMIT: usize = 64;
+
+/// Resolves the macro invoked by `call` (which lives in `file_id`) and expands
+/// it, parsing the expansion according to `kind`. Returns the id of the
+/// expansion file together with its syntax tree.
+pub(crate) fn expand_macro_call(
+    db: &(impl DefDatabase + AstDatabase),
+    resolver: &Resolver,
+    file_id: HirFileId,
+    call: &ast::MacroCall,
+    kind: MacroFileKind,
+) -> Option<(HirFileId, TreeArc<SyntaxNode>)> {
+    let path = call.path().and_then(Path::from_ast)?;
+    let offset = call.syntax().range().start();
+    let def = resolver.resolve_path_as_macro(db, &path, file_id, offset)?;
+    let ast_id = db.ast_id_map(file_id).ast_id(call).with_file_id(file_id);
+    let call_id = MacroCallLoc { def: def.id, kind: MacroCallKind::FnLike(ast_id) }.id(db);
+    let file_id = call_id.as_file(kind);
+    let node = db.parse_or_expand(file_id)?;
+    Some((file_id, node))
+}
+
 #[derive(Debug)]
 pub struct ItemLoc<N: AstNode> {
     pub(crate) module: Module,
@@ -363,11 +527,11 @@ impl AstItemDef<ast::TypeAliasDef> for TypeAliasId {
 impl MacroCallId {
     pub fn debug_dump(&self, db: &impl AstDatabase) -> String {
         let loc = self.clone().loc(db);
-        let node = loc.ast_id.to_node(db);
-        let syntax_str = node.syntax().text().chunks().collect::<Vec<_>>().join(" ");
+        let node = loc.kind.node(db);
+        let syntax_str = node.text().chunks().collect::<Vec<_>>().join(" ");
 
         // dump the file name
-        let file_id: HirFileId = self.loc(db).ast_id.file_id();
+        let file_id: HirFileId = loc.kind.file_id();
         let original = file_id.original_file(db);
         let macro_rules = db.macro_def(loc.def);
 
@@ -3,38 +3,47 @@ use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id, map::ArenaMap};
 use ra_syntax::{
-    AstPtr, SourceFile, TreeArc,
-    ast::{self, AstNode}
+    AstPtr, SyntaxNode, TreeArc,
+    ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner}
 };
 
 use crate::{
     Const, TypeAlias, Function, HirFileId, AstDatabase, HasSource, Source,
-    HirDatabase, DefDatabase, TraitRef,
+    HirDatabase, DefDatabase, TraitRef, Either, AdtDef, Struct, Union, Enum,
     type_ref::TypeRef,
     ids::LocationCtx,
     resolve::Resolver,
     ty::Ty,
     generics::HasGenericParams,
+    derive::{self, BuiltinDerive},
     code_model::{Module, ModuleSource}
 };
 
+/// An impl block is either written out explicitly, or synthesized from a
+/// `#[derive]` attribute.
+pub type ImplSource = Either<TreeArc<ast::ImplBlock>, TreeArc<ast::Attr>>;
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ImplSourceMap {
-    map: ArenaMap<ImplId, AstPtr<ast::ImplBlock>>,
+    map: ArenaMap<ImplId, (HirFileId, Either<AstPtr<ast::ImplBlock>, AstPtr<ast::Attr>>)>,
 }
 
 impl ImplSourceMap {
-    fn insert(&mut self, impl_id: ImplId, impl_block: &ast::ImplBlock) {
-        self.map.insert(impl_id, AstPtr::new(impl_block))
+    fn insert(&mut self, impl_id: ImplId, file_id: HirFileId, impl_block: &ast::ImplBlock) {
+        self.map.insert(impl_id, (file_id, Either::A(AstPtr::new(impl_block))))
     }
 
-    pub fn get(&self, source: &ModuleSource, impl_id: ImplId) -> TreeArc<ast::ImplBlock> {
-        let file = match source {
-            ModuleSource::SourceFile(file) => &*file,
-            ModuleSource::Module(m) => m.syntax().ancestors().find_map(SourceFile::cast).unwrap(),
-        };
+    fn insert_derive(&mut self, impl_id: ImplId, file_id: HirFileId, derive_attr: &ast::Attr) {
+        self.map.insert(impl_id, (file_id, Either::B(AstPtr::new(derive_attr))))
+    }
 
-        self.map[impl_id].to_node(file.syntax()).to_owned()
+    pub fn get(&self, db: &impl AstDatabase, impl_id: ImplId) -> Source<ImplSource> {
+        let (file_id, ptr) = self.map[impl_id];
+        // Impls from macro expansions live in the expansion file, not in the
+        // file of the module.
+        let root = db.parse_or_expand(file_id).expect("impl block in unparsable file");
+        let ast = ptr.map(|ptr| ptr.to_node(&root).to_owned(), |ptr| ptr.to_node(&root).to_owned());
+        Source { file_id, ast }
     }
 }
 
@@ -45,11 +54,10 @@ pub struct ImplBlock {
 }
 
 impl HasSource for ImplBlock {
-    type Ast = TreeArc<ast::ImplBlock>;
-    fn source(self, db: &(impl DefDatabase + AstDatabase)) -> Source<TreeArc<ast::ImplBlock>> {
+    type Ast = ImplSource;
+    fn source(self, db: &(impl DefDatabase + AstDatabase)) -> Source<ImplSource> {
         let source_map = db.impls_in_module_with_source_map(self.module).1;
-        let src = self.module.definition_source(db);
-        Source { file_id: src.file_id, ast: source_map.get(&src.ast, self.impl_id) }
+        source_map.get(db, self.impl_id)
     }
 }
 
@@ -99,6 +107,12 @@ impl ImplBlock {
         db.impls_in_module(self.module).impls[self.impl_id].negative
     }
 
+    /// For impls synthesized from a `#[derive]`, returns the ADT the impl is
+    /// derived for.
+    pub fn derived_for(&self, db: &impl DefDatabase) -> Option<AdtDef> {
+        db.impls_in_module(self.module).impls[self.impl_id].derived_for
+    }
+
     pub(crate) fn resolver(&self, db: &impl DefDatabase) -> Resolver {
         let r = self.module().resolver(db);
         // add generic params, if present
@@ -115,6 +129,7 @@ pub struct ImplData {
     target_type: TypeRef,
     items: Vec<ImplItem>,
     negative: bool,
+    derived_for: Option<AdtDef>,
 }
 
 impl ImplData {
@@ -140,7 +155,21 @@ impl ImplData {
         } else {
             Vec::new()
         };
-        ImplData { target_trait, target_type, items, negative }
+        ImplData { target_trait, target_type, items, negative, derived_for: None }
+    }
+
+    fn from_derive(
+        adt: AdtDef,
+        adt_node: &(impl NameOwner + TypeParamsOwner),
+        derive: BuiltinDerive,
+    ) -> Self {
+        ImplData {
+            target_trait: Some(TypeRef::Path(derive.trait_path())),
+            target_type: derive::derived_self_type(adt_node),
+            items: Vec::new(),
+            negative: false,
+            derived_for: Some(adt),
+        }
     }
 
     pub fn target_trait(&self) -> Option<&TypeRef> {
@@ -208,17 +237,62 @@ impl ModuleImplBlocks {
             }
         };
 
+        m.collect_impls(db, src.file_id, node, source_map);
+
+        let ctx = LocationCtx::new(db, m.module, src.file_id);
+        for struct_ast in node.children().filter_map(ast::StructDef::cast) {
+            let id = ctx.to_def(struct_ast);
+            let adt: AdtDef =
+                if struct_ast.is_union() { Union { id }.into() } else { Struct { id }.into() };
+            m.collect_derives(adt, src.file_id, struct_ast, source_map);
+        }
+        for enum_ast in node.children().filter_map(ast::EnumDef::cast) {
+            let adt: AdtDef = Enum { id: ctx.to_def(enum_ast) }.into();
+            m.collect_derives(adt, src.file_id, enum_ast, source_map);
+        }
+
+        // Impls generated by macros, most notably by custom derives.
+        let def_map = db.crate_def_map(module.krate);
+        for &file_id in def_map[module.module_id].macro_expansions.iter() {
+            if let Some(node) = db.parse_or_expand(file_id) {
+                m.collect_impls(db, file_id, &node, source_map);
+            }
+        }
+
+        m
+    }
+
+    fn collect_impls(
+        &mut self,
+        db: &(impl DefDatabase + AstDatabase),
+        file_id: HirFileId,
+        node: &SyntaxNode,
+        source_map: &mut ImplSourceMap,
+    ) {
         for impl_block_ast in node.children().filter_map(ast::ImplBlock::cast) {
-            let impl_block = ImplData::from_ast(db, src.file_id, m.module, impl_block_ast);
-            let id = m.impls.alloc(impl_block);
-            for &impl_item in &m.impls[id].items {
-                m.impls_by_def.insert(impl_item, id);
+            let impl_block = ImplData::from_ast(db, file_id, self.module, impl_block_ast);
+            let id = self.impls.alloc(impl_block);
+            for &impl_item in &self.impls[id].items {
+                self.impls_by_def.insert(impl_item, id);
             }
 
-            source_map.insert(id, impl_block_ast);
+            source_map.insert(id, file_id, impl_block_ast);
         }
+    }
 
-        m
+    fn collect_derives(
+        &mut self,
+        adt: AdtDef,
+        file_id: HirFileId,
+        adt_node: &(impl AttrsOwner + NameOwner + TypeParamsOwner),
+        source_map: &mut ImplSourceMap,
+    ) {
+        for attr in adt_node.attrs() {
+            for derive in derive::builtin_derives(attr) {
+                let id = self.impls.alloc(ImplData::from_derive(adt, adt_node, derive));
+                source_map.insert_derive(id, file_id, attr);
+            }
+        }
     }
 }
 
@@ -38,7 +38,9 @@ mod impl_block;
 mod expr;
 mod lang_item;
 mod generics;
+mod derive;
 mod resolve;
+mod visibility;
 pub mod diagnostics;
 
 mod code_model;
@@ -68,6 +70,7 @@ pub use self::{
     resolve::Resolution,
     generics::{GenericParams, GenericParam, HasGenericParams},
     source_binder::{SourceAnalyzer, PathResolution, ScopeEntryWithSyntax},
+    visibility::{Visibility, HasVisibility},
 };
 
 pub use self::code_model::{
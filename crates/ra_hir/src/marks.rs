@@ -8,6 +8,7 @@ test_utils::marks!(
     glob_enum
     glob_across_crates
     std_prelude
+    glob_imports_are_ambiguous
     match_ergonomics_ref
     trait_resolution_on_fn_type
 );
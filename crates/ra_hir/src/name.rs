@@ -51,6 +51,27 @@ impl Name {
         Name::new("Target".into())
     }
 
+    // Needed for the crates which rustc injects into the crate root
+    pub(crate) fn std() -> Name {
+        Name::new("std".into())
+    }
+
+    pub(crate) fn core() -> Name {
+        Name::new("core".into())
+    }
+
+    // Needed for built-in derives, which refer to the derived traits by their
+    // path in `std`
+    pub(crate) fn std_item(text: &'static str) -> Name {
+        Name::new(text.into())
+    }
+
+    // Needed for derives and attribute macros, whose names are parsed out of
+    // the token tree of an attribute
+    pub(crate) fn attr_macro(text: SmolStr) -> Name {
+        Name::new(text)
+    }
+
     // There's should be no way to extract a string out of `Name`: `Name` in the
     // future, `Name` will include hygiene information, and you can't encode
     // hygiene into a String.
@@ -123,6 +144,12 @@ impl AsName for ra_db::Dependency {
     }
 }
 
+impl AsName for ra_db::ProcMacro {
+    fn as_name(&self) -> Name {
+        Name::new(self.name.clone())
+    }
+}
+
 // Ideally, should be replaced with
 // ```
 // const ISIZE: Name = Name::new("isize")
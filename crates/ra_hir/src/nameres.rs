@@ -38,8 +38,16 @@
 ///
 /// ## Resolving Macros
 ///
-/// macro_rules from the same crate use a global mutable namespace. We expand
-/// them immediately, when we collect modules.
+/// macro_rules from the same crate are scoped textually, like `let` bindings:
+/// a macro is visible after its definition until the end of the enclosing
+/// module, including in the child modules declared after it. `#[macro_use]` on
+/// a module extends the scope of its macros past the end of the module. We
+/// expand calls to such macros immediately, when we collect modules, so the
+/// textual order of items is preserved.
+///
+/// `#[macro_use] extern crate foo;` brings the exported macros of `foo` into
+/// the crate-wide `macro_use` prelude. The crate which provides the standard
+/// prelude is treated as if it was imported this way.
 ///
 /// Macros from other crates (including proc-macros) can be used with
 /// `foo::bar!` syntax. We handle them similarly to imports. There's a list of
@@ -59,7 +67,7 @@ use rustc_hash::{FxHashMap, FxHashSet};
 use ra_arena::{Arena, RawId, impl_arena_id};
 use ra_db::{FileId, Edition};
 use test_utils::tested_by;
-use ra_syntax::ast;
+use ra_syntax::{ast, TextRange, TextUnit};
 use ra_prof::profile;
 use once_cell::sync::Lazy;
 
@@ -68,6 +76,7 @@ use crate::{
     DefDatabase, Path, PathKind, HirFileId, Trait,
     ids::MacroDefId,
     diagnostics::DiagnosticSink,
+    visibility::{RawVisibility, Visibility},
     nameres::diagnostics::DefDiagnostic,
     either::Either,
     AstId,
@@ -90,6 +99,9 @@ pub struct CrateDefMap {
     /// a dependency (`std` or `core`).
     prelude: Option<Module>,
     extern_prelude: FxHashMap<Name, ModuleDef>,
+    /// Macros imported with `#[macro_use] extern crate`. They are visible in
+    /// the whole crate, but are shadowed by the textually scoped macros.
+    macro_use_prelude: FxHashMap<Name, MacroDef>,
     root: CrateModuleId,
     modules: Arena<CrateModuleId, ModuleData>,
 
@@ -131,27 +143,53 @@ pub(crate) struct ModuleData {
     ///
     /// Note that non-inline modules, by definition, live inside non-macro file.
     pub(crate) definition: Option<FileId>,
+    /// Files produced by the macros invoked in item position in this module.
+    pub(crate) macro_expansions: Vec<HirFileId>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct ModuleScope {
     items: FxHashMap<Name, Resolution>,
+    /// Macros which can be referred to by path, like `#[macro_export]` and
+    /// imported macros.
     macros: FxHashMap<Name, MacroDef>,
+    /// `macro_rules` which are in textual scope at the end of the module.
+    legacy_macros: FxHashMap<Name, MacroDef>,
+    /// All the `macro_rules` which come into textual scope in the module, in
+    /// order, to resolve the macro calls in bodies.
+    textual_macros: Vec<(Name, MacroDef, LegacyMacroOrigin)>,
+}
+
+/// Where a `macro_rules` comes into the textual scope of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LegacyMacroOrigin {
+    /// The macro is in scope from the start of the module.
+    Parent,
+    /// After its definition.
+    Definition(AstId<ast::MacroCall>),
+    /// After the `#[macro_use]` module which defines it.
+    MacroUse(AstId<ast::Module>),
 }
 
 static BUILTIN_SCOPE: Lazy<FxHashMap<Name, Resolution>> = Lazy::new(|| {
     BuiltinType::ALL
         .iter()
         .map(|&(known_name, ty)| {
-            (known_name.as_name(), Resolution { def: PerNs::types(ty.into()), import: None })
+            let resolution = Resolution {
+                def: PerNs::types(ty.into()),
+                import: None,
+                visibility: PerNs::types(Visibility::Public),
+            };
+            (known_name.as_name(), resolution)
         })
         .collect()
 });
 
 impl ModuleScope {
     pub fn entries<'a>(&'a self) -> impl Iterator<Item = (&'a Name, &'a Resolution)> + 'a {
-        //FIXME: shadowing
-        self.items.iter().chain(BUILTIN_SCOPE.iter())
+        // Builtin types are shadowed by the items with the same name.
+        let builtins = BUILTIN_SCOPE.iter().filter(move |(name, _)| !self.items.contains_key(name));
+        self.items.iter().chain(builtins)
     }
     pub fn get(&self, name: &Name) -> Option<&Resolution> {
         self.items.get(name).or_else(|| BUILTIN_SCOPE.get(name))
@@ -162,6 +200,36 @@ impl ModuleScope {
             _ => None,
         })
     }
+    pub(crate) fn get_legacy_macro(&self, name: &Name) -> Option<MacroDef> {
+        self.legacy_macros.get(name).cloned()
+    }
+    /// The `macro_rules` in textual scope at `offset` in `file_id`, a file of
+    /// the module. The macros which come into scope in other files, like
+    /// macro expansions, are assumed to come before `offset`.
+    pub(crate) fn get_legacy_macro_at(
+        &self,
+        db: &impl AstDatabase,
+        name: &Name,
+        file_id: HirFileId,
+        offset: TextUnit,
+    ) -> Option<MacroDef> {
+        let is_before = |range: TextRange| range.end() <= offset;
+        self.textual_macros
+            .iter()
+            .rev()
+            .filter(|(it, _, _)| it == name)
+            .find(|(_, _, origin)| match *origin {
+                LegacyMacroOrigin::Parent => true,
+                LegacyMacroOrigin::Definition(it) if it.file_id() == file_id => {
+                    is_before(it.range(db))
+                }
+                LegacyMacroOrigin::MacroUse(it) if it.file_id() == file_id => {
+                    is_before(it.range(db))
+                }
+                LegacyMacroOrigin::Definition(_) | LegacyMacroOrigin::MacroUse(_) => true,
+            })
+            .map(|(_, def, _)| *def)
+    }
     fn get_item_or_macro(&self, name: &Name) -> Option<ItemOrMacro> {
         match (self.get(name), self.macros.get(name)) {
             (Some(item), _) if !item.def.is_none() => Some(Either::A(item.def)),
@@ -179,6 +247,9 @@ pub struct Resolution {
     pub def: PerNs<ModuleDef>,
     /// ident by which this is imported into local scope.
     pub import: Option<ImportId>,
+    /// Visibility of the binding in each namespace. For imports, this is the
+    /// visibility of the import, not of the imported item.
+    pub visibility: PerNs<Visibility>,
 }
 
 #[derive(Debug, Clone)]
@@ -243,6 +314,7 @@ impl CrateDefMap {
                 krate,
                 edition,
                 extern_prelude: FxHashMap::default(),
+                macro_use_prelude: FxHashMap::default(),
                 prelude: None,
                 root,
                 modules,
@@ -308,6 +380,28 @@ impl CrateDefMap {
         (res.resolved_def.a().unwrap_or_else(PerNs::none), res.segment_index)
     }
 
+    /// Resolves the module a `pub(in path)`, `pub(crate)` or `pub(super)`
+    /// visibility refers to. Visibilities which don't refer to a module are
+    /// treated as private.
+    pub(crate) fn resolve_visibility(
+        &self,
+        db: &impl DefDatabase,
+        original_module: CrateModuleId,
+        visibility: &RawVisibility,
+    ) -> Visibility {
+        let path = match visibility {
+            RawVisibility::Module(path) => path,
+            RawVisibility::Public => return Visibility::Public,
+        };
+        let res = self.resolve_path_fp_with_macro(db, ResolveMode::Other, original_module, path);
+        match res.resolved_def.a().and_then(|it| it.take_types()) {
+            Some(ModuleDef::Module(module)) if res.segment_index.is_none() => {
+                Visibility::Module(module)
+            }
+            _ => Visibility::Module(self.mk_module(original_module)),
+        }
+    }
+
     pub(crate) fn resolve_path_with_macro(
         &self,
         db: &impl DefDatabase,
@@ -481,14 +575,19 @@ impl CrateDefMap {
         // Resolve in:
         //  - current module / scope
         //  - extern prelude
+        //  - `macro_use` prelude
         //  - std prelude
         let from_scope =
             self[module].scope.get_item_or_macro(name).unwrap_or_else(|| Either::A(PerNs::none()));
+        let from_macro_use_prelude = self
+            .macro_use_prelude
+            .get(name)
+            .map_or_else(|| Either::A(PerNs::none()), |&it| Either::B(it));
         let from_extern_prelude =
             self.extern_prelude.get(name).map_or(PerNs::none(), |&it| PerNs::types(it));
         let from_prelude = self.resolve_in_prelude(db, name);
 
-        or(from_scope, or(Either::A(from_extern_prelude), from_prelude))
+        or(from_scope, or(Either::A(from_extern_prelude), or(from_macro_use_prelude, from_prelude)))
     }
 
     fn resolve_name_in_extern_prelude(&self, name: &Name) -> PerNs<ModuleDef> {
@@ -511,12 +610,15 @@ impl CrateDefMap {
 
 mod diagnostics {
     use relative_path::RelativePathBuf;
+    use ra_db::FileId;
     use ra_syntax::{AstPtr, ast};
 
     use crate::{
-        AstId, DefDatabase, AstDatabase,
-        nameres::CrateModuleId,
-        diagnostics::{DiagnosticSink, UnresolvedModule}
+        AstId, DefDatabase, AstDatabase, Name,
+        nameres::{CrateModuleId, ImportId},
+        diagnostics::{
+            DiagnosticSink, UnresolvedModule, UnresolvedImport, AmbiguousImport, PrivateItem,
+        },
 };
 
     #[derive(Debug, PartialEq, Eq)]
@@ -526,6 +628,23 @@ mod diagnostics {
             declaration: AstId<ast::Module>,
             candidate: RelativePathBuf,
         },
+        UnresolvedImport {
+            module: CrateModuleId,
+            file_id: FileId,
+            import: ImportId,
+        },
+        AmbiguousImport {
+            module: CrateModuleId,
+            file_id: FileId,
+            import: ImportId,
+            name: Name,
+        },
+        PrivateItem {
+            module: CrateModuleId,
+            file_id: FileId,
+            import: ImportId,
+            name: Name,
+        },
     }
 
     impl DefDiagnostic {
@@ -547,7 +666,48 @@ mod diagnostics {
                         candidate: candidate.clone(),
                     })
                 }
+                DefDiagnostic::UnresolvedImport { module, file_id, import } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    if let Some(use_tree) = use_tree_ptr(db, *file_id, *import) {
+                        sink.push(UnresolvedImport { file: (*file_id).into(), use_tree })
+                    }
+                }
+                DefDiagnostic::AmbiguousImport { module, file_id, import, name } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    if let Some(use_tree) = use_tree_ptr(db, *file_id, *import) {
+                        sink.push(AmbiguousImport {
+                            file: (*file_id).into(),
+                            use_tree,
+                            name: name.clone(),
+                        })
+                    }
+                }
+                DefDiagnostic::PrivateItem { module, file_id, import, name } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    if let Some(use_tree) = use_tree_ptr(db, *file_id, *import) {
+                        sink.push(PrivateItem {
+                            file: (*file_id).into(),
+                            use_tree,
+                            name: name.clone(),
+                        })
+                    }
+                }
             }
         }
     }
+
+    fn use_tree_ptr(
+        db: &(impl DefDatabase + AstDatabase),
+        file_id: FileId,
+        import: ImportId,
+    ) -> Option<AstPtr<ast::UseTree>> {
+        let (_, source_map) = db.raw_items_with_source_map(file_id.into());
+        source_map.get_ptr(import).a()
+    }
 }
@@ -1,21 +1,25 @@
 use arrayvec::ArrayVec;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use relative_path::RelativePathBuf;
 use test_utils::tested_by;
-use ra_db::FileId;
+use ra_db::{FileId, ProcMacroKind, Edition};
 use ra_syntax::ast;
 
 use crate::{
-    Function, Module, Struct, Union, Enum, Const, Static, Trait, TypeAlias, MacroDef,
-    DefDatabase, HirFileId, Name, Path, AstDatabase,
+    Crate, Function, Module, Struct, Union, Enum, Const, Static, Trait, TypeAlias, MacroDef,
+    DefDatabase, HirFileId, Name, AsName, Path, PathKind, AstDatabase,
     KnownName, AstId,
     nameres::{
-        Resolution, PerNs, ModuleDef, ReachedFixedPoint, ResolveMode,
-        CrateDefMap, CrateModuleId, ModuleData, ItemOrMacro,
+        Resolution, PerNs, Namespace, ModuleDef, ReachedFixedPoint, ResolveMode,
+        CrateDefMap, CrateModuleId, ModuleData, ModuleScope, ItemOrMacro, LegacyMacroOrigin,
         diagnostics::DefDiagnostic,
         raw,
     },
-    ids::{AstItemDef, LocationCtx, MacroCallLoc, MacroCallId, MacroDefId, MacroFileKind},
+    visibility::{RawVisibility, Visibility},
+    ids::{
+        AstItemDef, LocationCtx, MacroCallLoc, MacroCallId, MacroCallKind, MacroDefId,
+        MacroDefKind, MacroFileKind,
+    },
     either::Either,
 };
 
@@ -23,28 +27,65 @@ pub(super) fn collect_defs(
     db: &(impl DefDatabase + AstDatabase),
     mut def_map: CrateDefMap,
 ) -> CrateDefMap {
+    let root_file = db.crate_graph().crate_root(def_map.krate.crate_id());
+    let crate_attrs = db.raw_items(root_file.into()).crate_attrs();
+    // rustc injects `extern crate std;`, or `extern crate core;` into
+    // `#![no_std]` crates, and uses the prelude of the injected crate.
+    let injected = match (crate_attrs.no_core, crate_attrs.no_std) {
+        (true, _) => None,
+        (false, true) => Some(Name::core()),
+        (false, false) => Some(Name::std()),
+    };
+
     // populate external prelude
+    let mut extern_crates = FxHashMap::default();
     for dep in def_map.krate.dependencies(db) {
         log::debug!("crate dep {:?} -> {:?}", dep.name, dep.krate);
-        if let Some(module) = dep.krate.root_module(db) {
+        let module = match dep.krate.root_module(db) {
+            Some(it) => it,
+            None => continue,
+        };
+        extern_crates.insert(dep.name.clone(), module.into());
+        let in_extern_prelude = if dep.name == Name::std() {
+            injected == Some(Name::std())
+        } else if dep.name == Name::core() {
+            injected.is_some()
+        } else {
+            dep.prelude
+        };
+        if in_extern_prelude {
             def_map.extern_prelude.insert(dep.name.clone(), module.into());
         }
-        // look for the prelude
-        if def_map.prelude.is_none() {
-            let map = db.crate_def_map(dep.krate);
-            if map.prelude.is_some() {
-                def_map.prelude = map.prelude;
+    }
+
+    // look for the prelude
+    match injected.as_ref().and_then(|it| extern_crates.get(it)) {
+        Some(ModuleDef::Module(m)) => def_map.prelude = db.crate_def_map(m.krate).prelude,
+        // Without a sysroot, the prelude can come from any dependency
+        _ if injected.is_some() => {
+            for dep in def_map.krate.dependencies(db) {
+                let map = db.crate_def_map(dep.krate);
+                if map.prelude.is_some() {
+                    def_map.prelude = map.prelude;
+                    break;
+                }
             }
         }
+        _ => (),
     }
 
     let mut collector = DefCollector {
         db,
         def_map,
+        extern_crates,
         glob_imports: FxHashMap::default(),
         unresolved_imports: Vec::new(),
+        named_imports: Vec::new(),
+        from_glob_import: FxHashSet::default(),
+        ambiguous_globs: FxHashSet::default(),
         unexpanded_macros: Vec::new(),
-        global_macro_scope: FxHashMap::default(),
+        attr_macro_items: Vec::new(),
+        replaced_items: FxHashSet::default(),
         macro_stack_monitor: MacroStackMonitor::default(),
     };
     collector.collect();
@@ -79,14 +120,43 @@ impl MacroStackMonitor {
     }
 }
 
+/// How a binding is introduced into a module scope, which determines whether
+/// it can be shadowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportType {
+    /// An item, or an import which is not a glob.
+    Named,
+    Glob,
+}
+
 /// Walks the tree of module recursively
 struct DefCollector<DB> {
     db: DB,
     def_map: CrateDefMap,
-    glob_imports: FxHashMap<CrateModuleId, Vec<(CrateModuleId, raw::ImportId)>>,
-    unresolved_imports: Vec<(CrateModuleId, raw::ImportId, raw::ImportData)>,
-    unexpanded_macros: Vec<(CrateModuleId, AstId<ast::MacroCall>, Path)>,
-    global_macro_scope: FxHashMap<Name, MacroDefId>,
+    /// The root modules of the dependencies, which can be named by `extern
+    /// crate` even if they are not in the extern prelude.
+    extern_crates: FxHashMap<Name, ModuleDef>,
+    /// Modules which glob import each module, with the id and the visibility
+    /// of the glob import.
+    glob_imports: FxHashMap<CrateModuleId, Vec<(CrateModuleId, raw::ImportId, Visibility)>>,
+    unresolved_imports: Vec<(CrateModuleId, HirFileId, raw::ImportId, raw::ImportData)>,
+    /// Resolved non-glob imports, which are checked for ambiguity and privacy
+    /// once all imports are resolved.
+    named_imports: Vec<(CrateModuleId, HirFileId, raw::ImportId, raw::ImportData)>,
+    /// Bindings which come from glob imports. Unlike other bindings, they can
+    /// be shadowed by explicit items and imports.
+    from_glob_import: FxHashSet<(CrateModuleId, Name, Namespace)>,
+    /// Names which are imported by several glob imports, referring to
+    /// different items.
+    ambiguous_globs: FxHashSet<(CrateModuleId, Name)>,
+    unexpanded_macros: Vec<(CrateModuleId, MacroCallKind, Path)>,
+    /// Definitions annotated with attributes which might be macros. They are
+    /// defined as is, and removed when the first of their attributes resolves
+    /// to a macro, which replaces them by its expansion.
+    attr_macro_items: Vec<(AstId<ast::ModuleItem>, CrateModuleId, HirFileId, raw::Def)>,
+    /// Items which were replaced by the expansion of an attribute macro. The
+    /// other attributes of the item are part of the macro input.
+    replaced_items: FxHashSet<AstId<ast::ModuleItem>>,
 
     /// Some macro use `$tt:tt which mean we have to handle the macro perfectly
     /// To prevent stackoverflow, we add a deep counter here for prevent that.
@@ -103,6 +173,23 @@ where
         let raw_items = self.db.raw_items(file_id.into());
         let module_id = self.def_map.root;
         self.def_map.modules[module_id].definition = Some(file_id);
+
+        // Proc-macro crates export their macros from the crate root.
+        let krate = self.def_map.krate.crate_id();
+        for (idx, proc_macro) in crate_graph.proc_macros(krate).iter().enumerate() {
+            let id = MacroDefId(MacroDefKind::ProcMacro { krate, idx: idx as u32 });
+            let resolution = (proc_macro.as_name(), Either::B(MacroDef { id }));
+            self.update(module_id, None, ImportType::Named, &[resolution]);
+        }
+
+        // rustc injects `#[macro_use] extern crate std;` together with the
+        // prelude import.
+        if let Some(prelude) = self.def_map.prelude {
+            if prelude.krate != self.def_map.krate {
+                self.import_macros_from_extern_crate(prelude.krate);
+            }
+        }
+
         ModCollector {
             def_collector: &mut *self,
             module_id,
@@ -127,8 +214,68 @@ where
 
         let unresolved_imports = std::mem::replace(&mut self.unresolved_imports, Vec::new());
         // show unresolved imports in completion, etc
-        for (module_id, import, import_data) in unresolved_imports {
-            self.record_resolved_import(module_id, Either::A(PerNs::none()), import, &import_data)
+        for (module_id, file_id, import, import_data) in unresolved_imports {
+            let def = Either::A(PerNs::none());
+            self.record_resolved_import(module_id, file_id, def, import, &import_data)
+        }
+
+        let named_imports = std::mem::replace(&mut self.named_imports, Vec::new());
+        for (module_id, file_id, import, import_data) in named_imports {
+            if let Some(file_id) = file_id.as_file_id() {
+                self.check_import_ambiguity(module_id, file_id, import, &import_data);
+                self.check_import_privacy(module_id, file_id, import, &import_data);
+            }
+        }
+    }
+
+    /// Removes the definition of an item which is replaced by the expansion of
+    /// an attribute macro.
+    fn remove_attr_macro_item(
+        &mut self,
+        module_id: CrateModuleId,
+        file_id: HirFileId,
+        def: raw::Def,
+    ) {
+        let raw_items = self.db.raw_items(file_id);
+        let def = &raw_items[def];
+        let per_ns =
+            ModCollector { def_collector: &mut *self, module_id, file_id, raw_items: &raw_items }
+                .def_per_ns(def);
+        self.remove_def(module_id, &def.name, per_ns);
+    }
+
+    /// Removes `def` from the bindings of `name` in the module, and in the
+    /// modules which glob import it.
+    fn remove_def(&mut self, module_id: CrateModuleId, name: &Name, def: PerNs<ModuleDef>) {
+        let module_items = &mut self.def_map.modules[module_id].scope;
+        let existing = match module_items.items.get_mut(name) {
+            Some(it) => it,
+            None => return,
+        };
+        let mut changed = false;
+        for &ns in &[Namespace::Types, Namespace::Values] {
+            if def.get(ns).is_some() && existing.def.get(ns) == def.get(ns) {
+                *existing.def.get_mut(ns) = None;
+                *existing.visibility.get_mut(ns) = None;
+                self.from_glob_import.remove(&(module_id, name.clone(), ns));
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+        if existing.def.is_none() && existing.import.is_none() {
+            module_items.items.remove(name);
+        }
+        let glob_importing_modules = self
+            .glob_imports
+            .get(&module_id)
+            .into_iter()
+            .flat_map(|v| v.iter())
+            .map(|(glob_importing_module, ..)| *glob_importing_module)
+            .collect::<Vec<_>>();
+        for glob_importing_module in glob_importing_modules {
+            self.remove_def(glob_importing_module, name, def);
         }
     }
 
@@ -139,38 +286,176 @@ where
         macro_id: MacroDefId,
         export: bool,
     ) {
-        // macro-by-example in Rust have completely weird name resolution logic,
-        // unlike anything else in the language. We'd don't fully implement yet,
-        // just give a somewhat precise approximation.
-        //
-        // Specifically, we store a set of visible macros in each module, just
-        // like how we do with usual items. This is wrong, however, because
-        // macros can be shadowed and their scopes are mostly unrelated to
-        // modules. To paper over the second problem, we also maintain
-        // `global_macro_scope` which works when we construct `CrateDefMap`, but
-        // is completely ignored in expressions.
-        //
-        // What we should do is that, in CrateDefMap, we should maintain a
-        // separate tower of macro scopes, with ids. Then, for each item in the
-        // module, we need to store it's macro scope.
-        let def = Either::B(MacroDef { id: macro_id });
+        let def = MacroDef { id: macro_id };
 
         // In Rust, `#[macro_export]` macros are unconditionally visible at the
         // crate root, even if the parent modules is **not** visible.
         if export {
-            self.update(self.def_map.root, None, &[(name.clone(), def.clone())]);
+            let resolution = (name.clone(), Either::B(def));
+            self.update(self.def_map.root, None, ImportType::Named, &[resolution]);
+        }
+        let origin = match macro_id.0 {
+            MacroDefKind::MacroRules(ast_id) => LegacyMacroOrigin::Definition(ast_id),
+            MacroDefKind::ProcMacro { .. } => LegacyMacroOrigin::Parent,
+        };
+        self.define_legacy_macro(module_id, name, def, origin);
+    }
+
+    /// Defines a macro in the textual scope of `module_id`, shadowing any
+    /// previous macro with the same name.
+    ///
+    /// Items are collected in textual order, so the textual scope of a module
+    /// at any point of the collection holds exactly the macros visible at the
+    /// item being collected. Child modules start with a copy of the scope of
+    /// the parent at the point of their declaration.
+    fn define_legacy_macro(
+        &mut self,
+        module_id: CrateModuleId,
+        name: Name,
+        def: MacroDef,
+        origin: LegacyMacroOrigin,
+    ) {
+        let scope = &mut self.def_map.modules[module_id].scope;
+        scope.textual_macros.push((name.clone(), def, origin));
+        scope.legacy_macros.insert(name, def);
+    }
+
+    /// Copies the textual macro scope of `from` into `into`, as done by
+    /// `#[macro_use] mod from;`.
+    fn import_all_legacy_macros(
+        &mut self,
+        into: CrateModuleId,
+        from: CrateModuleId,
+        declaration: AstId<ast::Module>,
+    ) {
+        let macros = self.def_map[from].scope.legacy_macros.clone();
+        for (name, def) in macros {
+            self.define_legacy_macro(into, name, def, LegacyMacroOrigin::MacroUse(declaration));
+        }
+    }
+
+    /// Adds the macros exported from the root of `krate` to the `macro_use`
+    /// prelude, as done by `#[macro_use] extern crate krate;`.
+    fn import_macros_from_extern_crate(&mut self, krate: Crate) {
+        let def_map = self.db.crate_def_map(krate);
+        for (name, def) in def_map[def_map.root].scope.macros.iter() {
+            self.def_map.macro_use_prelude.insert(name.clone(), *def);
+        }
+    }
+
+    fn report_unresolved_import(
+        &mut self,
+        module_id: CrateModuleId,
+        file_id: HirFileId,
+        import: raw::ImportId,
+    ) {
+        // FIXME: report unresolved imports in macro expansions
+        if let Some(file_id) = file_id.as_file_id() {
+            let diagnostic = DefDiagnostic::UnresolvedImport { module: module_id, file_id, import };
+            self.def_map.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Reports a named import whose first segment is ambiguous, because it is
+    /// imported by several glob imports or because, in the 2018 edition, it
+    /// refers both to an item in the module and to a crate.
+    fn check_import_ambiguity(
+        &mut self,
+        module_id: CrateModuleId,
+        file_id: FileId,
+        import: raw::ImportId,
+        import_data: &raw::ImportData,
+    ) {
+        if import_data.path.kind != PathKind::Plain {
+            return;
+        }
+        let name = match import_data.path.segments.first() {
+            Some(segment) => segment.name.clone(),
+            None => return,
+        };
+        let is_ambiguous = self.ambiguous_globs.contains(&(module_id, name.clone()))
+            || self.is_ambiguous_with_extern_prelude(module_id, &name);
+        if is_ambiguous {
+            let diagnostic =
+                DefDiagnostic::AmbiguousImport { module: module_id, file_id, import, name };
+            self.def_map.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Reports a named import which goes through a module, or refers to an
+    /// item, which is not visible from the importing module.
+    fn check_import_privacy(
+        &mut self,
+        module_id: CrateModuleId,
+        file_id: FileId,
+        import: raw::ImportId,
+        import_data: &raw::ImportData,
+    ) {
+        let segments = &import_data.path.segments;
+        for i in 1..segments.len() {
+            let prefix = Path { kind: import_data.path.kind, segments: segments[..i].to_vec() };
+            let res = self.def_map.resolve_path_fp_with_macro(
+                self.db,
+                ResolveMode::Import,
+                module_id,
+                &prefix,
+            );
+            let module = match res.resolved_def.a().and_then(|it| it.take_types()) {
+                Some(ModuleDef::Module(it)) => it,
+                // enum variants are as visible as the enum
+                _ => return,
+            };
+            let name = &segments[i].name;
+            if !self.is_binding_visible(module, name, module_id) {
+                let name = name.clone();
+                let diagnostic =
+                    DefDiagnostic::PrivateItem { module: module_id, file_id, import, name };
+                self.def_map.diagnostics.push(diagnostic);
+                return;
+            }
+        }
+    }
+
+    /// Checks that the item `name` of `module` is visible from `from` in at
+    /// least one namespace. Macros are always visible.
+    fn is_binding_visible(&self, module: Module, name: &Name, from: CrateModuleId) -> bool {
+        let visibility = if module.krate == self.def_map.krate {
+            self.def_map[module.module_id].scope.get(name).map(|it| it.visibility)
+        } else {
+            let def_map = self.db.crate_def_map(module.krate);
+            def_map[module.module_id].scope.get(name).map(|it| it.visibility)
+        };
+        let visibility = match visibility {
+            Some(it) if !it.is_none() => it,
+            _ => return true,
+        };
+        [visibility.types, visibility.values]
+            .iter()
+            .flatten()
+            .any(|it| it.is_visible_from_def_map(&self.def_map, from))
+    }
+
+    fn is_ambiguous_with_extern_prelude(&self, module_id: CrateModuleId, name: &Name) -> bool {
+        if self.def_map.edition != Edition::Edition2018 {
+            return false;
+        }
+        let krate = match self.def_map.extern_prelude.get(name) {
+            Some(it) => *it,
+            None => return false,
+        };
+        match self.def_map[module_id].scope.items.get(name).and_then(|it| it.def.types) {
+            Some(local) => local != krate,
+            None => false,
         }
-        self.update(module_id, None, &[(name.clone(), def)]);
-        self.global_macro_scope.insert(name, macro_id);
     }
 
     fn resolve_imports(&mut self) -> ReachedFixedPoint {
         let mut imports = std::mem::replace(&mut self.unresolved_imports, Vec::new());
         let mut resolved = Vec::new();
-        imports.retain(|(module_id, import, import_data)| {
+        imports.retain(|(module_id, file_id, import, import_data)| {
             let (def, fp) = self.resolve_import(*module_id, import_data);
             if fp == ReachedFixedPoint::Yes {
-                resolved.push((*module_id, def, *import, import_data.clone()))
+                resolved.push((*module_id, *file_id, def, *import, import_data.clone()))
             }
             fp == ReachedFixedPoint::No
         });
@@ -178,8 +463,8 @@ where
         // Resolves imports, filling-in module scopes
         let result =
             if resolved.is_empty() { ReachedFixedPoint::Yes } else { ReachedFixedPoint::No };
-        for (module_id, def, import, import_data) in resolved {
-            self.record_resolved_import(module_id, def, import, &import_data)
+        for (module_id, file_id, def, import, import_data) in resolved {
+            self.record_resolved_import(module_id, file_id, def, import, &import_data)
         }
         result
     }
@@ -191,12 +476,11 @@ where
     ) -> (ItemOrMacro, ReachedFixedPoint) {
         log::debug!("resolving import: {:?} ({:?})", import, self.def_map.edition);
         if import.is_extern_crate {
-            let res = self.def_map.resolve_name_in_extern_prelude(
-                &import
-                    .path
-                    .as_ident()
-                    .expect("extern crate should have been desugared to one-element path"),
-            );
+            let name = import
+                .path
+                .as_ident()
+                .expect("extern crate should have been desugared to one-element path");
+            let res = self.extern_crates.get(name).map_or(PerNs::none(), |&it| PerNs::types(it));
             (Either::A(res), ReachedFixedPoint::Yes)
         } else {
             let res = self.def_map.resolve_path_fp_with_macro(
@@ -213,10 +497,12 @@ where
     fn record_resolved_import(
         &mut self,
         module_id: CrateModuleId,
+        file_id: HirFileId,
         def: ItemOrMacro,
         import_id: raw::ImportId,
         import: &raw::ImportData,
     ) {
+        let vis = self.def_map.resolve_visibility(self.db, module_id, &import.visibility);
         if import.is_glob {
             log::debug!("glob import: {:?}", import);
             match def.a().and_then(|item| item.take_types()) {
@@ -229,39 +515,20 @@ where
                         // glob import from other crate => we can just import everything once
                         let item_map = self.db.crate_def_map(m.krate);
                         let scope = &item_map[m.module_id].scope;
-                        let items = scope
-                            .items
-                            .iter()
-                            .map(|(name, res)| (name.clone(), Either::A(res.clone())));
-                        let macros = scope
-                            .macros
-                            .iter()
-                            .map(|(name, res)| (name.clone(), Either::B(res.clone())));
-
-                        let all = items.chain(macros).collect::<Vec<_>>();
-                        self.update(module_id, Some(import_id), &all);
+                        let all = self.glob_resolutions(module_id, vis, scope);
+                        self.update(module_id, Some(import_id), ImportType::Glob, &all);
                     } else {
                         // glob import from same crate => we do an initial
                         // import, and then need to propagate any further
                         // additions
                         let scope = &self.def_map[m.module_id].scope;
-                        let items = scope
-                            .items
-                            .iter()
-                            .map(|(name, res)| (name.clone(), Either::A(res.clone())));
-                        let macros = scope
-                            .macros
-                            .iter()
-                            .map(|(name, res)| (name.clone(), Either::B(res.clone())));
-
-                        let all = items.chain(macros).collect::<Vec<_>>();
-
-                        self.update(module_id, Some(import_id), &all);
+                        let all = self.glob_resolutions(module_id, vis, scope);
+                        self.update(module_id, Some(import_id), ImportType::Glob, &all);
                         // record the glob import in case we add further items
                         self.glob_imports
                             .entry(m.module_id)
                             .or_default()
-                            .push((module_id, import_id));
+                            .push((module_id, import_id, vis));
                     }
                 }
                 Some(ModuleDef::Enum(e)) => {
@@ -274,18 +541,20 @@ where
                             let res = Resolution {
                                 def: PerNs::both(variant.into(), variant.into()),
                                 import: Some(import_id),
+                                visibility: PerNs::both(vis, vis),
                             };
                             let name = variant.name(self.db)?;
                             Some((name, Either::A(res)))
                         })
                         .collect::<Vec<_>>();
-                    self.update(module_id, Some(import_id), &resolutions);
+                    self.update(module_id, Some(import_id), ImportType::Glob, &resolutions);
                 }
                 Some(d) => {
                     log::debug!("glob import {:?} from non-module/enum {:?}", import, d);
                 }
                 None => {
                     log::debug!("glob import {:?} didn't resolve as type", import);
+                    self.report_unresolved_import(module_id, file_id, import_id);
                 }
             }
         } else {
@@ -298,36 +567,138 @@ where
                     if import.is_extern_crate && module_id == self.def_map.root {
                         if let Some(def) = def.a().and_then(|item| item.take_types()) {
                             self.def_map.extern_prelude.insert(name.clone(), def);
+                            match def {
+                                ModuleDef::Module(m) if import.is_macro_use => {
+                                    self.import_macros_from_extern_crate(m.krate)
+                                }
+                                _ => (),
+                            }
                         }
                     }
 
+                    let macro_with_same_name = match def {
+                        Either::A(_) => self.resolve_import_macro(module_id, import),
+                        Either::B(_) => None,
+                    };
                     let resolution = match def {
-                        Either::A(item) => {
-                            Either::A(Resolution { def: item, import: Some(import_id) })
-                        }
+                        Either::A(item) => Either::A(Resolution {
+                            def: item,
+                            import: Some(import_id),
+                            visibility: item.map(|_| vis),
+                        }),
                         Either::B(macro_) => Either::B(macro_),
                     };
 
-                    self.update(module_id, Some(import_id), &[(name, resolution)]);
+                    let is_unresolved = match def {
+                        Either::A(item) => item.is_none() && macro_with_same_name.is_none(),
+                        Either::B(_) => false,
+                    };
+                    if !import.is_extern_crate {
+                        if is_unresolved {
+                            self.report_unresolved_import(module_id, file_id, import_id);
+                        } else {
+                            let named_import = (module_id, file_id, import_id, import.clone());
+                            self.named_imports.push(named_import);
+                        }
+                    }
+
+                    let named = ImportType::Named;
+                    self.update(module_id, Some(import_id), named, &[(name.clone(), resolution)]);
+                    if let Some(macro_) = macro_with_same_name {
+                        let resolution = (name, Either::B(macro_));
+                        self.update(module_id, Some(import_id), named, &[resolution]);
+                    }
                 }
                 None => tested_by!(bogus_paths),
             }
         }
     }
 
+    /// Returns the bindings of `scope` which are brought into `module_id` by a
+    /// glob import with the visibility `vis`.
+    fn glob_resolutions(
+        &self,
+        module_id: CrateModuleId,
+        vis: Visibility,
+        scope: &ModuleScope,
+    ) -> Vec<(Name, Either<Resolution, MacroDef>)> {
+        let items = scope.items.iter().filter_map(|(name, res)| {
+            let res = self.glob_resolution(module_id, vis, res)?;
+            Some((name.clone(), Either::A(res)))
+        });
+        let macros = scope.macros.iter().map(|(name, res)| (name.clone(), Either::B(*res)));
+        items.chain(macros).collect()
+    }
+
+    /// Glob imports only import the bindings which are visible from the
+    /// importing module, and re-export them with the visibility of the import.
+    fn glob_resolution(
+        &self,
+        module_id: CrateModuleId,
+        vis: Visibility,
+        res: &Resolution,
+    ) -> Option<Resolution> {
+        let is_visible = |ns| match res.visibility.take(ns) {
+            Some(it) => it.is_visible_from_def_map(&self.def_map, module_id),
+            None => false,
+        };
+        let def = PerNs {
+            types: res.def.types.filter(|_| is_visible(Namespace::Types)),
+            values: res.def.values.filter(|_| is_visible(Namespace::Values)),
+        };
+        if def.is_none() {
+            return None;
+        }
+        Some(Resolution { def, import: res.import, visibility: def.map(|_| vis) })
+    }
+
+    /// `ItemOrMacro` holds either an item or a macro, but an imported name can
+    /// refer to both: `serde::Serialize` is a trait and a derive. When an
+    /// import resolves to an item, this finds the macro with the same name, if
+    /// any, so that it can be imported too.
+    fn resolve_import_macro(
+        &self,
+        module_id: CrateModuleId,
+        import: &raw::ImportData,
+    ) -> Option<MacroDef> {
+        let (last_segment, prefix) = import.path.segments.split_last()?;
+        if prefix.is_empty() {
+            return None;
+        }
+        let prefix = Path { kind: import.path.kind, segments: prefix.to_vec() };
+        let res = self.def_map.resolve_path_fp_with_macro(
+            self.db,
+            ResolveMode::Import,
+            module_id,
+            &prefix,
+        );
+        let module = match res.resolved_def.a()?.take_types()? {
+            ModuleDef::Module(it) => it,
+            _ => return None,
+        };
+        if module.krate == self.def_map.krate {
+            self.def_map[module.module_id].scope.macros.get(&last_segment.name).cloned()
+        } else {
+            let def_map = self.db.crate_def_map(module.krate);
+            def_map[module.module_id].scope.macros.get(&last_segment.name).cloned()
+        }
+    }
+
     fn update(
         &mut self,
         module_id: CrateModuleId,
         import: Option<raw::ImportId>,
+        import_type: ImportType,
         resolutions: &[(Name, Either<Resolution, MacroDef>)],
     ) {
-        self.update_recursive(module_id, import, resolutions, 0)
+        self.update_recursive(module_id, import, import_type, resolutions, 0)
     }
 
     fn update_recursive(
         &mut self,
         module_id: CrateModuleId,
         import: Option<raw::ImportId>,
+        import_type: ImportType,
         resolutions: &[(Name, Either<Resolution, MacroDef>)],
         depth: usize,
     ) {
@@ -343,14 +714,39 @@ where
                 Either::A(res) => {
                     let existing = module_items.items.entry(name.clone()).or_default();
 
-                    if existing.def.types.is_none() && res.def.types.is_some() {
-                        existing.def.types = res.def.types;
-                        existing.import = import.or(res.import);
-                        changed = true;
-                    }
-                    if existing.def.values.is_none() && res.def.values.is_some() {
-                        existing.def.values = res.def.values;
+                    for &ns in &[Namespace::Types, Namespace::Values] {
+                        let new = match res.def.take(ns) {
+                            Some(it) => it,
+                            None => continue,
+                        };
+                        let key = (module_id, name.clone(), ns);
+                        if let Some(old) = existing.def.take(ns) {
+                            if old == new {
+                                continue;
+                            }
+                            let is_from_glob = self.from_glob_import.contains(&key);
+                            match import_type {
+                                // Explicit items and imports shadow glob imports.
+                                ImportType::Named if is_from_glob => (),
+                                // A glob import can update the bindings it
+                                // introduced itself.
+                                ImportType::Glob if is_from_glob && existing.import == import => (),
+                                ImportType::Glob if is_from_glob => {
+                                    tested_by!(glob_imports_are_ambiguous);
+                                    self.ambiguous_globs.insert((module_id, name.clone()));
+                                    continue;
+                                }
+                                // Otherwise, the first binding wins.
+                                _ => continue,
+                            }
+                        }
+                        *existing.def.get_mut(ns) = Some(new);
+                        *existing.visibility.get_mut(ns) = res.visibility.take(ns);
                         existing.import = import.or(res.import);
+                        match import_type {
+                            ImportType::Named => self.from_glob_import.remove(&key),
+                            ImportType::Glob => self.from_glob_import.insert(key),
+                        };
                         changed = true;
                     }
 
@@ -380,9 +776,27 @@ where
             .flat_map(|v| v.iter())
             .cloned()
             .collect::<Vec<_>>();
-        for (glob_importing_module, glob_import) in glob_imports {
+        for (glob_importing_module, glob_import, vis) in glob_imports {
+            let resolutions = resolutions
+                .iter()
+                .filter_map(|(name, res)| {
+                    let res = match res {
+                        Either::A(res) => {
+                            Either::A(self.glob_resolution(glob_importing_module, vis, res)?)
+                        }
+                        Either::B(res) => Either::B(*res),
+                    };
+                    Some((name.clone(), res))
+                })
+                .collect::<Vec<_>>();
             // We pass the glob import so that the tracked import in those modules is that glob import
-            self.update_recursive(glob_importing_module, Some(glob_import), resolutions, depth + 1);
+            self.update_recursive(
+                glob_importing_module,
+                Some(glob_import),
+                ImportType::Glob,
+                &resolutions,
+                depth + 1,
+            );
         }
     }
 
@@ -390,7 +804,28 @@ where
         let mut macros = std::mem::replace(&mut self.unexpanded_macros, Vec::new());
         let mut resolved = Vec::new();
         let mut res = ReachedFixedPoint::Yes;
-        macros.retain(|(module_id, ast_id, path)| {
+        macros.retain(|(module_id, kind, path)| {
+            if let MacroCallKind::Derive(_) | MacroCallKind::Attr(..) = kind {
+                // Derives and attributes share names with other items (like
+                // the `Serialize` trait and derive), so we look only at the
+                // macros in scope. The name is known by now, unless it's
+                // imported, so we don't need to keep the fixed point going.
+                let def = path
+                    .as_ident()
+                    .and_then(|name| {
+                        let scope = &self.def_map[*module_id].scope;
+                        scope.macros.get(name).or_else(|| self.def_map.macro_use_prelude.get(name))
+                    })
+                    .cloned()
+                    .filter(|def| self.is_proc_macro_of_kind(def.id, kind));
+                if let Some(def) = def {
+                    resolved.push((*module_id, kind.clone(), def.id));
+                    res = ReachedFixedPoint::No;
+                    return false;
+                }
+                return true;
+            }
+
             let resolved_res = self.def_map.resolve_path_fp_with_macro(
                 self.db,
                 ResolveMode::Other,
@@ -399,8 +834,7 @@ where
             );
 
             if let Some(def) = resolved_res.resolved_def.b() {
-                let call_id = MacroCallLoc { def: def.id, ast_id: *ast_id }.id(self.db);
-                resolved.push((*module_id, call_id, def.id));
+                resolved.push((*module_id, kind.clone(), def.id));
                 res = ReachedFixedPoint::No;
                 return false;
             }
@@ -440,13 +874,40 @@ where
 
         self.unexpanded_macros = macros;
 
-        for (module_id, macro_call_id, macro_def_id) in resolved {
+        for (module_id, kind, macro_def_id) in resolved {
+            if let MacroCallKind::Attr(ast_id, _) = &kind {
+                if !self.replaced_items.insert(*ast_id) {
+                    continue;
+                }
+                let items = &mut self.attr_macro_items;
+                if let Some(idx) = items.iter().position(|(it, ..)| it == ast_id) {
+                    let (_, module_id, file_id, def) = items.swap_remove(idx);
+                    self.remove_attr_macro_item(module_id, file_id, def);
+                }
+            }
+            let macro_call_id = MacroCallLoc { def: macro_def_id, kind }.id(self.db);
             self.collect_macro_expansion(module_id, macro_call_id, macro_def_id);
         }
 
         res
     }
 
+    /// Checks that `def` is a proc macro which can be invoked as a derive or as
+    /// an attribute, according to `call`.
+    fn is_proc_macro_of_kind(&self, def: MacroDefId, call: &MacroCallKind) -> bool {
+        let (krate, idx) = match def.0 {
+            MacroDefKind::ProcMacro { krate, idx } => (krate, idx),
+            MacroDefKind::MacroRules(_) => return false,
+        };
+        let expected = match call {
+            MacroCallKind::Derive(_) => ProcMacroKind::CustomDerive,
+            MacroCallKind::Attr(..) => ProcMacroKind::Attr,
+            MacroCallKind::FnLike(_) => ProcMacroKind::FuncLike,
+        };
+        let crate_graph = self.db.crate_graph();
+        crate_graph.proc_macros(krate).get(idx as usize).map(|it| it.kind) == Some(expected)
+    }
+
     fn collect_macro_expansion(
         &mut self,
         module_id: CrateModuleId,
@@ -461,6 +922,7 @@ where
 
         if !self.macro_stack_monitor.is_poison(macro_def_id) {
             let file_id: HirFileId = macro_call_id.as_file(MacroFileKind::Items);
+            self.def_map.modules[module_id].macro_expansions.push(file_id);
             let raw_items = self.db.raw_items(file_id);
             ModCollector { def_collector: &mut *self, file_id, module_id, raw_items: &raw_items }
                 .collect(raw_items.items());
@@ -495,11 +957,13 @@ where
                 raw::RawItem::Module(m) => self.collect_module(&self.raw_items[m]),
                 raw::RawItem::Import(import) => self.def_collector.unresolved_imports.push((
                     self.module_id,
+                    self.file_id,
                     import,
                     self.raw_items[import].clone(),
                 )),
                 raw::RawItem::Def(def) => self.define_def(&self.raw_items[def]),
                 raw::RawItem::Macro(mac) => self.collect_macro(&self.raw_items[mac]),
+                raw::RawItem::AttrMacro(mac) => self.collect_attr_macro(&self.raw_items[mac]),
             }
         }
     }
@@ -507,9 +971,9 @@ where
     fn collect_module(&mut self, module: &raw::ModuleData) {
         match module {
             // inline module, just recurse
-            raw::ModuleData::Definition { name, items, ast_id } => {
-                let module_id =
-                    self.push_child_module(name.clone(), ast_id.with_file_id(self.file_id), None);
+            raw::ModuleData::Definition { name, items, ast_id, visibility, is_macro_use } => {
+                let ast_id = ast_id.with_file_id(self.file_id);
+                let module_id = self.push_child_module(name.clone(), ast_id, None, visibility);
                 ModCollector {
                     def_collector: &mut *self.def_collector,
                     module_id,
@@ -517,14 +981,18 @@ where
                     raw_items: self.raw_items,
                 }
                 .collect(&*items);
+                if *is_macro_use {
+                    self.def_collector.import_all_legacy_macros(self.module_id, module_id, ast_id);
+                }
             }
             // out of line module, resovle, parse and recurse
-            raw::ModuleData::Declaration { name, ast_id } => {
+            raw::ModuleData::Declaration { name, ast_id, visibility, is_macro_use } => {
                 let ast_id = ast_id.with_file_id(self.file_id);
                 let is_root = self.def_collector.def_map.modules[self.module_id].parent.is_none();
                 match resolve_submodule(self.def_collector.db, self.file_id, name, is_root) {
                     Ok(file_id) => {
-                        let module_id = self.push_child_module(name.clone(), ast_id, Some(file_id));
+                        let module_id =
+                            self.push_child_module(name.clone(), ast_id, Some(file_id), visibility);
                         let raw_items = self.def_collector.db.raw_items(file_id.into());
                         ModCollector {
                             def_collector: &mut *self.def_collector,
@@ -532,7 +1000,14 @@ where
                             file_id: file_id.into(),
                             raw_items: &raw_items,
                         }
-                        .collect(raw_items.items())
+                        .collect(raw_items.items());
+                        if *is_macro_use {
+                            self.def_collector.import_all_legacy_macros(
+                                self.module_id,
+                                module_id,
+                                ast_id,
+                            );
+                        }
                     }
                     Err(candidate) => self.def_collector.def_map.diagnostics.push(
                         DefDiagnostic::UnresolvedModule {
@@ -551,24 +1026,43 @@ where
         name: Name,
         declaration: AstId<ast::Module>,
         definition: Option<FileId>,
+        visibility: &RawVisibility,
     ) -> CrateModuleId {
+        let vis = self.resolve_visibility(visibility);
         let modules = &mut self.def_collector.def_map.modules;
         let res = modules.alloc(ModuleData::default());
         modules[res].parent = Some(self.module_id);
         modules[res].declaration = Some(declaration);
         modules[res].definition = definition;
+        let legacy_macros = modules[self.module_id].scope.legacy_macros.clone();
+        modules[res].scope.textual_macros = legacy_macros
+            .iter()
+            .map(|(name, def)| (name.clone(), *def, LegacyMacroOrigin::Parent))
+            .collect();
+        modules[res].scope.legacy_macros = legacy_macros;
         modules[self.module_id].children.insert(name.clone(), res);
         let resolution = Resolution {
             def: PerNs::types(
                 Module { krate: self.def_collector.def_map.krate, module_id: res }.into(),
             ),
             import: None,
+            visibility: PerNs::types(vis),
         };
-        self.def_collector.update(self.module_id, None, &[(name, Either::A(resolution))]);
+        let resolution = (name, Either::A(resolution));
+        self.def_collector.update(self.module_id, None, ImportType::Named, &[resolution]);
         res
     }
 
     fn define_def(&mut self, def: &raw::DefData) {
+        let name = def.name.clone();
+        let vis = self.resolve_visibility(&def.visibility);
+        let def = self.def_per_ns(def);
+        let resolution = Resolution { def, import: None, visibility: def.map(|_| vis) };
+        let resolution = (name, Either::A(resolution));
+        self.def_collector.update(self.module_id, None, ImportType::Named, &[resolution])
+    }
+
+    fn def_per_ns(&self, def: &raw::DefData) -> PerNs<ModuleDef> {
         let module = Module { krate: self.def_collector.def_map.krate, module_id: self.module_id };
         let ctx = LocationCtx::new(self.def_collector.db, module, self.file_id);
 
@@ -577,8 +1071,7 @@ where
                 $kind { id: AstItemDef::from_ast_id(ctx, $ast_id) }.into()
             };
         }
-        let name = def.name.clone();
-        let def: PerNs<ModuleDef> = match def.kind {
+        match def.kind {
             raw::DefKind::Function(ast_id) => PerNs::values(def!(Function, ast_id)),
             raw::DefKind::Struct(ast_id) => {
                 let s = def!(Struct, ast_id);
@@ -593,16 +1086,20 @@ where
             raw::DefKind::Static(ast_id) => PerNs::values(def!(Static, ast_id)),
             raw::DefKind::Trait(ast_id) => PerNs::types(def!(Trait, ast_id)),
             raw::DefKind::TypeAlias(ast_id) => PerNs::types(def!(TypeAlias, ast_id)),
-        };
-        let resolution = Resolution { def, import: None };
-        self.def_collector.update(self.module_id, None, &[(name, Either::A(resolution))])
+        }
+    }
+
+    fn resolve_visibility(&self, visibility: &RawVisibility) -> Visibility {
+        let def_map = &self.def_collector.def_map;
+        def_map.resolve_visibility(self.def_collector.db, self.module_id, visibility)
     }
 
     fn collect_macro(&mut self, mac: &raw::MacroData) {
-        // Case 1: macro rules, define a macro in crate-global mutable scope
+        // Case 1: macro rules, define a macro in the textual scope
         if is_macro_rules(&mac.path) {
             if let Some(name) = &mac.name {
-                let macro_id = MacroDefId(mac.ast_id.with_file_id(self.file_id));
+                let ast_id = mac.ast_id.with_file_id(self.file_id);
+                let macro_id = MacroDefId(MacroDefKind::MacroRules(ast_id));
                 self.def_collector.define_macro(self.module_id, name.clone(), macro_id, mac.export)
             }
             return;
@@ -610,20 +1107,43 @@ where
 
         let ast_id = mac.ast_id.with_file_id(self.file_id);
 
-        // Case 2: try to expand macro_rules from this crate, triggering
+        // Case 2: try to expand macro_rules from the textual scope, triggering
         // recursive item collection.
-        if let Some(macro_id) =
-            mac.path.as_ident().and_then(|name| self.def_collector.global_macro_scope.get(&name))
-        {
-            let def = *macro_id;
-            let macro_call_id = MacroCallLoc { def, ast_id }.id(self.def_collector.db);
+        if let Some(macro_def) = mac.path.as_ident().and_then(|name| {
+            self.def_collector.def_map[self.module_id].scope.get_legacy_macro(name)
+        }) {
+            let def = macro_def.id;
+            let kind = MacroCallKind::FnLike(ast_id);
+            let macro_call_id = MacroCallLoc { def, kind }.id(self.def_collector.db);
 
             self.def_collector.collect_macro_expansion(self.module_id, macro_call_id, def);
             return;
         }
 
-        // Case 3: path to a macro from another crate, expand during name resolution
-        self.def_collector.unexpanded_macros.push((self.module_id, ast_id, mac.path.clone()))
+        // Case 3: path to a macro from another crate, or a macro which is
+        // imported or defined later, expand during name resolution
+        self.def_collector.unexpanded_macros.push((
+            self.module_id,
+            MacroCallKind::FnLike(ast_id),
+            mac.path.clone(),
+        ))
+    }
+
+    fn collect_attr_macro(&mut self, mac: &raw::AttrMacroData) {
+        // Derives and attribute macros are procedural macros, which always
+        // come from another crate, so we expand them during name resolution.
+        let ast_id = mac.ast_id.with_file_id(self.file_id);
+        let kind = match mac.kind {
+            raw::AttrMacroKind::Derive => MacroCallKind::Derive(ast_id),
+            raw::AttrMacroKind::Attr => MacroCallKind::Attr(ast_id, mac.name.clone()),
+        };
+        if let Some(def) = mac.item {
+            let items = &mut self.def_collector.attr_macro_items;
+            if !items.iter().any(|(it, ..)| *it == ast_id) {
+                items.push((ast_id, self.module_id, self.file_id, def));
+            }
+        }
+        self.def_collector.unexpanded_macros.push((self.module_id, kind, mac.name.clone().into()))
     }
 }
 
@@ -682,10 +1202,15 @@ mod tests {
         let mut collector = DefCollector {
             db,
             def_map,
+            extern_crates: FxHashMap::default(),
             glob_imports: FxHashMap::default(),
             unresolved_imports: Vec::new(),
+            named_imports: Vec::new(),
+            from_glob_import: FxHashSet::default(),
+            ambiguous_globs: FxHashSet::default(),
             unexpanded_macros: Vec::new(),
-            global_macro_scope: FxHashMap::default(),
+            attr_macro_items: Vec::new(),
+            replaced_items: FxHashSet::default(),
             macro_stack_monitor: monitor,
         };
         collector.collect();
@@ -705,6 +1230,7 @@ mod tests {
                 krate,
                 edition,
                 extern_prelude: FxHashMap::default(),
+                macro_use_prelude: FxHashMap::default(),
                 prelude: None,
                 root,
                 modules,
@@ -60,6 +60,13 @@ impl<T> PerNs<T> {
         self.as_ref().take(namespace)
     }
 
+    pub(crate) fn get_mut(&mut self, namespace: Namespace) -> &mut Option<T> {
+        match namespace {
+            Namespace::Types => &mut self.types,
+            Namespace::Values => &mut self.values,
+        }
+    }
+
     pub fn as_ref(&self) -> PerNs<&T> {
         PerNs { types: self.types.as_ref(), values: self.values.as_ref() }
     }
@@ -7,7 +7,12 @@ use ra_syntax::{
     ast::{self, NameOwner, AttrsOwner},
 };
 
-use crate::{DefDatabase, Name, AsName, Path, HirFileId, ModuleSource, AstIdMap, FileAstId, Either, AstDatabase};
+use crate::{
+    DefDatabase, Name, AsName, Path, HirFileId, ModuleSource, AstIdMap, FileAstId, Either,
+    AstDatabase,
+    derive::{self, BuiltinDerive},
+    visibility::RawVisibility,
+};
 
 /// `RawItems` is a set of top-level items in a file (except for impls).
 ///
@@ -19,8 +24,37 @@ pub struct RawItems {
     imports: Arena<ImportId, ImportData>,
     defs: Arena<Def, DefData>,
     macros: Arena<Macro, MacroData>,
+    attr_macros: Arena<AttrMacro, AttrMacroData>,
     /// items for top-level module
     items: Vec<RawItem>,
+    /// Inner attributes of the file, which matter if it is a crate root.
+    crate_attrs: CrateAttrs,
+}
+
+/// Crate-level attributes which change what rustc injects into the crate.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub(super) struct CrateAttrs {
+    /// `#![no_std]`: `core` is injected instead of `std`.
+    pub(super) no_std: bool,
+    /// `#![no_core]`: neither `std` nor `core` is injected.
+    pub(super) no_core: bool,
+}
+
+impl CrateAttrs {
+    fn from_source_file(source_file: &SourceFile) -> CrateAttrs {
+        let mut res = CrateAttrs::default();
+        for attr in source_file.syntax().children().filter_map(ast::Attr::cast) {
+            if !attr.is_inner() {
+                continue;
+            }
+            match attr.as_atom().as_ref().map(|it| it.as_str()) {
+                Some("no_std") => res.no_std = true,
+                Some("no_core") => res.no_core = true,
+                _ => (),
+            }
+        }
+        res
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -45,6 +79,10 @@ impl ImportSourceMap {
         self.map.insert(import, ptr)
     }
 
+    pub(crate) fn get_ptr(&self, import: ImportId) -> ImportSourcePtr {
+        self.map[import]
+    }
+
     pub(crate) fn get(&self, source: &ModuleSource, import: ImportId) -> ImportSource {
         let file = match source {
             ModuleSource::SourceFile(file) => &*file,
@@ -74,6 +112,7 @@ impl RawItems {
         };
         if let Some(node) = db.parse_or_expand(file_id) {
             if let Some(source_file) = ast::SourceFile::cast(&node) {
+                collector.raw_items.crate_attrs = CrateAttrs::from_source_file(source_file);
                 collector.process_module(None, &*source_file);
             }
         }
@@ -83,6 +122,10 @@ impl RawItems {
     pub(super) fn items(&self) -> &[RawItem] {
         &self.items
     }
+
+    pub(super) fn crate_attrs(&self) -> CrateAttrs {
+        self.crate_attrs
+    }
 }
 
 impl Index<Module> for RawItems {
@@ -113,12 +156,20 @@ impl Index<Macro> for RawItems {
     }
 }
 
+impl Index<AttrMacro> for RawItems {
+    type Output = AttrMacroData;
+    fn index(&self, idx: AttrMacro) -> &AttrMacroData {
+        &self.attr_macros[idx]
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub(super) enum RawItem {
     Module(Module),
     Import(ImportId),
     Def(Def),
     Macro(Macro),
+    AttrMacro(AttrMacro),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -127,8 +178,19 @@ impl_arena_id!(Module);
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) enum ModuleData {
-    Declaration { name: Name, ast_id: FileAstId<ast::Module> },
-    Definition { name: Name, ast_id: FileAstId<ast::Module>, items: Vec<RawItem> },
+    Declaration {
+        name: Name,
+        ast_id: FileAstId<ast::Module>,
+        visibility: RawVisibility,
+        is_macro_use: bool,
+    },
+    Definition {
+        name: Name,
+        ast_id: FileAstId<ast::Module>,
+        visibility: RawVisibility,
+        is_macro_use: bool,
+        items: Vec<RawItem>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -139,9 +201,11 @@ impl_arena_id!(ImportId);
 pub struct ImportData {
     pub(super) path: Path,
     pub(super) alias: Option<Name>,
+    pub(super) visibility: RawVisibility,
     pub(super) is_glob: bool,
     pub(super) is_prelude: bool,
     pub(super) is_extern_crate: bool,
+    pub(super) is_macro_use: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -152,6 +216,7 @@ impl_arena_id!(Def);
 pub(super) struct DefData {
     pub(super) name: Name,
     pub(super) kind: DefKind,
+    pub(super) visibility: RawVisibility,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -178,6 +243,29 @@ pub(super) struct MacroData {
     pub(super) export: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct AttrMacro(RawId);
+impl_arena_id!(AttrMacro);
+
+/// A custom derive or an attribute which might be an attribute macro. We only
+/// know which attributes are macros after name resolution: an attribute is a
+/// macro if its path resolves to one.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct AttrMacroData {
+    pub(super) ast_id: FileAstId<ast::ModuleItem>,
+    pub(super) name: Name,
+    pub(super) kind: AttrMacroKind,
+    /// For an attribute, the definition it annotates. It's defined as is, and
+    /// replaced by the expansion if the attribute turns out to be a macro.
+    pub(super) item: Option<Def>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum AttrMacroKind {
+    Derive,
+    Attr,
+}
+
 struct RawItemsCollector {
     raw_items: RawItems,
     source_ast_id_map: Arc<AstIdMap>,
@@ -195,56 +283,76 @@ impl RawItemsCollector {
     }
 
     fn add_item(&mut self, current_module: Option<Module>, item: &ast::ModuleItem) {
-        let (kind, name) = match item.kind() {
+        let (kind, name, visibility) = match item.kind() {
             ast::ModuleItemKind::Module(module) => {
+                self.add_attr_macros(current_module, item, None);
                 self.add_module(current_module, module);
                 return;
             }
             ast::ModuleItemKind::UseItem(use_item) => {
+                self.add_attr_macros(current_module, item, None);
                 self.add_use_item(current_module, use_item);
                 return;
             }
             ast::ModuleItemKind::ExternCrateItem(extern_crate) => {
+                self.add_attr_macros(current_module, item, None);
                 self.add_extern_crate_item(current_module, extern_crate);
                 return;
             }
             ast::ModuleItemKind::ImplBlock(_) => {
                 // impls don't participate in name resolution
+                self.add_attr_macros(current_module, item, None);
                 return;
             }
             ast::ModuleItemKind::StructDef(it) => {
                 let id = self.source_ast_id_map.ast_id(it);
                 let name = it.name();
+                let visibility = RawVisibility::from_ast(it);
                 if it.is_union() {
-                    (DefKind::Union(id), name)
+                    (DefKind::Union(id), name, visibility)
                 } else {
-                    (DefKind::Struct(id), name)
+                    (DefKind::Struct(id), name, visibility)
                 }
             }
-            ast::ModuleItemKind::EnumDef(it) => {
-                (DefKind::Enum(self.source_ast_id_map.ast_id(it)), it.name())
-            }
-            ast::ModuleItemKind::FnDef(it) => {
-                (DefKind::Function(self.source_ast_id_map.ast_id(it)), it.name())
-            }
-            ast::ModuleItemKind::TraitDef(it) => {
-                (DefKind::Trait(self.source_ast_id_map.ast_id(it)), it.name())
-            }
-            ast::ModuleItemKind::TypeAliasDef(it) => {
-                (DefKind::TypeAlias(self.source_ast_id_map.ast_id(it)), it.name())
-            }
-            ast::ModuleItemKind::ConstDef(it) => {
-                (DefKind::Const(self.source_ast_id_map.ast_id(it)), it.name())
-            }
-            ast::ModuleItemKind::StaticDef(it) => {
-                (DefKind::Static(self.source_ast_id_map.ast_id(it)), it.name())
-            }
+            ast::ModuleItemKind::EnumDef(it) => (
+                DefKind::Enum(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
+            ast::ModuleItemKind::FnDef(it) => (
+                DefKind::Function(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
+            ast::ModuleItemKind::TraitDef(it) => (
+                DefKind::Trait(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
+            ast::ModuleItemKind::TypeAliasDef(it) => (
+                DefKind::TypeAlias(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
+            ast::ModuleItemKind::ConstDef(it) => (
+                DefKind::Const(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
+            ast::ModuleItemKind::StaticDef(it) => (
+                DefKind::Static(self.source_ast_id_map.ast_id(it)),
+                it.name(),
+                RawVisibility::from_ast(it),
+            ),
         };
-        if let Some(name) = name {
+        let def = name.map(|name| {
             let name = name.as_name();
-            let def = self.raw_items.defs.alloc(DefData { name, kind });
+            self.raw_items.defs.alloc(DefData { name, kind, visibility })
+        });
+        if let Some(def) = def {
             self.push_item(current_module, RawItem::Def(def))
         }
+        self.add_attr_macros(current_module, item, def);
     }
 
     fn add_module(&mut self, current_module: Option<Module>, module: &ast::Module) {
@@ -253,8 +361,15 @@ impl RawItemsCollector {
             None => return,
         };
         let ast_id = self.source_ast_id_map.ast_id(module);
+        let visibility = RawVisibility::from_ast(module);
+        let is_macro_use = module.has_atom_attr("macro_use");
         if module.has_semi() {
-            let item = self.raw_items.modules.alloc(ModuleData::Declaration { name, ast_id });
+            let item = self.raw_items.modules.alloc(ModuleData::Declaration {
+                name,
+                ast_id,
+                visibility,
+                is_macro_use,
+            });
             self.push_item(current_module, RawItem::Module(item));
             return;
         }
@@ -263,6 +378,8 @@ impl RawItemsCollector {
             let item = self.raw_items.modules.alloc(ModuleData::Definition {
                 name,
                 ast_id,
+                visibility,
+                is_macro_use,
                 items: Vec::new(),
             });
             self.process_module(Some(item), item_list);
@@ -274,10 +391,18 @@ impl RawItemsCollector {
 
     fn add_use_item(&mut self, current_module: Option<Module>, use_item: &ast::UseItem) {
         let is_prelude = use_item.has_atom_attr("prelude_import");
+        let visibility = RawVisibility::from_ast(use_item);
 
         Path::expand_use_item(use_item, |path, use_tree, is_glob, alias| {
-            let import_data =
-                ImportData { path, alias, is_glob, is_prelude, is_extern_crate: false };
+            let import_data = ImportData {
+                path,
+                alias,
+                visibility: visibility.clone(),
+                is_glob,
+                is_prelude,
+                is_extern_crate: false,
+                is_macro_use: false,
+            };
             self.push_import(current_module, import_data, Either::A(AstPtr::new(use_tree)));
         })
     }
@@ -293,9 +418,11 @@ impl RawItemsCollector {
             let import_data = ImportData {
                 path,
                 alias,
+                visibility: RawVisibility::from_ast(extern_crate),
                 is_glob: false,
                 is_prelude: false,
                 is_extern_crate: true,
+                is_macro_use: extern_crate.has_atom_attr("macro_use"),
             };
             self.push_import(current_module, import_data, Either::B(AstPtr::new(extern_crate)));
         }
@@ -314,6 +441,42 @@ impl RawItemsCollector {
         self.push_item(current_module, RawItem::Macro(m));
     }
 
+    /// Adds the derives and the attributes of `item`, which might be macros.
+    /// `def` is the definition of the item, if any.
+    fn add_attr_macros(
+        &mut self,
+        current_module: Option<Module>,
+        item: &ast::ModuleItem,
+        def: Option<Def>,
+    ) {
+        let mut ast_id = None;
+        for attr in item.syntax().children().filter_map(ast::Attr::cast) {
+            let attr_name = match attr.as_named() {
+                Some(it) => it,
+                None => continue,
+            };
+            let macros = if attr_name == "derive" {
+                derive::derive_names(attr)
+                    .into_iter()
+                    .filter(|name| BuiltinDerive::from_name(name).is_none())
+                    .map(|name| (Name::attr_macro(name), AttrMacroKind::Derive))
+                    .collect()
+            } else {
+                vec![(Name::attr_macro(attr_name), AttrMacroKind::Attr)]
+            };
+            for (name, kind) in macros {
+                let ast_id = *ast_id.get_or_insert_with(|| self.source_ast_id_map.ast_id(item));
+                let item = match kind {
+                    AttrMacroKind::Derive => None,
+                    AttrMacroKind::Attr => def,
+                };
+                let m =
+                    self.raw_items.attr_macros.alloc(AttrMacroData { ast_id, name, kind, item });
+                self.push_item(current_module, RawItem::AttrMacro(m));
+            }
+        }
+    }
+
     fn push_import(
         &mut self,
         current_module: Option<Module>,
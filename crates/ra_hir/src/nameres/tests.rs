@@ -256,6 +256,74 @@ fn std_prelude() {
     "###);
 }
 
+#[test]
+fn no_std_prelude() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        #![no_std]
+        use CoreEnum::*;
+        use StdEnum::*;
+        use core::prelude as core_prelude;
+        use std::prelude as std_prelude;
+
+        //- /std/lib.rs
+        pub mod prelude;
+        #[prelude_import]
+        use prelude::*;
+
+        //- /std/prelude.rs
+        pub enum StdEnum { Std }
+
+        //- /core/lib.rs
+        pub mod prelude;
+        #[prelude_import]
+        use prelude::*;
+
+        //- /core/prelude.rs
+        pub enum CoreEnum { Core }
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["std", "core"]),
+            "std": ("/std/lib.rs", ["core"]),
+            "core": ("/core/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+        ⋮crate
+        ⋮Core: t v
+        ⋮core_prelude: t
+    "###);
+}
+
+#[test]
+fn no_core_prelude() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        #![no_core]
+        extern crate core;
+        use CoreEnum::*;
+
+        //- /core/lib.rs
+        pub mod prelude;
+        #[prelude_import]
+        use prelude::*;
+
+        //- /core/prelude.rs
+        pub enum CoreEnum { Core }
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["core"]),
+            "core": ("/core/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+        ⋮crate
+        ⋮core: t
+    "###);
+}
+
 #[test]
 fn can_import_enum_variant() {
     covers!(can_import_enum_variant);
@@ -574,3 +642,96 @@ fn unresolved_module_diagnostics() {
 "###
     );
 }
+
+#[test]
+fn unresolved_import_diagnostics() {
+    let diagnostics = MockDatabase::with_files(
+        r"
+        //- /lib.rs
+        mod foo;
+        use foo::Bar;
+        use foo::Missing;
+        use missing::*;
+        //- /foo.rs
+        pub struct Bar;
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot_matches!(diagnostics, @r###"
+"missing::*": unresolved import
+"foo::Missing": unresolved import
+"###
+    );
+}
+
+#[test]
+fn private_item_diagnostics() {
+    let diagnostics = MockDatabase::with_files(
+        r"
+        //- /lib.rs
+        mod foo;
+        use foo::Public;
+        use foo::CrateVisible;
+        use foo::Private;
+        use foo::private_mod::Item;
+        use foo::bar::SuperVisible;
+        use foo::bar::InFoo;
+        //- /foo.rs
+        pub struct Public;
+        pub(crate) struct CrateVisible;
+        struct Private;
+        mod private_mod {
+            pub struct Item;
+        }
+        pub mod bar {
+            pub(super) struct SuperVisible;
+            pub(in crate::foo) struct InFoo;
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot_matches!(diagnostics, @r###"
+"foo::Private": `Private` is private
+"foo::private_mod::Item": `private_mod` is private
+"foo::bar::SuperVisible": `SuperVisible` is private
+"foo::bar::InFoo": `InFoo` is private
+"###
+    );
+}
+
+#[test]
+fn ambiguous_import_diagnostics() {
+    covers!(glob_imports_are_ambiguous);
+    let mut db = MockDatabase::with_files(
+        r"
+        //- /main.rs
+        mod foo;
+        mod bar;
+        mod baz;
+        use bar::*;
+        use baz::*;
+        use Enum::Variant;
+        use foo::Bar;
+        //- /foo.rs
+        pub struct Bar;
+        //- /bar.rs
+        pub enum Enum { Variant }
+        //- /baz.rs
+        pub enum Enum { Variant }
+        //- /lib.rs
+        pub struct Bar;
+        ",
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", "2018", ["foo"]),
+        "foo": ("/lib.rs", []),
+    });
+
+    assert_snapshot_matches!(db.diagnostics(), @r###"
+"foo::Bar": `foo` is ambiguous
+"Enum::Variant": `Enum` is ambiguous
+"###
+    );
+}
@@ -116,3 +116,99 @@ fn glob_enum() {
     "###
     );
 }
+
+#[test]
+fn explicit_import_shadows_glob_import() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        mod foo;
+        mod bar;
+        use foo::*;
+        use bar::baz;
+        use self::baz::*;
+
+        //- /foo.rs
+        pub mod baz {
+            pub struct FromGlob;
+        }
+
+        //- /bar.rs
+        pub mod baz {
+            pub struct FromNamed;
+        }
+        ",
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮FromNamed: t v
+   ⋮bar: t
+   ⋮baz: t
+   ⋮foo: t
+   ⋮
+   ⋮crate::bar
+   ⋮baz: t
+   ⋮
+   ⋮crate::bar::baz
+   ⋮FromNamed: t v
+   ⋮
+   ⋮crate::foo
+   ⋮baz: t
+   ⋮
+   ⋮crate::foo::baz
+   ⋮FromGlob: t v
+    "###);
+}
+
+#[test]
+fn glob_imports_only_visible_items() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use foo::*;
+
+        //- /foo.rs
+        pub struct Public;
+        pub(crate) struct CrateVisible;
+        pub(super) struct SuperVisible;
+        struct Private;
+        ",
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮CrateVisible: t v
+   ⋮Public: t v
+   ⋮SuperVisible: t v
+   ⋮foo: t
+   ⋮
+   ⋮crate::foo
+   ⋮CrateVisible: t v
+   ⋮Private: t v
+   ⋮Public: t v
+   ⋮SuperVisible: t v
+    "###);
+}
+
+#[test]
+fn glob_across_crates_imports_only_public_items() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        use test_crate::*;
+
+        //- /lib.rs
+        pub struct Public;
+        pub(crate) struct CrateVisible;
+        struct Private;
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["test_crate"]),
+            "test_crate": ("/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Public: t v
+    "###);
+}
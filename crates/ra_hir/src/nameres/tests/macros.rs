@@ -21,7 +21,6 @@ fn macro_rules_are_globally_visible() {
    ⋮crate
    ⋮Foo: t v
    ⋮nested: t
-   ⋮structs: m
    ⋮
    ⋮crate::nested
    ⋮Bar: t v
@@ -47,7 +46,6 @@ fn macro_rules_can_define_modules() {
     );
     assert_snapshot_matches!(map, @r###"
    ⋮crate
-   ⋮m: m
    ⋮n1: t
    ⋮
    ⋮crate::n1
@@ -133,7 +131,261 @@ fn unexpanded_macro_should_expand_by_fixedpoint_loop() {
    ⋮crate
    ⋮Foo: t v
    ⋮bar: m
-   ⋮baz: m
    ⋮foo: m
     "###);
 }
+
+#[test]
+fn macro_rules_are_scoped_textually() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        mod before;
+        structs!(Foo);
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        structs!(Bar);
+        macro_rules! structs {
+            ($i:ident) => { fn $i() {} }
+        }
+        structs!(baz);
+
+        //- /before.rs
+        structs!(Baz);
+        ",
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Bar: t v
+   ⋮baz: v
+   ⋮before: t
+   ⋮
+   ⋮crate::before
+    "###);
+}
+
+#[test]
+fn macro_use_extends_macro_scope_past_module() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        #[macro_use]
+        mod m;
+        structs!(Foo);
+        fns!(bar);
+
+        //- /m.rs
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        mod n;
+
+        //- /m/n.rs
+        macro_rules! fns {
+            ($i:ident) => { fn $i() {} }
+        }
+        ",
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Foo: t v
+   ⋮m: t
+   ⋮
+   ⋮crate::m
+   ⋮n: t
+   ⋮
+   ⋮crate::m::n
+    "###);
+}
+
+#[test]
+fn macro_use_extern_crate_imports_exported_macros() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        #[macro_use]
+        extern crate foo;
+
+        structs!(Foo);
+
+        //- /lib.rs
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["foo"]),
+            "foo": ("/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Foo: t v
+   ⋮foo: t
+    "###);
+}
+
+#[test]
+fn extern_crate_without_macro_use_does_not_import_macros() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        extern crate foo;
+
+        structs!(Foo);
+
+        //- /lib.rs
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["foo"]),
+            "foo": ("/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮foo: t
+    "###);
+}
+
+#[test]
+fn macros_from_prelude_crate_are_visible() {
+    let map = def_map_with_crate_graph(
+        "
+        //- /main.rs
+        structs!(Foo);
+
+        //- /std/lib.rs
+        #[prelude_import]
+        use self::prelude::*;
+        mod prelude {}
+
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        ",
+        crate_graph! {
+            "main": ("/main.rs", ["std"]),
+            "std": ("/std/lib.rs", []),
+        },
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Foo: t v
+    "###);
+}
+
+/// A proc macro which ignores its input and always expands to `text`.
+#[derive(Debug)]
+struct FixedExpansion(&'static str);
+
+impl ra_db::ProcMacroExpander for FixedExpansion {
+    fn expand(
+        &self,
+        _subtree: &tt::Subtree,
+        _attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, String> {
+        let parse = ra_syntax::SourceFile::parse(self.0);
+        Ok(mbe::syntax_node_to_token_tree(parse.tree.syntax()).unwrap().0)
+    }
+}
+
+#[test]
+fn proc_macros_are_expanded() {
+    let mut db = MockDatabase::with_files(
+        "
+        //- /main.rs
+        use foo_macros::{Foo, generate};
+
+        #[derive(Foo, Clone)]
+        struct S;
+
+        #[generate]
+        fn original() {}
+
+        #[not_a_macro]
+        fn kept() {}
+
+        mod m {
+            pub use crate::kept as reexported;
+        }
+
+        //- /lib.rs
+        ",
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["foo_macros"]),
+        "foo_macros": ("/lib.rs", []),
+    });
+    let mut crate_graph = (*db.crate_graph()).clone();
+    let proc_macro_crate = crate_graph.crate_id_for_crate_root(db.file_id_of("/lib.rs")).unwrap();
+    crate_graph.set_proc_macros(
+        proc_macro_crate,
+        vec![
+            ra_db::ProcMacro {
+                name: "Foo".into(),
+                kind: ra_db::ProcMacroKind::CustomDerive,
+                expander: Arc::new(FixedExpansion("fn derived() {}")),
+            },
+            ra_db::ProcMacro {
+                name: "generate".into(),
+                kind: ra_db::ProcMacroKind::Attr,
+                expander: Arc::new(FixedExpansion("fn replaced() {} fn generated() {}")),
+            },
+        ],
+    );
+    let main_crate = crate_graph.crate_id_for_crate_root(db.file_id_of("/main.rs")).unwrap();
+    db.set_crate_graph(Arc::new(crate_graph));
+
+    let map = render_crate_def_map(&db.crate_def_map(Crate { crate_id: main_crate }));
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮Foo: m
+   ⋮S: t v
+   ⋮derived: v
+   ⋮generate: m
+   ⋮generated: v
+   ⋮kept: v
+   ⋮m: t
+   ⋮replaced: v
+   ⋮
+   ⋮crate::m
+   ⋮reexported: v
+    "###);
+}
+
+#[test]
+fn items_with_inert_attributes_are_defined() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        #[rustc_something]
+        pub fn f() {}
+
+        #[thread_local]
+        pub static X: u32 = 0;
+
+        mod m {
+            pub use super::*;
+            pub use super::f as g;
+        }
+        ",
+    );
+    assert_snapshot_matches!(map, @r###"
+   ⋮crate
+   ⋮X: v
+   ⋮f: v
+   ⋮m: t
+   ⋮
+   ⋮crate::m
+   ⋮X: v
+   ⋮f: v
+   ⋮g: v
+    "###);
+}
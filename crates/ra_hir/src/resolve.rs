@@ -2,11 +2,12 @@
 use std::sync::Arc;
 
 use rustc_hash::{FxHashMap, FxHashSet};
+use ra_syntax::TextUnit;
 
 use crate::{
-    ModuleDef, Trait, MacroDef,
+    ModuleDef, Trait, MacroDef, HirFileId,
     code_model::Crate,
-    db::HirDatabase,
+    db::{HirDatabase, DefDatabase, AstDatabase},
     name::{Name, KnownName},
     nameres::{PerNs, CrateDefMap, CrateModuleId},
     generics::GenericParams,
@@ -130,12 +131,21 @@ impl Resolver {
         resolution
     }
 
+    /// Resolves the path of a macro call at `offset` in `file_id`, which is in
+    /// a body of the module of the resolver.
     pub(crate) fn resolve_path_as_macro(
         &self,
-        db: &impl HirDatabase,
+        db: &(impl DefDatabase + AstDatabase),
         path: &Path,
+        file_id: HirFileId,
+        offset: TextUnit,
     ) -> Option<MacroDef> {
         let (item_map, module) = self.module()?;
+        if let Some(def) = path.as_ident().and_then(|name| {
+            item_map[module].scope.get_legacy_macro_at(db, name, file_id, offset)
+        }) {
+            return Some(def);
+        }
         match item_map.resolve_path_with_macro(db, module, path) {
             (Either::B(macro_def), None) => Some(macro_def),
             _ => None,
@@ -83,6 +83,7 @@ fn module_from_source(
     decl_id: Option<AstId<ast::Module>>,
 ) -> Option<Module> {
     let source_root_id = db.file_source_root(file_id.as_original_file());
+    // Prefers the crate compiled without `cfg(test)`, see `source_root_crates`.
     db.source_root_crates(source_root_id).iter().map(|&crate_id| Crate { crate_id }).find_map(
         |krate| {
             let def_map = db.crate_def_map(krate);
@@ -166,6 +167,7 @@ fn def_with_body_from_child_node(
 /// original source files. It should not be used inside the HIR itself.
 #[derive(Debug)]
 pub struct SourceAnalyzer {
+    file_id: FileId,
     resolver: Resolver,
     body_source_map: Option<Arc<BodySourceMap>>,
     infer: Option<Arc<crate::ty::InferenceResult>>,
@@ -224,6 +226,7 @@ impl SourceAnalyzer {
             };
             let resolver = expr::resolver_for_scope(def.body(db), db, scope);
             SourceAnalyzer {
+                file_id,
                 resolver,
                 body_source_map: Some(source_map),
                 infer: Some(def.infer(db)),
@@ -231,6 +234,7 @@ impl SourceAnalyzer {
             }
         } else {
             SourceAnalyzer {
+                file_id,
                 resolver: node
                     .ancestors()
                     .find_map(|node| try_get_resolver_for_node(db, file_id, node))
@@ -268,7 +272,8 @@ impl SourceAnalyzer {
         macro_call: &ast::MacroCall,
     ) -> Option<MacroDef> {
         let path = macro_call.path().and_then(Path::from_ast)?;
-        self.resolver.resolve_path_as_macro(db, &path)
+        let offset = macro_call.syntax().range().start();
+        self.resolver.resolve_path_as_macro(db, &path, self.file_id.into(), offset)
     }
 
     pub fn resolve_hir_path(
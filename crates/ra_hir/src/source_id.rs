@@ -1,7 +1,7 @@
 use std::{marker::PhantomData, sync::Arc, hash::{Hash, Hasher}};
 
 use ra_arena::{Arena, RawId, impl_arena_id};
-use ra_syntax::{SyntaxNodePtr, TreeArc, SyntaxNode, AstNode, ast};
+use ra_syntax::{SyntaxNodePtr, TreeArc, SyntaxNode, AstNode, TextRange, ast};
 
 use crate::{HirFileId, AstDatabase};
 
@@ -38,6 +38,10 @@ impl<N: AstNode> AstId<N> {
         self.file_id
     }
 
+    pub(crate) fn range(&self, db: &impl AstDatabase) -> TextRange {
+        db.ast_id_map(self.file_id).arena[self.file_ast_id.raw].range()
+    }
+
     pub(crate) fn to_node(&self, db: &impl AstDatabase) -> TreeArc<N> {
         let syntax_node = db.ast_id_to_node(self.file_id, self.file_ast_id.raw);
         N::cast(&syntax_node).unwrap().to_owned()
@@ -42,6 +42,8 @@ pub struct CrateImplBlocks {
     krate: Crate,
     impls: FxHashMap<TyFingerprint, Vec<(CrateModuleId, ImplId)>>,
     impls_by_trait: FxHashMap<Trait, Vec<(CrateModuleId, ImplId)>>,
+    /// Trait impls, indexed by their self type.
+    trait_impls_by_self_ty: FxHashMap<TyFingerprint, Vec<(CrateModuleId, ImplId)>>,
 }
 
 impl CrateImplBlocks {
@@ -55,6 +57,19 @@ impl CrateImplBlocks {
         )
     }
 
+    /// Returns the trait impls (including derived ones) for `ty`.
+    pub fn lookup_trait_impl_blocks<'a>(&'a self, ty: &Ty) -> impl Iterator<Item = ImplBlock> + 'a {
+        let fingerprint = TyFingerprint::for_impl(ty);
+        fingerprint
+            .and_then(|f| self.trait_impls_by_self_ty.get(&f))
+            .into_iter()
+            .flat_map(|i| i.iter())
+            .map(move |(module_id, impl_id)| {
+                let module = Module { krate: self.krate, module_id: *module_id };
+                ImplBlock::from_id(module, *impl_id)
+            })
+    }
+
     pub fn lookup_impl_blocks_for_trait<'a>(
         &'a self,
         tr: &Trait,
@@ -81,6 +96,12 @@ impl CrateImplBlocks {
                         .entry(tr.trait_)
                         .or_insert_with(Vec::new)
                         .push((module.module_id, impl_id));
+                    if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
+                        self.trait_impls_by_self_ty
+                            .entry(target_ty_fp)
+                            .or_insert_with(Vec::new)
+                            .push((module.module_id, impl_id));
+                    }
                 }
             } else {
                 if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
@@ -105,6 +126,7 @@ impl CrateImplBlocks {
             krate,
             impls: FxHashMap::default(),
             impls_by_trait: FxHashMap::default(),
+            trait_impls_by_self_ty: FxHashMap::default(),
         };
         if let Some(module) = krate.root_module(db) {
             crate_impl_blocks.collect_recursive(db, &module);
@@ -1969,6 +1969,73 @@ mod foo {
     assert_eq!("i128", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_derived_clone() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+use core::clone::Clone;
+
+#[derive(Clone)]
+struct S;
+
+fn test() {
+    S.clone()<|>;
+}
+
+//- /lib.rs
+pub mod clone {
+    pub trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["core"]),
+        "core": ("/lib.rs", []),
+    });
+    assert_eq!("S", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_derived_default_in_no_std() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+#![no_std]
+use core::default::Default;
+
+#[derive(Default)]
+struct S;
+
+trait Make {
+    fn make(&self) -> u8;
+}
+impl<T: Default> Make for T {
+    fn make(&self) -> u8 { 0 }
+}
+
+fn test() {
+    let s: S = Default::default();
+    s.make()<|>;
+}
+
+//- /lib.rs
+pub mod default {
+    pub trait Default {
+        fn default() -> Self;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["core"]),
+        "core": ("/lib.rs", []),
+    });
+    assert_eq!("u8", type_at_pos(&db, pos));
+}
+
 #[test]
 fn infer_const() {
     assert_snapshot_matches!(
@@ -2488,6 +2555,104 @@ fn main() {
     );
 }
 
+#[test]
+fn infer_type_macro_expanded() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! int { () => { u32 } }
+type Int = int!();
+fn test(a: Int) {
+    let b: int!() = a;
+    b<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_recursive_macros_expanded() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! ty { () => { ty!() } }
+macro_rules! expr { () => { expr!() } }
+type T = ty!();
+fn test(a: T) {
+    let b: ty!() = expr!();
+    b<|>;
+}
+"#,
+    );
+    assert_eq!(t, "{unknown}");
+}
+
+#[test]
+fn infer_pat_macro_expanded() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! pair { ($a:ident, $b:ident) => { ($a, $b) } }
+fn test() {
+    let pair!(a, b) = (1u8, 1i64);
+    b<|>;
+}
+"#,
+    );
+    assert_eq!(t, "i64");
+}
+
+#[test]
+fn infer_stmts_macro_expanded() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! decl { ($name:ident) => { let $name = 0u16; } }
+fn test() {
+    decl!(x);
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u16");
+}
+
+#[test]
+fn infer_macro_shadowed_later_in_module() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! num { () => { 0u8 } }
+fn test() {
+    let a = num!();
+    a<|>;
+}
+macro_rules! num { () => { 0u64 } }
+"#,
+    );
+    assert_eq!(t, "u8");
+}
+
+#[test]
+fn infer_macro_from_macro_use_module_before_call() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! num { () => { 0u8 } }
+fn test() {
+    let a = num!();
+    a<|>;
+}
+#[macro_use]
+mod m;
+//- /m.rs
+macro_rules! num { () => { 0u64 } }
+"#,
+    );
+    assert_eq!(t, "u8");
+}
+
 #[ignore]
 #[test]
 fn method_resolution_trait_before_autoref() {
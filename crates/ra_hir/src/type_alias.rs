@@ -2,9 +2,18 @@
 
 use std::sync::Arc;
 
-use ra_syntax::ast::NameOwner;
+use ra_syntax::{
+    AstNode,
+    ast::{self, NameOwner},
+};
 
-use crate::{TypeAlias, db::{DefDatabase, AstDatabase}, type_ref::TypeRef, name::{Name, AsName}, HasSource};
+use crate::{
+    TypeAlias, HirFileId, Resolver, HasSource,
+    db::{DefDatabase, AstDatabase},
+    type_ref::TypeRef,
+    name::{Name, AsName},
+    ids::{expand_macro_call, MacroFileKind, MACRO_EXPANSION_DEPTH_LIMIT},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypeAliasData {
@@ -16,8 +25,28 @@ pub(crate) fn type_alias_data_query(
     db: &(impl DefDatabase + AstDatabase),
     typ: TypeAlias,
 ) -> Arc<TypeAliasData> {
-    let node = typ.source(db).ast;
-    let name = node.name().map_or_else(Name::missing, |n| n.as_name());
-    let type_ref = node.type_ref().map(TypeRef::from_ast);
+    let src = typ.source(db);
+    let name = src.ast.name().map_or_else(Name::missing, |n| n.as_name());
+    let resolver = typ.module(db).resolver(db);
+    let type_ref = src.ast.type_ref().map(|it| lower_type_ref(db, &resolver, src.file_id, it, 0));
     Arc::new(TypeAliasData { name, type_ref })
 }
+
+/// Lowers `node`, expanding macro calls in type position (`type T = foo!();`).
+/// `depth` is the number of expansions `node` is nested in.
+fn lower_type_ref(
+    db: &(impl DefDatabase + AstDatabase),
+    resolver: &Resolver,
+    file_id: HirFileId,
+    node: &ast::TypeRef,
+    depth: usize,
+) -> TypeRef {
+    TypeRef::from_ast_with_macros(node, &mut |call| {
+        if depth >= MACRO_EXPANSION_DEPTH_LIMIT {
+            return None;
+        }
+        let (file_id, node) = expand_macro_call(db, resolver, file_id, call, MacroFileKind::Type)?;
+        let type_ref = ast::TypeRef::cast(&*node)?;
+        Some(lower_type_ref(db, resolver, file_id, type_ref, depth + 1))
+    })
+}
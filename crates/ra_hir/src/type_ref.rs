@@ -57,31 +57,53 @@ pub enum TypeRef {
 impl TypeRef {
     /// Converts an `ast::TypeRef` to a `hir::TypeRef`.
     pub(crate) fn from_ast(node: &ast::TypeRef) -> Self {
+        TypeRef::from_ast_with_macros(node, &mut |_| None)
+    }
+
+    /// Converts an `ast::TypeRef` to a `hir::TypeRef`, lowering macro calls in
+    /// type position with `expand`. Unexpanded macros become `TypeRef::Error`.
+    pub(crate) fn from_ast_with_macros(
+        node: &ast::TypeRef,
+        expand: &mut dyn FnMut(&ast::MacroCall) -> Option<TypeRef>,
+    ) -> Self {
         use ra_syntax::ast::TypeRefKind::*;
         match node.kind() {
-            ParenType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
-            TupleType(inner) => TypeRef::Tuple(inner.fields().map(TypeRef::from_ast).collect()),
+            ParenType(inner) => TypeRef::from_ast_opt_with_macros(inner.type_ref(), expand),
+            TupleType(inner) => TypeRef::Tuple(
+                inner.fields().map(|it| TypeRef::from_ast_with_macros(it, expand)).collect(),
+            ),
             NeverType(..) => TypeRef::Never,
             PathType(inner) => {
                 inner.path().and_then(Path::from_ast).map(TypeRef::Path).unwrap_or(TypeRef::Error)
             }
             PointerType(inner) => {
-                let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
+                let inner_ty = TypeRef::from_ast_opt_with_macros(inner.type_ref(), expand);
                 let mutability = Mutability::from_mutable(inner.is_mut());
                 TypeRef::RawPtr(Box::new(inner_ty), mutability)
             }
-            ArrayType(inner) => TypeRef::Array(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
-            SliceType(inner) => TypeRef::Slice(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
+            ArrayType(inner) => TypeRef::Array(Box::new(TypeRef::from_ast_opt_with_macros(
+                inner.type_ref(),
+                expand,
+            ))),
+            SliceType(inner) => TypeRef::Slice(Box::new(TypeRef::from_ast_opt_with_macros(
+                inner.type_ref(),
+                expand,
+            ))),
             ReferenceType(inner) => {
-                let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
+                let inner_ty = TypeRef::from_ast_opt_with_macros(inner.type_ref(), expand);
                 let mutability = Mutability::from_mutable(inner.is_mut());
                 TypeRef::Reference(Box::new(inner_ty), mutability)
             }
             PlaceholderType(_inner) => TypeRef::Placeholder,
             FnPointerType(inner) => {
-                let ret_ty = TypeRef::from_ast_opt(inner.ret_type().and_then(|rt| rt.type_ref()));
+                let ret_ty = TypeRef::from_ast_opt_with_macros(
+                    inner.ret_type().and_then(|rt| rt.type_ref()),
+                    expand,
+                );
                 let mut params = if let Some(pl) = inner.param_list() {
-                    pl.params().map(|p| p.ascribed_type()).map(TypeRef::from_ast_opt).collect()
+                    pl.params()
+                        .map(|p| TypeRef::from_ast_opt_with_macros(p.ascribed_type(), expand))
+                        .collect()
                 } else {
                     Vec::new()
                 };
@@ -89,15 +111,25 @@ impl TypeRef {
                 TypeRef::Fn(params)
             }
             // for types are close enough for our purposes to the inner type for now...
-            ForType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
+            ForType(inner) => TypeRef::from_ast_opt_with_macros(inner.type_ref(), expand),
             ImplTraitType(_inner) => TypeRef::Error,
             DynTraitType(_inner) => TypeRef::Error,
+            MacroType(inner) => {
+                inner.macro_call().and_then(|it| expand(it)).unwrap_or(TypeRef::Error)
+            }
         }
     }
 
     pub(crate) fn from_ast_opt(node: Option<&ast::TypeRef>) -> Self {
+        TypeRef::from_ast_opt_with_macros(node, &mut |_| None)
+    }
+
+    pub(crate) fn from_ast_opt_with_macros(
+        node: Option<&ast::TypeRef>,
+        expand: &mut dyn FnMut(&ast::MacroCall) -> Option<TypeRef>,
+    ) -> Self {
         if let Some(node) = node {
-            TypeRef::from_ast(node)
+            TypeRef::from_ast_with_macros(node, expand)
         } else {
             TypeRef::Error
         }
@@ -0,0 +1,219 @@
+//! Visibility of items, fields and associated items.
+//!
+//! The visibility is stored in two forms. `RawVisibility` is what is written in
+//! the source, like `pub(super)`; it is recorded by the `raw` items collector
+//! before any name resolution happens. `Visibility` is the resolved form,
+//! which names the module the item is visible in.
+
+use ra_syntax::ast::{self, VisibilityKind};
+
+use crate::{
+    Module, ModuleDef, StructField, Struct, Union, Enum, EnumVariant, Function, Const, Static,
+    Trait, TypeAlias, FieldSource, HasSource, Container, ImplItem, Path, PathKind,
+    db::{DefDatabase, HirDatabase},
+    nameres::{CrateDefMap, CrateModuleId},
+    adt::VariantDef,
+};
+
+/// Visibility, as written in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RawVisibility {
+    /// `pub(in path)`, `pub(crate)`, `pub(super)`, `pub(self)`, or no
+    /// visibility at all, which is the same as `pub(self)`.
+    Module(Path),
+    /// `pub`.
+    Public,
+}
+
+impl RawVisibility {
+    pub(crate) fn private() -> RawVisibility {
+        RawVisibility::Module(Path { kind: PathKind::Self_, segments: Vec::new() })
+    }
+
+    pub(crate) fn from_ast(node: &impl ast::VisibilityOwner) -> RawVisibility {
+        let visibility = match node.visibility() {
+            Some(it) => it,
+            None => return RawVisibility::private(),
+        };
+        let kind = match visibility.kind() {
+            VisibilityKind::In(path) => match Path::from_ast(path) {
+                Some(path) => return RawVisibility::Module(path),
+                None => return RawVisibility::private(),
+            },
+            VisibilityKind::PubCrate => PathKind::Crate,
+            VisibilityKind::PubSuper => PathKind::Super,
+            VisibilityKind::PubSelf => PathKind::Self_,
+            VisibilityKind::Pub => return RawVisibility::Public,
+        };
+        RawVisibility::Module(Path { kind, segments: Vec::new() })
+    }
+}
+
+/// Resolved visibility of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    /// Visible everywhere.
+    Public,
+    /// Visible in the module and in all of its descendants.
+    Module(Module),
+}
+
+impl Visibility {
+    pub fn is_visible_from(self, db: &impl DefDatabase, from: Module) -> bool {
+        let to = match self {
+            Visibility::Public => return true,
+            Visibility::Module(it) => it,
+        };
+        if to.krate != from.krate {
+            return false;
+        }
+        let def_map = db.crate_def_map(from.krate);
+        self.is_visible_from_def_map(&def_map, from.module_id)
+    }
+
+    /// Like `is_visible_from`, but works with the def map which is still being
+    /// collected.
+    pub(crate) fn is_visible_from_def_map(
+        self,
+        def_map: &CrateDefMap,
+        from: CrateModuleId,
+    ) -> bool {
+        let to = match self {
+            Visibility::Public => return true,
+            Visibility::Module(it) => it,
+        };
+        if to.krate != def_map.krate() {
+            return false;
+        }
+        let mut module = Some(from);
+        while let Some(module_id) = module {
+            if module_id == to.module_id {
+                return true;
+            }
+            module = def_map[module_id].parent;
+        }
+        false
+    }
+}
+
+pub trait HasVisibility {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility;
+}
+
+/// Resolves the visibility written on `node`, which is declared in `module`.
+fn declared_visibility(
+    db: &impl HirDatabase,
+    module: Module,
+    node: &impl ast::VisibilityOwner,
+) -> Visibility {
+    let def_map = db.crate_def_map(module.krate);
+    def_map.resolve_visibility(db, module.module_id, &RawVisibility::from_ast(node))
+}
+
+/// Associated items of traits are as visible as the trait, and the items of
+/// trait impls are as visible as the trait they implement.
+fn associated_item_visibility(
+    db: &impl HirDatabase,
+    container: Option<Container>,
+) -> Option<Visibility> {
+    match container? {
+        Container::Trait(it) => Some(it.visibility(db)),
+        Container::ImplBlock(it) if it.target_trait(db).is_some() => Some(Visibility::Public),
+        Container::ImplBlock(_) => None,
+    }
+}
+
+impl HasVisibility for Module {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        match (self.parent(db), self.declaration_source(db)) {
+            (Some(parent), Some(src)) => declared_visibility(db, parent, &*src.ast),
+            // the crate root
+            _ => Visibility::Public,
+        }
+    }
+}
+
+impl HasVisibility for ModuleDef {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        match self {
+            ModuleDef::Module(it) => it.visibility(db),
+            ModuleDef::Function(it) => it.visibility(db),
+            ModuleDef::Struct(it) => it.visibility(db),
+            ModuleDef::Union(it) => it.visibility(db),
+            ModuleDef::Enum(it) => it.visibility(db),
+            ModuleDef::EnumVariant(it) => it.visibility(db),
+            ModuleDef::Const(it) => it.visibility(db),
+            ModuleDef::Static(it) => it.visibility(db),
+            ModuleDef::Trait(it) => it.visibility(db),
+            ModuleDef::TypeAlias(it) => it.visibility(db),
+            ModuleDef::BuiltinType(_) => Visibility::Public,
+        }
+    }
+}
+
+impl HasVisibility for StructField {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        let strukt = match self.parent_def(db) {
+            VariantDef::Struct(it) => it,
+            // fields of enum variants are as visible as the enum
+            VariantDef::EnumVariant(it) => return it.visibility(db),
+        };
+        let module = strukt.module(db);
+        match self.source(db).ast {
+            FieldSource::Named(it) => declared_visibility(db, module, &*it),
+            FieldSource::Pos(it) => declared_visibility(db, module, &*it),
+        }
+    }
+}
+
+impl HasVisibility for EnumVariant {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        self.parent_enum(db).visibility(db)
+    }
+}
+
+impl HasVisibility for ImplItem {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        match self {
+            ImplItem::Method(it) => it.visibility(db),
+            ImplItem::Const(it) => it.visibility(db),
+            ImplItem::TypeAlias(it) => it.visibility(db),
+        }
+    }
+}
+
+impl HasVisibility for Function {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        associated_item_visibility(db, self.container(db))
+            .unwrap_or_else(|| declared_visibility(db, self.module(db), &*self.source(db).ast))
+    }
+}
+
+impl HasVisibility for Const {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        let container = self.impl_block(db).map(Container::from);
+        associated_item_visibility(db, container)
+            .unwrap_or_else(|| declared_visibility(db, self.module(db), &*self.source(db).ast))
+    }
+}
+
+impl HasVisibility for TypeAlias {
+    fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+        associated_item_visibility(db, self.container(db))
+            .unwrap_or_else(|| declared_visibility(db, self.module(db), &*self.source(db).ast))
+    }
+}
+
+macro_rules! impl_declared_visibility {
+    ($($ty:ident),*) => {
+        $(
+            impl HasVisibility for $ty {
+                fn visibility(&self, db: &impl HirDatabase) -> Visibility {
+                    declared_visibility(db, self.module(db), &*self.source(db).ast)
+                }
+            }
+        )*
+    }
+}
+
+impl_declared_visibility!(Struct, Union, Enum, Static, Trait);
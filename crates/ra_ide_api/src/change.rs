@@ -5,10 +5,11 @@ use std::{
 
 use rustc_hash::FxHashMap;
 use ra_db::{
-    SourceRootId, FileId, CrateGraph, SourceDatabase, SourceRoot,
+    SourceRootId, FileId, CrateGraph, SourceDatabase, SourceRoot, FileEdits,
     salsa::{Database, SweepStrategy},
 };
 use ra_syntax::SourceFile;
+use ra_text_edit::AtomTextEdit;
 use ra_prof::profile;
 use relative_path::RelativePathBuf;
 use rayon::prelude::*;
@@ -22,8 +23,10 @@ use crate::{
 #[derive(Default)]
 pub struct AnalysisChange {
     new_roots: Vec<(SourceRootId, bool)>,
+    removed_roots: Vec<SourceRootId>,
     roots_changed: FxHashMap<SourceRootId, RootChange>,
-    files_changed: Vec<(FileId, Arc<String>)>,
+    files_changed: Vec<(FileId, Arc<String>, Option<Vec<AtomTextEdit>>)>,
+    edits_discarded: Vec<FileId>,
     libraries_added: Vec<LibraryData>,
     crate_graph: Option<CrateGraph>,
 }
@@ -34,12 +37,18 @@ impl fmt::Debug for AnalysisChange {
         if !self.new_roots.is_empty() {
             d.field("new_roots", &self.new_roots);
         }
+        if !self.removed_roots.is_empty() {
+            d.field("removed_roots", &self.removed_roots);
+        }
         if !self.roots_changed.is_empty() {
             d.field("roots_changed", &self.roots_changed);
         }
         if !self.files_changed.is_empty() {
             d.field("files_changed", &self.files_changed.len());
         }
+        if !self.edits_discarded.is_empty() {
+            d.field("edits_discarded", &self.edits_discarded);
+        }
         if !self.libraries_added.is_empty() {
             d.field("libraries_added", &self.libraries_added.len());
         }
@@ -59,6 +68,12 @@ impl AnalysisChange {
         self.new_roots.push((root_id, is_local));
     }
 
+    /// Removes a root and all of its files, for example when a dependency is
+    /// removed from the workspace.
+    pub fn remove_root(&mut self, root_id: SourceRootId) {
+        self.removed_roots.push(root_id);
+    }
+
     pub fn add_file(
         &mut self,
         root_id: SourceRootId,
@@ -71,7 +86,25 @@ impl AnalysisChange {
     }
 
     pub fn change_file(&mut self, file_id: FileId, new_text: Arc<String>) {
-        self.files_changed.push((file_id, new_text))
+        self.files_changed.push((file_id, new_text, None))
+    }
+
+    /// Like `change_file`, with the edits which turn the previous text of the
+    /// file into `new_text`. Only the parts of the syntax tree touched by the
+    /// edits are reparsed.
+    pub fn change_file_with_edits(
+        &mut self,
+        file_id: FileId,
+        new_text: Arc<String>,
+        edits: Vec<AtomTextEdit>,
+    ) {
+        self.files_changed.push((file_id, new_text, Some(edits)))
+    }
+
+    /// Drops the old syntax tree kept to reparse the edits of the file, when
+    /// the file isn't edited anymore.
+    pub fn discard_edits(&mut self, file_id: FileId) {
+        self.edits_discarded.push(file_id)
     }
 
     pub fn remove_file(&mut self, root_id: SourceRootId, file_id: FileId, path: RelativePathBuf) {
@@ -160,23 +193,40 @@ impl RootDatabase {
             let _p = profile("RootDatabase::apply_change/cancellation");
             self.salsa_runtime().next_revision();
         }
-        if !change.new_roots.is_empty() {
+        if !change.new_roots.is_empty() || !change.removed_roots.is_empty() {
             let mut local_roots = Vec::clone(&self.local_roots());
+            let mut libraries = Vec::clone(&self.library_roots());
+            for root_id in change.removed_roots {
+                self.set_source_root(root_id, Default::default());
+                local_roots.retain(|&it| it != root_id);
+                libraries.retain(|&it| it != root_id);
+            }
             for (root_id, is_local) in change.new_roots {
                 self.set_source_root(root_id, Default::default());
+                // When the workspace is reloaded, the ids of the roots are reused
+                local_roots.retain(|&it| it != root_id);
+                libraries.retain(|&it| it != root_id);
                 if is_local {
                     local_roots.push(root_id);
                 }
             }
             self.set_local_roots(Arc::new(local_roots));
+            self.set_library_roots(Arc::new(libraries));
         }
 
         for (root_id, root_change) in change.roots_changed {
             self.apply_root_change(root_id, root_change);
         }
-        for (file_id, text) in change.files_changed {
+        for (file_id, text, edits) in change.files_changed {
+            // The edits are applied to the tree of the current text
+            let edits =
+                edits.map(|edits| Arc::new(FileEdits { parse: self.parse(file_id), edits }));
+            self.set_file_edits(file_id, edits);
             self.set_file_text(file_id, text)
         }
+        for file_id in change.edits_discarded {
+            self.set_file_edits(file_id, None);
+        }
         if !change.libraries_added.is_empty() {
             let mut libraries = Vec::clone(&self.library_roots());
             for library in change.libraries_added {
@@ -196,12 +246,14 @@ impl RootDatabase {
         let mut source_root = SourceRoot::clone(&self.source_root(root_id));
         for add_file in root_change.added {
             self.set_file_text(add_file.file_id, add_file.text);
+            self.set_file_edits(add_file.file_id, None);
             self.set_file_relative_path(add_file.file_id, add_file.path.clone());
             self.set_file_source_root(add_file.file_id, root_id);
             source_root.files.insert(add_file.path, add_file.file_id);
         }
         for remove_file in root_change.removed {
             self.set_file_text(remove_file.file_id, Default::default());
+            self.set_file_edits(remove_file.file_id, None);
             source_root.files.remove(&remove_file.path);
         }
         self.set_source_root(root_id, Arc::new(source_root));
@@ -243,3 +295,52 @@ impl RootDatabase {
         self.query(hir::db::BodyHirQuery).sweep(sweep);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ra_syntax::{AstNode, SourceFile, TextRange};
+    use ra_text_edit::AtomTextEdit;
+
+    use crate::{AnalysisChange, AnalysisHost, FileId, SourceRootId};
+
+    const TEXT: &str = "fn foo() {\n    1 + 1\n}\n\nfn bar() {}\n";
+
+    fn host_with_file() -> (AnalysisHost, FileId) {
+        let mut host = AnalysisHost::default();
+        let source_root = SourceRootId(0);
+        let file_id = FileId(0);
+        let mut change = AnalysisChange::new();
+        change.add_root(source_root, true);
+        change.add_file(source_root, file_id, "main.rs".into(), Arc::new(TEXT.to_string()));
+        host.apply_change(change);
+        (host, file_id)
+    }
+
+    fn check_edits(edits: Vec<AtomTextEdit>, new_text: &str) {
+        let (mut host, file_id) = host_with_file();
+        host.analysis().parse(file_id);
+        let mut change = AnalysisChange::new();
+        change.change_file_with_edits(file_id, Arc::new(new_text.to_string()), edits);
+        host.apply_change(change);
+        let actual = host.analysis().parse(file_id);
+        let expected = SourceFile::parse(new_text).tree;
+        assert_eq!(actual.syntax().debug_dump(), expected.syntax().debug_dump());
+    }
+
+    #[test]
+    fn edits_are_applied_in_order() {
+        let edits = vec![
+            AtomTextEdit::replace(TextRange::from_to(19.into(), 20.into()), "92".to_string()),
+            AtomTextEdit::insert(6.into(), "_baz".to_string()),
+        ];
+        check_edits(edits, "fn foo_baz() {\n    1 + 92\n}\n\nfn bar() {}\n");
+    }
+
+    #[test]
+    fn edits_which_dont_match_the_text_are_ignored() {
+        let edits = vec![AtomTextEdit::insert(6.into(), "_baz".to_string())];
+        check_edits(edits, "struct S;\n");
+    }
+}
@@ -24,10 +24,26 @@ use crate::{
 
 };
 #[cfg(test)]
-use crate::completion::completion_item::{do_completion, check_completion};
+use crate::completion::completion_item::{do_completion, do_completion_with_config, check_completion};
 
 pub use crate::completion::completion_item::{CompletionItem, CompletionItemKind, InsertTextFormat};
 
+/// Which completions are offered, and how they are inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompletionConfig {
+    /// Postfix completions, like `expr.if`.
+    pub enable_postfix_completions: bool,
+    /// Whether functions are completed with parentheses, with the cursor
+    /// between them if the function has arguments.
+    pub add_call_parenthesis: bool,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> CompletionConfig {
+        CompletionConfig { enable_postfix_completions: true, add_call_parenthesis: true }
+    }
+}
+
 /// Main entry point for completion. We run completion as a two-phase process.
 ///
 /// First, we look at the position and collect a so-called `CompletionContext.
@@ -50,9 +66,13 @@ pub use crate::completion::completion_item::{CompletionItem, CompletionItemKind,
 /// `foo` *should* be present among the completion variants. Filtering by
 /// identifier prefix/fuzzy match should be done higher in the stack, together
 /// with ordering of completions (currently this is done by the client).
-pub(crate) fn completions(db: &db::RootDatabase, position: FilePosition) -> Option<Completions> {
+pub(crate) fn completions(
+    db: &db::RootDatabase,
+    position: FilePosition,
+    config: &CompletionConfig,
+) -> Option<Completions> {
     let original_parse = db.parse(position.file_id);
-    let ctx = CompletionContext::new(db, &original_parse, position)?;
+    let ctx = CompletionContext::new(db, &original_parse, position, config)?;
 
     let mut acc = Completions::default();
 
@@ -66,6 +86,8 @@ pub(crate) fn completions(db: &db::RootDatabase, position: FilePosition) -> Opti
     complete_dot::complete_dot(&mut acc, &ctx);
     complete_struct_literal::complete_struct_literal(&mut acc, &ctx);
     complete_pattern::complete_pattern(&mut acc, &ctx);
-    complete_postfix::complete_postfix(&mut acc, &ctx);
+    if config.enable_postfix_completions {
+        complete_postfix::complete_postfix(&mut acc, &ctx);
+    }
     Some(acc)
 }
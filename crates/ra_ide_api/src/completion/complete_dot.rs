@@ -1,4 +1,4 @@
-use hir::{Ty, AdtDef, TypeCtor};
+use hir::{Ty, AdtDef, TypeCtor, HasVisibility};
 
 use crate::completion::{CompletionContext, Completions};
 
@@ -20,6 +20,9 @@ fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty)
             match a_ty.ctor {
                 TypeCtor::Adt(AdtDef::Struct(s)) => {
                     for field in s.fields(ctx.db) {
+                        if !ctx.is_visible(field.visibility(ctx.db)) {
+                            continue;
+                        }
                         acc.add_field(ctx, field, &a_ty.parameters);
                     }
                 }
@@ -38,7 +41,7 @@ fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty)
 fn complete_methods(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty) {
     ctx.analyzer.iterate_method_candidates(ctx.db, receiver, None, |_ty, func| {
         let data = func.data(ctx.db);
-        if data.has_self_param() {
+        if data.has_self_param() && ctx.is_visible(func.visibility(ctx.db)) {
             acc.add_function(ctx, func);
         }
         None::<()>
@@ -158,6 +161,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_private_fields_and_methods_are_not_completed() {
+        let completions = do_ref_completion(
+            r"
+            mod m {
+                pub struct A { pub public_field: u32, private_field: u32 }
+                impl A {
+                    pub fn public_method(&self) {}
+                    fn private_method(&self) {}
+                }
+            }
+            fn foo(a: m::A) {
+               a.<|>
+            }
+            ",
+        );
+        let labels = completions.iter().map(|it| it.label()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["public_field", "public_method"]);
+    }
+
     #[test]
     fn test_no_struct_field_completion_for_method_call() {
         assert_debug_snapshot_matches!(
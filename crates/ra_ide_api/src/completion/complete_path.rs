@@ -1,4 +1,4 @@
-use hir::{Resolution, Either};
+use hir::{Resolution, Either, HasVisibility};
 use ra_syntax::AstNode;
 use test_utils::tested_by;
 
@@ -23,6 +23,14 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) {
                         continue;
                     }
                 }
+                let is_visible = [res.visibility.types, res.visibility.values]
+                    .iter()
+                    .flatten()
+                    .any(|&vis| ctx.is_visible(vis));
+                if !res.def.is_none() && !is_visible {
+                    tested_by!(dont_complete_private_items);
+                    continue;
+                }
                 if Some(module) == ctx.module {
                     if let Some(import) = res.import {
                         if let Either::A(use_tree) = module.import_source(ctx.db, import) {
@@ -47,6 +55,9 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) {
             let krate = ctx.module.and_then(|m| m.krate(ctx.db));
             if let Some(krate) = krate {
                 ty.iterate_impl_items(ctx.db, krate, |item| {
+                    if !ctx.is_visible(item.visibility(ctx.db)) {
+                        return None;
+                    }
                     match item {
                         hir::ImplItem::Method(func) => {
                             let data = func.data(ctx.db);
@@ -101,6 +112,26 @@ mod tests {
         assert!(completions.is_empty());
     }
 
+    #[test]
+    fn dont_complete_private_items() {
+        covers!(dont_complete_private_items);
+        let completions = do_completion(
+            r"
+            mod m {
+                pub struct Public;
+                pub(crate) struct CrateVisible;
+                struct Private;
+                pub(super) fn super_visible() {}
+                mod private_module {}
+            }
+            use self::m::<|>;
+            ",
+            CompletionKind::Reference,
+        );
+        let labels = completions.iter().map(|it| it.label()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["CrateVisible", "Public", "super_visible"]);
+    }
+
     #[test]
     fn completes_primitives() {
         let completions =
@@ -132,7 +163,7 @@ mod tests {
             use self::m::<|>;
 
             mod m {
-                struct Bar;
+                pub struct Bar;
             }
             ",
         );
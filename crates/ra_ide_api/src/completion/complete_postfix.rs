@@ -56,7 +56,9 @@ pub(super) fn complete_postfix(acc: &mut Completions, ctx: &CompletionContext) {
 
 #[cfg(test)]
 mod tests {
-    use crate::completion::{CompletionKind, check_completion};
+    use crate::completion::{
+        CompletionKind, CompletionConfig, check_completion, do_completion_with_config,
+    };
 
     fn check_snippet_completion(test_name: &str, code: &str) {
         check_completion(test_name, code, CompletionKind::Postfix);
@@ -74,4 +76,20 @@ mod tests {
             "#,
         );
     }
+
+    #[test]
+    fn postfix_completion_can_be_disabled() {
+        let config = CompletionConfig { enable_postfix_completions: false, ..Default::default() };
+        let completions = do_completion_with_config(
+            r#"
+            fn main() {
+                let bar = "a";
+                bar.<|>
+            }
+            "#,
+            CompletionKind::Postfix,
+            &config,
+        );
+        assert!(completions.is_empty());
+    }
 }
@@ -7,13 +7,14 @@ use ra_syntax::{
 };
 use hir::source_binder;
 
-use crate::{db, FilePosition};
+use crate::{db, FilePosition, completion::CompletionConfig};
 
 /// `CompletionContext` is created early during completion to figure out, where
 /// exactly is the cursor, syntax-wise.
 #[derive(Debug)]
 pub(crate) struct CompletionContext<'a> {
     pub(super) db: &'a db::RootDatabase,
+    pub(super) config: &'a CompletionConfig,
     pub(super) analyzer: hir::SourceAnalyzer,
     pub(super) offset: TextUnit,
     pub(super) token: SyntaxToken<'a>,
@@ -45,6 +46,7 @@ impl<'a> CompletionContext<'a> {
         db: &'a db::RootDatabase,
         original_parse: &'a Parse,
         position: FilePosition,
+        config: &'a CompletionConfig,
     ) -> Option<CompletionContext<'a>> {
         let module = source_binder::module_from_position(db, position);
         let token =
@@ -53,6 +55,7 @@ impl<'a> CompletionContext<'a> {
             hir::SourceAnalyzer::new(db, position.file_id, token.parent(), Some(position.offset));
         let mut ctx = CompletionContext {
             db,
+            config,
             analyzer,
             token,
             offset: position.offset,
@@ -83,6 +86,15 @@ impl<'a> CompletionContext<'a> {
         }
     }
 
+    /// Checks that an item with visibility `vis` can be referred to from the
+    /// module being completed.
+    pub(crate) fn is_visible(&self, vis: hir::Visibility) -> bool {
+        match self.module {
+            Some(module) => vis.is_visible_from(self.db, module),
+            None => true,
+        }
+    }
+
     fn fill(&mut self, original_parse: &'a Parse, offset: TextUnit) {
         // Insert a fake ident to get a valid parse tree. We will use this file
         // to determine context, though the original_file will be used for
@@ -285,6 +285,15 @@ impl Into<Vec<CompletionItem>> for Completions {
 
 #[cfg(test)]
 pub(crate) fn do_completion(code: &str, kind: CompletionKind) -> Vec<CompletionItem> {
+    do_completion_with_config(code, kind, &Default::default())
+}
+
+#[cfg(test)]
+pub(crate) fn do_completion_with_config(
+    code: &str,
+    kind: CompletionKind,
+    config: &crate::completion::CompletionConfig,
+) -> Vec<CompletionItem> {
     use crate::mock_analysis::{single_file_with_position, analysis_and_position};
     use crate::completion::completions;
     let (analysis, position) = if code.contains("//-") {
@@ -292,7 +301,7 @@ pub(crate) fn do_completion(code: &str, kind: CompletionKind) -> Vec<CompletionI
     } else {
         single_file_with_position(code)
     };
-    let completions = completions(&analysis.db, position).unwrap();
+    let completions = completions(&analysis.db, position, config).unwrap();
     let completion_items: Vec<CompletionItem> = completions.into();
     let mut kind_completions: Vec<CompletionItem> =
         completion_items.into_iter().filter(|c| c.completion_kind == kind).collect();
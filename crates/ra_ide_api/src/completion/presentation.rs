@@ -112,7 +112,7 @@ impl Completions {
             .set_documentation(func.docs(ctx.db))
             .detail(detail);
         // If not an import, add parenthesis automatically.
-        if ctx.use_item_syntax.is_none() && !ctx.is_call {
+        if ctx.config.add_call_parenthesis && ctx.use_item_syntax.is_none() && !ctx.is_call {
             tested_by!(inserts_parens_for_function_calls);
             let snippet =
                 if data.params().is_empty() || data.has_self_param() && data.params().len() == 1 {
@@ -178,7 +178,10 @@ impl Completions {
 mod tests {
     use test_utils::covers;
 
-    use crate::completion::{CompletionKind, check_completion};
+    use crate::completion::{
+        CompletionKind, CompletionConfig, InsertTextFormat, check_completion,
+        do_completion_with_config,
+    };
 
     fn check_reference_completion(code: &str, expected_completions: &str) {
         check_completion(code, expected_completions, CompletionKind::Reference);
@@ -215,6 +218,21 @@ mod tests {
         )
     }
 
+    #[test]
+    fn parens_are_not_inserted_if_disabled() {
+        let config = CompletionConfig { add_call_parenthesis: false, ..Default::default() };
+        let completions = do_completion_with_config(
+            r"
+            fn with_args(x: i32, y: String) {}
+            fn main() { with_<|> }
+            ",
+            CompletionKind::Reference,
+            &config,
+        );
+        let item = completions.iter().find(|it| it.label() == "with_args").unwrap();
+        assert_eq!(item.insert_text_format(), InsertTextFormat::PlainText);
+    }
+
     #[test]
     fn dont_render_function_parens_in_use_item() {
         check_reference_completion(
@@ -1,19 +1,23 @@
 use std::cell::RefCell;
 
 use itertools::Itertools;
-use hir::{source_binder, diagnostics::{Diagnostic as _, DiagnosticSink}};
+use hir::{source_binder, HasVisibility, diagnostics::{Diagnostic as _, DiagnosticSink}};
 use ra_db::SourceDatabase;
 use ra_syntax::{
-    T, Location, TextRange, SyntaxNode,
+    T, Location, TextRange, SyntaxNode, SourceFile,
     ast::{self, AstNode, NamedFieldList, NamedField},
 };
 use ra_assists::ast_editor::{AstEditor, AstBuilder};
 use ra_text_edit::{TextEdit, TextEditBuilder};
 use ra_prof::profile;
 
-use crate::{Diagnostic, FileId, FileSystemEdit, SourceChange, SourceFileEdit, db::RootDatabase};
+use crate::{
+    Diagnostic, FileId, FileSystemEdit, Query, SourceChange, SourceFileEdit,
+    db::RootDatabase,
+    symbol_index::{self, FileSymbol},
+};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Severity {
     Error,
     WeakWarning,
@@ -57,6 +61,17 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
             fix: Some(fix),
         })
     })
+    .on::<hir::diagnostics::UnresolvedImport, _>(|d| {
+        // Imports of items generated by macros, or behind `cfg`s, may be
+        // reported by mistake.
+        let fix = auto_import_fix(db, file_id, &source_file, d);
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::WeakWarning,
+            fix,
+        })
+    })
     .on::<hir::diagnostics::MissingFields, _>(|d| {
         let syntax_node = d.syntax_node_ptr();
         let node = NamedFieldList::cast(syntax_node.to_node(source_file.syntax())).unwrap();
@@ -82,6 +97,88 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
     drop(sink);
     res.into_inner()
 }
+
+/// Suggests replacing the path of an unresolved import with the path to an
+/// item with the same name, declared in the current crate or in one of its
+/// dependencies. The items are looked up in the symbol index.
+fn auto_import_fix(
+    db: &RootDatabase,
+    file_id: FileId,
+    source_file: &SourceFile,
+    d: &hir::diagnostics::UnresolvedImport,
+) -> Option<SourceChange> {
+    let use_tree = ast::UseTree::cast(d.syntax_node_ptr().to_node(source_file.syntax()))?;
+    // Paths of nested use trees are relative to the prefix of the parent tree
+    if use_tree.syntax().parent().and_then(ast::UseItem::cast).is_none() {
+        return None;
+    }
+    let path = use_tree.path()?;
+    let name = path.segment()?.name_ref()?.text().clone();
+    let current_module = source_binder::module_from_child_node(db, file_id, use_tree.syntax())?;
+    let krate = current_module.krate(db)?;
+
+    let mut roots = vec![(krate, "crate".to_string())];
+    roots.extend(krate.dependencies(db).into_iter().map(|dep| (dep.krate, dep.name.to_string())));
+    let mut candidates = Vec::new();
+    for &libs in [false, true].iter() {
+        let mut query = Query::new(name.to_string());
+        query.exact();
+        if libs {
+            query.libs();
+        }
+        for symbol in symbol_index::world_symbols(db, query) {
+            candidates.extend(import_path(db, &symbol, current_module, &roots));
+        }
+    }
+
+    let candidate = candidates.into_iter().min_by_key(|it| (it.len(), it.clone()))?;
+    let mut builder = TextEditBuilder::default();
+    builder.replace(path.syntax().range(), candidate.clone());
+    let label = format!("import `{}`", candidate);
+    Some(SourceChange::source_file_edit_from(label, file_id, builder.finish()))
+}
+
+/// The path to the item declared by `symbol`, starting with the name of one
+/// of the `roots` crates, if the item can be imported into `current_module`.
+fn import_path(
+    db: &RootDatabase,
+    symbol: &FileSymbol,
+    current_module: hir::Module,
+    roots: &[(hir::Crate, String)],
+) -> Option<String> {
+    let source_file = db.parse(symbol.file_id).tree;
+    // The parent, as the module of a `mod` symbol is the module it declares
+    let node = symbol.ptr.to_node(source_file.syntax()).parent()?;
+    let module = source_binder::module_from_child_node(db, symbol.file_id, node)?;
+    let krate = module.krate(db)?;
+    let (_, crate_name) = roots.iter().find(|(it, _)| *it == krate)?;
+
+    // Items in bodies and associated items are not in the scope of the module
+    let is_declared_here = module.scope(db).entries().any(|(entry_name, res)| {
+        let is_visible = [res.visibility.types, res.visibility.values]
+            .iter()
+            .flatten()
+            .any(|vis| vis.is_visible_from(db, current_module));
+        res.import.is_none() && is_visible && entry_name.to_string() == symbol.name.as_str()
+    });
+    if !is_declared_here {
+        return None;
+    }
+
+    let mut segments = vec![symbol.name.to_string()];
+    let mut curr = module;
+    while let Some(parent) = curr.parent(db) {
+        if !curr.visibility(db).is_visible_from(db, current_module) {
+            return None;
+        }
+        segments.push(curr.name(db)?.to_string());
+        curr = parent;
+    }
+    segments.push(crate_name.clone());
+    segments.reverse();
+    Some(segments.join("::"))
+}
+
 fn location_to_range(location: Location) -> TextRange {
     match location {
         Location::Offset(offset) => TextRange::offset_len(offset, 1.into()),
@@ -332,6 +429,44 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_unresolved_import_diagnostic_fix() {
+        let before = r"
+            mod foo {
+                pub mod bar {
+                    pub struct Baz;
+                }
+            }
+            use bar::Baz;
+        ";
+        let after = r"
+            mod foo {
+                pub mod bar {
+                    pub struct Baz;
+                }
+            }
+            use crate::foo::bar::Baz;
+        ";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_unresolved_import_diagnostic_no_fix_for_private_items() {
+        let (analysis, file_id) = single_file(
+            r"
+            mod foo {
+                mod bar {
+                    pub struct Baz;
+                }
+            }
+            use bar::Baz;
+        ",
+        );
+        let diagnostic = analysis.diagnostics(file_id).unwrap().pop().unwrap();
+        assert_eq!(diagnostic.severity, Severity::WeakWarning);
+        assert!(diagnostic.fix.is_none());
+    }
+
     #[test]
     fn test_check_unnecessary_braces_in_use_statement() {
         check_not_applicable(
@@ -225,11 +225,16 @@ impl NavigationTarget {
         impl_block: hir::ImplBlock,
     ) -> NavigationTarget {
         let src = impl_block.source(db);
+        // Derived impls navigate to the `#[derive]` attribute they come from.
+        let node = match &src.ast {
+            hir::Either::A(impl_block) => impl_block.syntax(),
+            hir::Either::B(derive_attr) => derive_attr.syntax(),
+        };
         NavigationTarget::from_syntax(
             src.file_id.as_original_file(),
             "impl".into(),
             None,
-            src.ast.syntax(),
+            node,
             None,
             None,
         )
@@ -243,15 +248,18 @@ impl NavigationTarget {
         }
     }
 
-    pub(crate) fn from_macro_def(db: &RootDatabase, macro_call: hir::MacroDef) -> NavigationTarget {
-        let src = macro_call.source(db);
+    pub(crate) fn from_macro_def(
+        db: &RootDatabase,
+        macro_call: hir::MacroDef,
+    ) -> Option<NavigationTarget> {
+        let src = macro_call.source(db)?;
         log::debug!("nav target {}", src.ast.syntax().debug_dump());
-        NavigationTarget::from_named(
+        Some(NavigationTarget::from_named(
             src.file_id.original_file(db),
             &*src.ast,
             src.ast.doc_comment_text(),
             None,
-        )
+        ))
     }
 
     #[cfg(test)]
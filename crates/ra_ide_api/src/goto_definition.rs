@@ -59,7 +59,10 @@ pub(crate) fn reference_definition(
     let analyzer = hir::SourceAnalyzer::new(db, file_id, name_ref.syntax(), None);
 
     match classify_name_ref(db, &analyzer, name_ref) {
-        Some(Macro(mac)) => return Exact(NavigationTarget::from_macro_def(db, mac)),
+        Some(Macro(mac)) => match NavigationTarget::from_macro_def(db, mac) {
+            Some(nav) => return Exact(nav),
+            None => return Approximate(vec![]),
+        },
         Some(FieldAccess(field)) => return Exact(NavigationTarget::from_field(db, field)),
         Some(AssocItem(assoc)) => return Exact(NavigationTarget::from_impl_item(db, assoc)),
         Some(Method(func)) => return Exact(NavigationTarget::from_def_source(db, func)),
@@ -51,6 +51,7 @@ fn impls_for_def(
     Some(
         impls
             .lookup_impl_blocks(&ty)
+            .chain(impls.lookup_trait_impl_blocks(&ty))
             .map(|imp| NavigationTarget::from_impl_block(db, imp))
             .collect(),
     )
@@ -159,6 +160,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_implementation_includes_trait_impls() {
+        check_goto(
+            "
+            //- /lib.rs
+            struct Foo<|>;
+            trait T {}
+            impl Foo {}
+            impl T for Foo {}
+            ",
+            &["impl IMPL_BLOCK FileId(1) [23; 34)", "impl IMPL_BLOCK FileId(1) [35; 52)"],
+        );
+    }
+
+    #[test]
+    fn goto_implementation_to_derive() {
+        check_goto(
+            "
+            //- /lib.rs
+            #[derive(Clone, Debug)]
+            struct Foo<|>;
+            //- /core/lib.rs
+            pub mod clone { pub trait Clone {} }
+            pub mod fmt { pub trait Debug {} }
+            ",
+            &["impl ATTR FileId(1) [0; 23)", "impl ATTR FileId(1) [0; 23)"],
+        );
+    }
+
     #[test]
     fn goto_implementation_for_trait_multiple_files() {
         check_goto(
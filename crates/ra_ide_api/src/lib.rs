@@ -58,15 +58,15 @@ use crate::{symbol_index::FileSymbol, db::LineIndexDatabase};
 
 pub use crate::{
     change::{AnalysisChange, LibraryData},
-    completion::{CompletionItem, CompletionItemKind, InsertTextFormat},
+    completion::{CompletionItem, CompletionItemKind, CompletionConfig, InsertTextFormat},
     runnables::{Runnable, RunnableKind},
     references::ReferenceSearchResult,
-    assists::{Assist, AssistId},
+    assists::{Assist, AssistId, AssistKind, AssistLabel},
     hover::{HoverResult},
     line_index::{LineIndex, LineCol},
     line_index_utils::translate_offset_with_edit,
     folding_ranges::{Fold, FoldKind},
-    syntax_highlighting::HighlightedRange,
+    syntax_highlighting::{HighlightedRange, HighlightModifiers},
     diagnostics::Severity,
     display::{FunctionSignature, NavigationTarget, StructureNode, file_structure},
 };
@@ -276,6 +276,11 @@ impl AnalysisHost {
     pub fn collect_garbage(&mut self) {
         self.db.collect_garbage();
     }
+
+    /// Changes how many syntax trees and macro expansions are kept in memory.
+    pub fn update_lru_capacity(&mut self, lru_capacity: Option<usize>) {
+        self.db.update_lru_capacity(lru_capacity);
+    }
     pub fn raw_database(&self) -> &(impl hir::db::HirDatabase + salsa::Database) {
         &self.db
     }
@@ -387,6 +392,19 @@ impl Analysis {
         typing::on_dot_typed(&self.db, position)
     }
 
+    /// Returns an edit which should be applied when a brace ('{' or '}') is
+    /// typed at the start of a line, indenting the line appropriately.
+    pub fn on_brace_typed(&self, position: FilePosition) -> Option<SourceChange> {
+        typing::on_brace_typed(&self.db, position)
+    }
+
+    /// Returns an edit which should be applied when a newline is typed right
+    /// after a '{', indenting the new line. `position` is the cursor after the
+    /// newline.
+    pub fn on_newline_typed(&self, position: FilePosition) -> Option<SourceChange> {
+        typing::on_newline_typed(&self.db, position)
+    }
+
     /// Returns a tree representation of symbols in the file. Useful to draw a
     /// file outline.
     pub fn file_structure(&self, file_id: FileId) -> Vec<StructureNode> {
@@ -480,8 +498,12 @@ impl Analysis {
     }
 
     /// Computes completions at the given position.
-    pub fn completions(&self, position: FilePosition) -> Cancelable<Option<Vec<CompletionItem>>> {
-        self.with_db(|db| completion::completions(db, position).map(Into::into))
+    pub fn completions(
+        &self,
+        position: FilePosition,
+        config: &CompletionConfig,
+    ) -> Cancelable<Option<Vec<CompletionItem>>> {
+        self.with_db(|db| completion::completions(db, position, config).map(Into::into))
     }
 
     /// Computes assists (aka code actions aka intentions) for the given
@@ -490,6 +512,23 @@ impl Analysis {
         self.with_db(|db| assists::assists(db, frange))
     }
 
+    /// Computes the labels of the assists for the given position, without
+    /// their edits.
+    pub fn assist_labels(&self, frange: FileRange) -> Cancelable<Vec<AssistLabel>> {
+        self.with_db(|db| ra_assists::applicable_assists(db, frange))
+    }
+
+    /// Computes the assist with the given id and label for the given position,
+    /// if it is still applicable.
+    pub fn resolve_assist(
+        &self,
+        frange: FileRange,
+        id: &str,
+        label: &str,
+    ) -> Cancelable<Option<Assist>> {
+        self.with_db(|db| assists::resolve_assist(db, frange, id, label))
+    }
+
     /// Computes the set of diagnostics for the given file.
     pub fn diagnostics(&self, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
         self.with_db(|db| diagnostics::diagnostics(db, file_id))
@@ -1,4 +1,5 @@
 use crate::TextUnit;
+use ra_text_edit::AtomTextEdit;
 use rustc_hash::FxHashMap;
 use superslice::Ext;
 
@@ -70,6 +71,41 @@ impl LineIndex {
         LineIndex { newlines, utf16_lines }
     }
 
+    /// Updates the index after `edit` turned the old text into `text`. Only
+    /// the lines touched by the edit are indexed again.
+    pub fn apply_edit(&mut self, text: &str, edit: &AtomTextEdit) {
+        let start_line = self.newlines.upper_bound(&edit.delete.start()) - 1;
+        let end_line = self.newlines.upper_bound(&edit.delete.end()) - 1;
+        let delta = |offset: TextUnit| offset + TextUnit::of_str(&edit.insert) - edit.delete.len();
+
+        // The touched lines, with the newline which ends the last of them
+        let start = self.newlines[start_line];
+        let next_line = self.newlines.get(end_line + 1).map(|&it| delta(it));
+        let end = next_line.unwrap_or_else(|| TextUnit::of_str(text));
+        let lines = LineIndex::new(&text[start.to_usize()..end.to_usize()]);
+        // The line after a trailing newline is the next untouched line
+        let new_lines = lines.newlines.len() - next_line.is_some() as usize;
+        let old_lines = end_line - start_line + 1;
+
+        let mut newlines = self.newlines[..start_line].to_vec();
+        newlines.extend(lines.newlines.iter().take(new_lines).map(|&it| start + it));
+        newlines.extend(self.newlines[end_line + 1..].iter().map(|&it| delta(it)));
+        self.newlines = newlines;
+
+        let utf16_lines = std::mem::replace(&mut self.utf16_lines, FxHashMap::default());
+        for (line, chars) in utf16_lines {
+            let line = line as usize;
+            if line < start_line {
+                self.utf16_lines.insert(line as u32, chars);
+            } else if line > end_line {
+                self.utf16_lines.insert((line + new_lines - old_lines) as u32, chars);
+            }
+        }
+        for (line, chars) in lines.utf16_lines {
+            self.utf16_lines.insert(line + start_line as u32, chars);
+        }
+    }
+
     pub fn line_col(&self, offset: TextUnit) -> LineCol {
         let line = self.newlines.upper_bound(&offset) - 1;
         let line_start_offset = self.newlines[line];
@@ -145,7 +181,8 @@ pub fn to_line_col(text: &str, offset: TextUnit) -> LineCol {
 mod test_line_index {
     use super::*;
     use proptest::{prelude::*, proptest};
-    use ra_text_edit::test_utils::{arb_text, arb_offset};
+    use ra_syntax::TextRange;
+    use ra_text_edit::test_utils::{arb_text, arb_offset, arb_text_with_edit, ArbTextWithEdit};
 
     #[test]
     fn test_line_index() {
@@ -192,6 +229,15 @@ mod test_line_index {
         res
     }
 
+    #[test]
+    fn test_apply_edit() {
+        let mut index = LineIndex::new("fn foo() {\n    1\n}\n");
+        let edit = AtomTextEdit::replace(TextRange::from_to(15.into(), 16.into()), "'メ'\n".into());
+        let text = "fn foo() {\n    'メ'\n\n}\n";
+        index.apply_edit(text, &edit);
+        assert_eq!(index, LineIndex::new(text));
+    }
+
     proptest! {
         #[test]
         fn test_line_index_proptest((offset, text) in arb_text_with_offset()) {
@@ -201,6 +247,17 @@ mod test_line_index {
 
             assert_eq!(actual, expected);
         }
+
+        #[test]
+        fn test_apply_edit_proptest(ArbTextWithEdit { text, edit } in arb_text_with_edit()) {
+            if let Some(atom) = edit.as_atoms().first() {
+                let mut line_index = LineIndex::new(&text);
+                let text = atom.apply(text);
+                line_index.apply_edit(&text, atom);
+
+                assert_eq!(line_index, LineIndex::new(&text));
+            }
+        }
     }
 }
 
@@ -7,4 +7,5 @@ test_utils::marks!(
     call_info_bad_offset
     dont_complete_current_use
     dont_complete_primitive_in_use
+    dont_complete_private_items
 );
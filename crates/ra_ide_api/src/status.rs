@@ -29,16 +29,44 @@ pub(crate) fn status(db: &RootDatabase) -> String {
     let macro_syntax_tree_stats = macro_syntax_tree_stats(db);
     let symbols_stats = db.query(LibrarySymbolsQuery).entries::<LibrarySymbolsStats>();
     format!(
-        "{}\n{}\n{}\n{} (macros)\n\n\nmemory:\n{}\ngc {:?} seconds ago",
+        "{}\n{}\n{}\n{} (macros)\n\n\nmemory:\n{}\ngc {:?} seconds ago\n\nqueries:\n{}",
         files_stats,
         symbols_stats,
         syntax_tree_stats,
         macro_syntax_tree_stats,
         MemoryStats::current(),
         db.last_gc.elapsed().as_secs(),
+        QueryStats::current(),
     )
 }
 
+/// The most expensive profiled scopes, among which the top-level queries.
+struct QueryStats(Vec<ra_prof::ScopeStats>);
+
+impl QueryStats {
+    fn current() -> QueryStats {
+        let mut stats = ra_prof::scope_stats();
+        stats.truncate(10);
+        QueryStats(stats)
+    }
+}
+
+impl fmt::Display for QueryStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for it in self.0.iter() {
+            writeln!(
+                fmt,
+                "{:>6}ms {:>6} calls {:>6}ms max  {}",
+                it.total.as_millis(),
+                it.count,
+                it.max.as_millis(),
+                it.desc,
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 struct FilesStats {
     total: usize,
@@ -1,20 +1,71 @@
+use std::ops::{BitOr, BitOrAssign};
+
 use rustc_hash::{FxHashSet, FxHashMap};
 
 use ra_syntax::{
-    ast, AstNode, TextRange, Direction, SmolStr, SyntaxKind, SyntaxKind::*, SyntaxElement, T,
+    ast, AstNode, TextRange, Direction, SmolStr, SyntaxKind, SyntaxKind::*, SyntaxElement,
+    SyntaxNode, T,
 };
 use ra_db::SourceDatabase;
 use ra_prof::profile;
 
-use crate::{FileId, db::RootDatabase};
+use crate::{FileId, db::RootDatabase, symbol_index::SymbolsDatabase};
 
 #[derive(Debug)]
 pub struct HighlightedRange {
     pub range: TextRange,
     pub tag: &'static str,
+    pub modifiers: HighlightModifiers,
     pub binding_hash: Option<u64>,
 }
 
+/// Properties of a highlighted range which don't change its tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HighlightModifiers(u32);
+
+impl HighlightModifiers {
+    /// A binding declared with `mut`.
+    pub const MUTABLE: HighlightModifiers = HighlightModifiers(1 << 0);
+    /// The `unsafe` keyword, or an unsafe function.
+    pub const UNSAFE: HighlightModifiers = HighlightModifiers(1 << 1);
+    pub const STATIC: HighlightModifiers = HighlightModifiers(1 << 2);
+    /// Inside the arguments of a macro call.
+    pub const MACRO: HighlightModifiers = HighlightModifiers(1 << 3);
+    /// An item defined in a library rather than in the workspace.
+    pub const LIBRARY: HighlightModifiers = HighlightModifiers(1 << 4);
+
+    /// All modifiers, in the order of their bits.
+    pub const ALL: &'static [HighlightModifiers] = &[
+        HighlightModifiers::MUTABLE,
+        HighlightModifiers::UNSAFE,
+        HighlightModifiers::STATIC,
+        HighlightModifiers::MACRO,
+        HighlightModifiers::LIBRARY,
+    ];
+
+    pub fn contains(self, other: HighlightModifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for HighlightModifiers {
+    type Output = HighlightModifiers;
+
+    fn bitor(self, rhs: HighlightModifiers) -> HighlightModifiers {
+        HighlightModifiers(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for HighlightModifiers {
+    fn bitor_assign(&mut self, rhs: HighlightModifiers) {
+        self.0 |= rhs.0;
+    }
+}
+
 fn is_control_keyword(kind: SyntaxKind) -> bool {
     match kind {
         T![for]
@@ -56,6 +107,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             continue;
         }
         let mut binding_hash = None;
+        let mut modifiers = HighlightModifiers::default();
         let tag = match node.kind() {
             COMMENT => "comment",
             STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => "string",
@@ -68,7 +120,20 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
 
                     // FIXME: try to reuse the SourceAnalyzers
                     let analyzer = hir::SourceAnalyzer::new(db, file_id, name_ref.syntax(), None);
-                    match classify_name_ref(db, &analyzer, name_ref) {
+                    let kind = classify_name_ref(db, &analyzer, name_ref);
+                    let def: Option<ModuleDef> = match &kind {
+                        Some(Method(it)) | Some(AssocItem(ImplItem::Method(it))) => {
+                            Some((*it).into())
+                        }
+                        Some(AssocItem(ImplItem::Const(it))) => Some((*it).into()),
+                        Some(AssocItem(ImplItem::TypeAlias(it))) => Some((*it).into()),
+                        Some(Def(it)) => Some(*it),
+                        _ => None,
+                    };
+                    if let Some(def) = def {
+                        modifiers |= def_modifiers(db, def);
+                    }
+                    match kind {
                         Some(Method(_)) => "function",
                         Some(Macro(_)) => "macro",
                         Some(FieldAccess(_)) => "field",
@@ -88,12 +153,12 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                         Some(Def(ModuleDef::BuiltinType(_))) => "type",
                         Some(SelfType(_)) => "type",
                         Some(Pat(ptr)) => {
+                            let pat = ptr.syntax_node_ptr().to_node(&source_file.syntax());
+                            if ast::BindPat::cast(pat).map_or(false, |it| it.is_mutable()) {
+                                modifiers |= HighlightModifiers::MUTABLE;
+                            }
                             binding_hash = Some({
-                                let text = ptr
-                                    .syntax_node_ptr()
-                                    .to_node(&source_file.syntax())
-                                    .text()
-                                    .to_smol_string();
+                                let text = pat.text().to_smol_string();
                                 let shadow_count =
                                     bindings_shadow_count.entry(text.clone()).or_default();
                                 calc_binding_hash(file_id, &text, *shadow_count)
@@ -111,6 +176,18 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             }
             NAME => {
                 if let Some(name) = node.as_node().and_then(ast::Name::cast) {
+                    let parent = name.syntax().parent();
+                    if parent.and_then(ast::BindPat::cast).map_or(false, |it| it.is_mutable()) {
+                        modifiers |= HighlightModifiers::MUTABLE;
+                    }
+                    if let Some(parent) = parent {
+                        if parent.kind() == STATIC_DEF {
+                            modifiers |= HighlightModifiers::STATIC;
+                        }
+                        if parent.kind() == FN_DEF && has_unsafe_keyword(parent) {
+                            modifiers |= HighlightModifiers::UNSAFE;
+                        }
+                    }
                     if name.syntax().ancestors().any(|x| ast::BindPat::cast(x).is_some()) {
                         binding_hash = Some({
                             let text = name.syntax().text().to_smol_string();
@@ -142,7 +219,10 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             }
             INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE => "literal",
             LIFETIME => "parameter",
-            T![unsafe] => "keyword.unsafe",
+            T![unsafe] => {
+                modifiers |= HighlightModifiers::UNSAFE;
+                "keyword.unsafe"
+            }
             k if is_control_keyword(k) => "keyword.control",
             k if k.is_keyword() => "keyword",
             _ => {
@@ -163,6 +243,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                                 res.push(HighlightedRange {
                                     range: TextRange::from_to(range_start, range_end),
                                     tag: "macro",
+                                    modifiers: HighlightModifiers::default(),
                                     binding_hash: None,
                                 })
                             }
@@ -172,11 +253,52 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                 continue;
             }
         };
-        res.push(HighlightedRange { range: node.range(), tag, binding_hash })
+        if node.ancestors().any(|it| it.kind() == TOKEN_TREE) {
+            modifiers |= HighlightModifiers::MACRO;
+        }
+        res.push(HighlightedRange { range: node.range(), tag, modifiers, binding_hash })
     }
     res
 }
 
+fn def_modifiers(db: &RootDatabase, def: hir::ModuleDef) -> HighlightModifiers {
+    use hir::{HasSource, ModuleDef};
+
+    let mut res = HighlightModifiers::default();
+    let module = match def {
+        ModuleDef::Module(it) => Some(it),
+        ModuleDef::Function(it) => {
+            if has_unsafe_keyword(it.source(db).ast.syntax()) {
+                res |= HighlightModifiers::UNSAFE;
+            }
+            Some(it.module(db))
+        }
+        ModuleDef::Struct(it) => Some(it.module(db)),
+        ModuleDef::Union(it) => Some(it.module(db)),
+        ModuleDef::Enum(it) => Some(it.module(db)),
+        ModuleDef::EnumVariant(it) => Some(it.module(db)),
+        ModuleDef::Const(it) => Some(it.module(db)),
+        ModuleDef::Static(it) => {
+            res |= HighlightModifiers::STATIC;
+            Some(it.module(db))
+        }
+        ModuleDef::Trait(it) => Some(it.module(db)),
+        ModuleDef::TypeAlias(it) => Some(it.module(db)),
+        ModuleDef::BuiltinType(_) => None,
+    };
+    if let Some(krate) = module.and_then(|it| it.krate(db)) {
+        let crate_root = db.crate_graph().crate_root(krate.crate_id());
+        if db.library_roots().contains(&db.file_source_root(crate_root)) {
+            res |= HighlightModifiers::LIBRARY;
+        }
+    }
+    res
+}
+
+fn has_unsafe_keyword(node: &SyntaxNode) -> bool {
+    node.children_with_tokens().any(|it| it.kind() == T![unsafe])
+}
+
 pub(crate) fn highlight_as_html(db: &RootDatabase, file_id: FileId, rainbow: bool) -> String {
     let source_file = db.parse(file_id).tree;
 
@@ -267,6 +389,8 @@ mod tests {
     use test_utils::{project_dir, read_text, assert_eq_text};
     use crate::mock_analysis::single_file;
 
+    use super::HighlightModifiers;
+
     #[test]
     fn test_highlighting() {
         let (analysis, file_id) = single_file(
@@ -324,4 +448,49 @@ fn main() {
         std::fs::write(dst_file, &actual_html).unwrap();
         assert_eq_text!(expected_html, actual_html);
     }
+
+    #[test]
+    fn test_highlight_modifiers() {
+        let (analysis, file_id) = single_file(
+            r#"
+static FOO: i32 = 92;
+unsafe fn bar() {}
+
+fn main() {
+    let mut x = FOO;
+    x += 1;
+    unsafe { bar() }
+    println!("{}", x);
+}
+"#
+            .trim(),
+        );
+        let text = analysis.file_text(file_id);
+        let modified = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.modifiers != HighlightModifiers::default())
+            .map(|it| {
+                let range = it.range.start().to_usize()..it.range.end().to_usize();
+                (text[range].to_string(), it.modifiers)
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("FOO", HighlightModifiers::STATIC),
+            ("bar", HighlightModifiers::UNSAFE),
+            ("x", HighlightModifiers::MUTABLE),
+            ("unsafe", HighlightModifiers::UNSAFE),
+            ("\"{}\"", HighlightModifiers::MACRO),
+        ];
+        for (text, modifiers) in expected.iter() {
+            assert!(
+                modified.iter().any(|(t, m)| t == text && m == modifiers),
+                "{} isn't highlighted with {:?}: {:?}",
+                text,
+                modifiers,
+                modified
+            );
+        }
+    }
 }
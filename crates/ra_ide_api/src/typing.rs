@@ -123,6 +123,109 @@ pub(crate) fn on_dot_typed(db: &RootDatabase, position: FilePosition) -> Option<
     Some(res)
 }
 
+/// Reindents a `{` or `}` which starts a line: a `}` gets the indent of the
+/// line of its `{`, and a `{` the indent of the item or expression it opens.
+pub(crate) fn on_brace_typed(db: &RootDatabase, position: FilePosition) -> Option<SourceChange> {
+    let file = db.parse(position.file_id).tree;
+    let brace = find_token_at_offset(file.syntax(), position.offset).right_biased()?;
+    // Braces typed in strings and comments are not tokens of their own.
+    if !(brace.kind() == L_CURLY || brace.kind() == R_CURLY)
+        || brace.range().start() != position.offset
+    {
+        return None;
+    }
+
+    let whitespace = ast::Whitespace::cast(brace.prev_token()?)?;
+    let current_indent = {
+        let text = whitespace.text();
+        let newline = text.rfind('\n')?;
+        &text[newline + 1..]
+    };
+    let current_indent_len = TextUnit::of_str(current_indent);
+
+    let target_indent = match brace.kind() {
+        R_CURLY => leading_indent(brace.parent())?,
+        L_CURLY => {
+            let owner =
+                brace.parent().ancestors().find(|it| it.range().start() < position.offset)?;
+            leading_indent(owner)?
+        }
+        _ => return None,
+    };
+    let target_indent_len = TextUnit::of_str(target_indent);
+    if current_indent == target_indent {
+        return None;
+    }
+    let mut edit = TextEditBuilder::default();
+    edit.replace(
+        TextRange::from_to(position.offset - current_indent_len, position.offset),
+        target_indent.to_string(),
+    );
+
+    let res =
+        SourceChange::source_file_edit_from("reindent brace", position.file_id, edit.finish())
+            .with_cursor(FilePosition {
+                offset: position.offset + target_indent_len - current_indent_len
+                    + TextUnit::of_char('{'),
+                file_id: position.file_id,
+            });
+
+    Some(res)
+}
+
+/// Indents the line opened right after a `{`, and moves a `}` which directly
+/// followed the `{` to a line of its own. Unlike the other `on_*_typed`
+/// functions, `position` is the cursor after the typed newline, as editors
+/// may have already indented the new line.
+pub(crate) fn on_newline_typed(db: &RootDatabase, position: FilePosition) -> Option<SourceChange> {
+    let file = db.parse(position.file_id).tree;
+    let whitespace = find_token_at_offset(file.syntax(), position.offset)
+        .left_biased()
+        .and_then(ast::Whitespace::cast)?;
+    let l_curly = whitespace.syntax().prev_token()?;
+    if l_curly.kind() != L_CURLY {
+        return None;
+    }
+
+    let ws_range = whitespace.syntax().range();
+    let (before, after) =
+        whitespace.text().split_at((position.offset - ws_range.start()).to_usize());
+    // Only the first line of the block is indented
+    if before.matches('\n').count() != 1 {
+        return None;
+    }
+    let line = &before[before.find('\n')? + 1..];
+    let line_start = position.offset - TextUnit::of_str(line);
+
+    let base_indent = leading_indent(l_curly.parent())?;
+    let indent = format!("{}    ", base_indent);
+    let (rest_of_line, replacement) = match after.find(|c: char| c == '\r' || c == '\n') {
+        Some(eol) => (&after[..eol], indent.clone()),
+        None => {
+            let r_curly = whitespace.syntax().next_token().filter(|it| it.kind() == R_CURLY);
+            let replacement = match r_curly {
+                Some(_) => format!("{}\n{}", indent, base_indent),
+                None => indent.clone(),
+            };
+            (after, replacement)
+        }
+    };
+    if format!("{}{}", line, rest_of_line) == replacement {
+        return None;
+    }
+    let range = TextRange::from_to(line_start, position.offset + TextUnit::of_str(rest_of_line));
+    let mut edit = TextEditBuilder::default();
+    edit.replace(range, replacement);
+
+    let res = SourceChange::source_file_edit_from("indent block", position.file_id, edit.finish())
+        .with_cursor(FilePosition {
+            offset: line_start + TextUnit::of_str(&indent),
+            file_id: position.file_id,
+        });
+
+    Some(res)
+}
+
 #[cfg(test)]
 mod tests {
     use test_utils::{add_cursor, assert_eq_text, extract_offset};
@@ -364,6 +467,203 @@ fn foo() {
         );
     }
 
+    fn type_brace(brace: char, before: &str, after: &str) {
+        let (offset, before) = extract_offset(before);
+        let mut edit = TextEditBuilder::default();
+        edit.insert(offset, brace.to_string());
+        let before = edit.finish().apply(&before);
+        let (analysis, file_id) = single_file(&before);
+        if let Some(result) = analysis.on_brace_typed(FilePosition { offset, file_id }) {
+            assert_eq!(result.source_file_edits.len(), 1);
+            let actual = result.source_file_edits[0].edit.apply(&before);
+            assert_eq_text!(after, &actual);
+        } else {
+            assert_eq_text!(&before, after)
+        };
+    }
+
+    #[test]
+    fn reindents_closing_brace() {
+        type_brace(
+            '}',
+            r"
+fn foo() {
+    if true {
+        1
+        <|>
+}
+",
+            r"
+fn foo() {
+    if true {
+        1
+    }
+}
+",
+        );
+        type_brace(
+            '}',
+            r"
+impl S {
+    fn foo() {}
+<|>
+",
+            r"
+impl S {
+    fn foo() {}
+}
+",
+        );
+    }
+
+    #[test]
+    fn reindents_opening_brace_after_where_clause() {
+        type_brace(
+            '{',
+            r"
+    fn foo<T>(t: T) -> T
+    where
+        T: Copy,
+        <|>
+        t
+    }
+",
+            r"
+    fn foo<T>(t: T) -> T
+    where
+        T: Copy,
+    {
+        t
+    }
+",
+        );
+    }
+
+    #[test]
+    fn dont_reindent_brace_after_code() {
+        type_brace(
+            '{',
+            r"
+fn foo() <|>
+",
+            r"
+fn foo() {
+",
+        );
+    }
+
+    #[test]
+    fn dont_reindent_brace_in_string_or_comment() {
+        type_brace(
+            '}',
+            r#"
+fn foo() {
+    let s = "
+        <|>";
+}
+"#,
+            r#"
+fn foo() {
+    let s = "
+        }";
+}
+"#,
+        );
+        type_brace(
+            '{',
+            r"
+fn foo() {
+    /*
+        <|>
+    */
+}
+",
+            r"
+fn foo() {
+    /*
+        {
+    */
+}
+",
+        );
+    }
+
+    /// Unlike for the other characters, `before` already contains the newline.
+    fn type_newline(before: &str, after: &str) {
+        let (offset, before) = extract_offset(before);
+        let (analysis, file_id) = single_file(&before);
+        match analysis.on_newline_typed(FilePosition { offset, file_id }) {
+            Some(result) => {
+                assert_eq!(result.source_file_edits.len(), 1);
+                let actual = result.source_file_edits[0].edit.apply(&before);
+                let actual = add_cursor(&actual, result.cursor_position.unwrap().offset);
+                assert_eq_text!(after, &actual);
+            }
+            None => assert_eq_text!(&add_cursor(&before, offset), after),
+        }
+    }
+
+    #[test]
+    fn indents_line_after_opening_brace() {
+        type_newline(
+            r"
+fn foo() {
+<|>}
+",
+            r"
+fn foo() {
+    <|>
+}
+",
+        );
+        type_newline(
+            r"
+impl S {
+    fn foo() {
+    <|>
+    }
+}
+",
+            r"
+impl S {
+    fn foo() {
+        <|>
+    }
+}
+",
+        );
+        type_newline(
+            r"
+fn foo() {
+<|>let x = 1;
+}
+",
+            r"
+fn foo() {
+    <|>let x = 1;
+}
+",
+        );
+    }
+
+    #[test]
+    fn dont_indent_line_after_code() {
+        type_newline(
+            r"
+fn foo() {
+    let x = 1;
+<|>
+}
+",
+            r"
+fn foo() {
+    let x = 1;
+<|>
+}
+",
+        );
+    }
+
     #[test]
     fn test_on_enter() {
         fn apply_on_enter(before: &str) -> Option<String> {
@@ -0,0 +1,16 @@
+//! A stand-in for `cargo check --message-format=json`, used by the heavy
+//! tests: it reports an unused variable on the second line of `src/lib.rs`,
+//! whatever the code is.
+
+const MESSAGES: &[&str] = &[
+    r#"{"reason":"compiler-artifact","package_id":"foo 0.0.0","target":{"kind":["lib"],"crate_types":["lib"],"name":"foo","src_path":"src/lib.rs","edition":"2015"},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":[],"executable":null,"fresh":false}"#,
+    r##"{"reason":"compiler-message","package_id":"foo 0.0.0","target":{"kind":["lib"],"crate_types":["lib"],"name":"foo","src_path":"src/lib.rs","edition":"2015"},"message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","byte_start":23,"byte_end":24,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"#[warn(unused_variables)] on by default","code":null,"level":"note","spans":[],"children":[],"rendered":null},{"message":"consider prefixing with an underscore","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":23,"byte_end":24,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable: `x`\n"}}"##,
+    "this line is not JSON",
+    r#"{"reason":"build-finished","success":true}"#,
+];
+
+fn main() {
+    for message in MESSAGES {
+        println!("{}", message);
+    }
+}
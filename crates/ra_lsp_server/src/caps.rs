@@ -1,18 +1,46 @@
 use lsp_types::{
     CodeActionProviderCapability, CodeLensOptions, CompletionOptions, DocumentOnTypeFormattingOptions,
     ExecuteCommandOptions, FoldingRangeProviderCapability, RenameOptions, RenameProviderCapability,
-    ServerCapabilities, SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
-    TextDocumentSyncOptions, ImplementationProviderCapability, GenericCapability, TypeDefinitionProviderCapability
+    SaveOptions, ServerCapabilities, SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, ImplementationProviderCapability, GenericCapability, TypeDefinitionProviderCapability,
+    WorkspaceCapability, WorkspaceFolderCapability, WorkspaceFolderCapabilityChangeNotifications,
 };
 
+use ra_ide_api::AssistKind;
+use serde_json::{json, Value};
+
+use crate::{conv::Conv, semantic_tokens};
+
+/// The capabilities, with the ones which `lsp_types` doesn't know yet.
+pub fn server_capabilities_json() -> Value {
+    let mut caps = serde_json::to_value(server_capabilities()).unwrap();
+    caps["semanticTokensProvider"] = json!({
+        "legend": semantic_tokens::legend(),
+        "range": true,
+        "full": { "delta": true },
+    });
+    caps["codeActionProvider"] = json!({
+        "codeActionKinds": [
+            AssistKind::QuickFix.conv(),
+            AssistKind::Refactor.conv(),
+            AssistKind::RefactorExtract.conv(),
+            AssistKind::RefactorInline.conv(),
+            AssistKind::RefactorRewrite.conv(),
+            AssistKind::OrganizeImports.conv(),
+        ],
+        "resolveProvider": true,
+    });
+    caps
+}
+
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
         text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
             open_close: Some(true),
-            change: Some(TextDocumentSyncKind::Full),
+            change: Some(TextDocumentSyncKind::Incremental),
             will_save: None,
             will_save_wait_until: None,
-            save: None,
+            save: Some(SaveOptions::default()),
         })),
         hover_provider: Some(true),
         completion_provider: Some(CompletionOptions {
@@ -32,10 +60,12 @@ pub fn server_capabilities() -> ServerCapabilities {
         code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
         code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(true) }),
         document_formatting_provider: Some(true),
-        document_range_formatting_provider: None,
+        document_range_formatting_provider: Some(true),
         document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
             first_trigger_character: "=".to_string(),
-            more_trigger_character: Some(vec![".".to_string()]),
+            more_trigger_character: Some(
+                [".", "{", "}", "\n"].iter().map(|it| it.to_string()).collect(),
+            ),
         }),
         selection_range_provider: Some(GenericCapability::default()),
         folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
@@ -46,6 +76,13 @@ pub fn server_capabilities() -> ServerCapabilities {
         execute_command_provider: Some(ExecuteCommandOptions {
             commands: vec!["apply_code_action".to_string()],
         }),
-        workspace: None,
+        workspace: Some(WorkspaceCapability {
+            workspace_folders: Some(WorkspaceFolderCapability {
+                supported: Some(true),
+                change_notifications: Some(WorkspaceFolderCapabilityChangeNotifications::Bool(
+                    true,
+                )),
+            }),
+        }),
     }
 }
@@ -0,0 +1,511 @@
+//! Runs `cargo check`, or another command which prints the messages of cargo
+//! in JSON, and converts the diagnostics of rustc to LSP diagnostics.
+//!
+//! rust-analyzer's own diagnostics cover only a few errors: the ones of rustc
+//! are published together with them, until the next check starts.
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    thread,
+};
+
+use crossbeam_channel::{select, unbounded, Receiver, Sender};
+use lsp_types::{
+    CodeAction, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
+    NumberOrString, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+use ra_project_model::{CargoMessage, RustDiagnostic, RustDiagnosticSpan};
+use rustc_hash::{FxHashMap, FxHashSet};
+use thread_worker::Worker;
+
+use crate::config::CheckConfig;
+
+/// Which workspaces to check, and how.
+#[derive(Debug)]
+pub struct CheckRequest {
+    /// The roots of the Cargo workspaces, the checker runs in each of them.
+    pub roots: Vec<PathBuf>,
+    pub config: CheckConfig,
+}
+
+#[derive(Debug)]
+pub enum CheckTask {
+    /// A check started, the diagnostics of the previous one are stale.
+    Begin,
+    Diagnostic(CheckDiagnostic),
+    /// The check is over, or a newer one replaced it.
+    End,
+    /// The checker couldn't be run.
+    Failed(String),
+}
+
+/// A diagnostic of rustc, and the fixes it suggests.
+#[derive(Debug, Clone)]
+pub struct CheckDiagnostic {
+    pub uri: Url,
+    pub diagnostic: Diagnostic,
+    pub fixes: Vec<CodeAction>,
+}
+
+/// The diagnostics of the latest check, by file.
+#[derive(Debug, Clone, Default)]
+pub struct CheckDiagnostics {
+    files: FxHashMap<Url, Vec<CheckDiagnostic>>,
+    seen: FxHashSet<DiagnosticKey>,
+}
+
+/// What tells two diagnostics apart: rustc reports the same error once per
+/// target of the package.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DiagnosticKey {
+    uri: Url,
+    range: ((u64, u64), (u64, u64)),
+    code: Option<String>,
+    message: String,
+}
+
+impl DiagnosticKey {
+    fn new(it: &CheckDiagnostic) -> DiagnosticKey {
+        let pos = |it: Position| (it.line, it.character);
+        let range = it.diagnostic.range;
+        DiagnosticKey {
+            uri: it.uri.clone(),
+            range: (pos(range.start), pos(range.end)),
+            code: it.diagnostic.code.as_ref().map(|code| match code {
+                NumberOrString::Number(it) => it.to_string(),
+                NumberOrString::String(it) => it.clone(),
+            }),
+            message: it.diagnostic.message.clone(),
+        }
+    }
+}
+
+impl CheckDiagnostics {
+    pub fn add(&mut self, diagnostic: CheckDiagnostic) {
+        if self.seen.insert(DiagnosticKey::new(&diagnostic)) {
+            self.files.entry(diagnostic.uri.clone()).or_default().push(diagnostic);
+        }
+    }
+
+    /// Removes all diagnostics, and returns the files which had some.
+    pub fn clear(&mut self) -> Vec<Url> {
+        self.seen.clear();
+        self.files.drain().map(|(uri, _)| uri).collect()
+    }
+
+    pub fn files<'a>(&'a self) -> impl Iterator<Item = &'a Url> + 'a {
+        self.files.keys()
+    }
+
+    pub fn diagnostics<'a>(&'a self, uri: &Url) -> impl Iterator<Item = &'a Diagnostic> + 'a {
+        self.files.get(uri).into_iter().flatten().map(|it| &it.diagnostic)
+    }
+
+    /// The fixes of the diagnostics which overlap `range`.
+    pub fn fixes<'a>(
+        &'a self,
+        uri: &Url,
+        range: Range,
+    ) -> impl Iterator<Item = &'a CodeAction> + 'a {
+        self.files
+            .get(uri)
+            .into_iter()
+            .flatten()
+            .filter(move |it| intersect(it.diagnostic.range, range))
+            .flat_map(|it| it.fixes.iter())
+    }
+}
+
+fn intersect(a: Range, b: Range) -> bool {
+    let pos = |it: Position| (it.line, it.character);
+    pos(a.start) <= pos(b.end) && pos(b.start) <= pos(a.end)
+}
+
+/// Spawns a worker which runs the checks. Only the latest request matters: a
+/// running check is killed when a new one is requested.
+pub fn check_worker() -> Worker<CheckRequest, CheckTask> {
+    Worker::<CheckRequest, CheckTask>::spawn(
+        "cargo check",
+        16,
+        move |input_receiver, output_sender| {
+            let mut next = None;
+            loop {
+                let mut request = match next.take() {
+                    Some(it) => it,
+                    None => match input_receiver.recv() {
+                        Ok(it) => it,
+                        Err(_) => return,
+                    },
+                };
+                while let Ok(it) = input_receiver.try_recv() {
+                    request = it;
+                }
+                output_sender.send(CheckTask::Begin).unwrap();
+                for root in request.roots.iter() {
+                    let mut checker = match start_checker(root, &request.config) {
+                        Ok(it) => it,
+                        Err(message) => {
+                            output_sender.send(CheckTask::Failed(message)).unwrap();
+                            continue;
+                        }
+                    };
+                    match read_messages(root, &mut checker, &input_receiver, &output_sender) {
+                        // Errors in the checked code are what we are after,
+                        // the exit status doesn't matter.
+                        None => {
+                            let _ = checker.wait();
+                        }
+                        Some(Canceled::NewRequest(it)) => {
+                            stop(&mut checker);
+                            next = Some(it);
+                            break;
+                        }
+                        Some(Canceled::Shutdown) => {
+                            stop(&mut checker);
+                            return;
+                        }
+                    }
+                }
+                output_sender.send(CheckTask::End).unwrap();
+            }
+        },
+    )
+}
+
+enum Canceled {
+    NewRequest(CheckRequest),
+    Shutdown,
+}
+
+fn stop(checker: &mut Child) {
+    let _ = checker.kill();
+    let _ = checker.wait();
+}
+
+fn start_checker(root: &Path, config: &CheckConfig) -> Result<Child, String> {
+    let (program, args) = match config.command.split_first() {
+        Some(it) => it,
+        None => return Err("rust-analyzer can't check the workspace: no command".to_string()),
+    };
+    log::info!("checking {} with {:?}", root.display(), config.command);
+    Command::new(program)
+        .args(args)
+        .current_dir(root)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("rust-analyzer failed to run {}: {}", program, e))
+}
+
+/// Sends the diagnostics printed by the checker, until it exits or the check
+/// is canceled. The checker might print nothing for a long time, so the
+/// requests are received while waiting for its output.
+fn read_messages(
+    root: &Path,
+    checker: &mut Child,
+    input_receiver: &Receiver<CheckRequest>,
+    output_sender: &Sender<CheckTask>,
+) -> Option<Canceled> {
+    let lines = read_lines(checker.stdout.take().unwrap());
+    loop {
+        select! {
+            recv(input_receiver) -> request => {
+                let canceled = match request {
+                    Ok(it) => Canceled::NewRequest(it),
+                    Err(_) => Canceled::Shutdown,
+                };
+                return Some(canceled);
+            },
+            recv(lines) -> line => {
+                let line = match line {
+                    Ok(it) => it,
+                    Err(_) => return None,
+                };
+                for diagnostic in parse_message(root, &line) {
+                    output_sender.send(CheckTask::Diagnostic(diagnostic)).unwrap();
+                }
+            },
+        }
+    }
+}
+
+/// Reads the output of the checker on a thread of its own, which exits when
+/// the checker does, or is killed.
+fn read_lines(stdout: ChildStdout) -> Receiver<String> {
+    let (sender, receiver) = unbounded();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let line = match line {
+                Ok(it) => it,
+                Err(_) => break,
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Converts a line printed by the checker. Lines which are not messages of
+/// cargo, like the output of build scripts, have no diagnostics.
+fn parse_message(root: &Path, line: &str) -> Vec<CheckDiagnostic> {
+    match CargoMessage::parse(line) {
+        Some(CargoMessage::CompilerMessage { message }) => {
+            convert_diagnostic(root, &message).into_iter().collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn convert_diagnostic(root: &Path, rd: &RustDiagnostic) -> Option<CheckDiagnostic> {
+    // Diagnostics without spans, like "aborting due to previous error",
+    // don't belong to any file.
+    let primary = rd.spans.iter().find(|it| it.is_primary)?;
+    let location = span_location(root, call_site(primary))?;
+
+    let mut message = rd.message.clone();
+    if let Some(label) = &primary.label {
+        message.push('\n');
+        message.push_str(label);
+    }
+    let mut related_information = rd
+        .spans
+        .iter()
+        .filter(|span| !span.is_primary)
+        .filter_map(|span| {
+            let message = span.label.clone()?;
+            let location = span_location(root, span)?;
+            Some(DiagnosticRelatedInformation { location, message })
+        })
+        .collect::<Vec<_>>();
+    let mut fixes = Vec::new();
+    for child in rd.children.iter() {
+        if child.spans.is_empty() {
+            message.push_str(&format!("\n{}: {}", child.level, child.message));
+        } else if child.spans.iter().any(|it| it.suggested_replacement.is_some()) {
+            fixes.push((child.message.clone(), suggested_edit(root, child)));
+        } else {
+            related_information.extend(child.spans.iter().filter_map(|span| {
+                let location = span_location(root, span)?;
+                Some(DiagnosticRelatedInformation { location, message: child.message.clone() })
+            }));
+        }
+    }
+
+    let diagnostic = Diagnostic {
+        range: location.range,
+        severity: severity(&rd.level),
+        code: rd.code.as_ref().map(|it| NumberOrString::String(it.code.clone())),
+        source: Some("rustc".to_string()),
+        message,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
+    };
+    let fixes = fixes
+        .into_iter()
+        .map(|(title, edit)| CodeAction {
+            title,
+            kind: Some("quickfix".to_string()),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(edit),
+            command: None,
+        })
+        .collect();
+    Some(CheckDiagnostic { uri: location.uri, diagnostic, fixes })
+}
+
+/// The span in the user's code: spans in the definitions of macros are
+/// mapped to the call of the macro.
+fn call_site(mut span: &RustDiagnosticSpan) -> &RustDiagnosticSpan {
+    while span.file_name.starts_with('<') {
+        match &span.expansion {
+            Some(expansion) => span = &expansion.span,
+            None => break,
+        }
+    }
+    span
+}
+
+fn span_location(root: &Path, span: &RustDiagnosticSpan) -> Option<Location> {
+    let uri = Url::from_file_path(root.join(&span.file_name)).ok()?;
+    let start = span.text.first().map(|it| it.text.as_str());
+    let end = span.text.last().map(|it| it.text.as_str());
+    let range = Range::new(
+        position(span.line_start, span.column_start, start),
+        position(span.line_end, span.column_end, end),
+    );
+    Some(Location::new(uri, range))
+}
+
+/// rustc counts the columns in characters, LSP in UTF-16 code units: the text
+/// of the line tells them apart. Without it, the line is assumed to be ASCII.
+fn position(line: u64, column: u64, text: Option<&str>) -> Position {
+    let column = column.saturating_sub(1);
+    let character = match text {
+        Some(text) => {
+            let chars = text.chars().take(column as usize);
+            let utf16 = chars.clone().map(|c| c.len_utf16() as u64).sum::<u64>();
+            // Columns past the end of the line, like the one after the last
+            // character, count one unit per character.
+            utf16 + column.saturating_sub(chars.count() as u64)
+        }
+        None => column,
+    };
+    Position::new(line.saturating_sub(1), character)
+}
+
+fn suggested_edit(root: &Path, rd: &RustDiagnostic) -> WorkspaceEdit {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for span in rd.spans.iter() {
+        let new_text = match &span.suggested_replacement {
+            Some(it) => it.clone(),
+            None => continue,
+        };
+        if let Some(location) = span_location(root, span) {
+            changes.entry(location.uri).or_default().push(TextEdit::new(location.range, new_text));
+        }
+    }
+    WorkspaceEdit { changes: Some(changes), document_changes: None }
+}
+
+fn severity(level: &str) -> Option<DiagnosticSeverity> {
+    let res = match level {
+        "error" | "error: internal compiler error" => DiagnosticSeverity::Error,
+        "warning" => DiagnosticSeverity::Warning,
+        "note" => DiagnosticSeverity::Information,
+        "help" => DiagnosticSeverity::Hint,
+        _ => return None,
+    };
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(json: &str) -> Vec<CheckDiagnostic> {
+        let line = json.lines().map(str::trim).collect::<String>();
+        let root = if cfg!(windows) { Path::new("C:\\ws") } else { Path::new("/ws") };
+        parse_message(root, &line)
+    }
+
+    #[test]
+    fn converts_warning_with_suggestion() {
+        let diagnostics = check(
+            r##"{"reason":"compiler-message","package_id":"foo 0.1.0","message":{
+                "message":"unused variable: `x`",
+                "code":{"code":"unused_variables","explanation":null},
+                "level":"warning",
+                "spans":[{"file_name":"src/lib.rs","byte_start":20,"byte_end":21,
+                    "line_start":2,"line_end":2,"column_start":9,"column_end":10,
+                    "is_primary":true,"text":[],"label":null,
+                    "suggested_replacement":null,"expansion":null}],
+                "children":[
+                    {"message":"#[warn(unused_variables)] on by default","code":null,
+                     "level":"note","spans":[],"children":[],"rendered":null},
+                    {"message":"consider prefixing with an underscore","code":null,
+                     "level":"help","spans":[{"file_name":"src/lib.rs","byte_start":20,
+                        "byte_end":21,"line_start":2,"line_end":2,"column_start":9,
+                        "column_end":10,"is_primary":true,"text":[],"label":null,
+                        "suggested_replacement":"_x",
+                        "suggestion_applicability":"MachineApplicable","expansion":null}],
+                     "children":[],"rendered":null}],
+                "rendered":"warning: unused variable: `x`"}}"##,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert!(d.uri.path().ends_with("/ws/src/lib.rs"));
+        assert_eq!(d.diagnostic.range, Range::new(Position::new(1, 8), Position::new(1, 9)));
+        assert_eq!(d.diagnostic.severity, Some(DiagnosticSeverity::Warning));
+        assert_eq!(d.diagnostic.code, Some(NumberOrString::String("unused_variables".to_string())));
+        assert_eq!(
+            d.diagnostic.message,
+            "unused variable: `x`\nnote: #[warn(unused_variables)] on by default"
+        );
+        assert_eq!(d.fixes.len(), 1);
+        let fix = &d.fixes[0];
+        assert_eq!(fix.title, "consider prefixing with an underscore");
+        let changes = fix.edit.as_ref().unwrap().changes.as_ref().unwrap();
+        assert_eq!(changes[&d.uri], vec![TextEdit::new(d.diagnostic.range, "_x".to_string())]);
+    }
+
+    #[test]
+    fn maps_macro_spans_to_call_site() {
+        let diagnostics = check(
+            r#"{"reason":"compiler-message","package_id":"foo 0.1.0","message":{
+                "message":"mismatched types","code":{"code":"E0308","explanation":null},
+                "level":"error",
+                "spans":[{"file_name":"<::core::macros::assert_eq macros>","byte_start":0,
+                    "byte_end":10,"line_start":3,"line_end":3,"column_start":1,
+                    "column_end":10,"is_primary":true,"text":[],
+                    "label":"expected i32, found &str","suggested_replacement":null,
+                    "expansion":{"span":{"file_name":"src/main.rs","byte_start":40,
+                        "byte_end":60,"line_start":4,"line_end":4,"column_start":5,
+                        "column_end":25,"is_primary":false,"text":[],"label":null,
+                        "suggested_replacement":null,"expansion":null},
+                        "macro_decl_name":"assert_eq!","def_site_span":null}}],
+                "children":[],"rendered":null}}"#,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert!(d.uri.path().ends_with("/ws/src/main.rs"));
+        assert_eq!(d.diagnostic.range, Range::new(Position::new(3, 4), Position::new(3, 24)));
+        assert_eq!(d.diagnostic.severity, Some(DiagnosticSeverity::Error));
+        assert_eq!(d.diagnostic.message, "mismatched types\nexpected i32, found &str");
+    }
+
+    #[test]
+    fn converts_columns_to_utf16() {
+        let diagnostics = check(
+            r#"{"reason":"compiler-message","package_id":"foo 0.1.0","message":{
+                "message":"unused variable: `x`","code":null,"level":"warning",
+                "spans":[{"file_name":"src/lib.rs","byte_start":22,"byte_end":23,
+                    "line_start":1,"line_end":1,"column_start":19,"column_end":20,
+                    "is_primary":true,"text":[{"text":"let s = \"𝕊ö\"; let x = 1;",
+                        "highlight_start":19,"highlight_end":20}],"label":null,
+                    "suggested_replacement":null,"expansion":null}],
+                "children":[],"rendered":null}}"#,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        let range = diagnostics[0].diagnostic.range;
+        assert_eq!(range, Range::new(Position::new(0, 19), Position::new(0, 20)));
+    }
+
+    #[test]
+    fn skips_duplicates() {
+        let json = r#"{"reason":"compiler-message","package_id":"foo 0.1.0","message":{
+            "message":"unused import","code":null,"level":"warning",
+            "spans":[{"file_name":"src/lib.rs","byte_start":4,"byte_end":7,
+                "line_start":1,"line_end":1,"column_start":5,"column_end":8,
+                "is_primary":true,"text":[],"label":null,
+                "suggested_replacement":null,"expansion":null}],
+            "children":[],"rendered":null}}"#;
+        let mut diagnostics = CheckDiagnostics::default();
+        for _ in 0..2 {
+            check(json).into_iter().for_each(|it| diagnostics.add(it));
+        }
+        let uri = diagnostics.files().next().unwrap().clone();
+        assert_eq!(diagnostics.diagnostics(&uri).count(), 1);
+        assert_eq!(diagnostics.clear(), vec![uri.clone()]);
+        check(json).into_iter().for_each(|it| diagnostics.add(it));
+        assert_eq!(diagnostics.diagnostics(&uri).count(), 1);
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert!(check("warning: build script printed this").is_empty());
+        assert!(check(r#"{"reason":"build-finished","success":true}"#).is_empty());
+        assert!(check(
+            r#"{"reason":"compiler-message","package_id":"foo 0.1.0","message":{
+                "message":"aborting due to previous error","code":null,"level":"error",
+                "spans":[],"children":[],"rendered":null}}"#
+        )
+        .is_empty());
+    }
+}
@@ -74,7 +74,8 @@ impl CargoTargetSpec {
                     target_kind: tgt.kind(&cargo),
                 })
             }
-            project_model::ProjectWorkspace::Json { .. } => None,
+            project_model::ProjectWorkspace::Json { .. }
+            | project_model::ProjectWorkspace::DetachedFiles { .. } => None,
         });
         Ok(res)
     }
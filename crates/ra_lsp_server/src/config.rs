@@ -0,0 +1,405 @@
+use std::path::PathBuf;
+
+use crate::project_model::{CargoFeatures, CargoCheckOutput, TargetConfig};
+use serde::{Deserialize, Deserializer};
+
+/// Client provided initialization options
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InitializationOptions {
+    /// Whether the client supports our custom highlighting publishing decorations.
+    /// This is different to the highlightingOn setting, which is whether the user
+    /// wants our custom highlighting to be used.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub publish_decorations: bool,
+
+    /// The initial configuration, the client changes it later with
+    /// `workspace/didChangeConfiguration`.
+    #[serde(flatten)]
+    pub config: ServerConfig,
+}
+
+impl Default for InitializationOptions {
+    fn default() -> InitializationOptions {
+        InitializationOptions { publish_decorations: false, config: ServerConfig::default() }
+    }
+}
+
+/// Capabilities of the client which the server relies on, beyond the ones
+/// every client has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClientCaps {
+    /// Whether the client answers `workspace/configuration` requests, in
+    /// which case the configuration is requested when the client reports a
+    /// change without sending the new settings.
+    pub config_pull: bool,
+
+    /// Whether the client shows `window/workDoneProgress`, the progress is
+    /// reported with `rust-analyzer/progress` otherwise.
+    pub work_done_progress: bool,
+
+    /// Whether the client accepts registrations of watchers for
+    /// `workspace/didChangeWatchedFiles`.
+    pub watched_files_registration: bool,
+
+    /// Whether the client resolves the edits of code actions with
+    /// `codeAction/resolve`, so that the edits of the assists are only
+    /// computed for the one which is applied.
+    pub code_action_resolve: bool,
+}
+
+impl ClientCaps {
+    /// Reads the capabilities from the JSON of the `initialize` parameters,
+    /// `lsp_types` doesn't know `window.workDoneProgress` yet.
+    pub fn from_initialize_params(params: &serde_json::Value) -> ClientCaps {
+        let flag = |pointer: &str| params.pointer(pointer).and_then(|it| it.as_bool());
+        ClientCaps {
+            config_pull: flag("/capabilities/workspace/configuration").unwrap_or(false),
+            work_done_progress: flag("/capabilities/window/workDoneProgress").unwrap_or(false),
+            watched_files_registration: flag(
+                "/capabilities/workspace/didChangeWatchedFiles/dynamicRegistration",
+            )
+            .unwrap_or(false),
+            code_action_resolve: flag("/capabilities/textDocument/codeAction/dataSupport")
+                .unwrap_or(false)
+                && params
+                    .pointer("/capabilities/textDocument/codeAction/resolveSupport/properties")
+                    .and_then(|it| it.as_array())
+                    .map_or(false, |it| it.iter().any(|it| it == "edit")),
+        }
+    }
+}
+
+/// Settings which can change while the server runs, the `rust-analyzer`
+/// section of the client configuration.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerConfig {
+    /// Whether or not the workspace loaded notification should be sent
+    ///
+    /// Defaults to `true`
+    #[serde(deserialize_with = "nullable_bool_true")]
+    pub show_workspace_loaded: bool,
+
+    /// Capacity of the caches of syntax trees and macro expansions.
+    pub lru_capacity: Option<usize>,
+
+    #[serde(deserialize_with = "nullable_default")]
+    pub diagnostics: DiagnosticsConfig,
+
+    #[serde(deserialize_with = "nullable_default")]
+    pub completion: CompletionConfig,
+
+    /// How the Cargo projects are loaded, the workspaces are loaded again
+    /// when this changes.
+    #[serde(deserialize_with = "nullable_default")]
+    pub cargo: CargoConfig,
+
+    #[serde(deserialize_with = "nullable_default")]
+    pub check: CheckConfig,
+
+    #[serde(deserialize_with = "nullable_default")]
+    pub files: FilesConfig,
+
+    #[serde(deserialize_with = "nullable_default")]
+    pub profile: ProfileConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            show_workspace_loaded: true,
+            lru_capacity: None,
+            diagnostics: DiagnosticsConfig::default(),
+            completion: CompletionConfig::default(),
+            cargo: CargoConfig::default(),
+            check: CheckConfig::default(),
+            files: FilesConfig::default(),
+            profile: ProfileConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiagnosticsConfig {
+    /// Whether to publish the diagnostics of the open files.
+    ///
+    /// Defaults to `true`
+    #[serde(deserialize_with = "nullable_bool_true")]
+    pub enable: bool,
+
+    /// Whether to publish weak warnings, like unnecessary braces in a `use`.
+    ///
+    /// Defaults to `true`
+    #[serde(deserialize_with = "nullable_bool_true")]
+    pub weak_warnings: bool,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> DiagnosticsConfig {
+        DiagnosticsConfig { enable: true, weak_warnings: true }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompletionConfig {
+    /// Whether to offer postfix completions, like `expr.if`.
+    ///
+    /// Defaults to `true`
+    #[serde(deserialize_with = "nullable_bool_true")]
+    pub enable_postfix_completions: bool,
+
+    /// Whether to complete functions with parentheses.
+    ///
+    /// Defaults to `true`
+    #[serde(deserialize_with = "nullable_bool_true")]
+    pub add_call_parenthesis: bool,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> CompletionConfig {
+        CompletionConfig { enable_postfix_completions: true, add_call_parenthesis: true }
+    }
+}
+
+impl CompletionConfig {
+    pub fn to_analysis_config(&self) -> ra_ide_api::CompletionConfig {
+        ra_ide_api::CompletionConfig {
+            enable_postfix_completions: self.enable_postfix_completions,
+            add_call_parenthesis: self.add_call_parenthesis,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CargoConfig {
+    /// Which features of the Cargo packages to enable.
+    ///
+    /// Defaults to the default features of each package
+    #[serde(deserialize_with = "nullable_default")]
+    pub features: CargoFeatures,
+
+    /// Path to the proc-macro server executable, `ra_proc_macro_srv`, which
+    /// must be built by the same compiler as the project. Procedural macros
+    /// are not expanded if this is not set.
+    pub proc_macro_server: Option<PathBuf>,
+
+    /// Whether to run `cargo check` to collect the outputs of build scripts,
+    /// like `OUT_DIR`.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub load_build_scripts: bool,
+
+    /// Path to a saved output of `cargo check --message-format=json`, which
+    /// is used instead of running `cargo check`.
+    pub check_output: Option<PathBuf>,
+
+    /// The target triple to analyze the project for, like
+    /// `thumbv7em-none-eabihf`.
+    ///
+    /// Defaults to the host
+    pub target: Option<String>,
+
+    /// The sysroot to take the standard library from, for targets whose
+    /// standard library isn't installed with rustup.
+    ///
+    /// Defaults to the sysroot of `rustc`
+    pub sysroot: Option<PathBuf>,
+}
+
+impl CargoConfig {
+    /// Where the proc-macro libraries and the build script outputs come from,
+    /// if they are needed.
+    pub fn check_output(&self) -> Option<CargoCheckOutput> {
+        match &self.check_output {
+            Some(path) => Some(CargoCheckOutput::Cached(path.clone())),
+            None if self.proc_macro_server.is_some() || self.load_build_scripts => {
+                Some(CargoCheckOutput::Run)
+            }
+            None => None,
+        }
+    }
+
+    pub fn target_config(&self) -> TargetConfig {
+        TargetConfig { target: self.target.clone(), sysroot: self.sysroot.clone() }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CheckConfig {
+    /// Whether to check the workspaces when a file is saved, and to publish
+    /// the diagnostics of rustc.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub enable: bool,
+
+    /// The checker and its arguments. It runs in the root of each Cargo
+    /// workspace, and should print the messages of cargo in JSON.
+    ///
+    /// Defaults to `cargo check --message-format=json`
+    #[serde(deserialize_with = "nullable_default_check_command")]
+    pub command: Vec<String>,
+}
+
+impl Default for CheckConfig {
+    fn default() -> CheckConfig {
+        CheckConfig { enable: false, command: default_check_command() }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilesConfig {
+    /// Who watches the files for changes made outside of the editor.
+    ///
+    /// Defaults to the client, if it supports watchers for
+    /// `workspace/didChangeWatchedFiles`
+    #[serde(deserialize_with = "nullable_default")]
+    pub watcher: FileWatcher,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FileWatcher {
+    /// The client sends `workspace/didChangeWatchedFiles`, which works where
+    /// the file system events don't reach the server, like on network mounts.
+    Client,
+    /// The VFS watches the roots itself.
+    Server,
+}
+
+impl Default for FileWatcher {
+    fn default() -> FileWatcher {
+        FileWatcher::Client
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProfileConfig {
+    /// Where to write the profiling scopes of all threads as a Chrome trace,
+    /// which `chrome://tracing` and Perfetto load. The `RA_PROFILE_TRACE`
+    /// environment variable sets it when the server starts.
+    pub trace_file: Option<PathBuf>,
+}
+
+fn default_check_command() -> Vec<String> {
+    vec!["cargo".to_string(), "check".to_string(), "--message-format=json".to_string()]
+}
+
+/// Deserializes a null value to the default checker
+fn nullable_default_check_command<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or_else(default_check_command))
+}
+
+/// Deserializes a null value to a bool false by default
+fn nullable_bool_false<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or(false))
+}
+
+/// Deserializes a null value to a bool true by default
+fn nullable_bool_true<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or(true))
+}
+
+/// Deserializes a null value to the default value of the type
+fn nullable_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_init_options_defaults() {
+        // check that null == default for both fields
+        let default = InitializationOptions::default();
+        assert_eq!(default, serde_json::from_str(r#"{}"#).unwrap());
+        assert_eq!(
+            default,
+            serde_json::from_str(
+                r#"{"publishDecorations":null, "showWorkspaceLoaded":null, "lruCapacity":null}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize_config_sections() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"diagnostics":{"weakWarnings":false},"completion":null,"cargo":{"target":"wasm32-unknown-unknown"},"files":{"watcher":"server"},"profile":{"traceFile":"trace.json"}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.diagnostics, DiagnosticsConfig { enable: true, weak_warnings: false });
+        assert_eq!(config.completion, CompletionConfig::default());
+        assert_eq!(config.cargo.target, Some("wasm32-unknown-unknown".to_string()));
+        assert_eq!(config.check, CheckConfig::default());
+        assert_eq!(config.files.watcher, FileWatcher::Server);
+        assert_eq!(config.profile.trace_file, Some(PathBuf::from("trace.json")));
+    }
+
+    #[test]
+    fn client_caps_from_initialize_params() {
+        let params = serde_json::json!({
+            "capabilities": {
+                "window": { "workDoneProgress": true },
+                "workspace": {},
+                "textDocument": {
+                    "codeAction": {
+                        "dataSupport": true,
+                        "resolveSupport": { "properties": ["edit"] }
+                    }
+                }
+            }
+        });
+        let caps = ClientCaps::from_initialize_params(&params);
+        assert_eq!(
+            caps,
+            ClientCaps {
+                config_pull: false,
+                work_done_progress: true,
+                watched_files_registration: false,
+                code_action_resolve: true,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_cargo_features() {
+        let options: InitializationOptions =
+            serde_json::from_str(r#"{"cargo":{"features":"all"}}"#).unwrap();
+        assert_eq!(options.config.cargo.features, CargoFeatures::All);
+
+        let options: InitializationOptions =
+            serde_json::from_str(r#"{"cargo":{"features":{"explicit":{"foo":["bar"]}}}}"#).unwrap();
+        let mut expected = rustc_hash::FxHashMap::default();
+        expected.insert("foo".to_string(), vec!["bar".to_string()]);
+        assert_eq!(options.config.cargo.features, CargoFeatures::Explicit(expected));
+    }
+}
@@ -5,9 +5,9 @@ use lsp_types::{
     WorkspaceEdit,
 };
 use ra_ide_api::{
-    CompletionItem, CompletionItemKind, FileId, FilePosition, FileRange, FileSystemEdit,
-    NavigationTarget, SourceChange, SourceFileEdit, RangeInfo,
-    LineCol, LineIndex, translate_offset_with_edit, InsertTextFormat
+    AssistKind, CompletionItem, CompletionItemKind, FileId, FilePosition, FileRange,
+    FileSystemEdit, NavigationTarget, SourceChange, SourceFileEdit, RangeInfo, LineCol, LineIndex,
+    translate_offset_with_edit, InsertTextFormat,
 };
 use ra_syntax::{SyntaxKind, TextRange, TextUnit};
 use ra_text_edit::{AtomTextEdit, TextEdit};
@@ -52,6 +52,22 @@ impl Conv for SyntaxKind {
     }
 }
 
+impl Conv for AssistKind {
+    type Output = String;
+
+    fn conv(self) -> String {
+        let kind = match self {
+            AssistKind::QuickFix => "quickfix",
+            AssistKind::Refactor => "refactor",
+            AssistKind::RefactorExtract => "refactor.extract",
+            AssistKind::RefactorInline => "refactor.inline",
+            AssistKind::RefactorRewrite => "refactor.rewrite",
+            AssistKind::OrganizeImports => "source.organizeImports",
+        };
+        kind.to_string()
+    }
+}
+
 impl Conv for CompletionItemKind {
     type Output = ::lsp_types::CompletionItemKind;
 
@@ -1,13 +1,15 @@
 mod caps;
+mod cargo_check;
 mod cargo_target_spec;
 mod conv;
 mod main_loop;
 mod markdown;
 mod project_model;
+mod semantic_tokens;
 mod vfs_filter;
 pub mod req;
-pub mod init;
+pub mod config;
 mod world;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
-pub use crate::{caps::server_capabilities, main_loop::main_loop, main_loop::LspError, init::InitializationOptions};
+pub use crate::{caps::{server_capabilities, server_capabilities_json}, main_loop::{main_loop, run_server}, main_loop::LspError, config::{ClientCaps, InitializationOptions, ServerConfig}};
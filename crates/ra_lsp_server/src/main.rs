@@ -1,8 +1,7 @@
-use serde::Deserialize;
 use flexi_logger::{Duplicate, Logger};
-use gen_lsp_server::{run_server, stdio_transport};
+use gen_lsp_server::{record_transport, stdio_transport};
 
-use ra_lsp_server::{Result, InitializationOptions};
+use ra_lsp_server::Result;
 use ra_prof;
 
 fn main() -> Result<()> {
@@ -16,8 +15,14 @@ fn main() -> Result<()> {
         Ok(spec) => ra_prof::Filter::from_spec(&spec),
         Err(_) => ra_prof::Filter::disabled(),
     });
+    ra_prof::enable_stats(true);
+    if let Ok(path) = std::env::var("RA_PROFILE_TRACE") {
+        ra_prof::start_trace(path.as_ref())?;
+    }
     log::info!("lifecycle: server started");
-    match std::panic::catch_unwind(main_inner) {
+    let res = std::panic::catch_unwind(main_inner);
+    ra_prof::stop_trace();
+    match res {
         Ok(res) => {
             log::info!("lifecycle: terminating process with {:?}", res);
             res
@@ -32,27 +37,14 @@ fn main() -> Result<()> {
 fn main_inner() -> Result<()> {
     let (receiver, sender, threads) = stdio_transport();
     let cwd = std::env::current_dir()?;
-    run_server(ra_lsp_server::server_capabilities(), receiver, sender, |params, r, s| {
-        let root = params.root_uri.and_then(|it| it.to_file_path().ok()).unwrap_or(cwd);
-
-        let workspace_roots = params
-            .workspace_folders
-            .map(|workspaces| {
-                workspaces
-                    .into_iter()
-                    .filter_map(|it| it.uri.to_file_path().ok())
-                    .collect::<Vec<_>>()
-            })
-            .filter(|workspaces| !workspaces.is_empty())
-            .unwrap_or_else(|| vec![root]);
-
-        let opts = params
-            .initialization_options
-            .and_then(|v| InitializationOptions::deserialize(v).ok())
-            .unwrap_or_default();
-
-        ra_lsp_server::main_loop(workspace_roots, opts, r, s)
-    })?;
+    match std::env::var("RA_LSP_RECORD") {
+        Ok(path) => {
+            let (receiver, sender, recording) = record_transport(receiver, sender, path.as_ref())?;
+            ra_lsp_server::run_server(receiver, sender, cwd)?;
+            recording.join()?;
+        }
+        Err(_) => ra_lsp_server::run_server(receiver, sender, cwd)?,
+    }
     log::info!("shutting down IO...");
     threads.join()?;
     log::info!("... IO is down");
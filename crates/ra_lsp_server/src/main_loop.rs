@@ -1,33 +1,51 @@
 mod handlers;
+mod progress;
 mod subscriptions;
+pub(crate) mod metrics;
 pub(crate) mod pending_requests;
 
-use std::{fmt, path::PathBuf, sync::Arc, time::Instant, error::Error};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+    error::Error,
+};
 
 use crossbeam_channel::{select, unbounded, Receiver, RecvError, Sender};
 use gen_lsp_server::{
-    handle_shutdown, ErrorCode, RawMessage, RawNotification, RawRequest, RawResponse,
+    handle_shutdown, run_server_raw, ErrorCode, RawMessage, RawNotification, RawRequest,
+    RawResponse,
 };
-use lsp_types::NumberOrString;
+use lsp_types::{InitializeParams, NumberOrString, Url};
 use ra_ide_api::{Canceled, FileId, LibraryData};
 use ra_vfs::VfsTask;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use threadpool::ThreadPool;
+use thread_worker::Worker;
 use ra_prof::profile;
 
 use crate::{
+    cargo_check::{check_worker, CheckRequest, CheckTask},
     main_loop::{
+        progress::{LoadProgress, LoadState},
         subscriptions::Subscriptions,
-        pending_requests::{PendingRequests, PendingRequest},
+        pending_requests::{Outcome, PendingRequests, PendingRequest},
+    },
+    project_model::{
+        workspace_loader, ProcMacroClient, ProjectWorkspace, WorkspaceRoots, WorkspaceLoad,
+        ProjectStatus, ProjectLoadError,
     },
-    project_model::workspace_loader,
     req,
     world::{WorldSnapshot, WorldState},
     Result,
+    ClientCaps,
     InitializationOptions,
+    ServerConfig,
 };
 
 const THREADPOOL_SIZE: usize = 8;
+const FILE_WATCHERS_ID: &str = "rust-analyzer/fileWatchers";
 const MAX_IN_FLIGHT_LIBS: usize = THREADPOOL_SIZE - 3;
 
 #[derive(Debug)]
@@ -50,35 +68,80 @@ impl fmt::Display for LspError {
 
 impl Error for LspError {}
 
+/// Runs the server from the `initialize` request to the `exit` notification.
+/// The workspaces are the folders of the client, or its root, or `cwd`.
+pub fn run_server(
+    receiver: Receiver<RawMessage>,
+    sender: Sender<RawMessage>,
+    cwd: PathBuf,
+) -> Result<()> {
+    let caps = crate::server_capabilities_json();
+    run_server_raw(caps, receiver, sender, |raw_params, r, s| {
+        let client_caps = ClientCaps::from_initialize_params(&raw_params);
+        let params: InitializeParams = serde_json::from_value(raw_params)?;
+        let root = params.root_uri.and_then(|it| it.to_file_path().ok()).unwrap_or(cwd);
+
+        let workspace_roots = params
+            .workspace_folders
+            .map(|workspaces| {
+                workspaces
+                    .into_iter()
+                    .filter_map(|it| it.uri.to_file_path().ok())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|workspaces| !workspaces.is_empty())
+            .unwrap_or_else(|| vec![root]);
+
+        let opts = params
+            .initialization_options
+            .and_then(|v| InitializationOptions::deserialize(v).ok())
+            .unwrap_or_default();
+
+        main_loop(workspace_roots, opts, client_caps, r, s)
+    })
+}
+
 pub fn main_loop(
     ws_roots: Vec<PathBuf>,
     options: InitializationOptions,
+    client_caps: ClientCaps,
     msg_receiver: &Receiver<RawMessage>,
     msg_sender: &Sender<RawMessage>,
 ) -> Result<()> {
-    // FIXME: support dynamic workspace loading.
-    let workspaces = {
-        let ws_worker = workspace_loader();
-        let mut loaded_workspaces = Vec::new();
-        for ws_root in &ws_roots {
-            ws_worker.sender().send(ws_root.clone()).unwrap();
-            match ws_worker.receiver().recv().unwrap() {
-                Ok(ws) => loaded_workspaces.push(ws),
-                Err(e) => {
-                    log::error!("loading workspace failed: {}", e);
-
-                    show_message(
-                        req::MessageType::Error,
-                        format!("rust-analyzer failed to load workspace: {}", e),
-                        msg_sender,
-                    );
-                }
-            }
-        }
-        loaded_workspaces
+    // The worker stays around: workspaces are loaded again when their
+    // manifests or the Cargo configuration change.
+    let ws_worker = workspace_loader();
+    let (workspaces, project_status, _) = {
+        let roots = WorkspaceRoots {
+            folders: ws_roots.clone(),
+            detached_files: Vec::new(),
+            cargo: options.config.cargo.clone(),
+        };
+        ws_worker.sender().send(roots).unwrap();
+        let loads = ws_worker.receiver().recv().unwrap();
+        collect_workspaces(loads, msg_sender)
     };
 
-    let mut state = WorldState::new(ws_roots, workspaces, options.lru_capacity);
+    let proc_macro_client =
+        start_proc_macro_client(options.config.cargo.proc_macro_server.as_ref(), msg_sender);
+    update_trace(None, options.config.profile.trace_file.as_ref());
+
+    let mut state = WorldState::new(
+        ws_roots,
+        workspaces,
+        project_status,
+        options.config,
+        client_caps,
+        proc_macro_client,
+    );
+    let mut server_requests = ServerRequests {
+        next_id: 0,
+        config_pull: client_caps.config_pull,
+        pending_config: None,
+        file_watchers: false,
+    };
+    let mut load_progress = LoadProgress::new(client_caps.work_done_progress);
+    let check_worker = check_worker();
 
     let pool = ThreadPool::new(THREADPOOL_SIZE);
     let (task_sender, task_receiver) = unbounded::<Task>();
@@ -86,8 +149,12 @@ pub fn main_loop(
 
     log::info!("server initialized, serving requests");
     let main_res = main_loop_inner(
-        options,
+        options.publish_decorations,
+        &mut server_requests,
+        &mut load_progress,
         &pool,
+        &ws_worker,
+        &check_worker,
         msg_sender,
         msg_receiver,
         task_sender,
@@ -111,9 +178,175 @@ pub fn main_loop(
     main_res
 }
 
+/// The requests sent to the client.
+#[derive(Debug)]
+struct ServerRequests {
+    next_id: u64,
+    /// Whether the client answers `workspace/configuration` requests.
+    config_pull: bool,
+    /// The `workspace/configuration` request whose response hasn't arrived
+    /// yet. Only the latest one counts, the responses to the older ones are
+    /// stale.
+    pending_config: Option<u64>,
+    /// Whether the watchers for `workspace/didChangeWatchedFiles` are
+    /// registered with the client.
+    file_watchers: bool,
+}
+
+impl ServerRequests {
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn send_config(&mut self, msg_sender: &Sender<RawMessage>) {
+        let id = self.next_id();
+        self.pending_config = Some(id);
+        let params = req::ConfigurationParams {
+            items: vec![req::ConfigurationItem {
+                scope_uri: None,
+                section: Some("rust-analyzer".to_string()),
+            }],
+        };
+        let request = RawRequest::new::<req::WorkspaceConfiguration>(id, &params);
+        msg_sender.send(request.into()).unwrap();
+    }
+
+    /// Registers the file watchers with the client when it should watch the
+    /// files, and unregisters them otherwise.
+    fn update_file_watchers(&mut self, client_watching: bool, msg_sender: &Sender<RawMessage>) {
+        if client_watching == self.file_watchers {
+            return;
+        }
+        self.file_watchers = client_watching;
+        let id = self.next_id();
+        let method = "workspace/didChangeWatchedFiles".to_string();
+        let request = if client_watching {
+            let watchers = ["**/*.rs", "**/Cargo.toml", "**/Cargo.lock", "**/rust-project.json"]
+                .iter()
+                .map(|glob| req::FileSystemWatcher { glob_pattern: glob.to_string(), kind: None })
+                .collect();
+            let options = req::DidChangeWatchedFilesRegistrationOptions { watchers };
+            let registration = req::Registration {
+                id: FILE_WATCHERS_ID.to_string(),
+                method,
+                register_options: Some(serde_json::to_value(options).unwrap()),
+            };
+            let params = req::RegistrationParams { registrations: vec![registration] };
+            RawRequest::new::<req::RegisterCapability>(id, &params)
+        } else {
+            let unregistration = req::Unregistration { id: FILE_WATCHERS_ID.to_string(), method };
+            let params = req::UnregistrationParams { unregisterations: vec![unregistration] };
+            RawRequest::new::<req::UnregisterCapability>(id, &params)
+        };
+        msg_sender.send(request.into()).unwrap();
+    }
+
+    /// Takes the configuration out of the response to the latest
+    /// configuration request. The responses to the other requests carry
+    /// nothing we need.
+    fn finish(&mut self, response: RawResponse) -> Option<ServerConfig> {
+        if self.pending_config != Some(response.id) {
+            if let Some(error) = response.error {
+                log::error!("request {} failed: {:?}", response.id, error);
+            }
+            return None;
+        }
+        self.pending_config = None;
+        if let Some(error) = response.error {
+            log::error!("failed to get the configuration: {:?}", error);
+            return None;
+        }
+        let mut items: Vec<serde_json::Value> =
+            response.result.and_then(|it| serde_json::from_value(it).ok()).unwrap_or_default();
+        if items.is_empty() {
+            return None;
+        }
+        parse_config(items.swap_remove(0))
+    }
+}
+
+/// Parses the `rust-analyzer` section of the client settings. `null` means
+/// that the client has no settings for us.
+fn parse_config(settings: serde_json::Value) -> Option<ServerConfig> {
+    if settings.is_null() {
+        return None;
+    }
+    match ServerConfig::deserialize(settings) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            log::error!("invalid configuration: {}", e);
+            None
+        }
+    }
+}
+
+/// Applies a new configuration, and starts a new proc-macro server if its
+/// path changed. The diagnostics of `cargo check` are removed when checking
+/// is disabled, and the profiling trace follows its file.
+fn update_config(
+    state: &mut WorldState,
+    subs: &Subscriptions,
+    config: ServerConfig,
+    msg_sender: &Sender<RawMessage>,
+) {
+    log::info!("configuration changed: {:?}", config);
+    if state.config.check.enable && !config.check.enable {
+        let stale = Arc::make_mut(&mut state.check_diagnostics).clear();
+        publish_check_diagnostics(state, subs, stale, msg_sender);
+    }
+    let proc_macro_client =
+        if config.cargo.proc_macro_server != state.config.cargo.proc_macro_server {
+            let path = config.cargo.proc_macro_server.as_ref();
+            Some(start_proc_macro_client(path, msg_sender))
+        } else {
+            None
+        };
+    if config.profile.trace_file != state.config.profile.trace_file {
+        update_trace(state.config.profile.trace_file.as_ref(), config.profile.trace_file.as_ref());
+    }
+    state.update_config(config, proc_macro_client);
+}
+
+/// Moves the profiling trace of the configuration from `old` to `new`. Only
+/// the trace which was started by the configuration is stopped, the one of
+/// `RA_PROFILE_TRACE` goes on.
+fn update_trace(old: Option<&PathBuf>, new: Option<&PathBuf>) {
+    if let Some(path) = old {
+        ra_prof::stop_trace_of(path);
+    }
+    if let Some(path) = new {
+        match ra_prof::start_trace(path) {
+            Ok(()) => log::info!("writing the profiling trace to {}", path.display()),
+            Err(e) => log::error!("failed to create {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn start_proc_macro_client(
+    path: Option<&PathBuf>,
+    msg_sender: &Sender<RawMessage>,
+) -> ProcMacroClient {
+    let path: &Path = match path {
+        None => return ProcMacroClient::dummy(),
+        Some(path) => path,
+    };
+    ProcMacroClient::extern_process(path).unwrap_or_else(|e| {
+        log::error!("failed to start proc-macro server {}: {}", path.display(), e);
+        show_message(
+            req::MessageType::Error,
+            format!("rust-analyzer failed to start proc-macro server: {}", e),
+            msg_sender,
+        );
+        ProcMacroClient::dummy()
+    })
+}
+
 #[derive(Debug)]
 enum Task {
-    Respond(RawResponse),
+    /// A response, with whether the request was canceled.
+    Respond(RawResponse, Outcome),
     Notify(RawNotification),
 }
 
@@ -121,7 +354,10 @@ enum Event {
     Msg(RawMessage),
     Task(Task),
     Vfs(VfsTask),
-    Lib(LibraryData),
+    /// An indexed library, with the generation of the VFS it was read from.
+    Lib(u32, LibraryData),
+    Workspaces(Vec<WorkspaceLoad>),
+    Check(CheckTask),
 }
 
 impl fmt::Debug for Event {
@@ -141,7 +377,7 @@ impl fmt::Debug for Event {
                     return debug_verbose_not(not, f);
                 }
             }
-            Event::Task(Task::Respond(resp)) => {
+            Event::Task(Task::Respond(resp, _)) => {
                 return f
                     .debug_struct("RawResponse")
                     .field("id", &resp.id)
@@ -154,14 +390,20 @@ impl fmt::Debug for Event {
             Event::Msg(it) => fmt::Debug::fmt(it, f),
             Event::Task(it) => fmt::Debug::fmt(it, f),
             Event::Vfs(it) => fmt::Debug::fmt(it, f),
-            Event::Lib(it) => fmt::Debug::fmt(it, f),
+            Event::Lib(_, it) => fmt::Debug::fmt(it, f),
+            Event::Workspaces(it) => write!(f, "Workspaces({})", it.len()),
+            Event::Check(it) => fmt::Debug::fmt(it, f),
         }
     }
 }
 
 fn main_loop_inner(
-    options: InitializationOptions,
+    publish_decorations: bool,
+    server_requests: &mut ServerRequests,
+    load_progress: &mut LoadProgress,
     pool: &ThreadPool,
+    ws_worker: &Worker<WorkspaceRoots, Vec<WorkspaceLoad>>,
+    check_worker: &Worker<CheckRequest, CheckTask>,
     msg_sender: &Sender<RawMessage>,
     msg_receiver: &Receiver<RawMessage>,
     task_sender: Sender<Task>,
@@ -175,6 +417,7 @@ fn main_loop_inner(
     let mut in_flight_libraries = 0;
     let mut pending_libraries = Vec::new();
     let mut send_workspace_notification = true;
+    let mut workspace_reload_in_flight = false;
 
     let (libdata_sender, libdata_receiver) = unbounded();
     loop {
@@ -189,7 +432,12 @@ fn main_loop_inner(
                 Ok(task) => Event::Vfs(task),
                 Err(RecvError) => Err("vfs died")?,
             },
-            recv(libdata_receiver) -> data => Event::Lib(data.unwrap())
+            recv(libdata_receiver) -> data => {
+                let (generation, data) = data.unwrap();
+                Event::Lib(generation, data)
+            },
+            recv(ws_worker.receiver()) -> ws => Event::Workspaces(ws.unwrap()),
+            recv(check_worker.receiver()) -> task => Event::Check(task.unwrap()),
         };
         let loop_start = Instant::now();
 
@@ -211,11 +459,52 @@ fn main_loop_inner(
                 state.vfs.write().handle_task(task);
                 state_changed = true;
             }
-            Event::Lib(lib) => {
-                state.add_lib(lib);
-                state.maybe_collect_garbage();
+            Event::Lib(generation, lib) => {
+                // Libraries read before the VFS was recreated have stale ids
+                if generation == state.vfs_generation {
+                    state.add_lib(lib);
+                    state.maybe_collect_garbage();
+                }
                 in_flight_libraries -= 1;
             }
+            Event::Workspaces(loads) => {
+                workspace_reload_in_flight = false;
+                let (workspaces, project_status, all_loaded) =
+                    collect_workspaces(loads, msg_sender);
+                // Keep the old workspaces while a manifest is broken
+                if all_loaded {
+                    let open_files = subs.subscriptions();
+                    if let Some(open_files) =
+                        state.set_workspaces(workspaces, project_status, open_files)
+                    {
+                        subs = Subscriptions::default();
+                        open_files.into_iter().for_each(|it| subs.add_sub(it));
+                        pending_libraries.clear();
+                    }
+                    state_changed = true;
+                } else {
+                    state.project_status = Arc::new(project_status);
+                }
+            }
+            Event::Check(task) => match task {
+                CheckTask::Begin => {
+                    let stale = Arc::make_mut(&mut state.check_diagnostics).clear();
+                    publish_check_diagnostics(state, &subs, stale, msg_sender);
+                    state_changed = true;
+                }
+                CheckTask::Diagnostic(diagnostic) => {
+                    Arc::make_mut(&mut state.check_diagnostics).add(diagnostic)
+                }
+                CheckTask::End => {
+                    let files = state.check_diagnostics.files().cloned().collect();
+                    publish_check_diagnostics(state, &subs, files, msg_sender);
+                    state_changed = true;
+                }
+                CheckTask::Failed(message) => {
+                    log::error!("{}", message);
+                    show_message(req::MessageType::Error, message, msg_sender);
+                }
+            },
             Event::Msg(msg) => match msg {
                 RawMessage::Request(req) => {
                     let req = match handle_shutdown(req, msg_sender) {
@@ -233,33 +522,84 @@ fn main_loop_inner(
                     )?
                 }
                 RawMessage::Notification(not) => {
-                    on_notification(msg_sender, state, pending_requests, &mut subs, not)?;
+                    on_notification(
+                        msg_sender,
+                        state,
+                        pending_requests,
+                        server_requests,
+                        &mut subs,
+                        not,
+                    )?;
                     state_changed = true;
                 }
-                RawMessage::Response(resp) => log::error!("unexpected response: {:?}", resp),
+                RawMessage::Response(resp) => {
+                    if let Some(config) = server_requests.finish(resp) {
+                        update_config(state, &subs, config, msg_sender);
+                        state_changed = true;
+                    }
+                }
             },
         };
 
+        if state.detached_files_changed {
+            let open_files = subs.subscriptions();
+            if let Some(open_files) = state.update_detached_files(open_files) {
+                subs = Subscriptions::default();
+                open_files.into_iter().for_each(|it| subs.add_sub(it));
+                pending_libraries.clear();
+            }
+            state_changed = true;
+        }
+
         pending_libraries.extend(state.process_changes());
         while in_flight_libraries < MAX_IN_FLIGHT_LIBS && !pending_libraries.is_empty() {
             let (root, files) = pending_libraries.pop().unwrap();
             in_flight_libraries += 1;
             let sender = libdata_sender.clone();
+            let generation = state.vfs_generation;
             pool.execute(move || {
                 log::info!("indexing {:?} ... ", root);
-                let _p = profile(&format!("indexed {:?}", root));
+                let _p = profile(format!("indexed {:?}", root));
                 let data = LibraryData::prepare(root, files);
-                sender.send(data).unwrap();
+                sender.send((generation, data)).unwrap();
             });
         }
 
+        if state.workspaces_outdated && !workspace_reload_in_flight {
+            log::info!("reloading workspaces");
+            state.workspaces_outdated = false;
+            workspace_reload_in_flight = true;
+            let roots = WorkspaceRoots {
+                folders: state.roots.clone(),
+                detached_files: state.detached_files.clone(),
+                cargo: state.config.cargo.clone(),
+            };
+            ws_worker.sender().send(roots).unwrap();
+        }
+
+        if state.check_requested {
+            state.check_requested = false;
+            let request =
+                CheckRequest { roots: state.check_roots(), config: state.config.check.clone() };
+            check_worker.sender().send(request).unwrap();
+        }
+
+        let load = LoadState {
+            generation: state.vfs_generation,
+            roots_total: state.roots_total(),
+            roots_to_scan: state.roots_to_scan,
+            libraries_to_index: pending_libraries.len() + in_flight_libraries,
+        };
+        load_progress.update(load, server_requests, msg_sender);
+        server_requests.update_file_watchers(state.client_watching(), msg_sender);
+
         if send_workspace_notification
             && state.roots_to_scan == 0
             && pending_libraries.is_empty()
             && in_flight_libraries == 0
         {
             let n_packages: usize = state.workspaces.iter().map(|it| it.count()).sum();
-            if options.show_workspace_loaded {
+            if state.config.show_workspace_loaded {
                 let msg = format!("workspace loaded, {} rust packages", n_packages);
                 show_message(req::MessageType::Info, msg, msg_sender);
             }
@@ -271,7 +611,7 @@ fn main_loop_inner(
             update_file_notifications_on_threadpool(
                 pool,
                 state.snapshot(),
-                options.publish_decorations,
+                publish_decorations,
                 task_sender.clone(),
                 subs.subscriptions(),
             )
@@ -286,8 +626,8 @@ fn on_task(
     state: &mut WorldState,
 ) {
     match task {
-        Task::Respond(response) => {
-            if let Some(completed) = pending_requests.finish(response.id) {
+        Task::Respond(response, outcome) => {
+            if let Some(completed) = pending_requests.finish(response.id, outcome) {
                 log::info!("handled req#{} in {:?}", completed.id, completed.duration);
                 state.complete_request(completed);
                 msg_sender.send(response.into()).unwrap();
@@ -328,6 +668,8 @@ fn on_request(
             handlers::handle_find_matching_brace(s.snapshot(), p)
         })?
         .on::<req::AnalyzerStatus>(handlers::handle_analyzer_status)?
+        .on::<req::WorkspaceStatus>(handlers::handle_workspace_status)?
+        .on::<req::Metrics>(handlers::handle_metrics)?
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
         .on::<req::ExtendSelection>(handlers::handle_extend_selection)?
         .on::<req::OnTypeFormatting>(handlers::handle_on_type_formatting)?
@@ -339,8 +681,12 @@ fn on_request(
         .on::<req::ParentModule>(handlers::handle_parent_module)?
         .on::<req::Runnables>(handlers::handle_runnables)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
+        .on::<req::SemanticTokensRequest>(handlers::handle_semantic_tokens)?
+        .on::<req::SemanticTokensDeltaRequest>(handlers::handle_semantic_tokens_delta)?
+        .on::<req::SemanticTokensRangeRequest>(handlers::handle_semantic_tokens_range)?
         .on::<req::Completion>(handlers::handle_completion)?
         .on::<req::CodeActionRequest>(handlers::handle_code_action)?
+        .on::<req::CodeActionResolveRequest>(handlers::handle_code_action_resolve)?
         .on::<req::CodeLensRequest>(handlers::handle_code_lens)?
         .on::<req::CodeLensResolve>(handlers::handle_code_lens_resolve)?
         .on::<req::FoldingRangeRequest>(handlers::handle_folding_range)?
@@ -350,6 +696,7 @@ fn on_request(
         .on::<req::Rename>(handlers::handle_rename)?
         .on::<req::References>(handlers::handle_references)?
         .on::<req::Formatting>(handlers::handle_formatting)?
+        .on::<req::RangeFormatting>(handlers::handle_range_formatting)?
         .on::<req::DocumentHighlightRequest>(handlers::handle_document_highlight)?
         .finish();
     Ok(())
@@ -359,6 +706,7 @@ fn on_notification(
     msg_sender: &Sender<RawMessage>,
     state: &mut WorldState,
     pending_requests: &mut PendingRequests,
+    server_requests: &mut ServerRequests,
     subs: &mut Subscriptions,
     not: RawNotification,
 ) -> Result<()> {
@@ -370,7 +718,8 @@ fn on_notification(
                     panic!("string id's not supported: {:?}", id);
                 }
             };
-            if pending_requests.cancel(id) {
+            if let Some(completed) = pending_requests.cancel(id) {
+                state.complete_request(completed);
                 let response = RawResponse::err(
                     id,
                     ErrorCode::RequestCanceled as i32,
@@ -382,25 +731,83 @@ fn on_notification(
         }
         Err(not) => not,
     };
+    let not = match not.cast::<req::DidChangeWorkspaceFolders>() {
+        Ok(params) => {
+            for folder in params.event.removed {
+                if let Ok(path) = folder.uri.to_file_path() {
+                    state.roots.retain(|it| *it != path);
+                }
+            }
+            for folder in params.event.added {
+                let path = folder
+                    .uri
+                    .to_file_path()
+                    .map_err(|()| format!("invalid uri: {}", folder.uri))?;
+                if !state.roots.contains(&path) {
+                    state.roots.push(path);
+                }
+            }
+            state.workspaces_outdated = true;
+            return Ok(());
+        }
+        Err(not) => not,
+    };
+    let not = match not.cast::<req::DidChangeConfiguration>() {
+        Ok(params) => {
+            // Clients either push the new settings, or only tell us that
+            // they changed and let us pull them.
+            match params.settings.get("rust-analyzer") {
+                Some(settings) if settings.is_object() => {
+                    if let Some(config) = parse_config(settings.clone()) {
+                        update_config(state, subs, config, msg_sender);
+                    }
+                }
+                _ if server_requests.config_pull => server_requests.send_config(msg_sender),
+                _ => log::info!("configuration change without settings: {:?}", params.settings),
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
     let not = match not.cast::<req::DidOpenTextDocument>() {
         Ok(params) => {
             let uri = params.text_document.uri;
             let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
-            if let Some(file_id) =
-                state.vfs.write().add_file_overlay(&path, params.text_document.text)
-            {
-                subs.add_sub(FileId(file_id.0));
+            if let Some(file_id) = state.open_file(path, params.text_document.text) {
+                subs.add_sub(file_id);
             }
             return Ok(());
         }
         Err(not) => not,
     };
     let not = match not.cast::<req::DidChangeTextDocument>() {
-        Ok(mut params) => {
+        Ok(params) => {
             let uri = params.text_document.uri;
             let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
-            let text = params.content_changes.pop().ok_or_else(|| format!("empty changes"))?.text;
-            state.vfs.write().change_file_overlay(path.as_path(), text);
+            state.change_file(path.as_path(), params.content_changes);
+            return Ok(());
+        }
+        Err(not) => not,
+    };
+    let not = match not.cast::<req::DidSaveTextDocument>() {
+        Ok(params) => {
+            let uri = params.text_document.uri;
+            let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
+            state.save_file(&path);
+            if state.config.check.enable {
+                state.check_requested = true;
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
+    let not = match not.cast::<req::DidChangeWatchedFiles>() {
+        Ok(params) => {
+            for change in params.changes {
+                let uri = change.uri;
+                let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
+                state.notify_changed(path);
+            }
             return Ok(());
         }
         Err(not) => not,
@@ -409,12 +816,12 @@ fn on_notification(
         Ok(params) => {
             let uri = params.text_document.uri;
             let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
-            if let Some(file_id) = state.vfs.write().remove_file_overlay(path.as_path()) {
-                subs.remove_sub(FileId(file_id.0));
+            if let Some(file_id) = state.close_file(path.as_path()) {
+                subs.remove_sub(file_id);
             }
-            let params = req::PublishDiagnosticsParams { uri, diagnostics: Vec::new() };
-            let not = RawNotification::new::<req::PublishDiagnostics>(&params);
-            msg_sender.send(not.into()).unwrap();
+            state.semantic_tokens.lock().remove(&uri);
+            // The diagnostics of `cargo check` stay until the next check
+            publish_check_diagnostics(state, subs, vec![uri], msg_sender);
             return Ok(());
         }
         Err(not) => not,
@@ -526,12 +933,14 @@ where
     R::Params: DeserializeOwned + Send + 'static,
     R::Result: Serialize + 'static,
 {
+    let mut outcome = Outcome::Answered;
     let response = match result {
         Ok(resp) => RawResponse::ok::<R>(id, &resp),
         Err(e) => match e.downcast::<LspError>() {
             Ok(lsp_error) => RawResponse::err(id, lsp_error.code, lsp_error.message),
             Err(e) => {
                 if is_canceled(&e) {
+                    outcome = Outcome::Canceled;
                     // FIXME: When https://github.com/Microsoft/vscode-languageserver-node/issues/457
                     // gets fixed, we can return the proper response.
                     // This works around the issue where "content modified" error would continuously
@@ -552,7 +961,7 @@ where
             }
         },
     };
-    Task::Respond(response)
+    Task::Respond(response, outcome)
 }
 
 fn update_file_notifications_on_threadpool(
@@ -592,6 +1001,73 @@ fn update_file_notifications_on_threadpool(
     });
 }
 
+/// Publishes the diagnostics of `cargo check` for the given files which are
+/// not open. The diagnostics of the open files are published together with
+/// our own ones.
+fn publish_check_diagnostics(
+    state: &WorldState,
+    subs: &Subscriptions,
+    files: Vec<Url>,
+    msg_sender: &Sender<RawMessage>,
+) {
+    let world = state.snapshot();
+    let open_files: Vec<Url> = subs
+        .subscriptions()
+        .into_iter()
+        .filter_map(|file_id| world.file_id_to_uri(file_id).ok())
+        .collect();
+    for uri in files.into_iter().filter(|it| !open_files.contains(it)) {
+        let diagnostics = if world.config.diagnostics.enable {
+            world.check_diagnostics.diagnostics(&uri).cloned().collect()
+        } else {
+            Vec::new()
+        };
+        let params = req::PublishDiagnosticsParams { uri, diagnostics };
+        let not = RawNotification::new::<req::PublishDiagnostics>(&params);
+        msg_sender.send(not.into()).unwrap();
+    }
+}
+
+/// Reports the workspaces which failed to load. Returns the loaded ones, the
+/// status of all of them, and whether all of them were loaded. Folders
+/// without a project don't count as failures.
+fn collect_workspaces(
+    loads: Vec<WorkspaceLoad>,
+    msg_sender: &Sender<RawMessage>,
+) -> (Vec<ProjectWorkspace>, Vec<ProjectStatus>, bool) {
+    let mut workspaces = Vec::new();
+    let mut project_status = Vec::new();
+    let mut all_loaded = true;
+    for load in loads {
+        let mut errors = load.errors;
+        let (loaded, packages) = match load.workspace {
+            Ok(ws) => {
+                let packages = ws.count();
+                workspaces.push(ws);
+                (true, packages)
+            }
+            Err(e @ ProjectLoadError::ManifestNotFound { .. }) => {
+                log::info!("{}", e);
+                errors.push(e);
+                (false, 0)
+            }
+            Err(e) => {
+                log::error!("loading workspace failed: {}", e);
+                show_message(
+                    req::MessageType::Error,
+                    format!("rust-analyzer failed to load workspace: {}", e),
+                    msg_sender,
+                );
+                all_loaded = false;
+                errors.push(e);
+                (false, 0)
+            }
+        };
+        project_status.push(ProjectStatus { folder: load.folder, loaded, packages, errors });
+    }
+    (workspaces, project_status, all_loaded)
+}
+
 fn show_message(typ: req::MessageType, message: impl Into<String>, sender: &Sender<RawMessage>) {
     let message = message.into();
     let params = req::ShowMessageParams { typ, message };
@@ -602,3 +1078,30 @@ fn show_message(typ: req::MessageType, message: impl Into<String>, sender: &Send
 fn is_canceled(e: &Box<dyn std::error::Error + Send + Sync>) -> bool {
     e.downcast_ref::<Canceled>().is_some()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsetting_the_trace_file_keeps_the_env_trace() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("ra_lsp_{}_{}.json", name, std::process::id()))
+        };
+        let (env, config) = (path("env"), path("config"));
+        // As `RA_PROFILE_TRACE` does in `main`.
+        ra_prof::start_trace(&env).unwrap();
+        update_trace(None, Some(&config));
+        update_trace(Some(&config), None);
+        drop(profile("after_config_trace"));
+        ra_prof::stop_trace_of(&env);
+        let env_trace = std::fs::read_to_string(&env).unwrap();
+        let config_trace = std::fs::read_to_string(&config).unwrap();
+        std::fs::remove_file(&env).unwrap();
+        std::fs::remove_file(&config).unwrap();
+
+        assert!(env_trace.contains(r#""name":"after_config_trace""#));
+        assert!(config_trace.ends_with("\n]\n"));
+        assert!(!config_trace.contains(r#""name":"after_config_trace""#));
+    }
+}
@@ -2,18 +2,18 @@ use std::{io::Write as _, fmt::Write as _};
 
 use gen_lsp_server::ErrorCode;
 use lsp_types::{
-    CodeActionResponse, CodeLens, Command, Diagnostic, DiagnosticSeverity, CodeAction,
-    DocumentFormattingParams, DocumentHighlight, DocumentSymbol, FoldingRange,
-    FoldingRangeKind, FoldingRangeParams, Hover, HoverContents, Location, MarkupContent,
-    MarkupKind, Position, PrepareRenameResponse, Range,
-    RenameParams,SymbolInformation, TextDocumentIdentifier, TextEdit,
-    WorkspaceEdit,
+    CodeLens, Command, Diagnostic, DiagnosticSeverity, DocumentFormattingParams, DocumentHighlight,
+    DocumentSymbol, FoldingRange, FoldingRangeKind, FoldingRangeParams, Hover, HoverContents,
+    Location, MarkupContent, MarkupKind, Position, PrepareRenameResponse, Range,
+    DocumentRangeFormattingParams, RenameParams, SymbolInformation, TextDocumentIdentifier,
+    TextEdit, WorkspaceEdit,
 };
 use ra_ide_api::{
-    FileId, FilePosition, FileRange, FoldKind, Query, RangeInfo, RunnableKind, Severity, Cancelable,
-    AssistId,
+    FileId, FilePosition, FileRange, FoldKind, Query, RangeInfo, RunnableKind, Severity,
+    Cancelable, AssistKind,
 };
 use ra_syntax::{AstNode, SyntaxKind, TextUnit, TextRange};
+use ra_text_edit::TextEditBuilder;
 use ra_prof::profile;
 use rustc_hash::FxHashMap;
 use serde::{Serialize, Deserialize};
@@ -23,7 +23,9 @@ use url_serde::Ser;
 use crate::{
     cargo_target_spec::{runnable_args, CargoTargetSpec},
     conv::{to_location, to_location_link, Conv, ConvWith, MapConvWith, TryConvWith},
+    main_loop::pending_requests::Outcome,
     req::{self, Decoration},
+    semantic_tokens::{self, SemanticTokensBuilder},
     world::WorldSnapshot,
     LspError, Result,
 };
@@ -34,11 +36,67 @@ pub fn handle_analyzer_status(world: WorldSnapshot, _: ()) -> Result<String> {
     let requests = world.latest_requests.read();
     for (is_last, r) in requests.iter() {
         let mark = if is_last { "*" } else { " " };
-        writeln!(buf, "{}{:4} {:<36}{}ms", mark, r.id, r.method, r.duration.as_millis()).unwrap();
+        let canceled = if r.outcome == Outcome::Answered { "" } else { " (canceled)" };
+        writeln!(
+            buf,
+            "{}{:4} {:<36}{}ms{}",
+            mark,
+            r.id,
+            r.method,
+            r.duration.as_millis(),
+            canceled
+        )
+        .unwrap();
+    }
+    writeln!(buf, "\nlatency (p50/p90/max):").unwrap();
+    for m in world.request_metrics.read().methods() {
+        writeln!(
+            buf,
+            "{:<36} {:>5}ms {:>5}ms {:>5}ms, {} requests, {} canceled",
+            m.method,
+            m.p50_ms,
+            m.p90_ms,
+            m.max_ms,
+            m.count,
+            m.canceled + m.client_canceled,
+        )
+        .unwrap();
     }
     Ok(buf)
 }
 
+pub fn handle_metrics(world: WorldSnapshot, _: ()) -> Result<req::ServerMetrics> {
+    let requests = world.request_metrics.read().methods();
+    let scopes = ra_prof::scope_stats()
+        .into_iter()
+        .map(|it| req::ScopeMetrics {
+            name: it.desc,
+            count: it.count,
+            total_ms: it.total.as_millis() as u64,
+            max_ms: it.max.as_millis() as u64,
+        })
+        .collect();
+    Ok(req::ServerMetrics { requests, scopes })
+}
+
+pub fn handle_workspace_status(world: WorldSnapshot, _: ()) -> Result<Vec<req::ProjectStatus>> {
+    let res = world
+        .project_status
+        .iter()
+        .map(|status| req::ProjectStatus {
+            folder: status.folder.clone(),
+            loaded: status.loaded,
+            packages: status.packages,
+            errors: status
+                .errors
+                .iter()
+                .map(|e| req::ProjectError { kind: e.kind().to_string(), message: e.to_string() })
+                .collect(),
+        })
+        .collect();
+    Ok(res)
+}
+
 pub fn handle_syntax_tree(world: WorldSnapshot, params: req::SyntaxTreeParams) -> Result<String> {
     let id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(id);
@@ -156,17 +214,22 @@ pub fn handle_on_type_formatting(
     let _p = profile("handle_on_type_formatting");
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id);
-    let position = FilePosition {
-        file_id,
-        /// in `ra_ide_api`, the `on_type` invariant is that
-        /// `text.char_at(position) == typed_char`.
-        offset: params.position.conv_with(&line_index) - TextUnit::of_char('.'),
-    };
+    let offset = params.position.conv_with(&line_index);
 
     let edit = match params.ch.as_str() {
-        "=" => world.analysis().on_eq_typed(position),
-        "." => world.analysis().on_dot_typed(position),
-        _ => return Ok(None),
+        // The new line may already be indented, so the cursor is passed as is
+        "\n" => world.analysis().on_newline_typed(FilePosition { file_id, offset }),
+        ch => {
+            // in `ra_ide_api`, the `on_type` invariant is that
+            // `text.char_at(position) == typed_char`.
+            let position = FilePosition { file_id, offset: offset - TextUnit::of_str(ch) };
+            match ch {
+                "=" => world.analysis().on_eq_typed(position),
+                "." => world.analysis().on_dot_typed(position),
+                "{" | "}" => world.analysis().on_brace_typed(position),
+                _ => return Ok(None),
+            }
+        }
     };
     let mut edit = match edit {
         Some(it) => it,
@@ -436,7 +499,8 @@ pub fn handle_completion(
         return Ok(None);
     }
 
-    let items = match world.analysis().completions(position)? {
+    let config = world.config.completion.to_analysis_config();
+    let items = match world.analysis().completions(position, &config)? {
         None => return Ok(None),
         Some(items) => items,
     };
@@ -606,18 +670,92 @@ pub fn handle_formatting(
     let file_line_index = world.analysis().file_line_index(file_id);
     let end_position = TextUnit::of_str(&file).conv_with(&file_line_index);
 
+    let captured_stdout = run_rustfmt(&file, &params.text_document)?;
+
+    Ok(Some(vec![TextEdit {
+        range: Range::new(Position::new(0, 0), end_position),
+        new_text: captured_stdout,
+    }]))
+}
+
+/// Formats the top-level items which intersect the range: they are valid
+/// files on their own, so rustfmt can format them without the rest of the
+/// file.
+pub fn handle_range_formatting(
+    world: WorldSnapshot,
+    params: DocumentRangeFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    let _p = profile("handle_range_formatting");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let range = params.range.conv_with(&line_index);
+    let file = world.analysis().file_text(file_id);
+
+    let source_file = world.analysis().parse(file_id);
+    let items = source_file
+        .syntax()
+        .children()
+        .map(|it| it.range())
+        .filter(|it| it.intersection(&range).is_some())
+        .collect::<Vec<_>>();
+    let items = match (items.first(), items.last()) {
+        (Some(first), Some(last)) => TextRange::from_to(first.start(), last.end()),
+        _ => return Ok(None),
+    };
+
+    let text = &file[items.start().to_usize()..items.end().to_usize()];
+    let formatted = run_rustfmt(text, &params.text_document)?;
+    let formatted = formatted.trim_end_matches('\n');
+
+    // Only the changed part is replaced, to keep the cursor and the markers of
+    // the editor in the rest of the items.
+    let (changed, replacement) = changed_range(text, formatted);
+    if changed.is_empty() && replacement.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    let mut edit = TextEditBuilder::default();
+    edit.replace(
+        TextRange::offset_len(items.start() + changed.start(), changed.len()),
+        replacement.to_string(),
+    );
+    Ok(Some(edit.finish().conv_with(&line_index)))
+}
+
+/// The range of `text` which differs from `new_text`, with its replacement.
+fn changed_range<'a>(text: &str, new_text: &'a str) -> (TextRange, &'a str) {
+    let mut prefix = text.bytes().zip(new_text.bytes()).take_while(|(a, b)| a == b).count();
+    while !text.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = text.len().min(new_text.len()) - prefix;
+    let mut suffix = text
+        .bytes()
+        .rev()
+        .zip(new_text.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !text.is_char_boundary(text.len() - suffix) {
+        suffix -= 1;
+    }
+    let range =
+        TextRange::from_to(TextUnit::from_usize(prefix), TextUnit::from_usize(text.len() - suffix));
+    (range, &new_text[prefix..new_text.len() - suffix])
+}
+
+fn run_rustfmt(text: &str, text_document: &TextDocumentIdentifier) -> Result<String> {
     use std::process;
     let mut rustfmt = process::Command::new("rustfmt");
     rustfmt.stdin(process::Stdio::piped()).stdout(process::Stdio::piped());
 
-    if let Ok(path) = params.text_document.uri.to_file_path() {
+    if let Ok(path) = text_document.uri.to_file_path() {
         if let Some(parent) = path.parent() {
             rustfmt.current_dir(parent);
         }
     }
     let mut rustfmt = rustfmt.spawn()?;
 
-    rustfmt.stdin.as_mut().unwrap().write_all(file.as_bytes())?;
+    rustfmt.stdin.as_mut().unwrap().write_all(text.as_bytes())?;
 
     let output = rustfmt.wait_with_output()?;
     let captured_stdout = String::from_utf8(output.stdout)?;
@@ -633,74 +771,151 @@ pub fn handle_formatting(
         )
         .into());
     }
-
-    Ok(Some(vec![TextEdit {
-        range: Range::new(Position::new(0, 0), end_position),
-        new_text: captured_stdout,
-    }]))
+    Ok(captured_stdout)
 }
 
 pub fn handle_code_action(
     world: WorldSnapshot,
     params: req::CodeActionParams,
-) -> Result<Option<CodeActionResponse>> {
+) -> Result<Option<Vec<req::CodeAction>>> {
     let _p = profile("handle_code_action");
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id);
     let range = params.range.conv_with(&line_index);
+    let frange = FileRange { file_id, range };
+    let only = params.context.only.as_ref();
+    let wanted = |kind: &str| {
+        only.map_or(true, |only| {
+            only.iter().any(|it| kind == it || kind.starts_with(&format!("{}.", it)))
+        })
+    };
 
-    let assists = world.analysis().assists(FileRange { file_id, range })?.into_iter();
-    let diagnostics = world.analysis().diagnostics(file_id)?;
-    let mut res: Vec<CodeAction> = Vec::new();
+    let mut res: Vec<req::CodeAction> = Vec::new();
 
-    let fixes_from_diagnostics = diagnostics
-        .into_iter()
-        .filter_map(|d| Some((d.range, d.fix?)))
-        .filter(|(diag_range, _fix)| diag_range.intersection(&range).is_some())
-        .map(|(_range, fix)| fix);
-
-    for source_edit in fixes_from_diagnostics {
-        let title = source_edit.label.clone();
-        let edit = source_edit.try_conv_with(&world)?;
-
-        let command = Command {
-            title,
-            command: "rust-analyzer.applySourceChange".to_string(),
-            arguments: Some(vec![to_value(edit).unwrap()]),
-        };
-        let action = CodeAction {
-            title: command.title.clone(),
-            kind: None,
-            diagnostics: None,
-            edit: None,
-            command: Some(command),
-        };
-        res.push(action);
+    let quick_fix = AssistKind::QuickFix.conv();
+    if wanted(&quick_fix) {
+        let diagnostics = world.analysis().diagnostics(file_id)?;
+        let fixes_from_diagnostics = diagnostics
+            .into_iter()
+            .filter_map(|d| Some((d.range, d.fix?)))
+            .filter(|(diag_range, _fix)| diag_range.intersection(&range).is_some())
+            .map(|(_range, fix)| fix);
+
+        for source_edit in fixes_from_diagnostics {
+            let title = source_edit.label.clone();
+            let edit = source_edit.try_conv_with(&world)?;
+            res.push(req::CodeAction {
+                title: title.clone(),
+                kind: Some(quick_fix.clone()),
+                diagnostics: None,
+                edit: None,
+                command: Some(apply_source_change_command(title, edit)),
+                data: None,
+            });
+        }
     }
 
-    for assist in assists {
-        let title = assist.change.label.clone();
-        let edit = assist.change.try_conv_with(&world)?;
+    let check_fixes = world
+        .check_diagnostics
+        .fixes(&params.text_document.uri, params.range)
+        .filter(|it| it.kind.as_ref().map_or(only.is_none(), |kind| wanted(kind)));
+    for fix in check_fixes {
+        res.push(req::CodeAction {
+            title: fix.title.clone(),
+            kind: fix.kind.clone(),
+            diagnostics: fix.diagnostics.clone(),
+            edit: fix.edit.clone(),
+            command: fix.command.clone(),
+            data: None,
+        });
+    }
 
-        let command = Command {
-            title,
-            command: "rust-analyzer.applySourceChange".to_string(),
-            arguments: Some(vec![to_value(edit).unwrap()]),
-        };
-        let action = CodeAction {
-            title: command.title.clone(),
-            kind: match assist.id {
-                AssistId("introduce_variable") => Some("refactor.extract.variable".to_string()),
-                _ => None,
-            },
-            diagnostics: None,
-            edit: None,
-            command: Some(command),
-        };
-        res.push(action);
+    // The assists are grouped by kind, quick fixes first
+    if world.client_caps.code_action_resolve {
+        let mut labels = world.analysis().assist_labels(frange)?;
+        labels.sort_by_key(|it| it.id.1);
+        for label in labels {
+            let kind = label.id.1.conv();
+            if !wanted(&kind) {
+                continue;
+            }
+            let data = req::CodeActionData {
+                text_document: params.text_document.clone(),
+                range: params.range,
+                id: label.id.0.to_string(),
+            };
+            res.push(req::CodeAction {
+                title: label.label,
+                kind: Some(kind),
+                diagnostics: None,
+                edit: None,
+                command: None,
+                data: Some(data),
+            });
+        }
+    } else {
+        // Without `codeAction/resolve`, the edits have to be computed upfront
+        let mut assists = world.analysis().assists(frange)?;
+        assists.sort_by_key(|it| it.id.1);
+        for assist in assists {
+            let kind = assist.id.1.conv();
+            if !wanted(&kind) {
+                continue;
+            }
+            let title = assist.change.label.clone();
+            let edit = assist.change.try_conv_with(&world)?;
+            res.push(req::CodeAction {
+                title: title.clone(),
+                kind: Some(kind),
+                diagnostics: None,
+                edit: None,
+                command: Some(apply_source_change_command(title, edit)),
+                data: None,
+            });
+        }
     }
 
-    Ok(Some(CodeActionResponse::Actions(res)))
+    Ok(Some(res))
+}
+
+pub fn handle_code_action_resolve(
+    world: WorldSnapshot,
+    mut action: req::CodeAction,
+) -> Result<req::CodeAction> {
+    let _p = profile("handle_code_action_resolve");
+    let data = match action.data.take() {
+        Some(it) => it,
+        None => return Ok(action),
+    };
+    let file_id = data.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let range = data.range.conv_with(&line_index);
+
+    let assist = match world.analysis().resolve_assist(
+        FileRange { file_id, range },
+        &data.id,
+        &action.title,
+    )? {
+        Some(it) => it,
+        None => {
+            return Err(LspError::new(
+                ErrorCode::ContentModified as i32,
+                format!("assist `{}` is not applicable anymore", action.title),
+            )
+            .into())
+        }
+    };
+    let change = assist.change.try_conv_with(&world)?;
+    action.edit = Some(change.workspace_edit);
+    Ok(action)
+}
+
+fn apply_source_change_command(title: String, edit: req::SourceChange) -> Command {
+    Command {
+        title,
+        command: "rust-analyzer.applySourceChange".to_string(),
+        arguments: Some(vec![to_value(edit).unwrap()]),
+    }
 }
 
 pub fn handle_code_lens(
@@ -849,11 +1064,16 @@ pub fn publish_diagnostics(
     file_id: FileId,
 ) -> Result<req::PublishDiagnosticsParams> {
     let uri = world.file_id_to_uri(file_id)?;
+    let config = &world.config.diagnostics;
+    if !config.enable {
+        return Ok(req::PublishDiagnosticsParams { uri, diagnostics: Vec::new() });
+    }
     let line_index = world.analysis().file_line_index(file_id);
-    let diagnostics = world
+    let mut diagnostics: Vec<Diagnostic> = world
         .analysis()
         .diagnostics(file_id)?
         .into_iter()
+        .filter(|d| config.weak_warnings || d.severity != Severity::WeakWarning)
         .map(|d| Diagnostic {
             range: d.range.conv_with(&line_index),
             severity: Some(to_diagnostic_severity(d.severity)),
@@ -863,6 +1083,7 @@ pub fn publish_diagnostics(
             related_information: None,
         })
         .collect();
+    diagnostics.extend(world.check_diagnostics.diagnostics(&uri).cloned());
     Ok(req::PublishDiagnosticsParams { uri, diagnostics })
 }
 
@@ -889,6 +1110,88 @@ fn highlight(world: &WorldSnapshot, file_id: FileId) -> Result<Vec<Decoration>>
     Ok(res)
 }
 
+pub fn handle_semantic_tokens(
+    world: WorldSnapshot,
+    params: req::SemanticTokensParams,
+) -> Result<Option<req::SemanticTokens>> {
+    let _p = profile("handle_semantic_tokens");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let mut tokens = semantic_tokens(&world, file_id, None)?;
+    world.semantic_tokens.lock().store(params.text_document.uri, &mut tokens);
+    Ok(Some(tokens))
+}
+
+/// Sends the edits of the previous tokens if the client has the latest ones,
+/// and all tokens otherwise.
+pub fn handle_semantic_tokens_delta(
+    world: WorldSnapshot,
+    params: req::SemanticTokensDeltaParams,
+) -> Result<Option<req::SemanticTokensDeltaResult>> {
+    let _p = profile("handle_semantic_tokens_delta");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let mut tokens = semantic_tokens(&world, file_id, None)?;
+    let uri = params.text_document.uri;
+    let mut cache = world.semantic_tokens.lock();
+    let previous = cache.get(&uri, &params.previous_result_id).map(|it| it.data.clone());
+    cache.store(uri, &mut tokens);
+    let res = match previous {
+        Some(previous) => req::SemanticTokensDeltaResult::Delta(req::SemanticTokensDelta {
+            edits: semantic_tokens::diff_tokens(&previous, &tokens.data),
+            result_id: tokens.result_id,
+        }),
+        None => req::SemanticTokensDeltaResult::Tokens(tokens),
+    };
+    Ok(Some(res))
+}
+
+pub fn handle_semantic_tokens_range(
+    world: WorldSnapshot,
+    params: req::SemanticTokensRangeParams,
+) -> Result<Option<req::SemanticTokens>> {
+    let _p = profile("handle_semantic_tokens_range");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let range = params.range.conv_with(&line_index);
+    Ok(Some(semantic_tokens(&world, file_id, Some(range))?))
+}
+
+/// The highlighting of a file, or of the ranges which intersect `range`, as
+/// semantic tokens. Tokens don't overlap, so the ranges inside an already
+/// highlighted range are skipped.
+fn semantic_tokens(
+    world: &WorldSnapshot,
+    file_id: FileId,
+    range: Option<TextRange>,
+) -> Result<req::SemanticTokens> {
+    let text = world.analysis().file_text(file_id);
+    let line_index = world.analysis().file_line_index(file_id);
+    let mut highlights = world.analysis().highlight(file_id)?;
+    highlights.sort_by_key(|it| it.range.start());
+
+    let mut builder = SemanticTokensBuilder::default();
+    let mut highlighted_up_to = TextUnit::from(0);
+    for h in highlights {
+        if h.range.start() < highlighted_up_to {
+            continue;
+        }
+        if let Some(range) = range {
+            if h.range.end() <= range.start() || range.end() <= h.range.start() {
+                continue;
+            }
+        }
+        let (token_type, modifiers) = match semantic_tokens::token_type(h.tag) {
+            Some(it) => it,
+            None => continue,
+        };
+        let modifiers = modifiers | semantic_tokens::modifiers(h.modifiers);
+        highlighted_up_to = h.range.end();
+        for line_range in semantic_tokens::split_lines(&text, h.range) {
+            builder.push(line_range.conv_with(&line_index), token_type, modifiers);
+        }
+    }
+    Ok(builder.build())
+}
+
 fn to_diagnostic_severity(severity: Severity) -> DiagnosticSeverity {
     use ra_ide_api::Severity::*;
 
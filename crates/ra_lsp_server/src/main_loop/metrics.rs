@@ -0,0 +1,143 @@
+use std::{iter, time::Duration};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    main_loop::pending_requests::{CompletedRequest, Outcome},
+    req::{HistogramBucket, MethodMetrics},
+};
+
+/// The number of latest latencies which are kept per method.
+const WINDOW: usize = 256;
+
+/// The upper bounds of the buckets of the latency histograms, in milliseconds.
+const BUCKETS_MS: &[u64] = &[1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000];
+
+/// Latencies and cancellations of the requests, per LSP method. The counts
+/// cover the whole session, while the latencies are rolling: only the latest
+/// `WINDOW` answered requests of each method are kept.
+#[derive(Debug, Default)]
+pub struct RequestMetrics {
+    methods: FxHashMap<String, MethodLatencies>,
+}
+
+#[derive(Debug, Default)]
+struct MethodLatencies {
+    count: u64,
+    canceled: u64,
+    client_canceled: u64,
+    latencies: Vec<Duration>,
+    /// Where the next latency goes once the window is full.
+    next: usize,
+}
+
+impl RequestMetrics {
+    pub(crate) fn record(&mut self, request: &CompletedRequest) {
+        let method = self.methods.entry(request.method.clone()).or_default();
+        method.count += 1;
+        match request.outcome {
+            Outcome::Answered => {
+                if method.latencies.len() < WINDOW {
+                    method.latencies.push(request.duration);
+                } else {
+                    method.latencies[method.next] = request.duration;
+                }
+                method.next = (method.next + 1) % WINDOW;
+            }
+            Outcome::Canceled => method.canceled += 1,
+            Outcome::ClientCanceled => method.client_canceled += 1,
+        }
+    }
+
+    /// The metrics of each method, the methods with the most requests first.
+    pub fn methods(&self) -> Vec<MethodMetrics> {
+        let mut res: Vec<MethodMetrics> =
+            self.methods.iter().map(|(method, it)| it.metrics(method)).collect();
+        res.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.method.cmp(&b.method)));
+        res
+    }
+}
+
+impl MethodLatencies {
+    fn metrics(&self, method: &str) -> MethodMetrics {
+        let mut millis: Vec<u64> = self.latencies.iter().map(|it| it.as_millis() as u64).collect();
+        millis.sort();
+        let percentile = |p: usize| match millis.len() {
+            0 => 0,
+            len => millis[(len - 1) * p / 100],
+        };
+
+        let mut histogram: Vec<HistogramBucket> = BUCKETS_MS
+            .iter()
+            .map(|&le| Some(le))
+            .chain(iter::once(None))
+            .map(|le_ms| HistogramBucket { le_ms, count: 0 })
+            .collect();
+        for &ms in millis.iter() {
+            let idx = BUCKETS_MS.iter().position(|&le| ms <= le).unwrap_or(BUCKETS_MS.len());
+            histogram[idx].count += 1;
+        }
+
+        MethodMetrics {
+            method: method.to_string(),
+            count: self.count,
+            canceled: self.canceled,
+            client_canceled: self.client_canceled,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: millis.last().cloned().unwrap_or(0),
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, millis: u64, outcome: Outcome) -> CompletedRequest {
+        CompletedRequest {
+            id: 0,
+            method: method.to_string(),
+            duration: Duration::from_millis(millis),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn latencies_are_bucketed_per_method() {
+        let mut metrics = RequestMetrics::default();
+        for millis in 1..=10 {
+            metrics.record(&request("textDocument/completion", millis * 10, Outcome::Answered));
+        }
+        metrics.record(&request("textDocument/completion", 0, Outcome::Canceled));
+        metrics.record(&request("textDocument/hover", 3, Outcome::ClientCanceled));
+
+        let methods = metrics.methods();
+        assert_eq!(methods.len(), 2);
+        let completion = &methods[0];
+        assert_eq!(completion.method, "textDocument/completion");
+        assert_eq!((completion.count, completion.canceled, completion.client_canceled), (11, 1, 0));
+        assert_eq!((completion.p50_ms, completion.p90_ms, completion.max_ms), (50, 90, 100));
+        let counts: Vec<u64> = completion.histogram.iter().map(|it| it.count).collect();
+        assert_eq!(counts, vec![0, 0, 0, 1, 1, 3, 5, 0, 0, 0, 0, 0, 0]);
+
+        let hover = &methods[1];
+        assert_eq!((hover.count, hover.client_canceled, hover.max_ms), (1, 1, 0));
+    }
+
+    #[test]
+    fn only_the_latest_latencies_are_kept() {
+        let mut metrics = RequestMetrics::default();
+        for _ in 0..WINDOW {
+            metrics.record(&request("textDocument/hover", 1000, Outcome::Answered));
+        }
+        for _ in 0..WINDOW {
+            metrics.record(&request("textDocument/hover", 1, Outcome::Answered));
+        }
+        let hover = &metrics.methods()[0];
+        assert_eq!(hover.count, 2 * WINDOW as u64);
+        assert_eq!(hover.max_ms, 1);
+    }
+}
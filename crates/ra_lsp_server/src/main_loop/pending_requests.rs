@@ -7,6 +7,18 @@ pub struct CompletedRequest {
     pub id: u64,
     pub method: String,
     pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The request was answered, possibly with an error.
+    Answered,
+    /// The analysis was canceled by a change, which salsa signals with
+    /// `Canceled`.
+    Canceled,
+    /// The client canceled the request with `$/cancelRequest`.
+    ClientCanceled,
 }
 
 #[derive(Debug)]
@@ -16,12 +28,13 @@ pub(crate) struct PendingRequest {
     pub(crate) received: Instant,
 }
 
-impl From<PendingRequest> for CompletedRequest {
-    fn from(pending: PendingRequest) -> CompletedRequest {
+impl PendingRequest {
+    fn complete(self, outcome: Outcome) -> CompletedRequest {
         CompletedRequest {
-            id: pending.id,
-            method: pending.method,
-            duration: pending.received.elapsed(),
+            id: self.id,
+            method: self.method,
+            duration: self.received.elapsed(),
+            outcome,
         }
     }
 }
@@ -37,11 +50,11 @@ impl PendingRequests {
         let prev = self.map.insert(id, request);
         assert!(prev.is_none(), "duplicate request with id {}", id);
     }
-    pub(crate) fn cancel(&mut self, id: u64) -> bool {
-        self.map.remove(&id).is_some()
+    pub(crate) fn cancel(&mut self, id: u64) -> Option<CompletedRequest> {
+        self.map.remove(&id).map(|it| it.complete(Outcome::ClientCanceled))
     }
-    pub(crate) fn finish(&mut self, id: u64) -> Option<CompletedRequest> {
-        self.map.remove(&id).map(CompletedRequest::from)
+    pub(crate) fn finish(&mut self, id: u64, outcome: Outcome) -> Option<CompletedRequest> {
+        self.map.remove(&id).map(|it| it.complete(outcome))
     }
 }
 
@@ -0,0 +1,126 @@
+use crossbeam_channel::Sender;
+use gen_lsp_server::{RawMessage, RawNotification, RawRequest};
+
+use crate::{
+    main_loop::ServerRequests,
+    req::{self, ProgressParams, WorkDoneProgress},
+};
+
+/// How far the loading of the VFS roots got. The VFS scans all roots, the
+/// local ones are done then, while the libraries are indexed on the thread
+/// pool, which builds their symbol index, before they are added with
+/// `add_lib`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LoadState {
+    /// The generation of the VFS the roots belong to.
+    pub generation: u32,
+    pub roots_total: usize,
+    /// The roots which are neither scanned local roots nor added libraries.
+    pub roots_to_scan: usize,
+    /// The scanned libraries which aren't indexed yet.
+    pub libraries_to_index: usize,
+}
+
+impl LoadState {
+    fn report(&self) -> (String, u32) {
+        let done = self.roots_total - self.roots_to_scan;
+        let scanned = done + self.libraries_to_index;
+        let message = if scanned < self.roots_total {
+            format!("scanning roots {}/{}", scanned, self.roots_total)
+        } else {
+            format!("indexing libraries, {} left", self.libraries_to_index)
+        };
+        let percentage = (done * 100 / self.roots_total.max(1)) as u32;
+        (message, percentage)
+    }
+}
+
+/// Reports the loading of the workspace to the client, with work-done
+/// progress if the client supports it and `rust-analyzer/progress`
+/// otherwise. There's one report per VFS generation.
+#[derive(Debug)]
+pub(super) struct LoadProgress {
+    work_done_progress: bool,
+    /// The generation which is being reported, with the last report.
+    active: Option<(u32, (String, u32))>,
+}
+
+impl LoadProgress {
+    pub(super) fn new(work_done_progress: bool) -> LoadProgress {
+        LoadProgress { work_done_progress, active: None }
+    }
+
+    pub(super) fn update(
+        &mut self,
+        load: LoadState,
+        requests: &mut ServerRequests,
+        msg_sender: &Sender<RawMessage>,
+    ) {
+        let loading = load.roots_to_scan > 0;
+        if let Some((generation, _)) = self.active {
+            if !loading || generation != load.generation {
+                self.send(generation, WorkDoneProgress::End { message: None }, msg_sender);
+                self.active = None;
+            }
+        }
+        if !loading {
+            return;
+        }
+        let (message, percentage) = load.report();
+        match &self.active {
+            Some((_, last)) if *last == (message.clone(), percentage) => (),
+            Some(_) => {
+                let value = WorkDoneProgress::Report {
+                    message: Some(message.clone()),
+                    percentage: Some(percentage),
+                };
+                self.send(load.generation, value, msg_sender);
+            }
+            None => {
+                if self.work_done_progress {
+                    let params =
+                        req::WorkDoneProgressCreateParams { token: token(load.generation) };
+                    let request =
+                        RawRequest::new::<req::WorkDoneProgressCreate>(requests.next_id(), &params);
+                    msg_sender.send(request.into()).unwrap();
+                }
+                let value = WorkDoneProgress::Begin {
+                    title: "Loading workspace".to_string(),
+                    message: Some(message.clone()),
+                    percentage: Some(percentage),
+                };
+                self.send(load.generation, value, msg_sender);
+            }
+        }
+        self.active = Some((load.generation, (message, percentage)));
+    }
+
+    fn send(&self, generation: u32, value: WorkDoneProgress, msg_sender: &Sender<RawMessage>) {
+        let params = ProgressParams { token: token(generation), value };
+        let not = if self.work_done_progress {
+            RawNotification::new::<req::Progress>(&params)
+        } else {
+            RawNotification::new::<req::LoadProgress>(&params)
+        };
+        msg_sender.send(not.into()).unwrap();
+    }
+}
+
+fn token(generation: u32) -> String {
+    format!("rustAnalyzer/load/{}", generation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_report_moves_from_scanning_to_indexing() {
+        let load =
+            LoadState { generation: 0, roots_total: 4, roots_to_scan: 3, libraries_to_index: 1 };
+        assert_eq!(load.report(), ("scanning roots 2/4".to_string(), 25));
+        let load =
+            LoadState { generation: 0, roots_total: 4, roots_to_scan: 2, libraries_to_index: 2 };
+        assert_eq!(load.report(), ("indexing libraries, 2 left".to_string(), 50));
+    }
+}
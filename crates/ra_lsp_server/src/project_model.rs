@@ -2,22 +2,97 @@ use std::path::PathBuf;
 
 use thread_worker::Worker;
 
-use crate::Result;
+use crate::config::CargoConfig;
 
 pub use ra_project_model::{
-    ProjectWorkspace, CargoWorkspace, Package, Target, TargetKind, Sysroot,
+    ProjectWorkspace, CargoWorkspace, CargoFeatures, CargoCheckOutput, Package, Target, TargetKind,
+    Sysroot, ProcMacroClient, ProjectLoadError, TargetConfig,
 };
 
-pub fn workspace_loader() -> Worker<PathBuf, Result<ProjectWorkspace>> {
-    Worker::<PathBuf, Result<ProjectWorkspace>>::spawn(
+/// Files which describe the structure of a workspace: when one of them
+/// changes, the workspace is loaded again.
+const WORKSPACE_MANIFESTS: &[&str] = &["Cargo.toml", "Cargo.lock", "rust-project.json"];
+
+pub fn is_workspace_manifest(file_name: &str) -> bool {
+    WORKSPACE_MANIFESTS.contains(&file_name)
+}
+
+/// What the workspace loader should load.
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceRoots {
+    /// The workspace folders, a project is discovered for each of them.
+    pub folders: Vec<PathBuf>,
+    /// Rust files outside of all projects.
+    pub detached_files: Vec<PathBuf>,
+    /// How the Cargo projects are loaded.
+    pub cargo: CargoConfig,
+}
+
+/// The project of a workspace folder, or of the detached files if `folder`
+/// is `None`.
+#[derive(Debug)]
+pub struct WorkspaceLoad {
+    pub folder: Option<PathBuf>,
+    pub workspace: Result<ProjectWorkspace, ProjectLoadError>,
+    /// Errors which didn't prevent loading the workspace.
+    pub errors: Vec<ProjectLoadError>,
+}
+
+/// What went wrong while loading the project of a workspace folder.
+#[derive(Debug, Clone)]
+pub struct ProjectStatus {
+    pub folder: Option<PathBuf>,
+    /// Whether the project is loaded, which is the case even if some
+    /// dependencies are missing from the crate graph.
+    pub loaded: bool,
+    pub packages: usize,
+    pub errors: Vec<ProjectLoadError>,
+}
+
+/// Spawns a worker which loads the workspaces of the given roots, with the
+/// features, the target and the check output of their Cargo configuration.
+///
+/// Folders without a `Cargo.toml` or a `rust-project.json` have no project:
+/// the files opened from them are detached files.
+pub fn workspace_loader() -> Worker<WorkspaceRoots, Vec<WorkspaceLoad>> {
+    Worker::<WorkspaceRoots, Vec<WorkspaceLoad>>::spawn(
         "workspace loader",
         1,
-        |input_receiver, output_sender| {
+        move |input_receiver, output_sender| {
             input_receiver
                 .into_iter()
-                .map(|path| ProjectWorkspace::discover(path.as_path()))
+                .map(|roots| {
+                    let cargo = &roots.cargo;
+                    let mut loads = roots
+                        .folders
+                        .iter()
+                        .map(|path| load_folder(path.clone(), cargo))
+                        .collect::<Vec<_>>();
+                    if !roots.detached_files.is_empty() {
+                        let files = roots.detached_files.clone();
+                        let workspace =
+                            ProjectWorkspace::load_detached_files(files, &cargo.target_config());
+                        loads.push(WorkspaceLoad { folder: None, workspace, errors: Vec::new() });
+                    }
+                    loads
+                })
                 .try_for_each(|it| output_sender.send(it))
                 .unwrap()
         },
     )
 }
+
+fn load_folder(path: PathBuf, cargo: &CargoConfig) -> WorkspaceLoad {
+    let mut errors = Vec::new();
+    let workspace = ProjectWorkspace::discover(&path, &cargo.features, &cargo.target_config());
+    let workspace = workspace.map(|mut ws| {
+        if let Some(check_output) = cargo.check_output() {
+            if let Err(e) = ws.load_cargo_check_output(&check_output) {
+                log::error!("{}", e);
+                errors.push(e);
+            }
+        }
+        ws
+    });
+    WorkspaceLoad { folder: Some(path), workspace, errors }
+}
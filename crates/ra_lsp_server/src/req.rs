@@ -1,4 +1,6 @@
-use lsp_types::{Location, Position, Range, TextDocumentIdentifier, Url};
+use std::path::PathBuf;
+
+use lsp_types::{Command, Diagnostic, Location, Position, Range, TextDocumentIdentifier, Url};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use url_serde;
@@ -9,7 +11,9 @@ pub use lsp_types::{
     DocumentSymbolResponse, ExecuteCommandParams, Hover, InitializeResult,
     PublishDiagnosticsParams, ReferenceParams, SignatureHelp, TextDocumentEdit,
     TextDocumentPositionParams, TextEdit, WorkspaceEdit, WorkspaceSymbolParams,
-    MessageType, ShowMessageParams,
+    MessageType, ShowMessageParams, ConfigurationParams, ConfigurationItem,
+    DidChangeWatchedFilesRegistrationOptions, FileSystemWatcher, Registration, RegistrationParams,
+    Unregistration, UnregistrationParams,
 };
 
 pub enum AnalyzerStatus {}
@@ -20,6 +24,85 @@ impl Request for AnalyzerStatus {
     const METHOD: &'static str = "rust-analyzer/analyzerStatus";
 }
 
+pub enum WorkspaceStatus {}
+
+impl Request for WorkspaceStatus {
+    type Params = ();
+    type Result = Vec<ProjectStatus>;
+    const METHOD: &'static str = "rust-analyzer/workspaceStatus";
+}
+
+pub enum Metrics {}
+
+impl Request for Metrics {
+    type Params = ();
+    type Result = ServerMetrics;
+    const METHOD: &'static str = "rust-analyzer/metrics";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerMetrics {
+    /// The requests of each method, the methods with the most requests first.
+    pub requests: Vec<MethodMetrics>,
+    /// The time spent in the profiled scopes, which include the top-level
+    /// queries, the most expensive scopes first.
+    pub scopes: Vec<ScopeMetrics>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodMetrics {
+    pub method: String,
+    pub count: u64,
+    /// The requests canceled by changes.
+    pub canceled: u64,
+    /// The requests canceled with `$/cancelRequest`.
+    pub client_canceled: u64,
+    /// The latencies are those of the latest answered requests.
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramBucket {
+    /// The upper bound of the latencies in the bucket, `None` for the last
+    /// bucket.
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopeMetrics {
+    pub name: String,
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStatus {
+    /// The workspace folder, `None` for the files outside of all folders.
+    pub folder: Option<PathBuf>,
+    pub loaded: bool,
+    pub packages: usize,
+    pub errors: Vec<ProjectError>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectError {
+    /// Like `cargoNotFound` or `sysrootSourcesMissing`.
+    pub kind: String,
+    pub message: String,
+}
+
 pub enum CollectGarbage {}
 
 impl Request for CollectGarbage {
@@ -196,3 +279,197 @@ pub struct SourceChange {
     pub workspace_edit: WorkspaceEdit,
     pub cursor_position: Option<TextDocumentPositionParams>,
 }
+
+/// Replaces the request of `lsp_types`, whose code actions can't carry the
+/// `data` of unresolved code actions yet.
+pub enum CodeActionRequest {}
+
+impl Request for CodeActionRequest {
+    type Params = CodeActionParams;
+    type Result = Option<Vec<CodeAction>>;
+    const METHOD: &'static str = "textDocument/codeAction";
+}
+
+pub enum CodeActionResolveRequest {}
+
+impl Request for CodeActionResolveRequest {
+    type Params = CodeAction;
+    type Result = CodeAction;
+    const METHOD: &'static str = "codeAction/resolve";
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeAction {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit: Option<WorkspaceEdit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Command>,
+    /// Set on the assists, whose edits are only computed when the client
+    /// resolves them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<CodeActionData>,
+}
+
+/// The position of an unresolved assist, the title of the code action is the
+/// label of the assist.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeActionData {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    pub id: String,
+}
+
+pub enum WorkDoneProgressCreate {}
+
+impl Request for WorkDoneProgressCreate {
+    type Params = WorkDoneProgressCreateParams;
+    type Result = ();
+    const METHOD: &'static str = "window/workDoneProgress/create";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkDoneProgressCreateParams {
+    pub token: String,
+}
+
+/// Work-done progress, for the clients which support `window/workDoneProgress`.
+pub enum Progress {}
+
+impl Notification for Progress {
+    type Params = ProgressParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+/// The same progress as `$/progress`, for the clients which don't support
+/// `window/workDoneProgress`.
+pub enum LoadProgress {}
+
+impl Notification for LoadProgress {
+    type Params = ProgressParams;
+    const METHOD: &'static str = "rust-analyzer/progress";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    pub token: String,
+    pub value: WorkDoneProgress,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WorkDoneProgress {
+    Begin {
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        /// From 0 to 100.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percentage: Option<u32>,
+    },
+    Report {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percentage: Option<u32>,
+    },
+    End {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+pub enum SemanticTokensRequest {}
+
+impl Request for SemanticTokensRequest {
+    type Params = SemanticTokensParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full";
+}
+
+pub enum SemanticTokensDeltaRequest {}
+
+impl Request for SemanticTokensDeltaRequest {
+    type Params = SemanticTokensDeltaParams;
+    type Result = Option<SemanticTokensDeltaResult>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full/delta";
+}
+
+pub enum SemanticTokensRangeRequest {}
+
+impl Request for SemanticTokensRangeRequest {
+    type Params = SemanticTokensRangeParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/range";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDeltaParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<String>,
+    pub token_modifiers: Vec<String>,
+}
+
+/// The tokens of a file, five numbers per token: the line relative to the
+/// previous token, the start character, relative to the previous token if
+/// it's on the same line, the length, the token type and the modifiers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub data: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+/// Replaces `delete_count` numbers of the previous tokens at `start`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Vec<u32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SemanticTokensDeltaResult {
+    Tokens(SemanticTokens),
+    Delta(SemanticTokensDelta),
+}
@@ -0,0 +1,211 @@
+//! Encodes the highlighting of `ra_ide_api` as LSP semantic tokens.
+//!
+//! The tags of the highlighted ranges become token types, and their
+//! modifiers keep the order of the bits of `HighlightModifiers`, so that the
+//! bits are the modifiers of the legend.
+use lsp_types::{Range, Url};
+use ra_ide_api::HighlightModifiers;
+use ra_syntax::{TextRange, TextUnit};
+use rustc_hash::FxHashMap;
+
+use crate::req::{SemanticTokens, SemanticTokensEdit, SemanticTokensLegend};
+
+const TOKEN_TYPES: &[&str] = &[
+    "comment",
+    "string",
+    "keyword",
+    "number",
+    "function",
+    "macro",
+    "property",
+    "variable",
+    "type",
+    "namespace",
+    "typeParameter",
+    "attribute",
+];
+
+/// The modifiers of `HighlightModifiers` come first.
+const TOKEN_MODIFIERS: &[&str] =
+    &["mutable", "unsafe", "static", "macro", "library", "readonly", "controlFlow"];
+
+const READONLY: u32 = 1 << 5;
+const CONTROL_FLOW: u32 = 1 << 6;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.iter().map(|it| it.to_string()).collect(),
+        token_modifiers: TOKEN_MODIFIERS.iter().map(|it| it.to_string()).collect(),
+    }
+}
+
+/// The token type of a highlighting tag, with the modifiers the tag implies.
+/// Plain text has no token.
+pub fn token_type(tag: &str) -> Option<(u32, u32)> {
+    let (name, modifiers) = match tag {
+        "comment" => ("comment", 0),
+        "string" => ("string", 0),
+        "attribute" => ("attribute", 0),
+        "function" => ("function", 0),
+        "macro" => ("macro", 0),
+        "field" => ("property", 0),
+        "constant" => ("variable", READONLY),
+        "type" => ("type", 0),
+        "module" => ("namespace", 0),
+        "variable" => ("variable", 0),
+        "literal" => ("number", 0),
+        "parameter" => ("typeParameter", 0),
+        "keyword" | "keyword.unsafe" => ("keyword", 0),
+        "keyword.control" => ("keyword", CONTROL_FLOW),
+        _ => return None,
+    };
+    let idx = TOKEN_TYPES.iter().position(|it| *it == name).unwrap();
+    Some((idx as u32, modifiers))
+}
+
+pub fn modifiers(modifiers: HighlightModifiers) -> u32 {
+    modifiers.bits()
+}
+
+/// Splits a range of `text` into its lines, as tokens can't span lines.
+pub fn split_lines(text: &str, range: TextRange) -> Vec<TextRange> {
+    let mut res = Vec::new();
+    let mut start = range.start();
+    for line in text[range.start().to_usize()..range.end().to_usize()].split('\n') {
+        let content = line.trim_end_matches('\r');
+        if !content.is_empty() {
+            res.push(TextRange::offset_len(start, TextUnit::of_str(content)));
+        }
+        start += TextUnit::of_str(line) + TextUnit::of_char('\n');
+    }
+    res
+}
+
+/// Encodes the tokens, which are pushed in order, relative to each other.
+#[derive(Debug, Default)]
+pub struct SemanticTokensBuilder {
+    prev_line: u32,
+    prev_char: u32,
+    data: Vec<u32>,
+}
+
+impl SemanticTokensBuilder {
+    /// `range` is on a single line.
+    pub fn push(&mut self, range: Range, token_type: u32, modifiers: u32) {
+        let line = range.start.line as u32;
+        let character = range.start.character as u32;
+        let delta_line = line - self.prev_line;
+        let delta_char = if delta_line == 0 { character - self.prev_char } else { character };
+        let len = (range.end.character - range.start.character) as u32;
+        self.data.extend_from_slice(&[delta_line, delta_char, len, token_type, modifiers]);
+        self.prev_line = line;
+        self.prev_char = character;
+    }
+
+    pub fn build(self) -> SemanticTokens {
+        SemanticTokens { result_id: None, data: self.data }
+    }
+}
+
+/// The edit which turns the `old` tokens into the `new` ones: everything
+/// between their common prefix and their common suffix is replaced.
+pub fn diff_tokens(old: &[u32], new: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix =
+        old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+    // Edits replace whole tokens
+    let prefix = prefix / 5 * 5;
+    let suffix = suffix / 5 * 5;
+    let deleted = old.len() - prefix - suffix;
+    let inserted = &new[prefix..new.len() - suffix];
+    if deleted == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+    let data = if inserted.is_empty() { None } else { Some(inserted.to_vec()) };
+    vec![SemanticTokensEdit { start: prefix as u32, delete_count: deleted as u32, data }]
+}
+
+/// The latest tokens of each file, which the deltas are computed from.
+#[derive(Debug, Default)]
+pub struct SemanticTokensCache {
+    next_result_id: u64,
+    tokens: FxHashMap<Url, SemanticTokens>,
+}
+
+impl SemanticTokensCache {
+    /// Remembers `tokens` as the latest tokens of `uri`, under a new result id.
+    pub fn store(&mut self, uri: Url, tokens: &mut SemanticTokens) {
+        tokens.result_id = Some(self.next_result_id.to_string());
+        self.next_result_id += 1;
+        self.tokens.insert(uri, tokens.clone());
+    }
+
+    /// The tokens of `uri` with the given result id, if they are the latest.
+    pub fn get(&self, uri: &Url, result_id: &str) -> Option<&SemanticTokens> {
+        self.tokens
+            .get(uri)
+            .filter(|it| it.result_id.as_ref().map(String::as_str) == Some(result_id))
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.tokens.remove(uri);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::*;
+
+    #[test]
+    fn modifiers_of_the_analysis_come_first_in_the_legend() {
+        let all = HighlightModifiers::ALL;
+        for (idx, name) in ["mutable", "unsafe", "static", "macro", "library"].iter().enumerate() {
+            assert_eq!(
+                modifiers(all[idx]),
+                1 << TOKEN_MODIFIERS.iter().position(|it| it == name).unwrap()
+            );
+        }
+        assert_eq!(TOKEN_MODIFIERS[5], "readonly");
+        assert_eq!(TOKEN_MODIFIERS[6], "controlFlow");
+    }
+
+    #[test]
+    fn tokens_are_relative_to_each_other() {
+        let range =
+            |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+        let mut builder = SemanticTokensBuilder::default();
+        builder.push(range(1, 4, 6), 2, 0);
+        builder.push(range(1, 8, 11), 4, 1);
+        builder.push(range(3, 2, 3), 7, 0);
+        assert_eq!(builder.build().data, vec![1, 4, 2, 2, 0, 0, 4, 3, 4, 1, 2, 2, 1, 7, 0]);
+    }
+
+    #[test]
+    fn multiline_ranges_are_split() {
+        let text = "/* a\r\n\nbc */";
+        let range = TextRange::offset_len(0.into(), TextUnit::of_str(text));
+        let lines = split_lines(text, range);
+        assert_eq!(
+            lines,
+            vec![TextRange::from_to(0.into(), 4.into()), TextRange::from_to(7.into(), 12.into())]
+        );
+    }
+
+    #[test]
+    fn diff_replaces_the_changed_tokens() {
+        let old = [0, 0, 3, 1, 0, 1, 2, 4, 2, 0, 2, 0, 1, 3, 0];
+        let new = [0, 0, 3, 1, 0, 1, 2, 5, 2, 0, 2, 0, 1, 3, 0];
+        assert_eq!(
+            diff_tokens(&old, &new),
+            vec![SemanticTokensEdit { start: 5, delete_count: 5, data: Some(new[5..10].to_vec()) }]
+        );
+        assert_eq!(
+            diff_tokens(&old, &old[..10]),
+            vec![SemanticTokensEdit { start: 10, delete_count: 5, data: None }]
+        );
+        assert!(diff_tokens(&old, &old).is_empty());
+    }
+}
@@ -2,8 +2,13 @@ use std::path::PathBuf;
 use ra_project_model::ProjectRoot;
 use ra_vfs::{RootEntry, Filter, RelativePath};
 
+use crate::project_model::is_workspace_manifest;
+
 /// `IncludeRustFiles` is used to convert
 /// from `ProjectRoot` to `RootEntry` for VFS
+///
+/// Manifests of the workspace members are included as well, so that we
+/// notice when the workspace changes.
 pub struct IncludeRustFiles {
     root: ProjectRoot,
 }
@@ -37,6 +42,7 @@ impl Filter for IncludeRustFiles {
 
     fn include_file(&self, file_path: &RelativePath) -> bool {
         self.root.include_file(file_path)
+            || (self.root.is_member() && file_path.file_name().map_or(false, is_workspace_manifest))
     }
 }
 
@@ -1,21 +1,33 @@
 use std::{
+    collections::VecDeque,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use lsp_types::Url;
+use lsp_types::{Position, TextDocumentContentChangeEvent, Url};
 use ra_ide_api::{
-    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, LibraryData,
-    SourceRootId
+    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, LibraryData, LineCol, LineIndex,
+    SourceRootId,
 };
-use ra_vfs::{Vfs, VfsChange, VfsFile, VfsRoot};
+use ra_syntax::{TextRange, TextUnit};
+use ra_text_edit::AtomTextEdit;
+use ra_vfs::{Vfs, VfsChange, VfsFile, VfsRoot, Watch};
 use relative_path::RelativePathBuf;
-use parking_lot::RwLock;
+use rustc_hash::{FxHashMap, FxHashSet};
+use parking_lot::{Mutex, RwLock};
 use gen_lsp_server::ErrorCode;
 
 use crate::{
-    main_loop::pending_requests::{CompletedRequest, LatestRequests},
-    project_model::ProjectWorkspace,
+    cargo_check::CheckDiagnostics,
+    config::{ClientCaps, FileWatcher, ServerConfig},
+    main_loop::{
+        metrics::RequestMetrics,
+        pending_requests::{CompletedRequest, LatestRequests},
+    },
+    semantic_tokens::SemanticTokensCache,
+    project_model::{
+        ProjectWorkspace, ProjectRoot, ProcMacroClient, ProjectStatus, is_workspace_manifest,
+    },
     vfs_filter::IncludeRustFiles,
     Result,
     LspError,
@@ -31,62 +43,490 @@ pub struct WorldState {
     pub roots_to_scan: usize,
     pub roots: Vec<PathBuf>,
     pub workspaces: Arc<Vec<ProjectWorkspace>>,
+    /// What went wrong when the workspaces were loaded.
+    pub project_status: Arc<Vec<ProjectStatus>>,
+    /// The status of the loaded projects, without the errors of the crate
+    /// graph.
+    loaded_status: Vec<ProjectStatus>,
     pub analysis_host: AnalysisHost,
     pub vfs: Arc<RwLock<Vfs>>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    pub request_metrics: Arc<RwLock<RequestMetrics>>,
+    /// The latest semantic tokens of each file, for the delta requests.
+    pub semantic_tokens: Arc<Mutex<SemanticTokensCache>>,
+    /// The configuration sent by the client, which can change at any time.
+    pub config: Arc<ServerConfig>,
+    /// The diagnostics of the latest `cargo check`.
+    pub check_diagnostics: Arc<CheckDiagnostics>,
+    /// Set when a file is saved and checking is enabled: the workspaces
+    /// should be checked again.
+    pub check_requested: bool,
+    /// Set when a `Cargo.toml`, a `Cargo.lock` or a `rust-project.json`
+    /// changes, or when the workspace folders or the Cargo configuration
+    /// change: the workspaces should be loaded again.
+    pub workspaces_outdated: bool,
+    /// Incremented each time the VFS is recreated, which gives new ids to all
+    /// source roots and files.
+    pub vfs_generation: u32,
+    /// Rust files which were opened from outside of all workspaces.
+    pub detached_files: Vec<PathBuf>,
+    /// Set when a detached file is opened or closed: the workspace of the
+    /// detached files should be updated.
+    pub detached_files_changed: bool,
+    /// The text of the open detached files which aren't in the VFS yet.
+    detached_overlays: Vec<(PathBuf, String)>,
+    /// The files open in the editor.
+    open_files: FxHashMap<PathBuf, OpenFile>,
+    /// The open files whose text on disk changed and differs from the text
+    /// in the editor. They are read again when they are closed.
+    changed_on_disk: FxHashSet<PathBuf>,
+    /// The edits of each change of an open file which the VFS hasn't
+    /// reported yet, in order.
+    pending_edits: FxHashMap<FileId, VecDeque<Vec<AtomTextEdit>>>,
+    project_roots: Vec<ProjectRoot>,
+    vfs_roots: Vec<VfsRoot>,
+    /// Whether the VFS watches its roots, rather than the client.
+    vfs_watch: bool,
+    client_caps: ClientCaps,
+    proc_macro_client: ProcMacroClient,
+}
+
+/// A file open in the editor, with the text which the changes made in the
+/// editor are applied to.
+#[derive(Debug)]
+struct OpenFile {
+    text: String,
+    line_index: LineIndex,
+}
+
+impl OpenFile {
+    fn new(text: String) -> OpenFile {
+        let line_index = LineIndex::new(&text);
+        OpenFile { text, line_index }
+    }
+
+    /// The offset of a position in the text. Positions past the end of a line
+    /// or of the text are clamped to it, as the protocol says.
+    fn offset(&self, position: Position) -> TextUnit {
+        let text_len = TextUnit::of_str(&self.text);
+        let last_line = self.line_index.line_col(text_len).line;
+        if position.line > u64::from(last_line) {
+            return text_len;
+        }
+        let line = position.line as u32;
+        let line_end = if line < last_line {
+            self.line_index.offset(LineCol { line: line + 1, col_utf16: 0 })
+                - TextUnit::of_char('\n')
+        } else {
+            text_len
+        };
+        let line_len = self.line_index.line_col(line_end).col_utf16;
+        let col_utf16 = position.character.min(u64::from(line_len)) as u32;
+        // A position inside of a character is moved to its start
+        let mut offset = self.line_index.offset(LineCol { line, col_utf16 }).to_usize();
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        TextUnit::from_usize(offset)
+    }
 }
 
 /// An immutable snapshot of the world's state at a point in time.
 pub struct WorldSnapshot {
     pub workspaces: Arc<Vec<ProjectWorkspace>>,
+    pub project_status: Arc<Vec<ProjectStatus>>,
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    pub request_metrics: Arc<RwLock<RequestMetrics>>,
+    pub semantic_tokens: Arc<Mutex<SemanticTokensCache>>,
+    pub config: Arc<ServerConfig>,
+    pub check_diagnostics: Arc<CheckDiagnostics>,
+    pub client_caps: ClientCaps,
 }
 
 impl WorldState {
     pub fn new(
         folder_roots: Vec<PathBuf>,
         workspaces: Vec<ProjectWorkspace>,
-        lru_capacity: Option<usize>,
+        project_status: Vec<ProjectStatus>,
+        config: ServerConfig,
+        client_caps: ClientCaps,
+        proc_macro_client: ProcMacroClient,
     ) -> WorldState {
+        let project_roots = project_roots(&folder_roots, &workspaces);
+        let vfs_watch = !client_watching(&config, client_caps);
+        let (vfs, vfs_roots) = Vfs::new(
+            IncludeRustFiles::from_roots(project_roots.clone()).collect(),
+            Watch(vfs_watch),
+        );
+
+        let mut world = WorldState {
+            roots_to_scan: 0,
+            roots: folder_roots,
+            workspaces: Arc::new(workspaces),
+            project_status: Arc::default(),
+            loaded_status: Vec::new(),
+            analysis_host: AnalysisHost::new(config.lru_capacity),
+            vfs: Arc::new(RwLock::new(vfs)),
+            latest_requests: Default::default(),
+            request_metrics: Default::default(),
+            semantic_tokens: Default::default(),
+            config: Arc::new(config),
+            check_diagnostics: Default::default(),
+            check_requested: false,
+            workspaces_outdated: false,
+            vfs_generation: 0,
+            detached_files: Vec::new(),
+            detached_files_changed: false,
+            detached_overlays: Vec::new(),
+            open_files: FxHashMap::default(),
+            changed_on_disk: FxHashSet::default(),
+            pending_edits: FxHashMap::default(),
+            project_roots,
+            vfs_roots: Vec::new(),
+            vfs_watch,
+            client_caps,
+            proc_macro_client,
+        };
         let mut change = AnalysisChange::new();
+        world.add_vfs_roots(&mut change, vfs_roots);
+        world.set_crate_graph(&mut change, project_status);
+        world.analysis_host.apply_change(change);
+        world
+    }
 
-        let mut roots = Vec::new();
-        roots.extend(folder_roots.iter().cloned().map(IncludeRustFiles::member));
-        for ws in workspaces.iter() {
-            roots.extend(IncludeRustFiles::from_roots(ws.to_roots()));
+    /// Replaces the workspaces with freshly loaded ones.
+    ///
+    /// If the roots of the new workspaces are inside of the roots of the VFS
+    /// and the watcher stays the same, only the crate graph is updated: the
+    /// roots which are gone are kept until the VFS is recreated. Otherwise,
+    /// the VFS is recreated, which gives new ids to all files: the new ids of
+    /// `open_files` and of the detached files which are now in the VFS are
+    /// returned in this case.
+    pub fn set_workspaces(
+        &mut self,
+        workspaces: Vec<ProjectWorkspace>,
+        project_status: Vec<ProjectStatus>,
+        open_files: Vec<FileId>,
+    ) -> Option<Vec<FileId>> {
+        self.workspaces = Arc::new(workspaces);
+        let mut change = AnalysisChange::new();
+        let project_roots = project_roots(&self.roots, &self.workspaces);
+        let vfs_watch = !self.client_watching();
+        let mut res = None;
+        let roots_loaded =
+            project_roots.iter().all(|root| self.project_roots.iter().any(|it| it.contains(root)));
+        if !roots_loaded || vfs_watch != self.vfs_watch {
+            // The VFS can't add roots, so we start from scratch and move the
+            // overlays of the open files over.
+            let (mut vfs, vfs_roots) = Vfs::new(
+                IncludeRustFiles::from_roots(project_roots.clone()).collect(),
+                Watch(vfs_watch),
+            );
+            let mut new_open_files: Vec<FileId> = {
+                let analysis = self.analysis_host.analysis();
+                let old_vfs = self.vfs.read();
+                open_files
+                    .into_iter()
+                    .filter_map(|file_id| {
+                        let path = old_vfs.file2path(VfsFile(file_id.0));
+                        let text = analysis.file_text(file_id);
+                        vfs.add_file_overlay(&path, text.to_string())
+                    })
+                    .map(|file| FileId(file.0))
+                    .collect()
+            };
+            for (path, text) in self.detached_overlays.drain(..) {
+                match vfs.add_file_overlay(&path, text) {
+                    Some(file) => new_open_files.push(FileId(file.0)),
+                    None => log::error!("detached file is not loaded: {}", path.display()),
+                }
+            }
+            *self.vfs.write() = vfs;
+            self.pending_edits.clear();
+
+            for root in self.vfs_roots.iter().filter(|it| !vfs_roots.contains(it)) {
+                change.remove_root(SourceRootId(root.0));
+            }
+            self.add_vfs_roots(&mut change, vfs_roots);
+            self.project_roots = project_roots;
+            self.vfs_watch = vfs_watch;
+            self.vfs_generation += 1;
+            res = Some(new_open_files);
         }
+        self.set_crate_graph(&mut change, project_status);
+        self.analysis_host.apply_change(change);
+        res
+    }
 
-        let (mut vfs, vfs_roots) = Vfs::new(roots);
-        let roots_to_scan = vfs_roots.len();
-        for r in vfs_roots {
+    /// Updates the workspace of the detached files after some of them were
+    /// opened or closed, like `set_workspaces`. The sysroot and the target
+    /// are taken from a Cargo workspace if there's no workspace of detached
+    /// files yet, and the workspaces are loaded again if there's none either.
+    pub fn update_detached_files(&mut self, open_files: Vec<FileId>) -> Option<Vec<FileId>> {
+        self.detached_files_changed = false;
+        let mut workspaces = (*self.workspaces).clone();
+        let mut project_status = self.loaded_status.clone();
+        let detached = workspaces.iter().position(|ws| match ws {
+            ProjectWorkspace::DetachedFiles { .. } => true,
+            _ => false,
+        });
+        match detached {
+            Some(idx) => {
+                if let ProjectWorkspace::DetachedFiles { files, .. } = &mut workspaces[idx] {
+                    *files = self.detached_files.clone();
+                }
+            }
+            None => {
+                let cargo = workspaces.iter().find_map(|ws| match ws {
+                    ProjectWorkspace::Cargo { sysroot, target, .. } => {
+                        Some((sysroot.clone(), target.clone()))
+                    }
+                    _ => None,
+                });
+                let (sysroot, target) = match cargo {
+                    Some(it) => it,
+                    None => {
+                        self.workspaces_outdated = true;
+                        return None;
+                    }
+                };
+                let files = self.detached_files.clone();
+                workspaces.push(ProjectWorkspace::DetachedFiles { files, sysroot, target });
+                let status =
+                    ProjectStatus { folder: None, loaded: true, packages: 0, errors: Vec::new() };
+                project_status.push(status);
+            }
+        }
+        // The loaded projects are in the same order as the workspaces
+        for (status, ws) in project_status.iter_mut().filter(|it| it.loaded).zip(&workspaces) {
+            status.packages = ws.count();
+        }
+        self.set_workspaces(workspaces, project_status, open_files)
+    }
+
+    /// Switches to a new configuration. The caches are resized right away,
+    /// the workspaces are loaded again if the Cargo configuration changed.
+    ///
+    /// `proc_macro_client` replaces the current client if the proc-macro
+    /// server changed.
+    pub fn update_config(
+        &mut self,
+        config: ServerConfig,
+        proc_macro_client: Option<ProcMacroClient>,
+    ) {
+        if config.lru_capacity != self.config.lru_capacity {
+            self.analysis_host.update_lru_capacity(config.lru_capacity);
+        }
+        // The VFS is recreated with the new watcher when the workspaces are
+        // loaded again
+        if config.cargo != self.config.cargo || config.files != self.config.files {
+            self.workspaces_outdated = true;
+        }
+        if let Some(proc_macro_client) = proc_macro_client {
+            self.proc_macro_client = proc_macro_client;
+        }
+        self.config = Arc::new(config);
+    }
+
+    /// The roots of the Cargo workspaces, which `cargo check` runs in.
+    pub fn check_roots(&self) -> Vec<PathBuf> {
+        self.workspaces
+            .iter()
+            .filter_map(|ws| match ws {
+                ProjectWorkspace::Cargo { cargo, .. } => Some(cargo.workspace_root().to_path_buf()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the client watches the files and reports their changes with
+    /// `workspace/didChangeWatchedFiles`.
+    pub fn client_watching(&self) -> bool {
+        client_watching(&self.config, self.client_caps)
+    }
+
+    /// Handles a change on disk reported by the client. The VFS ignores the
+    /// changes of open files, which are remembered if they differ from the
+    /// text in the editor.
+    pub fn notify_changed(&mut self, path: PathBuf) {
+        if let Some(file) = self.open_files.get(&path) {
+            if std::fs::read_to_string(&path).ok().as_ref() != Some(&file.text) {
+                log::info!("open file changed on disk: {}", path.display());
+                if is_manifest(&path) {
+                    self.workspaces_outdated = true;
+                }
+                self.changed_on_disk.insert(path.clone());
+            } else {
+                self.changed_on_disk.remove(&path);
+            }
+        }
+        self.vfs.write().notify_changed(path);
+    }
+
+    /// The number of roots of the VFS, local roots and libraries.
+    pub fn roots_total(&self) -> usize {
+        self.vfs_roots.len()
+    }
+
+    fn add_vfs_roots(&mut self, change: &mut AnalysisChange, vfs_roots: Vec<VfsRoot>) {
+        let vfs = self.vfs.read();
+        for &r in vfs_roots.iter() {
             let vfs_root_path = vfs.root2path(r);
-            let is_local = folder_roots.iter().any(|it| vfs_root_path.starts_with(it));
-            change.add_root(SourceRootId(r.0), is_local);
+            change.add_root(SourceRootId(r.0), self.is_local(&vfs_root_path));
         }
+        self.roots_to_scan = vfs_roots.len();
+        self.vfs_roots = vfs_roots;
+    }
 
-        // Create crate graph from all the workspaces
-        let mut crate_graph = CrateGraph::default();
-        let mut load = |path: &std::path::Path| {
-            let vfs_file = vfs.load(path);
-            vfs_file.map(|f| FileId(f.0))
+    /// Whether the root at `path` is edited by the user: a workspace folder
+    /// or the directory of a detached file.
+    fn is_local(&self, path: &Path) -> bool {
+        self.roots.iter().any(|it| path.starts_with(it))
+            || self.detached_files.iter().any(|it| it.parent() == Some(path))
+    }
+
+    /// Whether `path` is outside of the roots of the workspaces, the detached
+    /// files aside.
+    fn is_detached(&self, path: &Path) -> bool {
+        if path.extension().map_or(true, |it| it != "rs") {
+            return false;
+        }
+        !self.workspaces.iter().any(|ws| match ws {
+            ProjectWorkspace::DetachedFiles { .. } => false,
+            _ => ws.to_roots().iter().any(|root| path.starts_with(root.path())),
+        })
+    }
+
+    /// Adds the overlay of a file opened in the editor. Files outside of the
+    /// workspaces are analyzed as detached files once the workspace of the
+    /// detached files is updated.
+    pub fn open_file(&mut self, path: PathBuf, text: String) -> Option<FileId> {
+        self.open_files.insert(path.clone(), OpenFile::new(text.clone()));
+        if !self.is_detached(&path) {
+            return self.vfs.write().add_file_overlay(&path, text).map(|it| FileId(it.0));
+        }
+        if !self.detached_files.contains(&path) {
+            self.detached_files.push(path.clone());
+            self.detached_files_changed = true;
+        }
+        let file = self.vfs.write().add_file_overlay(&path, text.clone());
+        if file.is_none() {
+            self.detached_overlays.push((path, text));
+        }
+        file.map(|it| FileId(it.0))
+    }
+
+    /// Applies the changes made in the editor to an open file. Incremental
+    /// changes are passed to the analysis as edits, so that only the edited
+    /// parts of the file are reparsed.
+    pub fn change_file(&mut self, path: &Path, changes: Vec<TextDocumentContentChangeEvent>) {
+        let file = match self.open_files.get_mut(path) {
+            Some(it) => it,
+            None => {
+                log::error!("change of a file which isn't open: {}", path.display());
+                return;
+            }
         };
-        for ws in workspaces.iter() {
-            crate_graph.extend(ws.to_crate_graph(&mut load));
+        let mut edits = Some(Vec::new());
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    // Each change is relative to the text after the previous one
+                    let start = file.offset(range.start);
+                    let end = file.offset(range.end).max(start);
+                    let edit = AtomTextEdit::replace(TextRange::from_to(start, end), change.text);
+                    file.text.replace_range(start.to_usize()..end.to_usize(), &edit.insert);
+                    file.line_index.apply_edit(&file.text, &edit);
+                    if let Some(edits) = &mut edits {
+                        edits.push(edit);
+                    }
+                }
+                None => {
+                    *file = OpenFile::new(change.text);
+                    edits = None;
+                }
+            }
         }
-        change.set_crate_graph(crate_graph);
+        let text = file.text.clone();
+        if let Some((_, overlay)) = self.detached_overlays.iter_mut().find(|(it, _)| it == path) {
+            *overlay = text;
+            return;
+        }
+        let mut vfs = self.vfs.write();
+        vfs.change_file_overlay(path, text);
+        if let (Some(file), Some(edits)) = (vfs.path2file(path), edits) {
+            self.pending_edits.entry(FileId(file.0)).or_default().push_back(edits);
+        }
+    }
 
-        let mut analysis_host = AnalysisHost::new(lru_capacity);
-        analysis_host.apply_change(change);
-        WorldState {
-            roots_to_scan,
-            roots: folder_roots,
-            workspaces: Arc::new(workspaces),
-            analysis_host,
-            vfs: Arc::new(RwLock::new(vfs)),
-            latest_requests: Default::default(),
+    /// The text on disk and the text in the editor are the same once the
+    /// file is saved. The workspaces are loaded again when a manifest is
+    /// saved, rather than on each change made in the editor.
+    pub fn save_file(&mut self, path: &Path) {
+        self.changed_on_disk.remove(path);
+        if is_manifest(path) {
+            self.workspaces_outdated = true;
+        }
+    }
+
+    /// Removes the overlay of a file closed in the editor. A file which
+    /// changed on disk while it was open is read again, a detached file is
+    /// not analyzed anymore.
+    pub fn close_file(&mut self, path: &Path) -> Option<FileId> {
+        self.open_files.remove(path);
+        self.detached_overlays.retain(|(it, _)| it != path);
+        if self.detached_files.iter().any(|it| it == path) {
+            self.detached_files.retain(|it| it != path);
+            self.detached_files_changed = true;
+        }
+        let mut vfs = self.vfs.write();
+        let file = vfs.remove_file_overlay(path).map(|it| FileId(it.0));
+        if self.changed_on_disk.remove(path) {
+            vfs.notify_changed(path.to_path_buf());
         }
+        // Applying a change waits for the requests in flight, which might
+        // read the VFS
+        drop(vfs);
+        if let Some(file) = file {
+            self.pending_edits.remove(&file);
+            let mut change = AnalysisChange::new();
+            change.discard_edits(file);
+            self.analysis_host.apply_change(change);
+        }
+        file
+    }
+
+    /// Creates crate graph from all the workspaces, and adds its errors to
+    /// the status of the projects.
+    fn set_crate_graph(
+        &mut self,
+        change: &mut AnalysisChange,
+        mut project_status: Vec<ProjectStatus>,
+    ) {
+        self.loaded_status = project_status.clone();
+        let mut crate_graph = CrateGraph::default();
+        let mut errors = Vec::new();
+        {
+            let mut vfs = self.vfs.write();
+            let mut load = |path: &Path| {
+                let vfs_file = vfs.load(path);
+                vfs_file.map(|f| FileId(f.0))
+            };
+            for ws in self.workspaces.iter() {
+                let (ws_crate_graph, ws_errors) =
+                    ws.to_crate_graph(&self.proc_macro_client, &mut load);
+                crate_graph.extend(ws_crate_graph);
+                errors.push(ws_errors);
+            }
+        }
+        // The loaded projects are in the same order as the workspaces
+        for (status, errors) in project_status.iter_mut().filter(|it| it.loaded).zip(errors) {
+            status.errors.extend(errors);
+        }
+        change.set_crate_graph(crate_graph);
+        self.project_status = Arc::new(project_status);
     }
 
     /// Returns a vec of libraries
@@ -104,7 +544,9 @@ impl WorldState {
             match c {
                 VfsChange::AddRoot { root, files } => {
                     let root_path = self.vfs.read().root2path(root);
-                    let is_local = self.roots.iter().any(|r| root_path.starts_with(r));
+                    let is_local = self.is_local(&root_path);
+                    // Manifests are watched, but they are not Rust files
+                    let files = files.into_iter().filter(|(_, path, _)| !is_manifest_path(path));
                     if is_local {
                         self.roots_to_scan -= 1;
                         for (file, path, text) in files {
@@ -112,12 +554,16 @@ impl WorldState {
                         }
                     } else {
                         let files = files
-                            .into_iter()
                             .map(|(vfsfile, path, text)| (FileId(vfsfile.0), path, text))
                             .collect();
                         libs.push((SourceRootId(root.0), files));
                     }
                 }
+                VfsChange::AddFile { ref path, .. } | VfsChange::RemoveFile { ref path, .. }
+                    if is_manifest_path(path) =>
+                {
+                    self.workspaces_outdated = true;
+                }
                 VfsChange::AddFile { root, file, path, text } => {
                     change.add_file(SourceRootId(root.0), FileId(file.0), path, text);
                 }
@@ -125,10 +571,25 @@ impl WorldState {
                     change.remove_file(SourceRootId(root.0), FileId(file.0), path)
                 }
                 VfsChange::ChangeFile { file, text } => {
-                    change.change_file(FileId(file.0), text);
+                    let path = self.vfs.read().file2path(file);
+                    if is_manifest(&path) {
+                        // Open manifests are loaded again when they are saved
+                        if !self.open_files.contains_key(&path) {
+                            self.workspaces_outdated = true;
+                        }
+                        continue;
+                    }
+                    let file_id = FileId(file.0);
+                    match self.pending_edits.get_mut(&file_id).and_then(|it| it.pop_front()) {
+                        Some(edits) => change.change_file_with_edits(file_id, text, edits),
+                        None => change.change_file(file_id, text),
+                    }
                 }
             }
         }
+        // All changes of the overlays are committed, the remaining edits
+        // don't belong to any of them.
+        self.pending_edits.clear();
         self.analysis_host.apply_change(change);
         libs
     }
@@ -143,9 +604,15 @@ impl WorldState {
     pub fn snapshot(&self) -> WorldSnapshot {
         WorldSnapshot {
             workspaces: Arc::clone(&self.workspaces),
+            project_status: Arc::clone(&self.project_status),
             analysis: self.analysis_host.analysis(),
             vfs: Arc::clone(&self.vfs),
             latest_requests: Arc::clone(&self.latest_requests),
+            request_metrics: Arc::clone(&self.request_metrics),
+            semantic_tokens: Arc::clone(&self.semantic_tokens),
+            config: Arc::clone(&self.config),
+            check_diagnostics: Arc::clone(&self.check_diagnostics),
+            client_caps: self.client_caps,
         }
     }
 
@@ -158,6 +625,7 @@ impl WorldState {
     }
 
     pub fn complete_request(&mut self, request: CompletedRequest) {
+        self.request_metrics.write().record(&request);
         self.latest_requests.write().record(request)
     }
 }
@@ -214,3 +682,149 @@ impl WorldSnapshot {
         self.workspaces.iter().find_map(|ws| ws.workspace_root_for(&path))
     }
 }
+
+/// Whether the changes on disk are reported by the client rather than by the
+/// watcher of the VFS: only if the user asked for it and the client can.
+fn client_watching(config: &ServerConfig, client_caps: ClientCaps) -> bool {
+    config.files.watcher == FileWatcher::Client && client_caps.watched_files_registration
+}
+
+/// The roots to watch: the workspace folders and the packages of the
+/// workspaces.
+fn project_roots(folder_roots: &[PathBuf], workspaces: &[ProjectWorkspace]) -> Vec<ProjectRoot> {
+    let mut roots = Vec::new();
+    roots.extend(folder_roots.iter().map(|it| ProjectRoot::new(it.clone(), true)));
+    for ws in workspaces.iter() {
+        roots.extend(ws.to_roots());
+    }
+    roots
+}
+
+fn is_manifest_path(path: &RelativePathBuf) -> bool {
+    path.file_name().map_or(false, is_workspace_manifest)
+}
+
+fn is_manifest(path: &Path) -> bool {
+    path.file_name().and_then(|it| it.to_str()).map_or(false, is_workspace_manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Range;
+    use ra_project_model::Sysroot;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A world with a single workspace folder, whose files are loaded.
+    fn world(folder: &Path, workspaces: Vec<ProjectWorkspace>) -> WorldState {
+        let config = ServerConfig::default();
+        let mut world = WorldState::new(
+            vec![folder.to_path_buf()],
+            workspaces,
+            Vec::new(),
+            config,
+            ClientCaps::default(),
+            ProcMacroClient::dummy(),
+        );
+        while world.roots_to_scan > 0 {
+            let task = world.vfs.read().task_receiver().recv().unwrap();
+            world.vfs.write().handle_task(task);
+            world.process_changes();
+        }
+        world
+    }
+
+    fn json_workspace(root: &Path) -> ProjectWorkspace {
+        let project = serde_json::json!({ "roots": [root], "crates": [] });
+        ProjectWorkspace::Json { project: serde_json::from_value(project).unwrap(), sysroot: None }
+    }
+
+    #[test]
+    fn open_manifests_are_reloaded_when_saved() {
+        let dir = TempDir::new().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest, "[package]\n").unwrap();
+        let mut world = world(dir.path(), Vec::new());
+
+        world.open_file(manifest.clone(), "[package]\n".to_string());
+        let change = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "[package]\nname = \"foo\"\n".to_string(),
+        };
+        world.change_file(&manifest, vec![change]);
+        world.process_changes();
+        assert!(!world.workspaces_outdated);
+
+        world.save_file(&manifest);
+        assert!(world.workspaces_outdated);
+    }
+
+    #[test]
+    fn changes_past_the_end_are_clamped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "").unwrap();
+        let mut world = world(dir.path(), Vec::new());
+        let change = |start: (u64, u64), end: (u64, u64), text: &str| {
+            let range = Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1));
+            TextDocumentContentChangeEvent {
+                range: Some(range),
+                range_length: None,
+                text: text.to_string(),
+            }
+        };
+
+        world.open_file(path.clone(), "fn foo() {}\n".to_string());
+        world.change_file(
+            &path,
+            vec![change((0, 20), (5, 0), " // foo"), change((0, 6), (0, 6), "o")],
+        );
+        assert_eq!(world.open_files[&path].text, "fn fooo() {} // foo");
+
+        let other = dir.path().join("other.rs");
+        world.change_file(&other, vec![change((0, 0), (0, 0), "fn bar() {}")]);
+        assert!(!world.open_files.contains_key(&other));
+    }
+
+    #[test]
+    fn vfs_is_recreated_only_for_new_roots() {
+        let dir = TempDir::new().unwrap();
+        let mut world = world(dir.path(), Vec::new());
+
+        let nested = json_workspace(&dir.path().join("nested"));
+        assert_eq!(world.set_workspaces(vec![nested], Vec::new(), Vec::new()), None);
+        assert_eq!(world.vfs_generation, 0);
+
+        let other_dir = TempDir::new().unwrap();
+        let other = json_workspace(other_dir.path());
+        assert_eq!(world.set_workspaces(vec![other], Vec::new(), Vec::new()), Some(Vec::new()));
+        assert_eq!(world.vfs_generation, 1);
+    }
+
+    #[test]
+    fn detached_files_are_added_without_reloading() {
+        let dir = TempDir::new().unwrap();
+        let detached_dir = TempDir::new().unwrap();
+        let file = detached_dir.path().join("script.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+        let sysroot = Sysroot::default();
+        let detached = ProjectWorkspace::DetachedFiles { files: Vec::new(), sysroot, target: None };
+        let mut world = world(dir.path(), vec![detached]);
+
+        assert_eq!(world.open_file(file.clone(), "fn main() {}".to_string()), None);
+        assert!(world.detached_files_changed);
+        let open_files = world.update_detached_files(Vec::new()).unwrap();
+        assert_eq!(open_files.len(), 1);
+        assert!(!world.workspaces_outdated);
+        match &world.workspaces[0] {
+            ProjectWorkspace::DetachedFiles { files, .. } => assert_eq!(files, &vec![file.clone()]),
+            ws => panic!("unexpected workspace: {:?}", ws),
+        }
+
+        world.close_file(&file);
+        assert!(world.detached_files.is_empty());
+        assert!(world.detached_files_changed);
+    }
+}
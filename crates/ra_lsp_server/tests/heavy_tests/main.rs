@@ -6,11 +6,17 @@ use std::{
 };
 
 use lsp_types::{
-    CodeActionContext, DocumentFormattingParams, FormattingOptions, Position, Range, DidOpenTextDocumentParams, TextDocumentItem, TextDocumentPositionParams
+    CodeActionContext, DocumentFormattingParams, FormattingOptions, Position, Range, DidOpenTextDocumentParams, TextDocumentItem, TextDocumentPositionParams,
+    DidSaveTextDocumentParams,
 };
-use ra_lsp_server::req::{
-    CodeActionParams, CodeActionRequest, Formatting, Runnables, RunnablesParams, CompletionParams, Completion,
-    DidOpenTextDocument, OnEnter,
+use ra_lsp_server::{
+    config::CheckConfig,
+    req::{
+        CodeActionParams, CodeActionRequest, Formatting, Runnables, RunnablesParams, CompletionParams, Completion,
+        DidOpenTextDocument, OnEnter, DidSaveTextDocument, PublishDiagnostics, LoadProgress,
+        WorkDoneProgress, SemanticTokensRequest, SemanticTokensParams,
+    },
+    ServerConfig,
 };
 use serde_json::json;
 use tempfile::TempDir;
@@ -261,6 +267,7 @@ fn main() {}
               "command": "rust-analyzer.applySourceChange",
               "title": "create module"
             },
+            "kind": "quickfix",
             "title": "create module"
           }
         ]),
@@ -330,6 +337,7 @@ fn main() {{}}
               "command": "rust-analyzer.applySourceChange",
               "title": "create module"
             },
+            "kind": "quickfix",
             "title": "create module"
           }
         ]),
@@ -409,3 +417,102 @@ fn main() {{}}
     let elapsed = start.elapsed();
     assert!(elapsed.as_millis() < 2000, "typing enter took {:?}", elapsed);
 }
+
+#[test]
+fn check_diagnostics_are_published_with_fixes() {
+    // `cargo test` builds the examples next to the test binaries
+    let checker = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .join("examples")
+        .join(format!("fake_checker{}", std::env::consts::EXE_SUFFIX));
+    let config = ServerConfig {
+        check: CheckConfig { enable: true, command: vec![checker.display().to_string()] },
+        ..ServerConfig::default()
+    };
+    let server = Project::with_fixture(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {
+    let x = 92;
+}
+"#,
+    )
+    .config(config)
+    .server();
+    server.wait_until_workspace_is_loaded();
+    server.notification::<DidSaveTextDocument>(DidSaveTextDocumentParams {
+        text_document: server.doc_id("src/lib.rs"),
+    });
+    server.wait_for_notification::<PublishDiagnostics>(&|params| {
+        params.diagnostics.iter().any(|it| it.source.as_ref().map(String::as_str) == Some("rustc"))
+    });
+
+    let actions = server.send_request::<CodeActionRequest>(CodeActionParams {
+        text_document: server.doc_id("src/lib.rs"),
+        range: Range::new(Position::new(1, 8), Position::new(1, 9)),
+        context: CodeActionContext { diagnostics: Vec::new(), only: None },
+    });
+    let fix = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|it| it["kind"] == "quickfix")
+        .expect("no quick fix");
+    assert_eq!(fix["title"], "consider prefixing with an underscore");
+    let edits = fix["edit"]["changes"].as_object().unwrap().values().next().unwrap();
+    assert_eq!(edits[0]["newText"], "_x");
+    assert_eq!(edits[0]["range"]["start"], json!({ "line": 1, "character": 8 }));
+}
+
+#[test]
+fn workspace_loading_progress_is_reported() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {}
+"#,
+    );
+    server.wait_for_notification::<LoadProgress>(&|params| match &params.value {
+        WorkDoneProgress::Begin { title, .. } => title == "Loading workspace",
+        _ => false,
+    });
+    server.wait_for_notification::<LoadProgress>(&|params| match params.value {
+        WorkDoneProgress::End { .. } => true,
+        _ => false,
+    });
+    server.wait_until_workspace_is_loaded();
+}
+
+#[test]
+fn semantic_tokens_are_encoded_relative_to_each_other() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn main() {}
+"#,
+    );
+    server.wait_until_workspace_is_loaded();
+    // `fn` is a keyword and `main` a function
+    server.request::<SemanticTokensRequest>(
+        SemanticTokensParams { text_document: server.doc_id("src/lib.rs") },
+        json!({ "resultId": "0", "data": [0, 0, 2, 2, 0, 0, 3, 4, 4, 0] }),
+    );
+}
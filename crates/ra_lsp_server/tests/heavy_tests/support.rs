@@ -15,7 +15,7 @@ use lsp_types::{
     DidOpenTextDocumentParams, TextDocumentIdentifier, TextDocumentItem, Url,
     notification::{Notification, ShowMessage},
 };
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{to_string_pretty, Value};
 use tempfile::TempDir;
 use thread_worker::Worker;
@@ -23,18 +23,19 @@ use test_utils::{parse_fixture, find_mismatch};
 
 use ra_lsp_server::{
     main_loop, req,
-    InitializationOptions,
+    ClientCaps, InitializationOptions, ServerConfig,
 };
 
 pub struct Project<'a> {
     fixture: &'a str,
     tmp_dir: Option<TempDir>,
     roots: Vec<PathBuf>,
+    config: ServerConfig,
 }
 
 impl<'a> Project<'a> {
     pub fn with_fixture(fixture: &str) -> Project {
-        Project { fixture, tmp_dir: None, roots: vec![] }
+        Project { fixture, tmp_dir: None, roots: vec![], config: ServerConfig::default() }
     }
 
     pub fn tmp_dir(mut self, tmp_dir: TempDir) -> Project<'a> {
@@ -47,6 +48,11 @@ impl<'a> Project<'a> {
         self
     }
 
+    pub fn config(mut self, config: ServerConfig) -> Project<'a> {
+        self.config = config;
+        self
+    }
+
     pub fn server(self) -> Server {
         let tmp_dir = self.tmp_dir.unwrap_or_else(|| TempDir::new().unwrap());
         static INIT: Once = Once::new();
@@ -70,7 +76,7 @@ impl<'a> Project<'a> {
 
         let roots = self.roots.into_iter().map(|root| tmp_dir.path().join(root)).collect();
 
-        Server::new(tmp_dir, roots, paths)
+        Server::new(tmp_dir, self.config, roots, paths)
     }
 }
 
@@ -86,8 +92,14 @@ pub struct Server {
 }
 
 impl Server {
-    fn new(dir: TempDir, roots: Vec<PathBuf>, files: Vec<(PathBuf, String)>) -> Server {
+    fn new(
+        dir: TempDir,
+        config: ServerConfig,
+        roots: Vec<PathBuf>,
+        files: Vec<(PathBuf, String)>,
+    ) -> Server {
         let path = dir.path().to_path_buf();
+        let options = InitializationOptions { config, ..InitializationOptions::default() };
 
         let roots = if roots.is_empty() { vec![path] } else { roots };
 
@@ -95,7 +107,7 @@ impl Server {
             "test server",
             128,
             move |msg_receiver, msg_sender| {
-                main_loop(roots, InitializationOptions::default(), &msg_receiver, &msg_sender)
+                main_loop(roots, options, ClientCaps::default(), &msg_receiver, &msg_sender)
                     .unwrap()
             },
         );
@@ -185,6 +197,21 @@ impl Server {
             _ => false,
         })
     }
+    /// Waits until the server sends a notification of type `N` whose
+    /// parameters satisfy `cond`.
+    pub fn wait_for_notification<N>(&self, cond: &dyn Fn(&N::Params) -> bool)
+    where
+        N: Notification,
+        N::Params: DeserializeOwned,
+    {
+        self.wait_for_message_cond(1, &|msg: &RawMessage| match msg {
+            RawMessage::Notification(n) if n.method == N::METHOD => {
+                let params = n.clone().cast::<N>().unwrap();
+                cond(&params)
+            }
+            _ => false,
+        })
+    }
     fn wait_for_message_cond(&self, n: usize, cond: &dyn Fn(&RawMessage) -> bool) {
         let mut total = 0;
         for msg in self.messages.borrow().iter() {
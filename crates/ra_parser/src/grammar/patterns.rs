@@ -40,15 +40,6 @@ pub(super) fn pattern_r(p: &mut Parser, recovery_set: TokenSet) {
             atom_pat(p, recovery_set);
             m.complete(p, RANGE_PAT);
         }
-        // test marco_pat
-        // fn main() {
-        //     let m!(x) = 0;
-        // }
-        else if lhs.kind() == PATH_PAT && p.at(T![!]) {
-            let m = lhs.precede(p);
-            items::macro_call_after_excl(p);
-            m.complete(p, MACRO_CALL);
-        }
     }
 }
 
@@ -130,6 +121,14 @@ fn path_pat(p: &mut Parser) -> CompletedMarker {
             field_pat_list(p);
             STRUCT_PAT
         }
+        // test marco_pat
+        // fn main() {
+        //     let m!(x) = 0;
+        // }
+        T![!] => {
+            items::macro_call_after_excl(p);
+            return m.complete(p, MACRO_CALL).precede(p).complete(p, MACRO_PAT);
+        }
         _ => PATH_PAT,
     };
     m.complete(p, kind)
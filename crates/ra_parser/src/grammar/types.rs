@@ -255,15 +255,13 @@ fn path_or_macro_type_(p: &mut Parser, allow_bounds: bool) {
     let m = p.start();
     paths::type_path(p);
 
-    let kind = if p.at(T![!]) {
+    let path = if p.at(T![!]) {
         items::macro_call_after_excl(p);
-        MACRO_CALL
+        m.complete(p, MACRO_CALL).precede(p).complete(p, MACRO_TYPE)
     } else {
-        PATH_TYPE
+        m.complete(p, PATH_TYPE)
     };
 
-    let path = m.complete(p, kind);
-
     if allow_bounds {
         opt_path_type_bounds_as_dyn_trait_type(p, path);
     }
@@ -153,6 +153,7 @@ pub enum SyntaxKind {
     FOR_TYPE,
     IMPL_TRAIT_TYPE,
     DYN_TRAIT_TYPE,
+    MACRO_TYPE,
     REF_PAT,
     BIND_PAT,
     PLACEHOLDER_PAT,
@@ -165,6 +166,7 @@ pub enum SyntaxKind {
     SLICE_PAT,
     RANGE_PAT,
     LITERAL_PAT,
+    MACRO_PAT,
     TUPLE_EXPR,
     ARRAY_EXPR,
     PAREN_EXPR,
@@ -620,6 +622,7 @@ impl SyntaxKind {
             FOR_TYPE => &SyntaxInfo { name: "FOR_TYPE" },
             IMPL_TRAIT_TYPE => &SyntaxInfo { name: "IMPL_TRAIT_TYPE" },
             DYN_TRAIT_TYPE => &SyntaxInfo { name: "DYN_TRAIT_TYPE" },
+            MACRO_TYPE => &SyntaxInfo { name: "MACRO_TYPE" },
             REF_PAT => &SyntaxInfo { name: "REF_PAT" },
             BIND_PAT => &SyntaxInfo { name: "BIND_PAT" },
             PLACEHOLDER_PAT => &SyntaxInfo { name: "PLACEHOLDER_PAT" },
@@ -632,6 +635,7 @@ impl SyntaxKind {
             SLICE_PAT => &SyntaxInfo { name: "SLICE_PAT" },
             RANGE_PAT => &SyntaxInfo { name: "RANGE_PAT" },
             LITERAL_PAT => &SyntaxInfo { name: "LITERAL_PAT" },
+            MACRO_PAT => &SyntaxInfo { name: "MACRO_PAT" },
             TUPLE_EXPR => &SyntaxInfo { name: "TUPLE_EXPR" },
             ARRAY_EXPR => &SyntaxInfo { name: "ARRAY_EXPR" },
             PAREN_EXPR => &SyntaxInfo { name: "PAREN_EXPR" },
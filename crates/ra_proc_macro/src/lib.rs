@@ -0,0 +1,101 @@
+//! Client side of procedural macro expansion.
+//!
+//! Procedural macros are compiled into dynamic libraries. Loading those into
+//! the analyzer itself is a bad idea: a library can crash or loop forever, and
+//! it has to be built by the same compiler as the code which loads it. So we
+//! load them in a separate process, the proc-macro server, and exchange token
+//! trees with it over stdio. See the `msg` module for the protocol, and the
+//! `ra_proc_macro_srv` crate for the server.
+
+pub mod msg;
+mod process;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ra_db::{ProcMacro, ProcMacroExpander, ProcMacroKind};
+
+use crate::{msg::ExpansionTask, process::ProcMacroProcessSrv};
+
+#[derive(Debug, Clone)]
+pub struct ProcMacroClient {
+    kind: ProcMacroClientKind,
+}
+
+#[derive(Debug, Clone)]
+enum ProcMacroClientKind {
+    Process(Arc<ProcMacroProcessSrv>),
+    /// Used when no proc-macro server is configured: all proc-macro crates
+    /// export no macros.
+    Dummy,
+}
+
+impl ProcMacroClient {
+    /// Spawns the proc-macro server at `server_path`.
+    pub fn extern_process(server_path: &Path) -> io::Result<ProcMacroClient> {
+        let process = ProcMacroProcessSrv::run(server_path)?;
+        Ok(ProcMacroClient { kind: ProcMacroClientKind::Process(Arc::new(process)) })
+    }
+
+    pub fn dummy() -> ProcMacroClient {
+        ProcMacroClient { kind: ProcMacroClientKind::Dummy }
+    }
+
+    /// Returns the procedural macros exported by the compiled proc-macro
+    /// library at `dylib_path`.
+    pub fn by_dylib_path(&self, dylib_path: &Path) -> Vec<ProcMacro> {
+        let process = match &self.kind {
+            ProcMacroClientKind::Process(it) => it,
+            ProcMacroClientKind::Dummy => return Vec::new(),
+        };
+        let macros = match process.find_proc_macros(dylib_path) {
+            Ok(it) => it,
+            Err(err) => {
+                log::error!("failed to load proc macros from {}: {}", dylib_path.display(), err);
+                return Vec::new();
+            }
+        };
+        macros
+            .into_iter()
+            .map(|(name, kind)| {
+                let kind = match kind {
+                    msg::ProcMacroKind::CustomDerive => ProcMacroKind::CustomDerive,
+                    msg::ProcMacroKind::FuncLike => ProcMacroKind::FuncLike,
+                    msg::ProcMacroKind::Attr => ProcMacroKind::Attr,
+                };
+                let expander = Arc::new(ProcMacroProcessExpander {
+                    process: process.clone(),
+                    dylib_path: dylib_path.to_path_buf(),
+                    name: name.clone(),
+                });
+                ProcMacro { name: name.into(), kind, expander }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct ProcMacroProcessExpander {
+    process: Arc<ProcMacroProcessSrv>,
+    dylib_path: PathBuf,
+    name: String,
+}
+
+impl ProcMacroExpander for ProcMacroProcessExpander {
+    fn expand(
+        &self,
+        subtree: &tt::Subtree,
+        attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, String> {
+        let task = ExpansionTask {
+            macro_body: subtree.clone(),
+            macro_name: self.name.clone(),
+            attributes: attrs.cloned(),
+            lib: self.dylib_path.clone(),
+        };
+        self.process.expand(task)
+    }
+}
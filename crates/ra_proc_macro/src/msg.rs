@@ -0,0 +1,154 @@
+//! The protocol between the analyzer and the proc-macro server.
+//!
+//! Messages are JSON objects, one per line. The server reads `Request`s from
+//! its stdin and answers each of them with exactly one `Response` on its
+//! stdout, in order.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tt::Subtree;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Request {
+    ListMacros(ListMacrosTask),
+    ExpandMacro(ExpansionTask),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Response {
+    Error(ResponseError),
+    ListMacros(ListMacrosResult),
+    ExpandMacro(ExpansionResult),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResponseError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The request could not be handled, e.g. the library failed to load.
+    ServerError,
+    /// The macro itself failed (panicked) during expansion.
+    ExpansionError,
+}
+
+/// Asks for the procedural macros exported by the proc-macro library `lib`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListMacrosTask {
+    pub lib: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ProcMacroKind {
+    CustomDerive,
+    FuncLike,
+    Attr,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ListMacrosResult {
+    pub macros: Vec<(String, ProcMacroKind)>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpansionTask {
+    /// The input of the macro: the argument of a function-like macro, or the
+    /// item a derive or an attribute is applied to.
+    pub macro_body: Subtree,
+    pub macro_name: String,
+    /// The arguments of an attribute macro, `None` for other kinds of macros.
+    pub attributes: Option<Subtree>,
+    /// The proc-macro library which exports the macro.
+    pub lib: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpansionResult {
+    pub expansion: Subtree,
+}
+
+pub trait Message: Serialize + DeserializeOwned {
+    fn read(inp: &mut impl BufRead) -> io::Result<Option<Self>> {
+        Ok(match read_json(inp)? {
+            None => None,
+            Some(text) => Some(serde_json::from_str(&text)?),
+        })
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let text = serde_json::to_string(self)?;
+        write_json(out, &text)
+    }
+}
+
+impl Message for Request {}
+impl Message for Response {}
+
+fn read_json(inp: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut buf = String::new();
+    if inp.read_line(&mut buf)? == 0 {
+        return Ok(None);
+    }
+    // `serde_json` escapes newlines inside strings, so a message never spans
+    // several lines.
+    let len = buf.trim_end_matches(|c| c == '\n' || c == '\r').len();
+    buf.truncate(len);
+    log::debug!("< {}", buf);
+    Ok(Some(buf))
+}
+
+fn write_json(out: &mut impl Write, msg: &str) -> io::Result<()> {
+    log::debug!("> {}", msg);
+    out.write_all(msg.as_bytes())?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use tt::{Delimiter, Ident, Leaf, Punct, Spacing, TokenId, TokenTree};
+
+    use super::*;
+
+    #[test]
+    fn request_roundtrips_through_a_single_line() {
+        let macro_body = Subtree {
+            delimiter: Delimiter::Brace,
+            token_trees: vec![
+                TokenTree::Leaf(Leaf::Ident(Ident { text: "struct".into(), id: TokenId(0) })),
+                TokenTree::Leaf(Leaf::Ident(Ident { text: "Foo".into(), id: TokenId(1) })),
+                TokenTree::Leaf(Leaf::Punct(Punct { char: ';', spacing: Spacing::Alone })),
+            ],
+        };
+        let request = Request::ExpandMacro(ExpansionTask {
+            macro_body: macro_body.clone(),
+            macro_name: "Serialize".to_string(),
+            attributes: None,
+            lib: PathBuf::from("/tmp/libserde_derive.so"),
+        });
+
+        let mut buf = Vec::new();
+        request.write(&mut buf).unwrap();
+        request.write(&mut buf).unwrap();
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 2);
+
+        let mut inp = &buf[..];
+        for _ in 0..2 {
+            match Request::read(&mut inp).unwrap() {
+                Some(Request::ExpandMacro(task)) => {
+                    assert_eq!(task.macro_body, macro_body);
+                    assert_eq!(task.macro_name, "Serialize");
+                }
+                req => panic!("unexpected request: {:?}", req),
+            }
+        }
+        assert!(Request::read(&mut inp).unwrap().is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! Handles the lifetime of the proc-macro server process.
+
+use std::{
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+
+use crate::msg::{
+    ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask, Message, ProcMacroKind,
+    Request, Response,
+};
+
+/// How long we wait for a response. A macro which loops forever would block
+/// the analysis otherwise, as the requests are not cancellable.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub(crate) struct ProcMacroProcessSrv {
+    path: PathBuf,
+    /// `None` if the server died; it is restarted on the next request.
+    process: Mutex<Option<Process>>,
+}
+
+#[derive(Debug)]
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// The responses read from the stdout of the server, by a thread which
+    /// exits when the server is killed.
+    responses: Receiver<io::Result<Response>>,
+}
+
+impl Process {
+    fn run(path: &Path) -> io::Result<Process> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, responses) = bounded(0);
+        thread::Builder::new()
+            .name("proc-macro server reader".to_string())
+            .spawn(move || read_responses(stdout, sender))?;
+        Ok(Process { child, stdin, responses })
+    }
+
+    fn send_request(&mut self, req: &Request) -> io::Result<Response> {
+        req.write(&mut self.stdin)?;
+        match self.responses.recv_timeout(REQUEST_TIMEOUT) {
+            Ok(res) => res,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "proc-macro server didn't respond in time",
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "proc-macro server closed its stdout",
+            )),
+        }
+    }
+}
+
+fn read_responses(mut stdout: BufReader<ChildStdout>, sender: Sender<io::Result<Response>>) {
+    loop {
+        let res = match Response::read(&mut stdout) {
+            Ok(Some(it)) => Ok(it),
+            Ok(None) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "proc-macro server closed its stdout",
+            )),
+            Err(err) => Err(err),
+        };
+        let is_err = res.is_err();
+        if sender.send(res).is_err() || is_err {
+            break;
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl ProcMacroProcessSrv {
+    pub(crate) fn run(path: &Path) -> io::Result<ProcMacroProcessSrv> {
+        // Start the server eagerly, so that a misconfigured path is reported
+        // right away rather than on the first expansion.
+        let process = Process::run(path)?;
+        Ok(ProcMacroProcessSrv { path: path.to_path_buf(), process: Mutex::new(Some(process)) })
+    }
+
+    pub(crate) fn find_proc_macros(
+        &self,
+        lib: &Path,
+    ) -> Result<Vec<(String, ProcMacroKind)>, String> {
+        let task = ListMacrosTask { lib: lib.to_path_buf() };
+        match self.send_request(Request::ListMacros(task))? {
+            Response::ListMacros(ListMacrosResult { macros }) => Ok(macros),
+            res => Err(format!("unexpected response from proc-macro server: {:?}", res)),
+        }
+    }
+
+    pub(crate) fn expand(&self, task: ExpansionTask) -> Result<tt::Subtree, String> {
+        match self.send_request(Request::ExpandMacro(task))? {
+            Response::ExpandMacro(ExpansionResult { expansion }) => Ok(expansion),
+            res => Err(format!("unexpected response from proc-macro server: {:?}", res)),
+        }
+    }
+
+    fn send_request(&self, req: Request) -> Result<Response, String> {
+        let mut process = self.process.lock().unwrap_or_else(|err| err.into_inner());
+        if process.is_none() {
+            log::info!("restarting proc-macro server {}", self.path.display());
+            *process = Some(
+                Process::run(&self.path)
+                    .map_err(|err| format!("failed to restart proc-macro server: {}", err))?,
+            );
+        }
+        let res = process.as_mut().unwrap().send_request(&req).map_err(|err| {
+            // We can't tell whether the server is in a consistent state
+            // anymore, so we kill it and start afresh next time.
+            *process = None;
+            format!("proc-macro server failed: {}", err)
+        })?;
+        match res {
+            Response::Error(err) => Err(err.message),
+            res => Ok(res),
+        }
+    }
+}
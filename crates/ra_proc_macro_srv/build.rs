@@ -0,0 +1,7 @@
+//! The server uses the unstable bridge of the `proc_macro` crate: it has to,
+//! as it must be built by the same compiler as the macros it loads anyway.
+//! `RUSTC_BOOTSTRAP` allows the nightly features on stable compilers.
+
+fn main() {
+    println!("cargo:rustc-env=RUSTC_BOOTSTRAP=1");
+}
@@ -0,0 +1,108 @@
+//! Loads proc-macro libraries and runs the macros they export.
+
+use std::{fs, io, path::Path};
+
+use goblin::{mach::Mach, Object};
+use libloading::Library;
+use proc_macro::bridge::{client::ProcMacro, server::SameThread};
+use ra_proc_macro::msg::ProcMacroKind;
+
+use crate::rustc_server::{Rustc, TokenStream};
+
+/// rustc exports the macros of a proc-macro library as a static slice, whose
+/// name starts with this and ends with a hash of the crate.
+const DECLS_SYMBOL_PREFIX: &str = "__rustc_proc_macro_decls_";
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn find_decls_symbol(file: &Path) -> io::Result<Option<String>> {
+    let buffer = fs::read(file)?;
+    let names: Vec<String> = match Object::parse(&buffer).map_err(invalid_data)? {
+        Object::Elf(elf) => elf
+            .dynstrtab
+            .to_vec()
+            .map_err(invalid_data)?
+            .into_iter()
+            .map(|it| it.to_string())
+            .collect(),
+        Object::PE(pe) => {
+            pe.exports.iter().filter_map(|it| it.name).map(|it| it.to_string()).collect()
+        }
+        Object::Mach(Mach::Binary(macho)) => {
+            // Mach-O symbols have an additional leading underscore.
+            macho
+                .exports()
+                .map_err(invalid_data)?
+                .into_iter()
+                .map(|it| it.name.trim_start_matches('_').to_string())
+                .collect()
+        }
+        _ => return Ok(None),
+    };
+    Ok(names.into_iter().find(|it| it.starts_with(DECLS_SYMBOL_PREFIX)))
+}
+
+pub(crate) struct Expander {
+    /// Points into the library, so it's declared, and dropped, before it.
+    macros: Vec<ProcMacro>,
+    _lib: Library,
+}
+
+impl Expander {
+    pub(crate) fn load(file: &Path) -> io::Result<Expander> {
+        let symbol = find_decls_symbol(file)?.ok_or_else(|| {
+            invalid_data(format!("{} is not a proc-macro library", file.display()))
+        })?;
+        let lib = Library::new(file)?;
+        let macros = unsafe {
+            let macros: libloading::Symbol<&&[ProcMacro]> = lib.get(symbol.as_bytes())?;
+            macros.to_vec()
+        };
+        Ok(Expander { macros, _lib: lib })
+    }
+
+    pub(crate) fn list_macros(&self) -> Vec<(String, ProcMacroKind)> {
+        self.macros
+            .iter()
+            .map(|it| match it {
+                ProcMacro::CustomDerive { trait_name, .. } => {
+                    (trait_name.to_string(), ProcMacroKind::CustomDerive)
+                }
+                ProcMacro::Bang { name, .. } => (name.to_string(), ProcMacroKind::FuncLike),
+                ProcMacro::Attr { name, .. } => (name.to_string(), ProcMacroKind::Attr),
+            })
+            .collect()
+    }
+
+    pub(crate) fn expand(
+        &self,
+        macro_name: &str,
+        body: &tt::Subtree,
+        attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, String> {
+        let body = TokenStream::with_subtree(body.clone());
+        let res = self.macros.iter().find_map(|it| match *it {
+            ProcMacro::CustomDerive { trait_name, client, .. } if trait_name == macro_name => {
+                Some(client.run(&SameThread, Rustc::default(), body.clone()))
+            }
+            ProcMacro::Bang { name, client } if name == macro_name => {
+                Some(client.run(&SameThread, Rustc::default(), body.clone()))
+            }
+            ProcMacro::Attr { name, client } if name == macro_name => {
+                let attrs =
+                    attrs.cloned().map_or_else(TokenStream::default, TokenStream::with_subtree);
+                Some(client.run(&SameThread, Rustc::default(), attrs, body.clone()))
+            }
+            _ => None,
+        });
+        match res {
+            None => Err(format!("no proc macro named `{}`", macro_name)),
+            Some(Ok(it)) => Ok(it.into_subtree()),
+            Some(Err(panic)) => {
+                Err(panic.as_str().unwrap_or("the proc macro panicked").to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! The proc-macro server: loads compiled proc-macro libraries and runs their
+//! macros on behalf of the analyzer, see `ra_proc_macro` for the client side.
+//!
+//! Macros talk to the compiler through the bridge of the `proc_macro` crate,
+//! `rustc_server` implements the compiler side of it over `tt` token trees.
+//! The bridge is unstable and its ABI changes between compiler versions, so
+//! the server must be built by the same compiler as the libraries it loads.
+
+#![feature(proc_macro_internals, proc_macro_diagnostic, proc_macro_span)]
+
+extern crate proc_macro;
+
+mod dylib;
+mod rustc_server;
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ra_proc_macro::msg::{
+    ErrorCode, ExpansionResult, ExpansionTask, ListMacrosResult, Message, Request, Response,
+    ResponseError,
+};
+
+use crate::dylib::Expander;
+
+/// Serves the requests read from stdin until it's closed.
+pub fn run() -> io::Result<()> {
+    let mut srv = ProcMacroSrv::default();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdin = stdin.lock();
+    let mut stdout = stdout.lock();
+    while let Some(req) = Request::read(&mut stdin)? {
+        srv.handle(req).write(&mut stdout)?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct ProcMacroSrv {
+    /// The loaded libraries, with their modification time: a library rebuilt
+    /// by cargo is loaded again.
+    expanders: HashMap<PathBuf, (SystemTime, Expander)>,
+}
+
+impl ProcMacroSrv {
+    pub fn handle(&mut self, req: Request) -> Response {
+        let res = match req {
+            Request::ListMacros(task) => self
+                .expander(&task.lib)
+                .map(|it| Response::ListMacros(ListMacrosResult { macros: it.list_macros() })),
+            Request::ExpandMacro(task) => self.expand(task).map(Response::ExpandMacro),
+        };
+        res.unwrap_or_else(Response::Error)
+    }
+
+    fn expand(&mut self, task: ExpansionTask) -> Result<ExpansionResult, ResponseError> {
+        let expander = self.expander(&task.lib)?;
+        let expansion = expander
+            .expand(&task.macro_name, &task.macro_body, task.attributes.as_ref())
+            .map_err(|message| ResponseError { code: ErrorCode::ExpansionError, message })?;
+        Ok(ExpansionResult { expansion })
+    }
+
+    fn expander(&mut self, lib: &Path) -> Result<&Expander, ResponseError> {
+        let server_error = |err: io::Error| ResponseError {
+            code: ErrorCode::ServerError,
+            message: format!("failed to load {}: {}", lib.display(), err),
+        };
+        let modified = fs::metadata(lib).and_then(|it| it.modified()).map_err(server_error)?;
+        if let Some((loaded, _)) = self.expanders.get(lib) {
+            if *loaded != modified {
+                // Unload the old library first, the loader could return it
+                // again otherwise.
+                self.expanders.remove(lib);
+            }
+        }
+        let entry = match self.expanders.entry(lib.to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let expander = Expander::load(lib).map_err(server_error)?;
+                entry.insert((modified, expander))
+            }
+        };
+        Ok(&entry.1)
+    }
+}
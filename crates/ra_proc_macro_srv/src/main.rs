@@ -0,0 +1,9 @@
+//! The proc-macro server: serves the requests of `ra_proc_macro` read from
+//! stdin, see `ra_proc_macro::msg` for the protocol.
+
+fn main() {
+    if let Err(err) = ra_proc_macro_srv::run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
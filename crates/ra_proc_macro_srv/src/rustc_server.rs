@@ -0,0 +1,540 @@
+//! The compiler side of the bridge of the `proc_macro` crate: macros call
+//! these methods to build and inspect token streams, which are `tt` token
+//! trees here.
+//!
+//! Token trees of the analyzer keep lifetimes as single idents and `true` and
+//! `false` as literals, while `proc_macro` has a `'` punct followed by an
+//! ident, and idents. Streams are converted at the boundary, in
+//! `TokenStream::with_subtree` and `TokenStream::into_subtree`.
+
+use std::{collections::HashMap, hash::Hash, ops::Bound, vec::IntoIter};
+
+use proc_macro::{
+    bridge::{self, server},
+    Delimiter, Level, LineColumn, Spacing,
+};
+use ra_syntax::{tokenize, SmolStr, SyntaxKind::*};
+
+type Group = tt::Subtree;
+type Literal = tt::Literal;
+type Span = tt::TokenId;
+type TokenTree = bridge::TokenTree<Group, PunctId, IdentId, Literal>;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TokenStream {
+    token_trees: Vec<tt::TokenTree>,
+}
+
+impl TokenStream {
+    pub(crate) fn with_subtree(subtree: tt::Subtree) -> TokenStream {
+        let subtree = from_analyzer(subtree);
+        if subtree.delimiter == tt::Delimiter::None {
+            TokenStream { token_trees: subtree.token_trees }
+        } else {
+            TokenStream { token_trees: vec![subtree.into()] }
+        }
+    }
+
+    pub(crate) fn into_subtree(self) -> tt::Subtree {
+        to_analyzer(tt::Subtree { delimiter: tt::Delimiter::None, token_trees: self.token_trees })
+    }
+}
+
+fn from_analyzer(subtree: tt::Subtree) -> tt::Subtree {
+    let mut token_trees = Vec::with_capacity(subtree.token_trees.len());
+    for tree in subtree.token_trees {
+        match tree {
+            tt::TokenTree::Subtree(it) => token_trees.push(from_analyzer(it).into()),
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) if ident.text.starts_with('\'') => {
+                let quote = tt::Punct { char: '\'', spacing: tt::Spacing::Joint };
+                let name = tt::Ident { text: ident.text[1..].into(), id: ident.id };
+                token_trees.push(tt::Leaf::from(quote).into());
+                token_trees.push(tt::Leaf::from(name).into());
+            }
+            tt::TokenTree::Leaf(tt::Leaf::Literal(lit))
+                if lit.text == "true" || lit.text == "false" =>
+            {
+                let ident = tt::Ident { text: lit.text, id: tt::TokenId::unspecified() };
+                token_trees.push(tt::Leaf::from(ident).into());
+            }
+            tree => token_trees.push(tree),
+        }
+    }
+    tt::Subtree { delimiter: subtree.delimiter, token_trees }
+}
+
+fn to_analyzer(subtree: tt::Subtree) -> tt::Subtree {
+    let mut token_trees: Vec<tt::TokenTree> = Vec::with_capacity(subtree.token_trees.len());
+    let mut after_quote = false;
+    for tree in subtree.token_trees {
+        match tree {
+            tt::TokenTree::Subtree(it) => token_trees.push(to_analyzer(it).into()),
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) if after_quote => {
+                token_trees.pop();
+                let text = format!("'{}", ident.text).into();
+                token_trees.push(tt::Leaf::from(tt::Ident { text, id: ident.id }).into());
+            }
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident))
+                if ident.text == "true" || ident.text == "false" =>
+            {
+                token_trees.push(tt::Leaf::from(tt::Literal { text: ident.text }).into());
+            }
+            tree => token_trees.push(tree),
+        }
+        after_quote = match token_trees.last() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(punct))) => {
+                punct.char == '\'' && punct.spacing == tt::Spacing::Joint
+            }
+            _ => false,
+        };
+    }
+    tt::Subtree { delimiter: subtree.delimiter, token_trees }
+}
+
+/// Lexes `text` into token trees, an unbalanced delimiter is closed at the
+/// end of its group.
+fn parse_stream(text: &str) -> TokenStream {
+    let mut groups: Vec<(tt::Delimiter, Vec<tt::TokenTree>)> =
+        vec![(tt::Delimiter::None, Vec::new())];
+    let tokens = tokenize(text);
+    let mut offset = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        let token_text = &text[offset..offset + token.len.to_usize()];
+        offset += token.len.to_usize();
+        let delimiter = match token.kind {
+            L_PAREN => Some(tt::Delimiter::Parenthesis),
+            L_CURLY => Some(tt::Delimiter::Brace),
+            L_BRACK => Some(tt::Delimiter::Bracket),
+            _ => None,
+        };
+        if let Some(delimiter) = delimiter {
+            groups.push((delimiter, Vec::new()));
+            continue;
+        }
+        if let R_PAREN | R_CURLY | R_BRACK = token.kind {
+            if groups.len() > 1 {
+                let (delimiter, token_trees) = groups.pop().unwrap();
+                groups.last_mut().unwrap().1.push(tt::Subtree { delimiter, token_trees }.into());
+            }
+            continue;
+        }
+        let acc = &mut groups.last_mut().unwrap().1;
+        let ident = |text: &str| {
+            tt::Leaf::from(tt::Ident { text: text.into(), id: tt::TokenId::unspecified() })
+        };
+        match token.kind {
+            WHITESPACE | COMMENT | SHEBANG => (),
+            UNDERSCORE => acc.push(ident(token_text).into()),
+            LIFETIME => {
+                acc.push(
+                    tt::Leaf::from(tt::Punct { char: '\'', spacing: tt::Spacing::Joint }).into(),
+                );
+                acc.push(ident(&token_text[1..]).into());
+            }
+            kind if kind.is_literal() => {
+                acc.push(tt::Leaf::from(tt::Literal { text: token_text.into() }).into())
+            }
+            kind if kind.is_punct() => {
+                let next_is_punct = tokens.get(idx + 1).map_or(false, |next| {
+                    next.kind.is_punct() && !is_delimiter(next.kind) && next.kind != UNDERSCORE
+                });
+                let mut chars = token_text.chars().peekable();
+                while let Some(char) = chars.next() {
+                    let spacing = if chars.peek().is_some() || next_is_punct {
+                        tt::Spacing::Joint
+                    } else {
+                        tt::Spacing::Alone
+                    };
+                    acc.push(tt::Leaf::from(tt::Punct { char, spacing }).into());
+                }
+            }
+            _ => acc.push(ident(token_text).into()),
+        }
+    }
+    while groups.len() > 1 {
+        let (delimiter, token_trees) = groups.pop().unwrap();
+        groups.last_mut().unwrap().1.push(tt::Subtree { delimiter, token_trees }.into());
+    }
+    TokenStream { token_trees: groups.pop().unwrap().1 }
+}
+
+fn is_delimiter(kind: ra_syntax::SyntaxKind) -> bool {
+    match kind {
+        L_PAREN | R_PAREN | L_CURLY | R_CURLY | L_BRACK | R_BRACK => true,
+        _ => false,
+    }
+}
+
+/// `Punct` and `Ident` handles of the bridge must be `Copy`, so they are
+/// indices into an interner.
+struct Interner<T> {
+    ids: HashMap<T, u32>,
+    values: Vec<T>,
+}
+
+impl<T> Default for Interner<T> {
+    fn default() -> Interner<T> {
+        Interner { ids: HashMap::new(), values: Vec::new() }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Interner<T> {
+    fn intern(&mut self, value: T) -> u32 {
+        let values = &mut self.values;
+        *self.ids.entry(value.clone()).or_insert_with(|| {
+            values.push(value);
+            values.len() as u32 - 1
+        })
+    }
+
+    fn get(&self, id: u32) -> &T {
+        &self.values[id as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PunctId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct IdentId(u32);
+
+pub(crate) struct TokenStreamBuilder {
+    acc: TokenStream,
+}
+
+#[derive(Clone)]
+pub(crate) struct TokenStreamIter {
+    trees: IntoIter<tt::TokenTree>,
+}
+
+#[derive(Clone)]
+pub(crate) struct SourceFile;
+
+pub(crate) struct Diagnostic {
+    level: Level,
+    message: String,
+    children: Vec<Diagnostic>,
+}
+
+#[derive(Default)]
+pub(crate) struct Rustc {
+    puncts: Interner<(char, bool)>,
+    idents: Interner<(SmolStr, Span)>,
+}
+
+impl Rustc {
+    fn punct(&mut self, punct: tt::Punct) -> PunctId {
+        PunctId(self.puncts.intern((punct.char, punct.spacing == tt::Spacing::Joint)))
+    }
+
+    fn ident(&mut self, ident: tt::Ident) -> IdentId {
+        IdentId(self.idents.intern((ident.text, ident.id)))
+    }
+}
+
+impl server::Types for Rustc {
+    type TokenStream = TokenStream;
+    type TokenStreamBuilder = TokenStreamBuilder;
+    type TokenStreamIter = TokenStreamIter;
+    type Group = Group;
+    type Punct = PunctId;
+    type Ident = IdentId;
+    type Literal = Literal;
+    type SourceFile = SourceFile;
+    type MultiSpan = Vec<Span>;
+    type Diagnostic = Diagnostic;
+    type Span = Span;
+}
+
+impl server::TokenStream for Rustc {
+    fn new(&mut self) -> TokenStream {
+        TokenStream::default()
+    }
+    fn is_empty(&mut self, stream: &TokenStream) -> bool {
+        stream.token_trees.is_empty()
+    }
+    fn from_str(&mut self, src: &str) -> TokenStream {
+        parse_stream(src)
+    }
+    fn to_string(&mut self, stream: &TokenStream) -> String {
+        let subtree =
+            tt::Subtree { delimiter: tt::Delimiter::None, token_trees: stream.token_trees.clone() };
+        subtree.to_string()
+    }
+    fn from_token_tree(&mut self, tree: TokenTree) -> TokenStream {
+        let tree: tt::TokenTree = match tree {
+            bridge::TokenTree::Group(group) => group.into(),
+            bridge::TokenTree::Punct(id) => {
+                let &(char, joint) = self.puncts.get(id.0);
+                let spacing = if joint { tt::Spacing::Joint } else { tt::Spacing::Alone };
+                tt::Leaf::from(tt::Punct { char, spacing }).into()
+            }
+            bridge::TokenTree::Ident(id) => {
+                let (text, id) = self.idents.get(id.0).clone();
+                tt::Leaf::from(tt::Ident { text, id }).into()
+            }
+            bridge::TokenTree::Literal(lit) => tt::Leaf::from(lit).into(),
+        };
+        TokenStream { token_trees: vec![tree] }
+    }
+    fn into_iter(&mut self, stream: TokenStream) -> TokenStreamIter {
+        TokenStreamIter { trees: stream.token_trees.into_iter() }
+    }
+}
+
+impl server::TokenStreamBuilder for Rustc {
+    fn new(&mut self) -> TokenStreamBuilder {
+        TokenStreamBuilder { acc: TokenStream::default() }
+    }
+    fn push(&mut self, builder: &mut TokenStreamBuilder, stream: TokenStream) {
+        builder.acc.token_trees.extend(stream.token_trees)
+    }
+    fn build(&mut self, builder: TokenStreamBuilder) -> TokenStream {
+        builder.acc
+    }
+}
+
+impl server::TokenStreamIter for Rustc {
+    fn next(&mut self, iter: &mut TokenStreamIter) -> Option<TokenTree> {
+        let tree = match iter.trees.next()? {
+            tt::TokenTree::Subtree(group) => bridge::TokenTree::Group(group),
+            tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) => {
+                bridge::TokenTree::Punct(self.punct(punct))
+            }
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => {
+                bridge::TokenTree::Ident(self.ident(ident))
+            }
+            tt::TokenTree::Leaf(tt::Leaf::Literal(lit)) => bridge::TokenTree::Literal(lit),
+        };
+        Some(tree)
+    }
+}
+
+impl server::Group for Rustc {
+    fn new(&mut self, delimiter: Delimiter, stream: TokenStream) -> Group {
+        let delimiter = match delimiter {
+            Delimiter::Parenthesis => tt::Delimiter::Parenthesis,
+            Delimiter::Brace => tt::Delimiter::Brace,
+            Delimiter::Bracket => tt::Delimiter::Bracket,
+            Delimiter::None => tt::Delimiter::None,
+        };
+        tt::Subtree { delimiter, token_trees: stream.token_trees }
+    }
+    fn delimiter(&mut self, group: &Group) -> Delimiter {
+        match group.delimiter {
+            tt::Delimiter::Parenthesis => Delimiter::Parenthesis,
+            tt::Delimiter::Brace => Delimiter::Brace,
+            tt::Delimiter::Bracket => Delimiter::Bracket,
+            tt::Delimiter::None => Delimiter::None,
+        }
+    }
+    fn stream(&mut self, group: &Group) -> TokenStream {
+        TokenStream { token_trees: group.token_trees.clone() }
+    }
+    // Subtrees have no token ids, so groups have no spans.
+    fn span(&mut self, _group: &Group) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn span_open(&mut self, _group: &Group) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn span_close(&mut self, _group: &Group) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn set_span(&mut self, _group: &mut Group, _span: Span) {}
+}
+
+impl server::Punct for Rustc {
+    fn new(&mut self, ch: char, spacing: Spacing) -> PunctId {
+        let spacing = match spacing {
+            Spacing::Joint => tt::Spacing::Joint,
+            Spacing::Alone => tt::Spacing::Alone,
+        };
+        self.punct(tt::Punct { char: ch, spacing })
+    }
+    fn as_char(&mut self, punct: PunctId) -> char {
+        self.puncts.get(punct.0).0
+    }
+    fn spacing(&mut self, punct: PunctId) -> Spacing {
+        if self.puncts.get(punct.0).1 {
+            Spacing::Joint
+        } else {
+            Spacing::Alone
+        }
+    }
+    fn span(&mut self, _punct: PunctId) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn with_span(&mut self, punct: PunctId, _span: Span) -> PunctId {
+        punct
+    }
+}
+
+impl server::Ident for Rustc {
+    fn new(&mut self, string: &str, span: Span, is_raw: bool) -> IdentId {
+        let text = if is_raw { format!("r#{}", string).into() } else { string.into() };
+        self.ident(tt::Ident { text, id: span })
+    }
+    fn span(&mut self, ident: IdentId) -> Span {
+        self.idents.get(ident.0).1
+    }
+    fn with_span(&mut self, ident: IdentId, span: Span) -> IdentId {
+        let text = self.idents.get(ident.0).0.clone();
+        self.ident(tt::Ident { text, id: span })
+    }
+}
+
+impl server::Literal for Rustc {
+    fn debug(&mut self, literal: &Literal) -> String {
+        format!("{:?}", literal)
+    }
+    fn integer(&mut self, n: &str) -> Literal {
+        Literal { text: n.into() }
+    }
+    fn typed_integer(&mut self, n: &str, kind: &str) -> Literal {
+        Literal { text: format!("{}{}", n, kind).into() }
+    }
+    fn float(&mut self, n: &str) -> Literal {
+        let mut text = n.to_string();
+        if !text.contains('.') {
+            text.push_str(".0");
+        }
+        Literal { text: text.into() }
+    }
+    fn f32(&mut self, n: &str) -> Literal {
+        Literal { text: format!("{}f32", n).into() }
+    }
+    fn f64(&mut self, n: &str) -> Literal {
+        Literal { text: format!("{}f64", n).into() }
+    }
+    fn string(&mut self, string: &str) -> Literal {
+        Literal { text: format!("\"{}\"", string.escape_debug()).into() }
+    }
+    fn character(&mut self, ch: char) -> Literal {
+        Literal { text: format!("'{}'", ch.escape_debug()).into() }
+    }
+    fn byte_string(&mut self, bytes: &[u8]) -> Literal {
+        let escaped: String =
+            bytes.iter().flat_map(|&it| std::ascii::escape_default(it)).map(char::from).collect();
+        Literal { text: format!("b\"{}\"", escaped).into() }
+    }
+    fn span(&mut self, _literal: &Literal) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn set_span(&mut self, _literal: &mut Literal, _span: Span) {}
+    fn subspan(
+        &mut self,
+        _literal: &Literal,
+        _start: Bound<usize>,
+        _end: Bound<usize>,
+    ) -> Option<Span> {
+        None
+    }
+}
+
+impl server::SourceFile for Rustc {
+    fn eq(&mut self, _file1: &SourceFile, _file2: &SourceFile) -> bool {
+        true
+    }
+    fn path(&mut self, _file: &SourceFile) -> String {
+        String::new()
+    }
+    fn is_real(&mut self, _file: &SourceFile) -> bool {
+        false
+    }
+}
+
+impl server::MultiSpan for Rustc {
+    fn new(&mut self) -> Vec<Span> {
+        Vec::new()
+    }
+    fn push(&mut self, spans: &mut Vec<Span>, span: Span) {
+        spans.push(span)
+    }
+}
+
+impl server::Diagnostic for Rustc {
+    fn new(&mut self, level: Level, msg: &str, _spans: Vec<Span>) -> Diagnostic {
+        Diagnostic { level, message: msg.to_string(), children: Vec::new() }
+    }
+    fn sub(&mut self, diag: &mut Diagnostic, level: Level, msg: &str, spans: Vec<Span>) {
+        let child = server::Diagnostic::new(self, level, msg, spans);
+        diag.children.push(child)
+    }
+    // FIXME: send the diagnostics to the analyzer instead.
+    fn emit(&mut self, diag: Diagnostic) {
+        eprintln!("{:?}: {}", diag.level, diag.message);
+        for child in diag.children {
+            eprintln!("  {:?}: {}", child.level, child.message);
+        }
+    }
+}
+
+impl server::Span for Rustc {
+    fn debug(&mut self, span: Span) -> String {
+        format!("{:?}", span.0)
+    }
+    fn def_site(&mut self) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn call_site(&mut self) -> Span {
+        tt::TokenId::unspecified()
+    }
+    fn source_file(&mut self, _span: Span) -> SourceFile {
+        SourceFile
+    }
+    fn parent(&mut self, _span: Span) -> Option<Span> {
+        None
+    }
+    fn source(&mut self, span: Span) -> Span {
+        span
+    }
+    fn start(&mut self, _span: Span) -> LineColumn {
+        LineColumn { line: 1, column: 0 }
+    }
+    fn end(&mut self, _span: Span) -> LineColumn {
+        LineColumn { line: 1, column: 0 }
+    }
+    fn join(&mut self, first: Span, _second: Span) -> Option<Span> {
+        Some(first)
+    }
+    fn resolved_at(&mut self, _span: Span, at: Span) -> Span {
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexes_into_groups_and_joint_puncts() {
+        let stream =
+            parse_stream("struct S<'a> { x: &'a u8 } // trailing\n fn f() -> bool { a::b(true) }");
+        let subtree = stream.clone().into_subtree();
+        assert_eq!(
+            subtree.to_string(),
+            "struct S < 'a > {x : & 'a u8} fn f () -> bool {a :: b (true)}"
+        );
+        let lifetime = &stream.token_trees[3];
+        let quote = tt::Punct { char: '\'', spacing: tt::Spacing::Joint };
+        assert_eq!(*lifetime, tt::TokenTree::from(tt::Leaf::from(quote)));
+    }
+
+    #[test]
+    fn converts_lifetimes_and_bools_back_and_forth() {
+        let ident = |text: &str| -> tt::TokenTree {
+            tt::Leaf::from(tt::Ident { text: text.into(), id: tt::TokenId(0) }).into()
+        };
+        let subtree = tt::Subtree {
+            delimiter: tt::Delimiter::None,
+            token_trees: vec![
+                ident("'a"),
+                tt::Leaf::from(tt::Literal { text: "true".into() }).into(),
+            ],
+        };
+        let stream = TokenStream::with_subtree(subtree.clone());
+        assert_eq!(stream.token_trees.len(), 3);
+        assert_eq!(stream.into_subtree(), subtree);
+    }
+}
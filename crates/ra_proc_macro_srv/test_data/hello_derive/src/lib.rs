@@ -0,0 +1,21 @@
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+
+/// Adds `fn hello() -> &'static str` to the type, which returns its name.
+#[proc_macro_derive(Hello)]
+pub fn derive_hello(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let name = loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(it))
+                if it.to_string() == "struct" || it.to_string() == "enum" =>
+            {
+                break tokens.next().expect("a type name").to_string();
+            }
+            Some(_) => (),
+            None => panic!("not a struct or an enum"),
+        }
+    };
+    format!("impl {0} {{ pub fn hello() -> &'static str {{ \"{0}\" }} }}", name).parse().unwrap()
+}
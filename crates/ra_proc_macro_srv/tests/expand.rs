@@ -0,0 +1,62 @@
+//! Builds the proc-macro crate in `test_data` and expands its derive through
+//! the server binary, like the analyzer does.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ra_db::ProcMacroKind;
+use ra_proc_macro::ProcMacroClient;
+
+/// The directory of the test executable's profile, e.g. `target/debug`.
+fn target_profile_dir() -> PathBuf {
+    let mut dir = env::current_exe().unwrap();
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir
+}
+
+fn build_fixture(name: &str) -> PathBuf {
+    let manifest =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data").join(name).join("Cargo.toml");
+    let target_dir = target_profile_dir().join("test_data");
+    let status = Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .args(&["build", "--quiet", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to build {}", manifest.display());
+    let file_name = format!("{}{}{}", env::consts::DLL_PREFIX, name, env::consts::DLL_SUFFIX);
+    target_dir.join("debug").join(file_name)
+}
+
+#[test]
+fn expands_a_derive() {
+    let dylib = build_fixture("hello_derive");
+    let server = target_profile_dir().join(format!("ra_proc_macro_srv{}", env::consts::EXE_SUFFIX));
+    let client = ProcMacroClient::extern_process(&server).unwrap();
+
+    let macros = client.by_dylib_path(&dylib);
+    assert_eq!(macros.len(), 1);
+    let hello = &macros[0];
+    assert_eq!(hello.name.as_str(), "Hello");
+    assert_eq!(hello.kind, ProcMacroKind::CustomDerive);
+
+    let ident = |text: &str, id: u32| -> tt::TokenTree {
+        tt::Leaf::from(tt::Ident { text: text.into(), id: tt::TokenId(id) }).into()
+    };
+    let semi: tt::TokenTree =
+        tt::Leaf::from(tt::Punct { char: ';', spacing: tt::Spacing::Alone }).into();
+    let item = tt::Subtree {
+        delimiter: tt::Delimiter::None,
+        token_trees: vec![ident("struct", 0), ident("Foo", 1), semi],
+    };
+    let expansion = hello.expander.expand(&item, None).unwrap();
+    assert_eq!(expansion.to_string(), "impl Foo {pub fn hello () -> & 'static str {\"Foo\"}}");
+}
@@ -1,16 +1,24 @@
+mod trace;
+
 use std::{
+    borrow::Cow,
     cell::RefCell,
     time::{Duration, Instant},
     mem,
     io::{stderr, Write},
     iter::repeat,
-    collections::HashSet,
-    sync::{RwLock, atomic::{AtomicBool, Ordering}},
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
 use once_cell::sync::Lazy;
 use itertools::Itertools;
 
+pub use crate::trace::{start_trace, stop_trace, stop_trace_of};
+
 /// Set profiling filter. It specifies descriptions allowed to profile.
 /// This is helpful when call stack has too many nested profiling scopes.
 /// Additionally filter can specify maximum depth of profiling scopes nesting.
@@ -61,13 +69,19 @@ pub fn set_filter(f: Filter) {
 ///  0ms - profile
 ///      0ms - profile2
 /// ```
-pub fn profile(desc: &str) -> Profiler {
+pub fn profile(desc: impl Into<Cow<'static, str>>) -> Profiler {
+    let desc = desc.into();
     assert!(!desc.is_empty());
+    let scope = if STATS_ENABLED.load(Ordering::Relaxed) || trace::is_enabled() {
+        Some((desc.clone(), Instant::now()))
+    } else {
+        None
+    };
     if !PROFILING_ENABLED.load(Ordering::Relaxed) {
-        return Profiler { desc: None };
+        return Profiler { desc: None, scope };
     }
 
-    PROFILE_STACK.with(|stack| {
+    let desc = PROFILE_STACK.with(|stack| {
         let mut stack = stack.borrow_mut();
         if stack.starts.is_empty() {
             if let Ok(f) = FILTER.try_read() {
@@ -77,20 +91,83 @@ pub fn profile(desc: &str) -> Profiler {
             };
         }
         if stack.starts.len() > stack.filter_data.depth {
-            return Profiler { desc: None };
+            return None;
         }
         let allowed = &stack.filter_data.allowed;
-        if stack.starts.is_empty() && !allowed.is_empty() && !allowed.contains(desc) {
-            return Profiler { desc: None };
+        if stack.starts.is_empty() && !allowed.is_empty() && !allowed.contains(&*desc) {
+            return None;
         }
 
         stack.starts.push(Instant::now());
-        Profiler { desc: Some(desc.to_string()) }
-    })
+        Some(desc.to_string())
+    });
+    Profiler { desc, scope }
 }
 
 pub struct Profiler {
     desc: Option<String>,
+    /// The description and the start of the scope, when the stats or the
+    /// trace are enabled.
+    scope: Option<(Cow<'static, str>, Instant)>,
+}
+
+/// Enables the aggregation of the time spent in each profiling scope, which
+/// `scope_stats` returns. Unlike printing, this doesn't depend on the filter.
+/// Each thread aggregates its own scopes, so a scope costs a lookup in a map
+/// which only `scope_stats` contends for, and descriptions which are not
+/// `&'static str` are copied.
+pub fn enable_stats(enabled: bool) {
+    STATS_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// The time spent in the scopes with the same description.
+#[derive(Debug, Clone)]
+pub struct ScopeStats {
+    pub desc: String,
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// Returns the stats of all scopes since the stats were enabled, the scopes
+/// with the largest total time first.
+pub fn scope_stats() -> Vec<ScopeStats> {
+    let mut merged: HashMap<String, ScopeStats> = HashMap::new();
+    for stats in THREAD_STATS.lock().unwrap().iter() {
+        for it in stats.lock().unwrap().values() {
+            match merged.get_mut(&it.desc) {
+                Some(total) => total.merge(it),
+                None => {
+                    merged.insert(it.desc.clone(), it.clone());
+                }
+            }
+        }
+    }
+    let mut res: Vec<ScopeStats> = merged.into_iter().map(|(_, it)| it).collect();
+    res.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.desc.cmp(&b.desc)));
+    res
+}
+
+impl ScopeStats {
+    fn merge(&mut self, other: &ScopeStats) {
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+fn record_stats(desc: Cow<'static, str>, duration: Duration) {
+    LOCAL_STATS.with(|stats| {
+        let mut stats = stats.lock().unwrap();
+        let it = ScopeStats { desc: String::new(), count: 1, total: duration, max: duration };
+        match stats.get_mut(&*desc) {
+            Some(stats) => stats.merge(&it),
+            None => {
+                let it = ScopeStats { desc: desc.to_string(), ..it };
+                stats.insert(desc, it);
+            }
+        }
+    })
 }
 
 pub struct Filter {
@@ -164,12 +241,35 @@ static PROFILING_ENABLED: AtomicBool = AtomicBool::new(false);
 
 static FILTER: Lazy<RwLock<FilterData>> = Lazy::new(Default::default);
 
+static STATS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+type Stats = Arc<Mutex<HashMap<Cow<'static, str>, ScopeStats>>>;
+
+/// The stats of every thread which ever recorded some, including the threads
+/// which exited since.
+static THREAD_STATS: Lazy<Mutex<Vec<Stats>>> = Lazy::new(Default::default);
+
+thread_local!(static LOCAL_STATS: Stats = {
+    let stats = Stats::default();
+    THREAD_STATS.lock().unwrap().push(Arc::clone(&stats));
+    stats
+});
+
 thread_local!(static PROFILE_STACK: RefCell<ProfileStack> = RefCell::new(ProfileStack::new()));
 
 impl Drop for Profiler {
     fn drop(&mut self) {
+        if let Some((desc, start)) = self.scope.take() {
+            let duration = start.elapsed();
+            if trace::is_enabled() {
+                trace::record(&desc, start, duration);
+            }
+            if STATS_ENABLED.load(Ordering::Relaxed) {
+                record_stats(desc, duration);
+            }
+        }
         match self {
-            Profiler { desc: Some(desc) } => {
+            Profiler { desc: Some(desc), .. } => {
                 PROFILE_STACK.with(|stack| {
                     let mut stack = stack.borrow_mut();
                     let start = stack.starts.pop().unwrap();
@@ -187,7 +287,7 @@ impl Drop for Profiler {
                     }
                 });
             }
-            Profiler { desc: None } => (),
+            Profiler { desc: None, .. } => (),
         }
     }
 }
@@ -275,4 +375,36 @@ mod tests {
     fn profiling_function2() {
         let _p = profile("profile2");
     }
+
+    #[test]
+    fn test_scope_stats_of_threads_are_merged() {
+        enable_stats(true);
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let _p = profile(format!("stats_thread_{}", i % 2));
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|it| it.join().unwrap());
+        let stats = scope_stats();
+        for desc in &["stats_thread_0", "stats_thread_1"] {
+            assert_eq!(stats.iter().find(|it| it.desc == *desc).unwrap().count, 2);
+        }
+    }
+
+    #[test]
+    fn test_scope_stats() {
+        enable_stats(true);
+        for _ in 0..3 {
+            let _p = profile("stats_outer");
+            let _p = profile("stats_inner");
+        }
+        let stats = scope_stats();
+        let outer = stats.iter().find(|it| it.desc == "stats_outer").unwrap();
+        let inner = stats.iter().find(|it| it.desc == "stats_inner").unwrap();
+        assert_eq!((outer.count, inner.count), (3, 3));
+        assert!(outer.total >= inner.total);
+        assert!(outer.max <= outer.total);
+    }
 }
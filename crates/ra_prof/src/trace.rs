@@ -0,0 +1,206 @@
+//! Records the profiling scopes of all threads in the Chrome Trace Event
+//! format, which `chrome://tracing` and Perfetto load. Unlike the printed
+//! tree, the trace doesn't depend on the filter: every scope is a complete
+//! event, with the thread it ran on and when it started.
+
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+/// Starts writing the profiling scopes to `path`, as a JSON array of trace
+/// events. The other traces which are being written go on, except for the one
+/// to `path`, if any, which is finished.
+pub fn start_trace(path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"[\n")?;
+    let mut traces = TRACES.lock().unwrap();
+    take_trace(&mut traces, path);
+    traces.push(Trace {
+        path: path.to_path_buf(),
+        out,
+        start: Instant::now(),
+        last_flush: Instant::now(),
+        empty: true,
+        named_threads: HashSet::new(),
+    });
+    TRACE_ENABLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Finishes all the traces which are being written.
+pub fn stop_trace() {
+    TRACE_ENABLED.store(false, Ordering::SeqCst);
+    for trace in TRACES.lock().unwrap().drain(..) {
+        trace.finish();
+    }
+}
+
+/// Finishes the trace which is being written to `path`, if any, the others go
+/// on.
+pub fn stop_trace_of(path: &Path) {
+    let mut traces = TRACES.lock().unwrap();
+    take_trace(&mut traces, path);
+    TRACE_ENABLED.store(!traces.is_empty(), Ordering::SeqCst);
+}
+
+fn take_trace(traces: &mut Vec<Trace>, path: &Path) {
+    if let Some(idx) = traces.iter().position(|it| it.path == path) {
+        traces.remove(idx).finish();
+    }
+}
+
+pub(crate) fn is_enabled() -> bool {
+    TRACE_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Writes the scope `desc` of the current thread, which started at `start`,
+/// to every trace. Scopes which started before a trace are skipped by it.
+pub(crate) fn record(desc: &str, start: Instant, duration: Duration) {
+    let tid = THREAD_ID.with(|it| it.get());
+    for trace in TRACES.lock().unwrap().iter_mut().filter(|it| start >= it.start) {
+        trace.record(desc, tid, start, duration);
+    }
+}
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Trace {
+    path: PathBuf,
+    out: BufWriter<File>,
+    start: Instant,
+    last_flush: Instant,
+    /// Whether no event was written yet, the others are preceded by a comma.
+    empty: bool,
+    /// The threads whose name was written.
+    named_threads: HashSet<usize>,
+}
+
+impl Trace {
+    fn record(&mut self, desc: &str, tid: usize, start: Instant, duration: Duration) {
+        if self.named_threads.insert(tid) {
+            let name = std::thread::current().name().map(String::from);
+            let name = name.unwrap_or_else(|| format!("thread {}", tid));
+            self.event(format_args!(
+                r#"{{"name":"thread_name","ph":"M","pid":{},"tid":{},"args":{{"name":"{}"}}}}"#,
+                std::process::id(),
+                tid,
+                escape(&name),
+            ));
+        }
+        self.event(format_args!(
+            r#"{{"name":"{}","ph":"X","ts":{},"dur":{},"pid":{},"tid":{}}}"#,
+            escape(desc),
+            (start - self.start).as_micros(),
+            duration.as_micros(),
+            std::process::id(),
+            tid,
+        ));
+        // The server is often killed rather than shut down, so the events are
+        // not only written when the trace is stopped.
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.last_flush = Instant::now();
+            let _ = self.out.flush();
+        }
+    }
+
+    fn event(&mut self, event: std::fmt::Arguments) {
+        let sep = if self.empty { "" } else { ",\n" };
+        self.empty = false;
+        if let Err(e) = write!(self.out, "{}{}", sep, event) {
+            eprintln!("failed to write the profiling trace: {}", e);
+        }
+    }
+
+    fn finish(mut self) {
+        if let Err(e) = self.out.write_all(b"\n]\n").and_then(|()| self.out.flush()) {
+            eprintln!("failed to write the profiling trace: {}", e);
+        }
+    }
+}
+
+/// Escapes `s` to be put in a JSON string.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+static TRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The traces being written, each to a file of its own.
+static TRACES: Lazy<Mutex<Vec<Trace>>> = Lazy::new(Default::default);
+
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// The id of the thread in the trace, `std::thread::ThreadId` can't be
+    /// converted to a number.
+    static THREAD_ID: Cell<usize> = Cell::new(NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile;
+
+    #[test]
+    fn test_trace() {
+        let path = std::env::temp_dir().join(format!("ra_prof_{}.json", std::process::id()));
+        start_trace(&path).unwrap();
+        {
+            let _p = profile("trace_outer");
+            let _p = profile("trace \"inner\"");
+        }
+        stop_trace_of(&path);
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(trace.starts_with("[\n"));
+        assert!(trace.ends_with("\n]\n"));
+        assert!(trace.contains(r#"{"name":"thread_name","ph":"M""#));
+        assert!(trace.contains(r#"{"name":"trace_outer","ph":"X","ts":"#));
+        assert!(trace.contains(r#"{"name":"trace \"inner\"","ph":"X","ts":"#));
+    }
+
+    #[test]
+    fn test_traces_are_independent() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("ra_prof_{}_{}.json", name, std::process::id()))
+        };
+        let (first, second) = (path("first"), path("second"));
+        start_trace(&first).unwrap();
+        start_trace(&second).unwrap();
+        drop(profile("trace_both"));
+        stop_trace_of(&second);
+        drop(profile("trace_first"));
+        stop_trace_of(&first);
+        let first_trace = std::fs::read_to_string(&first).unwrap();
+        let second_trace = std::fs::read_to_string(&second).unwrap();
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+
+        assert!(first_trace.contains(r#""name":"trace_both""#));
+        assert!(first_trace.contains(r#""name":"trace_first""#));
+        assert!(second_trace.contains(r#""name":"trace_both""#));
+        assert!(!second_trace.contains(r#""name":"trace_first""#));
+    }
+}
@@ -0,0 +1,80 @@
+//! The messages printed by `cargo check --message-format=json`, both the
+//! project model and the checker of the language server read them.
+use std::path::PathBuf;
+
+use cargo_metadata::{Artifact, PackageId};
+use serde::Deserialize;
+
+/// The messages of cargo we are interested in.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum CargoMessage {
+    CompilerArtifact(Artifact),
+    BuildScriptExecuted(BuildScriptExecuted),
+    CompilerMessage {
+        message: RustDiagnostic,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl CargoMessage {
+    /// Build scripts and rustc might print lines which are not messages, they
+    /// are `None`.
+    pub fn parse(line: &str) -> Option<CargoMessage> {
+        serde_json::from_str(line).ok()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BuildScriptExecuted {
+    pub package_id: PackageId,
+    #[serde(default)]
+    pub cfgs: Vec<String>,
+    /// Older versions of cargo don't report the `OUT_DIR`.
+    #[serde(default)]
+    pub out_dir: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RustDiagnostic {
+    pub message: String,
+    pub code: Option<RustDiagnosticCode>,
+    pub level: String,
+    pub spans: Vec<RustDiagnosticSpan>,
+    pub children: Vec<RustDiagnostic>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RustDiagnosticCode {
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RustDiagnosticSpan {
+    /// Relative to the root of the workspace.
+    pub file_name: String,
+    /// Lines and columns start at 1, columns count characters.
+    pub line_start: u64,
+    pub line_end: u64,
+    pub column_start: u64,
+    pub column_end: u64,
+    pub is_primary: bool,
+    /// The lines of the span, from `line_start` to `line_end`.
+    #[serde(default)]
+    pub text: Vec<RustDiagnosticLine>,
+    pub label: Option<String>,
+    pub suggested_replacement: Option<String>,
+    pub expansion: Option<Box<RustMacroExpansion>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RustDiagnosticLine {
+    pub text: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RustMacroExpansion {
+    /// Where the macro was called.
+    pub span: RustDiagnosticSpan,
+}
@@ -1,11 +1,17 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 
-use cargo_metadata::{MetadataCommand, CargoOpt};
+use cargo_metadata::{MetadataCommand, CargoOpt, Artifact, PackageId, DependencyKind};
 use ra_arena::{Arena, RawId, impl_arena_id};
 use rustc_hash::FxHashMap;
 use ra_db::Edition;
+use serde::Deserialize;
 
-use crate::Result;
+use crate::{Result, ProjectLoadError, CargoMessage};
 
 /// `CargoWorkspace` represents the logical structure of, well, a Cargo
 /// workspace. It pretty closely mirrors `cargo metadata` output.
@@ -19,6 +25,64 @@ pub struct CargoWorkspace {
     packages: Arena<Package, PackageData>,
     targets: Arena<Target, TargetData>,
     pub(crate) workspace_root: PathBuf,
+    features: CargoFeatures,
+    /// The target passed to cargo with `--target`, if any.
+    target: Option<String>,
+}
+
+/// Which features of the packages are enabled when the workspace is loaded.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CargoFeatures {
+    /// The default features, like a plain `cargo build`.
+    Default,
+    /// `--all-features`.
+    All,
+    /// `--no-default-features`.
+    None,
+    /// The default features and the listed features of each package, keyed
+    /// by package name.
+    Explicit(FxHashMap<String, Vec<String>>),
+}
+
+impl Default for CargoFeatures {
+    fn default() -> CargoFeatures {
+        CargoFeatures::Default
+    }
+}
+
+impl CargoFeatures {
+    fn explicit_features(per_package: &FxHashMap<String, Vec<String>>) -> Vec<String> {
+        let mut res = per_package
+            .iter()
+            .flat_map(|(pkg, features)| features.iter().map(move |it| format!("{}/{}", pkg, it)))
+            .collect::<Vec<_>>();
+        res.sort();
+        res
+    }
+
+    fn cargo_opt(&self) -> Option<CargoOpt> {
+        match self {
+            CargoFeatures::Default => None,
+            CargoFeatures::All => Some(CargoOpt::AllFeatures),
+            CargoFeatures::None => Some(CargoOpt::NoDefaultFeatures),
+            CargoFeatures::Explicit(it) => {
+                Some(CargoOpt::SomeFeatures(CargoFeatures::explicit_features(it)))
+            }
+        }
+    }
+
+    /// The same selection as command line flags of cargo.
+    fn cargo_args(&self) -> Vec<String> {
+        match self {
+            CargoFeatures::Default => Vec::new(),
+            CargoFeatures::All => vec!["--all-features".to_string()],
+            CargoFeatures::None => vec!["--no-default-features".to_string()],
+            CargoFeatures::Explicit(it) => {
+                vec!["--features".to_string(), CargoFeatures::explicit_features(it).join(" ")]
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -31,18 +95,78 @@ impl_arena_id!(Target);
 
 #[derive(Debug, Clone)]
 struct PackageData {
+    id: PackageId,
     name: String,
     manifest: PathBuf,
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
     edition: Edition,
+    /// The enabled features, as resolved by cargo.
+    features: Vec<String>,
+    /// The outputs of the build script, see `load_cargo_check_output`.
+    build_script: Option<BuildScriptOutput>,
+}
+
+/// What a build script told cargo about the package.
+#[derive(Debug, Clone, Default)]
+pub struct BuildScriptOutput {
+    /// The `OUT_DIR` the build script generated its files into.
+    pub out_dir: Option<PathBuf>,
+    /// `cargo:rustc-cfg` flags, like `foo` or `foo="bar"`.
+    pub cfgs: Vec<String>,
+}
+
+/// Where the output of `cargo check --message-format=json` comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CargoCheckOutput {
+    /// Run `cargo check` on the workspace.
+    Run,
+    /// Read a previously saved output from the file.
+    Cached(PathBuf),
 }
 
 #[derive(Debug, Clone)]
 pub struct PackageDependency {
     pub pkg: Package,
     pub name: String,
+    pub kind: DepKind,
+}
+
+/// The section of `Cargo.toml` a dependency is declared in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DepKind {
+    /// `[dependencies]`, available to all targets except the build script.
+    Normal,
+    /// `[dev-dependencies]`, available to tests, benches and examples.
+    Dev,
+    /// `[build-dependencies]`, available to the build script.
+    Build,
+}
+
+impl DepKind {
+    fn new(kind: DependencyKind) -> DepKind {
+        match kind {
+            DependencyKind::Development => DepKind::Dev,
+            DependencyKind::Build => DepKind::Build,
+            _ => DepKind::Normal,
+        }
+    }
+
+    /// The kinds of the edges to add for a dependency declared in the given
+    /// sections.
+    fn merge(mut kinds: Vec<DepKind>) -> Vec<DepKind> {
+        // Everything which sees dev-dependencies sees normal ones as well.
+        if kinds.contains(&DepKind::Normal) {
+            kinds.retain(|&it| it != DepKind::Dev);
+        }
+        kinds.sort();
+        kinds.dedup();
+        if kinds.is_empty() {
+            kinds.push(DepKind::Normal);
+        }
+        kinds
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +175,9 @@ struct TargetData {
     name: String,
     root: PathBuf,
     kind: TargetKind,
+    is_proc_macro: bool,
+    /// The compiled proc-macro library, see `load_cargo_check_output`.
+    proc_macro_dylib_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -71,6 +198,7 @@ impl TargetKind {
                 "test" => TargetKind::Test,
                 "bench" => TargetKind::Bench,
                 "example" => TargetKind::Example,
+                "proc-macro" => TargetKind::Lib,
                 _ if kind.contains("lib") => TargetKind::Lib,
                 _ => continue,
             };
@@ -92,6 +220,12 @@ impl Package {
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.packages[self].targets.iter().cloned()
     }
+    pub fn features(self, ws: &CargoWorkspace) -> &[String] {
+        ws.packages[self].features.as_slice()
+    }
+    pub fn build_script(self, ws: &CargoWorkspace) -> Option<&BuildScriptOutput> {
+        ws.packages[self].build_script.as_ref()
+    }
     #[allow(unused)]
     pub fn is_member(self, ws: &CargoWorkspace) -> bool {
         ws.packages[self].is_member
@@ -117,17 +251,31 @@ impl Target {
     pub fn kind(self, ws: &CargoWorkspace) -> TargetKind {
         ws.targets[self].kind
     }
+    pub fn is_proc_macro(self, ws: &CargoWorkspace) -> bool {
+        ws.targets[self].is_proc_macro
+    }
+    pub fn proc_macro_dylib_path(self, ws: &CargoWorkspace) -> Option<&Path> {
+        ws.targets[self].proc_macro_dylib_path.as_ref().map(PathBuf::as_path)
+    }
 }
 
 impl CargoWorkspace {
-    pub fn from_cargo_metadata(cargo_toml: &Path) -> Result<CargoWorkspace> {
+    pub fn from_cargo_metadata(
+        cargo_toml: &Path,
+        features: &CargoFeatures,
+        target: Option<&str>,
+    ) -> Result<CargoWorkspace> {
         let mut meta = MetadataCommand::new();
-        meta.manifest_path(cargo_toml).features(CargoOpt::AllFeatures);
+        meta.manifest_path(cargo_toml);
+        if let Some(opt) = features.cargo_opt() {
+            meta.features(opt);
+        }
         if let Some(parent) = cargo_toml.parent() {
             meta.current_dir(parent);
         }
-        let meta = meta.exec().map_err(|e| format!("cargo metadata failed: {}", e))?;
+        let meta = meta.exec().map_err(|e| metadata_error(cargo_toml, e))?;
         let mut pkg_by_id = FxHashMap::default();
+        let mut declared_deps = FxHashMap::default();
         let mut packages = Arena::default();
         let mut targets = Arena::default();
 
@@ -136,21 +284,28 @@ impl CargoWorkspace {
         for meta_pkg in meta.packages {
             let is_member = ws_members.contains(&meta_pkg.id);
             let pkg = packages.alloc(PackageData {
+                id: meta_pkg.id.clone(),
                 name: meta_pkg.name,
                 manifest: meta_pkg.manifest_path.clone(),
                 targets: Vec::new(),
                 is_member,
                 edition: Edition::from_string(&meta_pkg.edition),
                 dependencies: Vec::new(),
+                features: Vec::new(),
+                build_script: None,
             });
             let pkg_data = &mut packages[pkg];
             pkg_by_id.insert(meta_pkg.id.clone(), pkg);
+            let deps = meta_pkg.dependencies.into_iter().map(|it| (it.name, DepKind::new(it.kind)));
+            declared_deps.insert(pkg, deps.collect::<Vec<_>>());
             for meta_tgt in meta_pkg.targets {
                 let tgt = targets.alloc(TargetData {
                     pkg,
                     name: meta_tgt.name,
                     root: meta_tgt.src_path.clone(),
                     kind: TargetKind::new(meta_tgt.kind.as_slice()),
+                    is_proc_macro: meta_tgt.kind.iter().any(|it| it == "proc-macro"),
+                    proc_macro_dylib_path: None,
                 });
                 pkg_data.targets.push(tgt);
             }
@@ -159,19 +314,229 @@ impl CargoWorkspace {
         for node in resolve.nodes {
             let source = pkg_by_id[&node.id];
             for dep_node in node.deps {
-                let dep = PackageDependency { name: dep_node.name, pkg: pkg_by_id[&dep_node.pkg] };
-                packages[source].dependencies.push(dep);
+                let pkg = pkg_by_id[&dep_node.pkg];
+                // The resolve graph doesn't say in which section a dependency
+                // is declared, so we look it up in the manifest by the name of
+                // the package.
+                let kinds = declared_deps[&source]
+                    .iter()
+                    .filter(|(name, _)| *name == packages[pkg].name)
+                    .map(|&(_, kind)| kind)
+                    .collect::<Vec<_>>();
+                for kind in DepKind::merge(kinds) {
+                    let dep = PackageDependency { name: dep_node.name.clone(), pkg, kind };
+                    packages[source].dependencies.push(dep);
+                }
             }
+            packages[source].features.extend(node.features);
         }
 
-        Ok(CargoWorkspace { packages, targets, workspace_root: meta.workspace_root })
+        Ok(CargoWorkspace {
+            packages,
+            targets,
+            workspace_root: meta.workspace_root,
+            features: features.clone(),
+            target: target.map(String::from),
+        })
+    }
+
+    /// Loads the output of `cargo check --message-format=json`, either by
+    /// building the workspace or from a saved copy. Records the dynamic
+    /// libraries of all proc-macro targets, so that their macros can be loaded
+    /// by the proc-macro server, and the outputs of the build scripts.
+    pub fn load_cargo_check_output(&mut self, source: &CargoCheckOutput) -> Result<()> {
+        let path = match source {
+            CargoCheckOutput::Run => return self.run_cargo_check(),
+            CargoCheckOutput::Cached(it) => it,
+        };
+        let file = File::open(path).map_err(|e| ProjectLoadError::CargoCheck {
+            message: format!("can't read {}: {}", path.display(), e),
+        })?;
+        self.record_check_messages(BufReader::new(file))
+    }
+
+    fn run_cargo_check(&mut self) -> Result<()> {
+        let mut child = Command::new("cargo")
+            .args(&["check", "--message-format=json"])
+            .args(self.features.cargo_args())
+            .args(self.target.iter().flat_map(|it| vec!["--target", it.as_str()]))
+            .arg("--manifest-path")
+            .arg(self.workspace_root.join("Cargo.toml"))
+            .current_dir(&self.workspace_root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => ProjectLoadError::CargoNotFound,
+                _ => check_error(e),
+            })?;
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        self.record_check_messages(stdout)?;
+        // Errors in the checked code are fine: proc-macro crates and build
+        // scripts are built before the crates which use them.
+        child.wait().map_err(check_error)?;
+        Ok(())
+    }
+
+    fn record_check_messages(&mut self, reader: impl BufRead) -> Result<()> {
+        for line in reader.lines() {
+            let line = line.map_err(check_error)?;
+            let message = match CargoMessage::parse(&line) {
+                Some(it) => it,
+                None => continue,
+            };
+            match message {
+                CargoMessage::CompilerArtifact(artifact) => self.record_artifact(artifact),
+                CargoMessage::BuildScriptExecuted(it) => {
+                    let pkg = match self.package_by_id(&it.package_id) {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    self.packages[pkg].build_script =
+                        Some(BuildScriptOutput { out_dir: it.out_dir, cfgs: it.cfgs });
+                }
+                CargoMessage::CompilerMessage { .. } | CargoMessage::Other => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn record_artifact(&mut self, artifact: Artifact) {
+        if !artifact.target.kind.iter().any(|it| it == "proc-macro") {
+            return;
+        }
+        let dylib = artifact.filenames.into_iter().find(|it| is_dylib(it));
+        if let (Some(tgt), Some(dylib)) = (self.target_by_root(&artifact.target.src_path), dylib) {
+            self.targets[tgt].proc_macro_dylib_path = Some(dylib);
+        }
+    }
+
+    /// The directory of the workspace's `Cargo.toml`.
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
     }
 
     pub fn packages<'a>(&'a self) -> impl Iterator<Item = Package> + 'a {
         self.packages.iter().map(|(id, _pkg)| id)
     }
 
+    fn package_by_id(&self, id: &PackageId) -> Option<Package> {
+        self.packages().find(|pkg| self.packages[*pkg].id == *id)
+    }
+
     pub fn target_by_root(&self, root: &Path) -> Option<Target> {
         self.packages().filter_map(|pkg| pkg.targets(self).find(|it| it.root(self) == root)).next()
     }
 }
+
+fn metadata_error(cargo_toml: &Path, err: cargo_metadata::Error) -> ProjectLoadError {
+    let stderr = match err {
+        cargo_metadata::Error::CargoMetadata { stderr } => stderr,
+        cargo_metadata::Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            return ProjectLoadError::CargoNotFound
+        }
+        err => err.to_string(),
+    };
+    ProjectLoadError::CargoMetadata { manifest: cargo_toml.to_path_buf(), stderr }
+}
+
+fn check_error(err: io::Error) -> ProjectLoadError {
+    ProjectLoadError::CargoCheck { message: err.to_string() }
+}
+
+fn is_dylib(path: &Path) -> bool {
+    match path.extension().and_then(|it| it.to_str()) {
+        Some("so") | Some("dylib") | Some("dll") => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A workspace whose packages have a single target each, a proc-macro
+    /// library for the names ending with `_macros`.
+    fn workspace(names: &[&str]) -> CargoWorkspace {
+        let mut packages = Arena::default();
+        let mut targets = Arena::default();
+        for name in names {
+            let id = format!("{} 0.1.0 (path+file:///ws/{})", name, name);
+            let pkg = packages.alloc(PackageData {
+                id: serde_json::from_value(serde_json::Value::String(id)).unwrap(),
+                name: name.to_string(),
+                manifest: PathBuf::from(format!("/ws/{}/Cargo.toml", name)),
+                targets: Vec::new(),
+                is_member: true,
+                dependencies: Vec::new(),
+                edition: Edition::Edition2018,
+                features: Vec::new(),
+                build_script: None,
+            });
+            let tgt = targets.alloc(TargetData {
+                pkg,
+                name: name.to_string(),
+                root: PathBuf::from(format!("/ws/{}/src/lib.rs", name)),
+                kind: TargetKind::Lib,
+                is_proc_macro: name.ends_with("_macros"),
+                proc_macro_dylib_path: None,
+            });
+            packages[pkg].targets.push(tgt);
+        }
+        CargoWorkspace {
+            packages,
+            targets,
+            workspace_root: PathBuf::from("/ws"),
+            features: CargoFeatures::Default,
+            target: None,
+        }
+    }
+
+    #[test]
+    fn dep_kinds_are_merged() {
+        assert_eq!(DepKind::new(DependencyKind::Normal), DepKind::Normal);
+        assert_eq!(DepKind::new(DependencyKind::Development), DepKind::Dev);
+        assert_eq!(DepKind::new(DependencyKind::Build), DepKind::Build);
+
+        assert_eq!(DepKind::merge(vec![]), vec![DepKind::Normal]);
+        assert_eq!(DepKind::merge(vec![DepKind::Dev, DepKind::Normal]), vec![DepKind::Normal]);
+        assert_eq!(
+            DepKind::merge(vec![DepKind::Build, DepKind::Dev, DepKind::Dev]),
+            vec![DepKind::Dev, DepKind::Build]
+        );
+    }
+
+    #[test]
+    fn check_messages_are_recorded() {
+        let mut ws = workspace(&["foo", "foo_macros"]);
+        let messages = r#"
+   Compiling foo v0.1.0 (/ws/foo)
+{"reason":"compiler-artifact","package_id":"foo_macros 0.1.0 (path+file:///ws/foo_macros)","target":{"kind":["proc-macro"],"crate_types":["proc-macro"],"name":"foo_macros","src_path":"/ws/foo_macros/src/lib.rs","edition":"2018"},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/ws/target/debug/deps/libfoo_macros-f00.so"],"executable":null,"fresh":false}
+{"reason":"build-script-executed","package_id":"foo 0.1.0 (path+file:///ws/foo)","linked_libs":[],"linked_paths":[],"cfgs":["has_foo","foo_version=\"2\""],"env":[["FOO_ENV","1"]],"out_dir":"/ws/target/debug/build/foo-f00/out"}
+{"reason":"compiler-message","package_id":"foo 0.1.0 (path+file:///ws/foo)","target":{"kind":["lib"],"crate_types":["lib"],"name":"foo","src_path":"/ws/foo/src/lib.rs","edition":"2018"},"message":{"message":"unused variable","code":null,"level":"warning","spans":[],"children":[],"rendered":null}}
+"#;
+        ws.record_check_messages(messages.as_bytes()).unwrap();
+
+        let package = |name| ws.packages().find(|it| it.name(&ws) == name).unwrap();
+        let build_script = package("foo").build_script(&ws).unwrap();
+        assert_eq!(build_script.out_dir, Some(PathBuf::from("/ws/target/debug/build/foo-f00/out")));
+        assert_eq!(build_script.cfgs, vec!["has_foo".to_string(), "foo_version=\"2\"".to_string()]);
+        assert!(package("foo_macros").build_script(&ws).is_none());
+
+        let macros = package("foo_macros").targets(&ws).next().unwrap();
+        let dylib = macros.proc_macro_dylib_path(&ws);
+        assert_eq!(dylib, Some(Path::new("/ws/target/debug/deps/libfoo_macros-f00.so")));
+    }
+
+    #[test]
+    fn build_scripts_of_old_cargo_have_no_out_dir() {
+        let mut ws = workspace(&["foo"]);
+        let messages = r#"{"reason":"build-script-executed","package_id":"foo 0.1.0 (path+file:///ws/foo)","linked_libs":[],"linked_paths":[],"cfgs":[],"env":[]}"#;
+        ws.record_check_messages(messages.as_bytes()).unwrap();
+
+        let foo = ws.packages().next().unwrap();
+        let build_script = foo.build_script(&ws).unwrap();
+        assert_eq!(build_script.out_dir, None);
+        assert!(build_script.cfgs.is_empty());
+    }
+}
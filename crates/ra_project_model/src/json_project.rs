@@ -1,7 +1,17 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 use serde::Deserialize;
 
+use crate::{Result, ProjectLoadError};
+
+/// Versions of the `rust-project.json` format we understand. Files without a
+/// version are of the first version.
+const SUPPORTED_VERSIONS: &[u32] = &[1];
+
 /// A root points to the directory which contains Rust crates. rust-analyzer watches all files in
 /// all roots. Roots might be nested.
 #[derive(Clone, Debug, Deserialize)]
@@ -13,10 +23,36 @@ pub struct Root {
 /// A crate points to the root module of a crate and lists the dependencies of the crate. This is
 /// useful in creating the crate graph.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Crate {
     pub(crate) root_module: PathBuf,
     pub(crate) edition: Edition,
     pub(crate) deps: Vec<Dep>,
+    /// Cfg flags the crate is compiled with, like `unix` or `feature="serde"`.
+    #[serde(default)]
+    pub(crate) cfg: Vec<String>,
+    /// The enabled features of the crate, which are added to `cfg` as
+    /// `feature="..."`.
+    #[serde(default)]
+    pub(crate) features: Vec<String>,
+    /// The target triple the crate is compiled for.
+    #[serde(default)]
+    pub(crate) target: Option<String>,
+    #[serde(default)]
+    pub(crate) is_proc_macro: bool,
+    /// The compiled library of a proc-macro crate. Implies `is_proc_macro`.
+    #[serde(default)]
+    pub(crate) proc_macro_dylib_path: Option<PathBuf>,
+    /// The directory with the sources generated for the crate by its build
+    /// script, which is loaded together with the crate.
+    #[serde(default)]
+    pub(crate) out_dir: Option<PathBuf>,
+}
+
+impl Crate {
+    pub(crate) fn is_proc_macro(&self) -> bool {
+        self.is_proc_macro || self.proc_macro_dylib_path.is_some()
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize)]
@@ -35,6 +71,7 @@ pub struct CrateId(pub usize);
 
 /// A dependency of a crate, identified by its id in the crates array and name.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Dep {
     #[serde(rename = "crate")]
     pub(crate) krate: CrateId,
@@ -43,7 +80,128 @@ pub struct Dep {
 
 /// Roots and crates that compose this Rust project.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct JsonProject {
+    #[serde(default = "first_version")]
+    pub(crate) version: u32,
     pub(crate) roots: Vec<Root>,
     pub(crate) crates: Vec<Crate>,
+    /// The sysroot to take the standard library from, as printed by
+    /// `rustc --print sysroot`.
+    #[serde(default)]
+    pub(crate) sysroot: Option<PathBuf>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl JsonProject {
+    /// Reads and validates the project file. Relative paths in the file are
+    /// relative to the directory of the file.
+    pub fn load(path: &Path) -> Result<JsonProject> {
+        let invalid = |message: String| ProjectLoadError::InvalidJsonProject {
+            path: path.to_path_buf(),
+            message,
+        };
+        let file = File::open(path).map_err(|e| invalid(format!("can't read the file: {}", e)))?;
+        let mut project: JsonProject =
+            serde_json::from_reader(BufReader::new(file)).map_err(|e| invalid(e.to_string()))?;
+        project.validate().map_err(invalid)?;
+        if let Some(base) = path.parent() {
+            project.make_paths_absolute(base);
+        }
+        Ok(project)
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        if !SUPPORTED_VERSIONS.contains(&self.version) {
+            return Err(format!(
+                "unsupported version {}, supported versions are {:?}",
+                self.version, SUPPORTED_VERSIONS
+            ));
+        }
+        for (id, krate) in self.crates.iter().enumerate() {
+            for dep in &krate.deps {
+                if dep.krate.0 >= self.crates.len() {
+                    return Err(format!(
+                        "crate {} depends on crate {} (`{}`), but there are only {} crates",
+                        id,
+                        dep.krate.0,
+                        dep.name,
+                        self.crates.len()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn make_paths_absolute(&mut self, base: &Path) {
+        let absolute = |path: &mut PathBuf| *path = base.join(&*path);
+        self.roots.iter_mut().for_each(|it| absolute(&mut it.path));
+        self.sysroot.iter_mut().for_each(absolute);
+        for krate in self.crates.iter_mut() {
+            absolute(&mut krate.root_module);
+            krate.proc_macro_dylib_path.iter_mut().for_each(absolute);
+            krate.out_dir.iter_mut().for_each(absolute);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn parse(text: &str) -> std::result::Result<JsonProject, String> {
+        let project: JsonProject = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        project.validate()?;
+        Ok(project)
+    }
+
+    #[test]
+    fn projects_without_version_are_of_the_first_one() {
+        let project = parse(r#"{ "roots": [], "crates": [] }"#).unwrap();
+        assert_eq!(project.version, 1);
+    }
+
+    #[test]
+    fn invalid_projects_are_rejected() {
+        let err = parse(r#"{ "version": 2, "roots": [], "crates": [] }"#).unwrap_err();
+        assert_eq!(err, "unsupported version 2, supported versions are [1]");
+
+        let err = parse(
+            r#"{
+                "roots": [],
+                "crates": [{ "root_module": "lib.rs", "edition": "2018", "deps": [{ "crate": 1, "name": "dep" }] }]
+            }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err, "crate 0 depends on crate 1 (`dep`), but there are only 1 crates");
+
+        let err = parse(r#"{ "roots": [], "crates": [], "sysrot": "/sysroot" }"#).unwrap_err();
+        assert!(err.starts_with("unknown field `sysrot`"), "{}", err);
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_project_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rust-project.json");
+        let text = r#"{
+            "roots": ["src"],
+            "crates": [{ "root_module": "src/lib.rs", "edition": "2015", "deps": [] }]
+        }"#;
+        std::fs::write(&path, text).unwrap();
+        let project = JsonProject::load(&path).unwrap();
+        assert_eq!(project.roots[0].path, dir.path().join("src"));
+        assert_eq!(project.crates[0].root_module, dir.path().join("src/lib.rs"));
+
+        std::fs::write(&path, "{").unwrap();
+        match JsonProject::load(&path) {
+            Err(ProjectLoadError::InvalidJsonProject { path: it, .. }) => assert_eq!(it, path),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
 }
@@ -1,53 +1,97 @@
+mod cargo_message;
 mod cargo_workspace;
 mod json_project;
+mod load_error;
 mod sysroot;
+mod target;
 
 use std::{
-    fs::File,
-    io::BufReader,
+    fmt,
     path::{Path, PathBuf},
-    error::Error
 };
 
 use rustc_hash::FxHashMap;
 
-use ra_db::{CrateGraph, FileId, Edition};
+use ra_db::{CrateGraph, CrateId, FileId, Edition, CfgOptions};
 
-use serde_json::from_reader;
-
-use relative_path::RelativePath;
+use relative_path::{RelativePath, RelativePathBuf};
 
 pub use crate::{
-    cargo_workspace::{CargoWorkspace, Package, Target, TargetKind},
+    cargo_message::{
+        CargoMessage, BuildScriptExecuted, RustDiagnostic, RustDiagnosticCode, RustDiagnosticSpan,
+        RustDiagnosticLine, RustMacroExpansion,
+    },
+    cargo_workspace::{
+        CargoWorkspace, CargoFeatures, CargoCheckOutput, BuildScriptOutput, Package,
+        PackageDependency, DepKind, Target, TargetKind,
+    },
     json_project::JsonProject,
-    sysroot::Sysroot,
+    load_error::ProjectLoadError,
+    sysroot::{Sysroot, SysrootCrate},
 };
+pub use ra_proc_macro::ProcMacroClient;
 
-// FIXME use proper error enum
-pub type Result<T> = ::std::result::Result<T, Box<dyn Error + Send + Sync>>;
+pub type Result<T> = ::std::result::Result<T, ProjectLoadError>;
 
 #[derive(Debug, Clone)]
 pub enum ProjectWorkspace {
     /// Project workspace was discovered by running `cargo metadata` and `rustc --print sysroot`.
-    Cargo { cargo: CargoWorkspace, sysroot: Sysroot },
+    /// The crates are compiled for `target`.
+    Cargo { cargo: CargoWorkspace, sysroot: Sysroot, target: Option<String> },
     /// Project workspace was manually specified using a `rust-project.json` file.
-    Json { project: JsonProject },
+    Json { project: JsonProject, sysroot: Option<Sysroot> },
+    /// Rust files which don't belong to any project, each of them is analyzed
+    /// as a crate of its own.
+    DetachedFiles { files: Vec<PathBuf>, sysroot: Sysroot, target: Option<String> },
+}
+
+/// The target to analyze Cargo projects and detached files for.
+/// `rust-project.json` specifies it per crate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetConfig {
+    /// The target triple, like `thumbv7em-none-eabihf`. Defaults to the host
+    /// of `rustc`.
+    pub target: Option<String>,
+    /// The sysroot to take the standard library from. Defaults to the one of
+    /// `rustc`, custom targets might need the one built by `cargo xbuild`.
+    pub sysroot: Option<PathBuf>,
+}
+
+impl TargetConfig {
+    fn sysroot(&self, dir: &Path) -> Result<Sysroot> {
+        match &self.sysroot {
+            Some(path) => Sysroot::load(path),
+            None => Sysroot::discover(dir),
+        }
+    }
+
+    fn target(&self, dir: &Path) -> Option<String> {
+        self.target.clone().or_else(|| target::host_target(dir))
+    }
 }
 
 /// `ProjectRoot` describes a workspace root folder.
 /// Which may be an external dependency, or a member of
 /// the current workspace.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProjectRoot {
     /// Path to the root folder
     path: PathBuf,
     /// Is a member of the current workspace
     is_member: bool,
+    /// The only files of the folder which belong to the root, without the
+    /// subfolders, if not all of them do.
+    files: Option<Vec<RelativePathBuf>>,
 }
 
 impl ProjectRoot {
     pub fn new(path: PathBuf, is_member: bool) -> ProjectRoot {
-        ProjectRoot { path, is_member }
+        ProjectRoot { path, is_member, files: None }
+    }
+
+    /// A root with some files of the folder at `path` only.
+    pub fn with_files(path: PathBuf, files: Vec<RelativePathBuf>) -> ProjectRoot {
+        ProjectRoot { path, is_member: true, files: Some(files) }
     }
 
     pub fn path(&self) -> &PathBuf {
@@ -58,7 +102,19 @@ impl ProjectRoot {
         self.is_member
     }
 
+    /// Whether all files of `other` belong to this root as well.
+    pub fn contains(&self, other: &ProjectRoot) -> bool {
+        self == other
+            || (self.files.is_none()
+                && other.path.starts_with(&self.path)
+                && other.is_member == self.is_member)
+    }
+
     pub fn include_dir(&self, dir_path: &RelativePath) -> bool {
+        if self.files.is_some() {
+            return dir_path.as_str().is_empty();
+        }
+
         const COMMON_IGNORED_DIRS: &[&str] = &["node_modules", "target", ".git"];
         const EXTERNAL_IGNORED_DIRS: &[&str] = &["examples", "tests", "benches"];
 
@@ -77,47 +133,108 @@ impl ProjectRoot {
     }
 
     pub fn include_file(&self, file_path: &RelativePath) -> bool {
-        file_path.extension() == Some("rs")
+        match &self.files {
+            Some(files) => files.iter().any(|it| it.as_str() == file_path.as_str()),
+            None => file_path.extension() == Some("rs"),
+        }
     }
 }
 
 impl ProjectWorkspace {
-    pub fn discover(path: &Path) -> Result<ProjectWorkspace> {
+    pub fn discover(
+        path: &Path,
+        cargo_features: &CargoFeatures,
+        target_config: &TargetConfig,
+    ) -> Result<ProjectWorkspace> {
         match find_rust_project_json(path) {
             Some(json_path) => {
-                let file = File::open(json_path)?;
-                let reader = BufReader::new(file);
-                Ok(ProjectWorkspace::Json { project: from_reader(reader)? })
+                let project = JsonProject::load(&json_path)?;
+                let sysroot = match (&project.sysroot, &target_config.sysroot) {
+                    (Some(path), _) | (None, Some(path)) => Some(Sysroot::load(path)?),
+                    (None, None) => None,
+                };
+                Ok(ProjectWorkspace::Json { project, sysroot })
             }
             None => {
                 let cargo_toml = find_cargo_toml(path)?;
+                let dir = cargo_toml.parent().unwrap();
+                let target = target_config.target.as_ref().map(String::as_str);
                 Ok(ProjectWorkspace::Cargo {
-                    cargo: CargoWorkspace::from_cargo_metadata(&cargo_toml)?,
-                    sysroot: Sysroot::discover(&cargo_toml)?,
+                    cargo: CargoWorkspace::from_cargo_metadata(
+                        &cargo_toml,
+                        cargo_features,
+                        target,
+                    )?,
+                    sysroot: target_config.sysroot(dir)?,
+                    target: target_config.target(dir),
                 })
             }
         }
     }
 
+    /// Creates a workspace with one crate per file, which can use the
+    /// standard library.
+    pub fn load_detached_files(
+        files: Vec<PathBuf>,
+        target_config: &TargetConfig,
+    ) -> Result<ProjectWorkspace> {
+        let dir = files.first().and_then(|it| it.parent()).unwrap_or(Path::new("."));
+        let sysroot = target_config.sysroot(dir)?;
+        let target = target_config.target(dir);
+        Ok(ProjectWorkspace::DetachedFiles { files, sysroot, target })
+    }
+
     /// Returns the roots for the current ProjectWorkspace
     /// The return type contains the path and whether or not
     /// the root is a member of the current workspace
     pub fn to_roots(&self) -> Vec<ProjectRoot> {
         match self {
-            ProjectWorkspace::Json { project } => {
+            ProjectWorkspace::Json { project, sysroot } => {
                 let mut roots = Vec::with_capacity(project.roots.len());
                 for root in &project.roots {
                     roots.push(ProjectRoot::new(root.path.clone(), true));
                 }
+                for out_dir in project.crates.iter().filter_map(|it| it.out_dir.as_ref()) {
+                    roots.push(ProjectRoot::new(out_dir.clone(), false));
+                }
+                for sysroot in sysroot.iter() {
+                    for krate in sysroot.crates() {
+                        roots.push(ProjectRoot::new(krate.root_dir(sysroot).to_path_buf(), false))
+                    }
+                }
                 roots
             }
-            ProjectWorkspace::Cargo { cargo, sysroot } => {
+            ProjectWorkspace::Cargo { cargo, sysroot, .. } => {
                 let mut roots =
                     Vec::with_capacity(cargo.packages().count() + sysroot.crates().count());
                 for pkg in cargo.packages() {
                     let root = pkg.root(&cargo).to_path_buf();
                     let member = pkg.is_member(&cargo);
                     roots.push(ProjectRoot::new(root, member));
+                    // Sources generated by the build script
+                    let out_dir = pkg.build_script(&cargo).and_then(|it| it.out_dir.as_ref());
+                    if let Some(out_dir) = out_dir {
+                        roots.push(ProjectRoot::new(out_dir.clone(), false));
+                    }
+                }
+                for krate in sysroot.crates() {
+                    roots.push(ProjectRoot::new(krate.root_dir(&sysroot).to_path_buf(), false))
+                }
+                roots
+            }
+            ProjectWorkspace::DetachedFiles { files, sysroot, .. } => {
+                // The other files of the folders don't belong to the crates
+                let mut roots: Vec<ProjectRoot> = Vec::new();
+                for file in files {
+                    let (dir, name) = match (file.parent(), file.file_name()) {
+                        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+                        _ => continue,
+                    };
+                    let name = RelativePathBuf::from(name.as_ref());
+                    match roots.iter_mut().find(|it| it.path == dir) {
+                        Some(root) => root.files.get_or_insert_with(Vec::new).push(name),
+                        None => roots.push(ProjectRoot::with_files(dir.to_path_buf(), vec![name])),
+                    }
                 }
                 for krate in sysroot.crates() {
                     roots.push(ProjectRoot::new(krate.root_dir(&sysroot).to_path_buf(), false))
@@ -127,17 +244,46 @@ impl ProjectWorkspace {
         }
     }
 
+    /// Loads the proc-macro libraries and the build script outputs of the
+    /// workspace from `cargo check`. `rust-project.json` lists the libraries
+    /// explicitly.
+    pub fn load_cargo_check_output(&mut self, source: &CargoCheckOutput) -> Result<()> {
+        match self {
+            ProjectWorkspace::Json { .. } | ProjectWorkspace::DetachedFiles { .. } => Ok(()),
+            ProjectWorkspace::Cargo { cargo, .. } => cargo.load_cargo_check_output(source),
+        }
+    }
+
     pub fn count(&self) -> usize {
         match self {
-            ProjectWorkspace::Json { project } => project.crates.len(),
+            ProjectWorkspace::Json { project, .. } => project.crates.len(),
             ProjectWorkspace::Cargo { cargo, .. } => cargo.packages().count(),
+            ProjectWorkspace::DetachedFiles { files, .. } => files.len(),
         }
     }
 
-    pub fn to_crate_graph(&self, load: &mut dyn FnMut(&Path) -> Option<FileId>) -> CrateGraph {
+    /// Creates the crate graph of the workspace. Dependencies which would
+    /// create cycles are skipped, and reported as errors.
+    pub fn to_crate_graph(
+        &self,
+        proc_macro_client: &ProcMacroClient,
+        load: &mut dyn FnMut(&Path) -> Option<FileId>,
+    ) -> (CrateGraph, Vec<ProjectLoadError>) {
         let mut crate_graph = CrateGraph::default();
+        let mut errors = Vec::new();
         match self {
-            ProjectWorkspace::Json { project } => {
+            ProjectWorkspace::Json { project, sysroot } => {
+                let sysroot_crates = match sysroot {
+                    Some(sysroot) => {
+                        sysroot_to_crate_graph(&mut crate_graph, &mut errors, sysroot, load)
+                    }
+                    None => FxHashMap::default(),
+                };
+                let sysroot_deps = match sysroot {
+                    Some(sysroot) => SysrootDeps::new(sysroot, &sysroot_crates),
+                    None => SysrootDeps::default(),
+                };
+
                 let mut crates = FxHashMap::default();
                 for (id, krate) in project.crates.iter().enumerate() {
                     let crate_id = json_project::CrateId(id);
@@ -146,7 +292,33 @@ impl ProjectWorkspace {
                             json_project::Edition::Edition2015 => Edition::Edition2015,
                             json_project::Edition::Edition2018 => Edition::Edition2018,
                         };
-                        crates.insert(crate_id, crate_graph.add_crate_root(file_id, edition));
+                        let crate_graph_id = crate_graph.add_crate_root(file_id, edition);
+                        let mut cfg_options = CfgOptions::default();
+                        if let Some(target) = &krate.target {
+                            target::insert_target_cfgs(&mut cfg_options, target);
+                        }
+                        for cfg in &krate.cfg {
+                            insert_cfg(&mut cfg_options, cfg);
+                        }
+                        for feature in &krate.features {
+                            cfg_options.insert_key_value("feature".into(), feature.into());
+                        }
+                        crate_graph.set_cfg_options(crate_graph_id, cfg_options);
+                        if let Some(dylib) = &krate.proc_macro_dylib_path {
+                            crate_graph.set_proc_macros(
+                                crate_graph_id,
+                                proc_macro_client.by_dylib_path(dylib),
+                            );
+                        }
+                        sysroot_deps.add_to(
+                            &mut crate_graph,
+                            &mut errors,
+                            crate_graph_id,
+                            krate.is_proc_macro(),
+                            krate.cfg.iter().any(|it| it == "test"),
+                            krate.root_module.display(),
+                        );
+                        crates.insert(crate_id, crate_graph_id);
                     }
                 }
 
@@ -157,77 +329,71 @@ impl ProjectWorkspace {
                         if let (Some(&from), Some(&to)) =
                             (crates.get(&from_crate_id), crates.get(&to_crate_id))
                         {
-                            if let Err(_) = crate_graph.add_dep(from, dep.name.clone().into(), to) {
-                                log::error!(
-                                    "cyclic dependency {:?} -> {:?}",
-                                    from_crate_id,
-                                    to_crate_id
-                                );
-                            }
+                            let name = krate.root_module.display();
+                            add_dep(&mut crate_graph, &mut errors, from, &dep.name, to, &name);
                         }
                     }
                 }
             }
-            ProjectWorkspace::Cargo { cargo, sysroot } => {
-                let mut sysroot_crates = FxHashMap::default();
-                for krate in sysroot.crates() {
-                    if let Some(file_id) = load(krate.root(&sysroot)) {
-                        sysroot_crates.insert(
-                            krate,
-                            crate_graph.add_crate_root(file_id, Edition::Edition2015),
-                        );
-                    }
-                }
-                for from in sysroot.crates() {
-                    for to in from.deps(&sysroot) {
-                        let name = to.name(&sysroot);
-                        if let (Some(&from), Some(&to)) =
-                            (sysroot_crates.get(&from), sysroot_crates.get(&to))
-                        {
-                            if let Err(_) = crate_graph.add_dep(from, name.into(), to) {
-                                log::error!("cyclic dependency between sysroot crates")
-                            }
-                        }
-                    }
-                }
-
-                let libstd = sysroot.std().and_then(|it| sysroot_crates.get(&it).map(|&it| it));
+            ProjectWorkspace::Cargo { cargo, sysroot, target } => {
+                let sysroot_crates =
+                    sysroot_to_crate_graph(&mut crate_graph, &mut errors, sysroot, load);
+                let sysroot_deps = SysrootDeps::new(sysroot, &sysroot_crates);
 
                 let mut pkg_to_lib_crate = FxHashMap::default();
                 let mut pkg_crates = FxHashMap::default();
                 // Next, create crates for each package, target pair
                 for pkg in cargo.packages() {
+                    let mut cfg_options = package_cfg_options(&cargo, pkg);
+                    if let Some(target) = target {
+                        target::insert_target_cfgs(&mut cfg_options, target);
+                    }
                     let mut lib_tgt = None;
                     for tgt in pkg.targets(&cargo) {
                         let root = tgt.root(&cargo);
-                        if let Some(file_id) = load(root) {
-                            let edition = pkg.edition(&cargo);
-                            let crate_id = crate_graph.add_crate_root(file_id, edition);
-                            if tgt.kind(&cargo) == TargetKind::Lib {
-                                lib_tgt = Some(crate_id);
-                                pkg_to_lib_crate.insert(pkg, crate_id);
-                            }
-                            pkg_crates.entry(pkg).or_insert_with(Vec::new).push(crate_id);
+                        let file_id = match load(root) {
+                            Some(it) => it,
+                            None => continue,
+                        };
+                        let kind = tgt.kind(&cargo);
+                        // Tests and benches are compiled with `--test`.
+                        let is_test = kind == TargetKind::Test || kind == TargetKind::Bench;
+                        let edition = pkg.edition(&cargo);
+                        let crate_id = crate_graph.add_crate_root(file_id, edition);
+                        let mut cfg_options = cfg_options.clone();
+                        if is_test {
+                            cfg_options.insert_atom("test".into());
+                        }
+                        crate_graph.set_cfg_options(crate_id, cfg_options);
+                        if let Some(dylib) = tgt.proc_macro_dylib_path(&cargo) {
+                            crate_graph
+                                .set_proc_macros(crate_id, proc_macro_client.by_dylib_path(dylib));
+                        }
+                        sysroot_deps.add_to(
+                            &mut crate_graph,
+                            &mut errors,
+                            crate_id,
+                            tgt.is_proc_macro(&cargo),
+                            is_test,
+                            pkg.name(&cargo),
+                        );
+                        if kind == TargetKind::Lib {
+                            lib_tgt = Some(crate_id);
+                            pkg_to_lib_crate.insert(pkg, crate_id);
                         }
+                        pkg_crates.entry(pkg).or_insert_with(Vec::new).push(TargetCrate {
+                            crate_id,
+                            kind,
+                            is_test,
+                        });
                     }
 
-                    // Set deps to the std and to the lib target of the current package
-                    for &from in pkg_crates.get(&pkg).into_iter().flatten() {
+                    // Set deps to the lib target of the current package
+                    for krate in pkg_crates.get(&pkg).into_iter().flatten() {
                         if let Some(to) = lib_tgt {
-                            if to != from {
-                                if let Err(_) =
-                                    crate_graph.add_dep(from, pkg.name(&cargo).into(), to)
-                                {
-                                    log::error!(
-                                        "cyclic dependency between targets of {}",
-                                        pkg.name(&cargo)
-                                    )
-                                }
-                            }
-                        }
-                        if let Some(std) = libstd {
-                            if let Err(_) = crate_graph.add_dep(from, "std".into(), std) {
-                                log::error!("cyclic dependency on std for {}", pkg.name(&cargo))
+                            if krate.crate_id != to {
+                                let (from, name) = (krate.crate_id, pkg.name(&cargo));
+                                add_dep(&mut crate_graph, &mut errors, from, name, to, name);
                             }
                         }
                     }
@@ -238,23 +404,38 @@ impl ProjectWorkspace {
                 for pkg in cargo.packages() {
                     for dep in pkg.dependencies(&cargo) {
                         if let Some(&to) = pkg_to_lib_crate.get(&dep.pkg) {
-                            for &from in pkg_crates.get(&pkg).into_iter().flatten() {
-                                if let Err(_) =
-                                    crate_graph.add_dep(from, dep.name.clone().into(), to)
-                                {
-                                    log::error!(
-                                        "cyclic dependency {} -> {}",
-                                        pkg.name(&cargo),
-                                        dep.pkg.name(&cargo)
-                                    )
+                            for krate in pkg_crates.get(&pkg).into_iter().flatten() {
+                                if !krate.sees_dep(dep.kind) {
+                                    continue;
                                 }
+                                let (from, name) = (krate.crate_id, pkg.name(&cargo));
+                                add_dep(&mut crate_graph, &mut errors, from, &dep.name, to, name);
                             }
                         }
                     }
                 }
             }
+            ProjectWorkspace::DetachedFiles { files, sysroot, target } => {
+                let sysroot_crates =
+                    sysroot_to_crate_graph(&mut crate_graph, &mut errors, sysroot, load);
+                let sysroot_deps = SysrootDeps::new(sysroot, &sysroot_crates);
+                for file in files {
+                    let file_id = match load(file) {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    let crate_id = crate_graph.add_crate_root(file_id, Edition::Edition2018);
+                    if let Some(target) = target {
+                        let mut cfg_options = CfgOptions::default();
+                        target::insert_target_cfgs(&mut cfg_options, target);
+                        crate_graph.set_cfg_options(crate_id, cfg_options);
+                    }
+                    let (from, name) = (crate_id, file.display());
+                    sysroot_deps.add_to(&mut crate_graph, &mut errors, from, false, false, name);
+                }
+            }
         }
-        crate_graph
+        (crate_graph, errors)
     }
 
     pub fn workspace_root_for(&self, path: &Path) -> Option<&Path> {
@@ -262,10 +443,160 @@ impl ProjectWorkspace {
             ProjectWorkspace::Cargo { cargo, .. } => {
                 Some(cargo.workspace_root.as_ref()).filter(|root| path.starts_with(root))
             }
-            ProjectWorkspace::Json { project: JsonProject { roots, .. } } => roots
+            ProjectWorkspace::Json { project: JsonProject { roots, .. }, .. } => roots
                 .iter()
                 .find(|root| path.starts_with(&root.path))
                 .map(|root| root.path.as_ref()),
+            ProjectWorkspace::DetachedFiles { .. } => None,
+        }
+    }
+}
+
+/// Adds the crates of the sysroot, returns the ids they got in the crate graph.
+fn sysroot_to_crate_graph(
+    crate_graph: &mut CrateGraph,
+    errors: &mut Vec<ProjectLoadError>,
+    sysroot: &Sysroot,
+    load: &mut dyn FnMut(&Path) -> Option<FileId>,
+) -> FxHashMap<SysrootCrate, CrateId> {
+    let mut sysroot_crates = FxHashMap::default();
+    for krate in sysroot.crates() {
+        if let Some(file_id) = load(krate.root(&sysroot)) {
+            sysroot_crates.insert(krate, crate_graph.add_crate_root(file_id, Edition::Edition2015));
+        }
+    }
+    for krate in sysroot.crates() {
+        for dep in krate.deps(&sysroot) {
+            let name = dep.name(&sysroot);
+            if let (Some(&from), Some(&to)) = (sysroot_crates.get(&krate), sysroot_crates.get(&dep))
+            {
+                add_dep(crate_graph, errors, from, name, to, krate.name(&sysroot));
+            }
+        }
+    }
+    sysroot_crates
+}
+
+/// The crates of the sysroot which the crates of a workspace depend on.
+#[derive(Default)]
+struct SysrootDeps {
+    std: Option<CrateId>,
+    core: Option<CrateId>,
+    alloc: Option<CrateId>,
+    proc_macro: Option<CrateId>,
+    test: Option<CrateId>,
+}
+
+impl SysrootDeps {
+    fn new(sysroot: &Sysroot, sysroot_crates: &FxHashMap<SysrootCrate, CrateId>) -> SysrootDeps {
+        let get =
+            |krate: Option<SysrootCrate>| krate.and_then(|it| sysroot_crates.get(&it)).cloned();
+        SysrootDeps {
+            std: get(sysroot.std()),
+            core: get(sysroot.core()),
+            alloc: get(sysroot.alloc()),
+            proc_macro: get(sysroot.proc_macro()),
+            test: get(sysroot.test()),
+        }
+    }
+
+    /// Adds the sysroot deps of the crate described by `krate`. Like in rustc,
+    /// only `std` and `core` are in the extern prelude, the others need an
+    /// `extern crate`.
+    fn add_to(
+        &self,
+        crate_graph: &mut CrateGraph,
+        errors: &mut Vec<ProjectLoadError>,
+        from: CrateId,
+        is_proc_macro: bool,
+        is_test: bool,
+        krate: impl fmt::Display,
+    ) {
+        let deps = [
+            ("std", self.std, true),
+            ("core", self.core, true),
+            ("alloc", self.alloc, false),
+            ("proc_macro", self.proc_macro.filter(|_| is_proc_macro), false),
+            ("test", self.test.filter(|_| is_test), false),
+        ];
+        for &(name, to, prelude) in deps.iter() {
+            if let Some(to) = to {
+                add_dep_with_prelude(crate_graph, errors, from, name, to, prelude, &krate);
+            }
+        }
+    }
+}
+
+/// Adds a dependency of the crate described by `krate`, if it doesn't create
+/// a cycle.
+fn add_dep(
+    crate_graph: &mut CrateGraph,
+    errors: &mut Vec<ProjectLoadError>,
+    from: CrateId,
+    name: &str,
+    to: CrateId,
+    krate: impl fmt::Display,
+) {
+    add_dep_with_prelude(crate_graph, errors, from, name, to, true, krate)
+}
+
+fn add_dep_with_prelude(
+    crate_graph: &mut CrateGraph,
+    errors: &mut Vec<ProjectLoadError>,
+    from: CrateId,
+    name: &str,
+    to: CrateId,
+    prelude: bool,
+    krate: impl fmt::Display,
+) {
+    if let Err(_) = crate_graph.add_dep_with_prelude(from, name.into(), to, prelude) {
+        log::error!("cyclic dependency on {} for {}", name, krate);
+        let krate = krate.to_string();
+        errors.push(ProjectLoadError::CyclicDependency { krate, dep: name.to_string() });
+    }
+}
+
+/// A crate built from a target of a Cargo package.
+struct TargetCrate {
+    crate_id: CrateId,
+    kind: TargetKind,
+    /// Whether the crate is compiled with `cfg(test)`.
+    is_test: bool,
+}
+
+impl TargetCrate {
+    fn sees_dep(&self, dep_kind: DepKind) -> bool {
+        match dep_kind {
+            DepKind::Normal => self.kind != TargetKind::Other,
+            DepKind::Dev => self.is_test || self.kind == TargetKind::Example,
+            DepKind::Build => self.kind == TargetKind::Other,
+        }
+    }
+}
+
+/// The cfg flags shared by all targets of the package: the enabled features
+/// and the flags set by the build script.
+fn package_cfg_options(cargo: &CargoWorkspace, pkg: Package) -> CfgOptions {
+    let mut cfg_options = CfgOptions::default();
+    for feature in pkg.features(cargo) {
+        cfg_options.insert_key_value("feature".into(), feature.into());
+    }
+    if let Some(build_script) = pkg.build_script(cargo) {
+        for cfg in &build_script.cfgs {
+            insert_cfg(&mut cfg_options, cfg);
+        }
+    }
+    cfg_options
+}
+
+/// Adds a cfg in the `cargo:rustc-cfg` format, `foo` or `foo="bar"`.
+fn insert_cfg(cfg_options: &mut CfgOptions, cfg: &str) {
+    match cfg.find('=') {
+        None => cfg_options.insert_atom(cfg.into()),
+        Some(pos) => {
+            let key = &cfg[..pos];
+            let value = cfg[pos + 1..].trim_matches('"');
+            cfg_options.insert_key_value(key.into(), value.into());
         }
     }
 }
@@ -299,5 +630,65 @@ fn find_cargo_toml(path: &Path) -> Result<PathBuf> {
         }
         curr = path.parent();
     }
-    Err(format!("can't find Cargo.toml at {}", path.display()))?
+    Err(ProjectLoadError::ManifestNotFound { path: path.to_path_buf() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_script_cfgs_are_inserted() {
+        let mut cfg_options = CfgOptions::default();
+        insert_cfg(&mut cfg_options, "has_foo");
+        insert_cfg(&mut cfg_options, "foo_version=\"2\"");
+        assert!(cfg_options.check_atom("has_foo"));
+        assert!(cfg_options.check_key_value("foo_version", "2"));
+        assert!(!cfg_options.check_atom("foo_version"));
+    }
+
+    #[test]
+    fn detached_files_are_roots_of_their_own() {
+        let files =
+            vec![PathBuf::from("/a/x.rs"), PathBuf::from("/b/y.rs"), PathBuf::from("/a/z.rs")];
+        let ws =
+            ProjectWorkspace::DetachedFiles { files, sysroot: Sysroot::default(), target: None };
+        let roots = ws.to_roots();
+        assert_eq!(roots.len(), 2);
+
+        let root = roots.iter().find(|it| it.path() == Path::new("/a")).unwrap();
+        assert!(root.include_file(RelativePath::new("x.rs")));
+        assert!(root.include_file(RelativePath::new("z.rs")));
+        assert!(!root.include_file(RelativePath::new("y.rs")));
+        assert!(!root.include_dir(RelativePath::new("src")));
+
+        let folder = ProjectRoot::new(PathBuf::from("/"), true);
+        assert!(folder.contains(root));
+        assert!(!root.contains(&folder));
+        assert!(!root.contains(&roots[1]));
+    }
+
+    #[test]
+    fn targets_see_deps_of_their_section() {
+        let target = |kind, is_test| TargetCrate { crate_id: CrateId(0), kind, is_test };
+        let lib = target(TargetKind::Lib, false);
+        let test = target(TargetKind::Test, true);
+        let example = target(TargetKind::Example, false);
+        let build_script = target(TargetKind::Other, false);
+
+        assert!(lib.sees_dep(DepKind::Normal));
+        assert!(!lib.sees_dep(DepKind::Dev));
+        assert!(!lib.sees_dep(DepKind::Build));
+
+        assert!(test.sees_dep(DepKind::Normal));
+        assert!(test.sees_dep(DepKind::Dev));
+        assert!(!test.sees_dep(DepKind::Build));
+
+        assert!(example.sees_dep(DepKind::Normal));
+        assert!(example.sees_dep(DepKind::Dev));
+
+        assert!(!build_script.sees_dep(DepKind::Normal));
+        assert!(!build_script.sees_dep(DepKind::Dev));
+        assert!(build_script.sees_dep(DepKind::Build));
+    }
 }
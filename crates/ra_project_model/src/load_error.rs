@@ -0,0 +1,79 @@
+use std::{fmt, path::PathBuf};
+
+/// Why a project, or a part of it, couldn't be loaded.
+///
+/// The messages say what the user can do about the failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectLoadError {
+    /// There's neither a `Cargo.toml` nor a `rust-project.json` in the
+    /// directory or in its ancestors.
+    ManifestNotFound { path: PathBuf },
+    /// `cargo` isn't installed, or isn't in `PATH`.
+    CargoNotFound,
+    /// `cargo metadata` failed, usually because the manifest is invalid.
+    CargoMetadata { manifest: PathBuf, stderr: String },
+    /// `cargo check` couldn't be run, or its output couldn't be read.
+    CargoCheck { message: String },
+    /// `rustc --print sysroot` failed.
+    SysrootNotFound { message: String },
+    /// The sysroot doesn't contain the sources of the standard library.
+    SysrootSourcesMissing { sources: PathBuf },
+    /// `rust-project.json` can't be read or doesn't follow the schema.
+    InvalidJsonProject { path: PathBuf, message: String },
+    /// Adding the dependency `dep` of the crate `krate` would create a cycle
+    /// in the crate graph. The dependency is skipped.
+    CyclicDependency { krate: String, dep: String },
+}
+
+impl ProjectLoadError {
+    /// A short identifier of the kind of error, for editors.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProjectLoadError::ManifestNotFound { .. } => "manifestNotFound",
+            ProjectLoadError::CargoNotFound => "cargoNotFound",
+            ProjectLoadError::CargoMetadata { .. } => "cargoMetadata",
+            ProjectLoadError::CargoCheck { .. } => "cargoCheck",
+            ProjectLoadError::SysrootNotFound { .. } => "sysrootNotFound",
+            ProjectLoadError::SysrootSourcesMissing { .. } => "sysrootSourcesMissing",
+            ProjectLoadError::InvalidJsonProject { .. } => "invalidJsonProject",
+            ProjectLoadError::CyclicDependency { .. } => "cyclicDependency",
+        }
+    }
+}
+
+impl fmt::Display for ProjectLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectLoadError::ManifestNotFound { path } => {
+                write!(f, "can't find Cargo.toml or rust-project.json at {}", path.display())
+            }
+            ProjectLoadError::CargoNotFound => {
+                write!(f, "can't run cargo, make sure that it is installed and in PATH")
+            }
+            ProjectLoadError::CargoMetadata { manifest, stderr } => {
+                write!(f, "cargo metadata failed for {}:\n{}", manifest.display(), stderr.trim())
+            }
+            ProjectLoadError::CargoCheck { message } => {
+                write!(f, "cargo check failed: {}", message)
+            }
+            ProjectLoadError::SysrootNotFound { message } => {
+                write!(f, "failed to locate sysroot: {}", message)
+            }
+            ProjectLoadError::SysrootSourcesMissing { sources } => write!(
+                f,
+                "can't load standard library from sysroot\n\
+                 {}\n\
+                 try running `rustup component add rust-src`",
+                sources.display()
+            ),
+            ProjectLoadError::InvalidJsonProject { path, message } => {
+                write!(f, "invalid {}: {}", path.display(), message)
+            }
+            ProjectLoadError::CyclicDependency { krate, dep } => {
+                write!(f, "cyclic dependency on {} for {}, the dependency is ignored", dep, krate)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProjectLoadError {}
@@ -5,9 +5,9 @@ use std::{
 
 use ra_arena::{Arena, RawId, impl_arena_id};
 
-use crate::Result;
+use crate::{Result, ProjectLoadError};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Sysroot {
     crates: Arena<SysrootCrate, SysrootCrateData>,
 }
@@ -28,28 +28,39 @@ impl Sysroot {
         self.by_name("std")
     }
 
+    pub fn core(&self) -> Option<SysrootCrate> {
+        self.by_name("core")
+    }
+
+    pub fn alloc(&self) -> Option<SysrootCrate> {
+        self.by_name("alloc")
+    }
+
     pub fn crates<'a>(&'a self) -> impl Iterator<Item = SysrootCrate> + 'a {
         self.crates.iter().map(|(id, _data)| id)
     }
 
-    pub fn discover(cargo_toml: &Path) -> Result<Sysroot> {
-        let rustc_output = Command::new("rustc")
-            .current_dir(cargo_toml.parent().unwrap())
-            .args(&["--print", "sysroot"])
-            .output()?;
+    /// Finds the sysroot of the `rustc` used in `dir`, which depends on the
+    /// rustup overrides.
+    pub fn discover(dir: &Path) -> Result<Sysroot> {
+        let mut rustc = Command::new("rustc");
+        rustc.current_dir(dir).args(&["--print", "sysroot"]);
+        let rustc_output = rustc.output().map_err(|e| ProjectLoadError::SysrootNotFound {
+            message: format!("can't run rustc: {}", e),
+        })?;
         if !rustc_output.status.success() {
-            Err("failed to locate sysroot")?
+            let stderr = String::from_utf8_lossy(&rustc_output.stderr);
+            return Err(ProjectLoadError::SysrootNotFound { message: stderr.trim().to_string() });
         }
-        let stdout = String::from_utf8(rustc_output.stdout)?;
-        let sysroot_path = Path::new(stdout.trim());
+        let stdout = String::from_utf8_lossy(&rustc_output.stdout);
+        Sysroot::load(Path::new(stdout.trim()))
+    }
+
+    /// Loads the standard library from the sources of the sysroot at `path`.
+    pub fn load(sysroot_path: &Path) -> Result<Sysroot> {
         let src = sysroot_path.join("lib/rustlib/src/rust/src");
         if !src.exists() {
-            Err(format!(
-                "can't load standard library from sysroot\n\
-                 {:?}\n\
-                 try running `rustup component add rust-src`",
-                src,
-            ))?;
+            return Err(ProjectLoadError::SysrootSourcesMissing { sources: src });
         }
 
         let mut sysroot = Sysroot { crates: Arena::default() };
@@ -70,14 +81,22 @@ impl Sysroot {
                 }
             }
         }
-        if let Some(alloc) = sysroot.by_name("alloc") {
-            if let Some(core) = sysroot.by_name("core") {
+        if let Some(alloc) = sysroot.alloc() {
+            if let Some(core) = sysroot.core() {
                 sysroot.crates[alloc].deps.push(core);
             }
         }
         Ok(sysroot)
     }
 
+    pub fn proc_macro(&self) -> Option<SysrootCrate> {
+        self.by_name("proc_macro")
+    }
+
+    pub fn test(&self) -> Option<SysrootCrate> {
+        self.by_name("test")
+    }
+
     fn by_name(&self, name: &str) -> Option<SysrootCrate> {
         self.crates.iter().find(|(_id, data)| data.name == name).map(|(id, _data)| id)
     }
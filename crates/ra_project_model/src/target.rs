@@ -0,0 +1,128 @@
+//! Cfgs which are derived from the target triple.
+
+use std::{path::Path, process::Command};
+
+use ra_db::CfgOptions;
+
+const OPERATING_SYSTEMS: &[&str] = &[
+    "linux",
+    "android",
+    "windows",
+    "macos",
+    "ios",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "fuchsia",
+    "redox",
+    "wasi",
+    "emscripten",
+    "none",
+];
+
+const UNIX_LIKE: &[&str] = &[
+    "linux",
+    "android",
+    "macos",
+    "ios",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "fuchsia",
+    "redox",
+    "emscripten",
+];
+
+const ENVIRONMENTS: &[&str] = &["gnu", "musl", "msvc", "sgx", "uclibc"];
+
+/// Adds the cfgs rustc sets for the target triple, like `unix` and
+/// `target_os = "linux"` for `x86_64-unknown-linux-gnu`. Triples don't have a
+/// fixed number of components, so this is a best effort.
+pub(crate) fn insert_target_cfgs(cfg_options: &mut CfgOptions, target: &str) {
+    let mut parts = target.split('-');
+    let arch = match parts.next() {
+        Some(it) if !it.is_empty() => it,
+        _ => return,
+    };
+    cfg_options.insert_key_value("target_arch".into(), target_arch(arch).into());
+
+    let parts = parts.map(|it| if it == "darwin" { "macos" } else { it }).collect::<Vec<_>>();
+    let os = parts.iter().find(|it| OPERATING_SYSTEMS.contains(it)).cloned().unwrap_or("unknown");
+    cfg_options.insert_key_value("target_os".into(), os.into());
+    let env = parts.iter().find(|it| ENVIRONMENTS.contains(it)).cloned().unwrap_or("");
+    cfg_options.insert_key_value("target_env".into(), env.into());
+
+    let family = if os == "windows" {
+        Some("windows")
+    } else if UNIX_LIKE.contains(&os) {
+        Some("unix")
+    } else {
+        None
+    };
+    if let Some(family) = family {
+        cfg_options.insert_atom(family.into());
+        cfg_options.insert_key_value("target_family".into(), family.into());
+    }
+}
+
+/// The `target_arch` of the first component of a triple: the architectures
+/// of a family, like `i686` or `thumbv7em`, share it.
+fn target_arch(arch: &str) -> &str {
+    match arch {
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "aarch64_be" => "aarch64",
+        "mipsel" => "mips",
+        "mips64el" => "mips64",
+        "powerpc64le" => "powerpc64",
+        "sparcv9" => "sparc64",
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        _ if arch.starts_with("riscv32") => "riscv32",
+        _ if arch.starts_with("riscv64") => "riscv64",
+        _ => arch,
+    }
+}
+
+/// The target triple of the `rustc` used in `dir`, from `rustc -vV`.
+pub(crate) fn host_target(dir: &Path) -> Option<String> {
+    let output = Command::new("rustc").current_dir(dir).arg("-vV").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().find(|it| it.starts_with("host: ")).map(|it| it["host: ".len()..].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_arch(target: &str, arch: &str) {
+        let mut cfg_options = CfgOptions::default();
+        insert_target_cfgs(&mut cfg_options, target);
+        assert!(cfg_options.check_key_value("target_arch", arch), "{}", target);
+    }
+
+    #[test]
+    fn target_arch_of_families() {
+        check_arch("x86_64-unknown-linux-gnu", "x86_64");
+        check_arch("i686-pc-windows-msvc", "x86");
+        check_arch("armv7-linux-androideabi", "arm");
+        check_arch("thumbv7em-none-eabihf", "arm");
+        check_arch("aarch64_be-unknown-linux-gnu", "aarch64");
+        check_arch("riscv64gc-unknown-none-elf", "riscv64");
+        check_arch("wasm32-unknown-unknown", "wasm32");
+    }
+
+    #[test]
+    fn os_and_family_of_triples() {
+        let mut cfg_options = CfgOptions::default();
+        insert_target_cfgs(&mut cfg_options, "x86_64-apple-darwin");
+        assert!(cfg_options.check_key_value("target_os", "macos"));
+        assert!(cfg_options.check_atom("unix"));
+        assert!(!cfg_options.check_atom("windows"));
+    }
+}
@@ -17,7 +17,7 @@ pub use self::{
     generated::*,
     traits::*,
     tokens::*,
-    extensions::{PathSegmentKind, StructKind,FieldKind, SelfParamKind},
+    extensions::{PathSegmentKind, StructKind,FieldKind, SelfParamKind, VisibilityKind},
     expr_extensions::{ElseBranch, PrefixOp, BinOp, LiteralKind,ArrayExprKind},
 };
 
@@ -351,6 +351,37 @@ impl ast::SelfParam {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityKind<'a> {
+    /// pub(in path)
+    In(&'a ast::Path),
+    /// pub(crate), or the `crate` keyword
+    PubCrate,
+    /// pub(super)
+    PubSuper,
+    /// pub(self)
+    PubSelf,
+    /// pub
+    Pub,
+}
+
+impl ast::Visibility {
+    pub fn kind(&self) -> VisibilityKind {
+        if let Some(path) = self.path() {
+            return VisibilityKind::In(path);
+        }
+        let mut tokens =
+            self.syntax().children_with_tokens().map(|it| it.kind()).filter(|it| !it.is_trivia());
+        // `pub ( kw )`, or just `crate`
+        match (tokens.next(), tokens.nth(1)) {
+            (Some(T![crate]), _) | (_, Some(T![crate])) => VisibilityKind::PubCrate,
+            (_, Some(T![super])) => VisibilityKind::PubSuper,
+            (_, Some(T![self])) => VisibilityKind::PubSelf,
+            _ => VisibilityKind::Pub,
+        }
+    }
+}
+
 impl ast::LifetimeParam {
     pub fn lifetime_token(&self) -> Option<SyntaxToken> {
         self.syntax()
@@ -1009,6 +1009,8 @@ impl ToOwned for ExternCrateItem {
 }
 
 
+impl ast::AttrsOwner for ExternCrateItem {}
+impl ast::VisibilityOwner for ExternCrateItem {}
 impl ExternCrateItem {
     pub fn name_ref(&self) -> Option<&NameRef> {
         super::child_opt(self)
@@ -1810,6 +1812,38 @@ impl ast::ModuleItemOwner for MacroItems {}
 impl ast::FnDefOwner for MacroItems {}
 impl MacroItems {}
 
+// MacroPat
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct MacroPat {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for MacroPat {
+    type Repr = rowan::SyntaxNode;
+}
+
+impl AstNode for MacroPat {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            MACRO_PAT => Some(MacroPat::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+}
+
+impl ToOwned for MacroPat {
+    type Owned = TreeArc<MacroPat>;
+    fn to_owned(&self) -> TreeArc<MacroPat> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl MacroPat {
+    pub fn macro_call(&self) -> Option<&MacroCall> {
+        super::child_opt(self)
+    }
+}
+
 // MacroStmts
 #[derive(Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -1846,6 +1880,38 @@ impl MacroStmts {
     }
 }
 
+// MacroType
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct MacroType {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for MacroType {
+    type Repr = rowan::SyntaxNode;
+}
+
+impl AstNode for MacroType {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            MACRO_TYPE => Some(MacroType::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+}
+
+impl ToOwned for MacroType {
+    type Owned = TreeArc<MacroType>;
+    fn to_owned(&self) -> TreeArc<MacroType> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl MacroType {
+    pub fn macro_call(&self) -> Option<&MacroCall> {
+        super::child_opt(self)
+    }
+}
+
 // MatchArm
 #[derive(Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -2626,6 +2692,7 @@ pub enum PatKind<'a> {
     SlicePat(&'a SlicePat),
     RangePat(&'a RangePat),
     LiteralPat(&'a LiteralPat),
+    MacroPat(&'a MacroPat),
 }
 impl<'a> From<&'a RefPat> for &'a Pat {
     fn from(n: &'a RefPat) -> &'a Pat {
@@ -2677,6 +2744,11 @@ impl<'a> From<&'a LiteralPat> for &'a Pat {
         Pat::cast(&n.syntax).unwrap()
     }
 }
+impl<'a> From<&'a MacroPat> for &'a Pat {
+    fn from(n: &'a MacroPat) -> &'a Pat {
+        Pat::cast(&n.syntax).unwrap()
+    }
+}
 
 
 impl AstNode for Pat {
@@ -2691,7 +2763,8 @@ impl AstNode for Pat {
             | TUPLE_PAT
             | SLICE_PAT
             | RANGE_PAT
-            | LITERAL_PAT => Some(Pat::from_repr(syntax.into_repr())),
+            | LITERAL_PAT
+            | MACRO_PAT => Some(Pat::from_repr(syntax.into_repr())),
             _ => None,
         }
     }
@@ -2716,6 +2789,7 @@ impl Pat {
             SLICE_PAT => PatKind::SlicePat(SlicePat::cast(&self.syntax).unwrap()),
             RANGE_PAT => PatKind::RangePat(RangePat::cast(&self.syntax).unwrap()),
             LITERAL_PAT => PatKind::LiteralPat(LiteralPat::cast(&self.syntax).unwrap()),
+            MACRO_PAT => PatKind::MacroPat(MacroPat::cast(&self.syntax).unwrap()),
             _ => unreachable!(),
         }
     }
@@ -4140,6 +4214,7 @@ pub enum TypeRefKind<'a> {
     ForType(&'a ForType),
     ImplTraitType(&'a ImplTraitType),
     DynTraitType(&'a DynTraitType),
+    MacroType(&'a MacroType),
 }
 impl<'a> From<&'a ParenType> for &'a TypeRef {
     fn from(n: &'a ParenType) -> &'a TypeRef {
@@ -4206,6 +4281,11 @@ impl<'a> From<&'a DynTraitType> for &'a TypeRef {
         TypeRef::cast(&n.syntax).unwrap()
     }
 }
+impl<'a> From<&'a MacroType> for &'a TypeRef {
+    fn from(n: &'a MacroType) -> &'a TypeRef {
+        TypeRef::cast(&n.syntax).unwrap()
+    }
+}
 
 
 impl AstNode for TypeRef {
@@ -4223,7 +4303,8 @@ impl AstNode for TypeRef {
             | FN_POINTER_TYPE
             | FOR_TYPE
             | IMPL_TRAIT_TYPE
-            | DYN_TRAIT_TYPE => Some(TypeRef::from_repr(syntax.into_repr())),
+            | DYN_TRAIT_TYPE
+            | MACRO_TYPE => Some(TypeRef::from_repr(syntax.into_repr())),
             _ => None,
         }
     }
@@ -4251,6 +4332,7 @@ impl TypeRef {
             FOR_TYPE => TypeRefKind::ForType(ForType::cast(&self.syntax).unwrap()),
             IMPL_TRAIT_TYPE => TypeRefKind::ImplTraitType(ImplTraitType::cast(&self.syntax).unwrap()),
             DYN_TRAIT_TYPE => TypeRefKind::DynTraitType(DynTraitType::cast(&self.syntax).unwrap()),
+            MACRO_TYPE => TypeRefKind::MacroType(MacroType::cast(&self.syntax).unwrap()),
             _ => unreachable!(),
         }
     }
@@ -4285,6 +4367,7 @@ impl ToOwned for UseItem {
 
 
 impl ast::AttrsOwner for UseItem {}
+impl ast::VisibilityOwner for UseItem {}
 impl UseItem {
     pub fn use_tree(&self) -> Option<&UseTree> {
         super::child_opt(self)
@@ -4389,7 +4472,11 @@ impl ToOwned for Visibility {
 }
 
 
-impl Visibility {}
+impl Visibility {
+    pub fn path(&self) -> Option<&Path> {
+        super::child_opt(self)
+    }
+}
 
 // WhereClause
 #[derive(Debug, PartialEq, Eq, Hash)]
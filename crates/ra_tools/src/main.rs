@@ -37,6 +37,7 @@ fn main() -> Result<()> {
 
 fn install_code_extension() -> Result<()> {
     run("cargo install --path crates/ra_lsp_server --force", ".")?;
+    run("cargo install --path crates/ra_proc_macro_srv --force", ".")?;
     if cfg!(windows) {
         run(r"cmd.exe /c npm.cmd ci", "./editors/code")?;
         run(r"cmd.exe /c npm.cmd run package", "./editors/code")?;
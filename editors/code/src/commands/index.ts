@@ -6,6 +6,7 @@ import * as onEnter from './on_enter';
 import * as parentModule from './parent_module';
 import * as runnables from './runnables';
 import * as syntaxTree from './syntaxTree';
+import * as workspaceStatus from './workspace_status';
 
 export {
     analyzerStatus,
@@ -15,5 +16,6 @@ export {
     parentModule,
     runnables,
     syntaxTree,
+    workspaceStatus,
     onEnter
 };
@@ -0,0 +1,32 @@
+import * as vscode from 'vscode';
+
+import { Server } from '../server';
+
+interface ProjectError {
+    kind: string;
+    message: string;
+}
+
+interface ProjectStatus {
+    folder: string | null;
+    loaded: boolean;
+    packages: number;
+    errors: ProjectError[];
+}
+
+export async function handle() {
+    const projects = await Server.client.sendRequest<ProjectStatus[]>(
+        'rust-analyzer/workspaceStatus',
+        null
+    );
+    const items = projects.map(project => ({
+        label: project.folder || 'Files outside of the workspace',
+        description: project.loaded
+            ? `${project.packages} packages`
+            : 'not loaded',
+        detail: project.errors.map(e => e.message).join('\n')
+    }));
+    await vscode.window.showQuickPick(items, {
+        placeHolder: 'Projects of the workspace'
+    });
+}
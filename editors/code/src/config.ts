@@ -4,9 +4,16 @@ import { Server } from './server';
 
 const RA_LSP_DEBUG = process.env.__RA_LSP_SERVER_DEBUG;
 
+export type FileWatcher = 'client' | 'server';
 export type CargoWatchStartupOptions = 'ask' | 'enabled' | 'disabled';
 export type CargoWatchTraceOptions = 'off' | 'error' | 'verbose';
 
+export type CargoFeatures =
+    | 'default'
+    | 'all'
+    | 'none'
+    | { explicit: { [pkg: string]: string[] } };
+
 export interface CargoWatchOptions {
     enableOnStartup: CargoWatchStartupOptions;
     checkArguments: string;
@@ -20,6 +27,20 @@ export class Config {
     public raLspServerPath = RA_LSP_DEBUG || 'ra_lsp_server';
     public showWorkspaceLoadedNotification = true;
     public lruCapacity: null | number = null;
+    public procMacroServer: null | string = null;
+    public cargoFeatures: CargoFeatures = 'default';
+    public loadBuildScripts = false;
+    public cargoCheckOutput: null | string = null;
+    public target: null | string = null;
+    public sysroot: null | string = null;
+    public enableDiagnostics = true;
+    public showWeakWarnings = true;
+    public enablePostfixCompletions = true;
+    public addCallParenthesis = true;
+    public checkOnSave = false;
+    public checkCommand = ['cargo', 'check', '--message-format=json'];
+    public fileWatcher: FileWatcher = 'client';
+    public traceFile: null | string = null;
     public cargoWatchOptions: CargoWatchOptions = {
         enableOnStartup: 'ask',
         trace: 'off',
@@ -113,5 +134,85 @@ export class Config {
         if (config.has('lruCapacity')) {
             this.lruCapacity = config.get('lruCapacity') as number;
         }
+        if (config.has('procMacroServer')) {
+            this.procMacroServer = config.get('procMacroServer') as string;
+        }
+        if (config.has('cargoFeatures')) {
+            this.cargoFeatures = config.get('cargoFeatures') as CargoFeatures;
+        }
+        if (config.has('loadBuildScripts')) {
+            this.loadBuildScripts = config.get('loadBuildScripts') as boolean;
+        }
+        if (config.has('cargoCheckOutput')) {
+            this.cargoCheckOutput = config.get('cargoCheckOutput') as string;
+        }
+        if (config.has('target')) {
+            this.target = config.get('target') as string;
+        }
+        if (config.has('sysroot')) {
+            this.sysroot = config.get('sysroot') as string;
+        }
+        if (config.has('enableDiagnostics')) {
+            this.enableDiagnostics = config.get('enableDiagnostics') as boolean;
+        }
+        if (config.has('showWeakWarnings')) {
+            this.showWeakWarnings = config.get('showWeakWarnings') as boolean;
+        }
+        if (config.has('enablePostfixCompletions')) {
+            this.enablePostfixCompletions = config.get(
+                'enablePostfixCompletions'
+            ) as boolean;
+        }
+        if (config.has('addCallParenthesis')) {
+            this.addCallParenthesis = config.get(
+                'addCallParenthesis'
+            ) as boolean;
+        }
+        if (config.has('checkOnSave')) {
+            this.checkOnSave = config.get('checkOnSave') as boolean;
+        }
+        if (config.has('checkCommand')) {
+            this.checkCommand = config.get('checkCommand') as string[];
+        }
+        if (config.has('files.watcher')) {
+            this.fileWatcher = config.get('files.watcher') as FileWatcher;
+        }
+        if (config.has('profile.traceFile')) {
+            this.traceFile = config.get('profile.traceFile') as null | string;
+        }
+    }
+
+    // The settings of the server, which it applies without a restart.
+    public serverConfig() {
+        return {
+            showWorkspaceLoaded: this.showWorkspaceLoadedNotification,
+            lruCapacity: this.lruCapacity,
+            diagnostics: {
+                enable: this.enableDiagnostics,
+                weakWarnings: this.showWeakWarnings
+            },
+            completion: {
+                enablePostfixCompletions: this.enablePostfixCompletions,
+                addCallParenthesis: this.addCallParenthesis
+            },
+            cargo: {
+                features: this.cargoFeatures,
+                procMacroServer: this.procMacroServer,
+                loadBuildScripts: this.loadBuildScripts,
+                checkOutput: this.cargoCheckOutput,
+                target: this.target,
+                sysroot: this.sysroot
+            },
+            check: {
+                enable: this.checkOnSave,
+                command: this.checkCommand
+            },
+            files: {
+                watcher: this.fileWatcher
+            },
+            profile: {
+                traceFile: this.traceFile
+            }
+        };
     }
 }
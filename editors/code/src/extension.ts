@@ -54,6 +54,10 @@ export function activate(context: vscode.ExtensionContext) {
         'rust-analyzer.analyzerStatus',
         commands.analyzerStatus.makeCommand(context)
     );
+    registerCommand(
+        'rust-analyzer.workspaceStatus',
+        commands.workspaceStatus.handle
+    );
     registerCommand('rust-analyzer.collectGarbage', () =>
         Server.client.sendRequest<null>('rust-analyzer/collectGarbage', null)
     );
@@ -93,7 +97,8 @@ export function activate(context: vscode.ExtensionContext) {
         [
             'rust-analyzer/publishDecorations',
             notifications.publishDecorations.handle
-        ]
+        ],
+        ['rust-analyzer/progress', notifications.progress.handle]
     ];
     const syntaxTreeContentProvider = new SyntaxTreeContentProvider();
 
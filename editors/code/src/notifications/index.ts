@@ -1,3 +1,4 @@
+import * as progress from './progress';
 import * as publishDecorations from './publish_decorations';
 
-export { publishDecorations };
+export { progress, publishDecorations };
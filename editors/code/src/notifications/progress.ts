@@ -0,0 +1,71 @@
+import * as vscode from 'vscode';
+
+export type WorkDoneProgress =
+    | { kind: 'begin'; title: string; message?: string; percentage?: number }
+    | { kind: 'report'; message?: string; percentage?: number }
+    | { kind: 'end'; message?: string };
+
+export interface ProgressParams {
+    token: string;
+    value: WorkDoneProgress;
+}
+
+interface ActiveProgress {
+    progress: vscode.Progress<{ message?: string; increment?: number }>;
+    percentage: number;
+    done: () => void;
+}
+
+const active: Map<string, ActiveProgress> = new Map();
+
+// Shows `rust-analyzer/progress` in the status bar. The server sends it
+// instead of `$/progress` to clients without `window.workDoneProgress`.
+export function handle(params: ProgressParams) {
+    const value = params.value;
+    switch (value.kind) {
+        case 'begin':
+            vscode.window.withProgress(
+                {
+                    location: vscode.ProgressLocation.Window,
+                    title: value.title
+                },
+                progress =>
+                    new Promise<void>(resolve => {
+                        active.set(params.token, {
+                            progress,
+                            percentage: 0,
+                            done: resolve
+                        });
+                        report(params.token, value);
+                    })
+            );
+            break;
+        case 'report':
+            report(params.token, value);
+            break;
+        case 'end': {
+            const it = active.get(params.token);
+            if (it) {
+                active.delete(params.token);
+                it.done();
+            }
+            break;
+        }
+    }
+}
+
+function report(
+    token: string,
+    value: { message?: string; percentage?: number }
+) {
+    const it = active.get(token);
+    if (!it) {
+        return;
+    }
+    let increment: number | undefined;
+    if (value.percentage !== undefined) {
+        increment = value.percentage - it.percentage;
+        it.percentage = value.percentage;
+    }
+    it.progress.report({ message: value.message, increment });
+}
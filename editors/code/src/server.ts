@@ -34,9 +34,24 @@ export class Server {
             documentSelector: [{ scheme: 'file', language: 'rust' }],
             initializationOptions: {
                 publishDecorations: true,
-                showWorkspaceLoaded:
-                    Server.config.showWorkspaceLoadedNotification,
-                lruCapacity: Server.config.lruCapacity
+                ...Server.config.serverConfig()
+            },
+            middleware: {
+                workspace: {
+                    // Our settings don't have the layout the server expects
+                    configuration: (params, token, next) => {
+                        if (
+                            params.items.some(
+                                item => item.section !== 'rust-analyzer'
+                            )
+                        ) {
+                            return next(params, token);
+                        }
+                        return params.items.map(_ =>
+                            Server.config.serverConfig()
+                        );
+                    }
+                }
             },
             traceOutputChannel
         };
@@ -80,6 +95,16 @@ export class Server {
             for (const [type, handler] of notificationHandlers) {
                 Server.client.onNotification(type, handler);
             }
+            workspace.onDidChangeConfiguration(_ =>
+                Server.client.sendNotification(
+                    lc.DidChangeConfigurationNotification.type,
+                    {
+                        settings: {
+                            'rust-analyzer': Server.config.serverConfig()
+                        }
+                    }
+                )
+            );
         });
         Server.client.start();
     }